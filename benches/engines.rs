@@ -0,0 +1,49 @@
+//! Benchmark of the execution engines, running bundled ROMs headless at batch speed once their blocks are decoded or
+//! compiled.
+
+#[macro_use] extern crate criterion;
+extern crate chip_8;
+
+use std::fs;
+use std::path::Path;
+
+use criterion::Criterion;
+
+use chip_8::cpu::{Cpu, Engine};
+use chip_8::interconnect::Interconnect;
+
+/// ROMs run by the benchmark.
+const ROMS: [&str; 3] = ["INVADERS", "PONG", "BRIX"];
+/// Instructions executed per frame, as in batch runs.
+const CYCLES_PER_FRAME: u32 = 1000;
+/// Frames run before measuring, decoding or compiling the blocks of the main loop.
+const WARM_UP_FRAMES: usize = 60;
+
+/// Engines to compare, with their names.
+fn engines() -> Vec<(&'static str, Engine)> {
+    #[allow(unused_mut)]
+    let mut engines = vec![("interpreter", Engine::Interpreter), ("cached", Engine::Cached)];
+    #[cfg(feature = "jit")]
+    engines.push(("jit", Engine::Jit));
+    engines
+}
+
+fn bench_engines(c: &mut Criterion) {
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("static").join("roms");
+    for name in ROMS.iter() {
+        let rom = fs::read(roms.join(name)).unwrap();
+        for (label, engine) in engines() {
+            let mut cpu = Cpu::new(Interconnect::new_test(rom.clone()));
+            cpu.set_engine(engine);
+            cpu.set_cycles_per_frame(CYCLES_PER_FRAME);
+            cpu.seed_rng(0);
+            for _ in 0..WARM_UP_FRAMES {
+                cpu.execute_frame();
+            }
+            c.bench_function(&format!("{}: {}", name, label), |b| b.iter(|| cpu.execute_frame()));
+        }
+    }
+}
+
+criterion_group!(benches, bench_engines);
+criterion_main!(benches);
@@ -0,0 +1,35 @@
+//! Benchmark of the reinforcement learning environment, stepping PONG headless with the fastest engine available.
+
+#[macro_use] extern crate criterion;
+extern crate chip_8;
+
+use std::fs;
+use std::path::Path;
+
+use criterion::Criterion;
+
+use chip_8::cpu::Engine;
+use chip_8::gym::{Environment, Game};
+
+fn bench_step(c: &mut Criterion) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("static");
+    let rom = fs::read(root.join("roms").join("PONG")).unwrap();
+    let game = Game::load(&root.join("games").join("PONG.game")).unwrap();
+    let mut env = Environment::new(rom, game).unwrap();
+    #[cfg(not(feature = "jit"))]
+    env.set_engine(Engine::Cached);
+    #[cfg(feature = "jit")]
+    env.set_engine(Engine::Jit);
+    let actions = env.actions();
+    env.reset(0);
+    let mut step = 0;
+    c.bench_function("PONG: step", |b| b.iter(|| {
+        step += 1;
+        if env.step(actions[step % actions.len()]).2 {
+            env.reset(step as u64);
+        }
+    }));
+}
+
+criterion_group!(benches, bench_step);
+criterion_main!(benches);
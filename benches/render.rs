@@ -0,0 +1,70 @@
+//! Benchmark of presenting the display, comparing a redraw after every sprite with one upload per frame.
+//!
+//! Uses the SDL dummy video driver, so no window is shown.
+
+#[macro_use] extern crate criterion;
+extern crate chip_8;
+extern crate sdl2;
+
+use criterion::Criterion;
+use sdl2::pixels::Color;
+use sdl2::rect::Point;
+
+use chip_8::config::Config;
+use chip_8::graphics::{Display, Graphics, HEIGHT, WIDTH};
+
+/// Sprite of an invader, as drawn by INVADERS.
+const INVADER: [u8; 6] = [0x3C, 0x7E, 0xDB, 0xFF, 0x24, 0x5A];
+/// Number of sprites drawn per frame.
+const SPRITES_PER_FRAME: usize = 30;
+
+/// Draw a frame worth of sprites, calling `after_draw` after each one.
+fn draw_frame<F>(display: &mut Display, mut after_draw: F) where F: FnMut(&mut Display) {
+    for idx in 0..SPRITES_PER_FRAME {
+        display.draw((idx % 6) * 10, (idx / 6) * 6, &INVADER, false);
+        after_draw(display);
+    }
+}
+
+/// The former presentation: clear the canvas and draw one point per lit pixel.
+fn present_points(canvas: &mut sdl2::render::WindowCanvas, display: &Display) {
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.clear();
+    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    for x in 0..WIDTH {
+        for y in 0..HEIGHT {
+            if display.get_pixel(x, y) {
+                let _ = canvas.draw_point(Point::new(x as i32, y as i32));
+            }
+        }
+    }
+    canvas.present();
+}
+
+fn render(c: &mut Criterion) {
+    std::env::set_var("SDL_VIDEODRIVER", "dummy");
+    let context = sdl2::init().unwrap();
+    let config = Config::default();
+    let mut display = Display::new(&context, &config);
+
+    let window = context.video().unwrap().window("points", (WIDTH * 10) as u32, (HEIGHT * 10) as u32).build().unwrap();
+    let mut canvas = window.into_canvas().software().build().unwrap();
+    let _ = canvas.set_scale(10.0, 10.0);
+
+    c.bench_function("frame: points after every draw", |b| {
+        b.iter(|| draw_frame(&mut display, |display| present_points(&mut canvas, display)))
+    });
+    c.bench_function("frame: texture after every draw", |b| {
+        b.iter(|| draw_frame(&mut display, |display| display.present()))
+    });
+    c.bench_function("frame: texture once per frame", |b| {
+        b.iter(|| {
+            draw_frame(&mut display, |_| ());
+            display.present();
+        })
+    });
+    c.bench_function("frame: idle", |b| b.iter(|| display.present()));
+}
+
+criterion_group!(benches, render);
+criterion_main!(benches);
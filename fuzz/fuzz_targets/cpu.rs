@@ -0,0 +1,26 @@
+//! Fuzz target comparing the CPU against the reference interpreter of the tests. The input is decoded into an initial
+//! state and a program by `Input::from_bytes`; a mismatch other than the known bugs, or a panic of the CPU, is
+//! minimized and reported as a unit test for `cpu.rs`.
+//!
+//! ```sh
+//! cargo +nightly fuzz run cpu
+//! ```
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../tests/reference/mod.rs"]
+mod reference;
+
+use reference::differential::{run, Input};
+
+fuzz_target!(|data: &[u8]| {
+    if let Some(input) = Input::from_bytes(data) {
+        if let Err(mismatch) = run(&input) {
+            if mismatch.known_bug().is_none() {
+                panic!("the CPU disagrees with the reference, reproduce with\n\n{}", mismatch.minimize());
+            }
+        }
+    }
+});
@@ -0,0 +1,144 @@
+//! Cache module. Backs the cached interpreter, which decodes straight-line runs of instructions, basic blocks, once
+//! and executes them from a cache instead of fetching and decoding every instruction again.
+//!
+//! A block ends after a branch or after `MAX_BLOCK_LEN` instructions. Its bytes are marked as code in memory, so that
+//! a program overwriting its own instructions has the blocks holding them dropped and decoded anew.
+
+use std::rc::Rc;
+
+use instruction::Instruction;
+use memory::{Memory, MEMORY_SIZE};
+
+/// Maximum number of instructions of a block.
+const MAX_BLOCK_LEN: usize = 32;
+
+/// A decoded basic block.
+pub struct Block {
+    /// Address of the first instruction.
+    pub start: u16,
+    /// Address after the last instruction.
+    pub end: u16,
+    /// Opcodes and their decoded instructions, in order.
+    pub instructions: Vec<(u16, Instruction)>,
+}
+
+impl Block {
+    /// Decode the block starting at an address. Fails like fetching an instruction does if the address is beyond
+    /// memory, a block otherwise stops before the end of memory.
+    pub fn decode(memory: &Memory, start: u16) -> Block {
+        let mut instructions = Vec::new();
+        let mut addr = start as usize;
+        loop {
+            let opcode = memory.read_word(addr);
+            let instruction = Instruction::decode(opcode);
+            instructions.push((opcode, instruction));
+            addr += 2;
+            if instruction.is_branch() || instructions.len() == MAX_BLOCK_LEN || addr + 1 >= MEMORY_SIZE {
+                break;
+            }
+        }
+        Block { start, end: addr as u16, instructions }
+    }
+
+    /// Checks if a byte of the block lies at an address.
+    pub fn contains(&self, addr: usize) -> bool {
+        (self.start as usize..self.end as usize).contains(&addr)
+    }
+}
+
+/// Decoded blocks indexed by their start address.
+pub struct BlockCache {
+    blocks: Vec<Option<Rc<Block>>>,
+}
+
+impl Default for BlockCache {
+    fn default() -> BlockCache {
+        BlockCache { blocks: vec![None; MEMORY_SIZE] }
+    }
+}
+
+impl BlockCache {
+    /// Constructor.
+    pub fn new() -> BlockCache {
+        BlockCache::default()
+    }
+
+    /// Drop all blocks, as when another program is loaded.
+    pub fn clear(&mut self) {
+        self.blocks.iter_mut().for_each(|block| *block = None);
+    }
+
+    /// Number of cached blocks.
+    pub fn len(&self) -> usize {
+        self.blocks.iter().filter(|block| block.is_some()).count()
+    }
+
+    /// Checks if no block is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop the blocks holding instructions written to since the last call.
+    ///
+    /// # Returns
+    /// Returns `true` if a block was dropped.
+    pub fn invalidate(&mut self, memory: &mut Memory) -> bool {
+        let writes = memory.take_code_writes();
+        if writes.is_empty() {
+            return false;
+        }
+        let mut dropped = false;
+        for entry in self.blocks.iter_mut() {
+            if entry.as_ref().is_some_and(|block| writes.iter().any(|&addr| block.contains(addr))) {
+                *entry = None;
+                dropped = true;
+            }
+        }
+        dropped
+    }
+
+    /// Get the block starting at an address, decoding it unless it is cached. Blocks holding instructions written to
+    /// since they were decoded are dropped first.
+    pub fn block(&mut self, memory: &mut Memory, start: u16) -> Rc<Block> {
+        self.invalidate(memory);
+        if let Some(block) = self.blocks.get(start as usize).and_then(Option::as_ref) {
+            return block.clone();
+        }
+        let block = Rc::new(Block::decode(memory, start));
+        memory.mark_code(block.start as usize..block.end as usize);
+        self.blocks[start as usize] = Some(block.clone());
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_end_after_branches() {
+        // LD V0, 0x05; ADD V0, 0x01; SE V0, 0x06; JP 0x200
+        let mut memory = Memory::new(vec![0x60, 0x05, 0x70, 0x01, 0x30, 0x06, 0x12, 0x00]).unwrap();
+        let mut cache = BlockCache::new();
+        let block = cache.block(&mut memory, 0x200);
+        assert_eq!((block.start, block.end), (0x200, 0x206));
+        assert_eq!(block.instructions[2], (0x3006, Instruction::SeByte(0, 6)));
+        assert_eq!(cache.block(&mut memory, 0x206).instructions.len(), 1);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn writes_to_code_drop_blocks() {
+        let mut memory = Memory::new(vec![0x60, 0x05, 0x70, 0x01, 0x30, 0x06, 0x12, 0x00]).unwrap();
+        let mut cache = BlockCache::new();
+        cache.block(&mut memory, 0x200);
+        cache.block(&mut memory, 0x206);
+
+        memory.write(0x208, 0xFF);
+        assert!(!cache.invalidate(&mut memory), "data after the code is not code");
+        memory.write(0x203, 0x02);
+        assert!(cache.invalidate(&mut memory));
+        assert_eq!(cache.len(), 1, "only the block holding the instruction is dropped");
+        assert_eq!(cache.block(&mut memory, 0x200).instructions[1].1, Instruction::AddByte(0, 2));
+    }
+}
@@ -0,0 +1,356 @@
+//! Configuration module. Contains the options the emulator was launched with.
+
+use std;
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+
+use cpu::{Engine, DEFAULT_CYCLES_PER_FRAME, FRAMES_PER_SECOND};
+use effects::Effect;
+use filter::Filter;
+use netplay::{NetplayConfig, Peer, MAX_INPUT_DELAY};
+use palette::{Palette, Rgb, Theme};
+use quirks::Quirks;
+use recorder::{VideoFormat, STDOUT};
+use rpc::Address;
+use speed::{DEFAULT_FAST_FORWARD, DEFAULT_SLOW_MOTION, DEFAULT_TURBO};
+use trace::{self, TraceFilter, TraceFormat};
+use viewport::Fit;
+
+/// Default factor the emulated display is scaled by to get the window size.
+const DEFAULT_SCALE: u32 = 10;
+/// Largest factor the emulated display may be scaled by, giving a window of 4096x2048.
+const MAX_SCALE: u32 = 64;
+/// Default half-life of fading pixels in milliseconds.
+const DEFAULT_HALF_LIFE_MS: u32 = 30;
+
+/// Options of the emulator.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Name of the running ROM.
+    pub rom_name: String,
+    /// Colour theme the display starts with.
+    pub theme: Theme,
+    /// Foreground colour overriding the one of the theme.
+    pub foreground: Option<Rgb>,
+    /// Background colour overriding the one of the theme.
+    pub background: Option<Rgb>,
+    /// Factor the emulated display is scaled by to get the initial window size.
+    pub scale: u32,
+    /// How the emulated display is fitted into the window.
+    pub fit: Fit,
+    /// Whether the window starts in fullscreen.
+    pub fullscreen: bool,
+    /// Number of instructions executed per 60 Hz frame.
+    pub cycles_per_frame: u32,
+    /// Behaviour of the instructions interpreters disagree on.
+    pub quirks: Quirks,
+    /// How instructions are executed.
+    pub engine: Engine,
+    /// Flicker reduction filter applied to the display.
+    pub filter: Filter,
+    /// Post-processing effects applied to the display, in order.
+    pub effects: Vec<Effect>,
+    /// Directory screenshots are saved to.
+    pub screenshot_dir: PathBuf,
+    /// Whether recording starts with the emulator.
+    pub record: bool,
+    /// Container recordings are saved in.
+    pub record_format: VideoFormat,
+    /// Directory recordings are saved to, `-` writing the video to stdout.
+    pub record_dir: PathBuf,
+    /// Whether the emulation statistics are shown.
+    pub stats: bool,
+    /// Path the profile reports are saved to at exit, `.txt` and `.json` being appended to it. Profiling is disabled
+    /// if absent.
+    pub profile: Option<PathBuf>,
+    /// File every executed instruction is logged to, `-` logging to stdout. Tracing is disabled if absent.
+    pub trace: Option<PathBuf>,
+    /// Layout of the trace lines.
+    pub trace_format: TraceFormat,
+    /// Instructions that are traced.
+    pub trace_filter: TraceFilter,
+    /// Speed multiplier while the fast-forward key is held.
+    pub fast_forward: f32,
+    /// Speed multiplier while turbo is on.
+    pub turbo: f32,
+    /// Speed multiplier while slow motion is on.
+    pub slow_motion: f32,
+    /// Two-player game over the network to host or join. Played alone if absent.
+    pub netplay: Option<NetplayConfig>,
+    /// Address the remote control server listens on. No server is started if absent.
+    pub rpc: Option<Address>,
+    /// Whether to run without a window, driven over the remote control server or by a script.
+    pub headless: bool,
+    /// Script whose hooks run as the program executes. No script runs if absent.
+    pub script: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            rom_name: String::from("ROM"),
+            theme: Theme::default(),
+            foreground: None,
+            background: None,
+            scale: DEFAULT_SCALE,
+            fit: Fit::default(),
+            fullscreen: false,
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            quirks: Quirks::default(),
+            engine: Engine::default(),
+            filter: Filter::default(),
+            effects: Vec::new(),
+            screenshot_dir: PathBuf::from("screenshots"),
+            record: false,
+            record_format: VideoFormat::default(),
+            record_dir: PathBuf::from("recordings"),
+            stats: false,
+            profile: None,
+            trace: None,
+            trace_format: TraceFormat::default(),
+            trace_filter: TraceFilter::default(),
+            fast_forward: DEFAULT_FAST_FORWARD,
+            turbo: DEFAULT_TURBO,
+            slow_motion: DEFAULT_SLOW_MOTION,
+            netplay: None,
+            rpc: None,
+            headless: false,
+            script: None,
+        }
+    }
+}
+
+impl Config {
+    /// Build the configuration from the command line arguments.
+    pub fn from_matches(matches: &ArgMatches) -> Config {
+        let mut config = Config::default();
+        if let Some(rom) = matches.value_of("ROM") {
+            config.rom_name = rom.to_string();
+        }
+        if let Some(theme) = matches.value_of("theme") {
+            config.theme = Theme::from_name(theme).expect("theme should be validated by clap");
+        }
+        config.foreground = matches.value_of("foreground").map(|hex| Config::parse_color("foreground", hex));
+        config.background = matches.value_of("background").map(|hex| Config::parse_color("background", hex));
+        if let Some(scale) = matches.value_of("scale") {
+            config.scale = Config::parse_scale(scale);
+        }
+        if let Some(fit) = matches.value_of("fit") {
+            config.fit = Fit::from_name(fit).expect("fit should be validated by clap");
+        }
+        config.fullscreen = matches.is_present("fullscreen");
+        if let Some(cycles) = matches.value_of("cycles-per-frame") {
+            config.cycles_per_frame = Config::parse_positive("cycles per frame", cycles);
+        }
+        if let Some(profile) = matches.value_of("quirks") {
+            config.quirks = Quirks::from_name(profile).expect("quirk profile should be validated by clap");
+        }
+        if let Some(engine) = matches.value_of("engine") {
+            config.engine = Engine::from_name(engine).unwrap_or_else(|| {
+                eprintln!("Unavailable engine '{}': build with --features {}", engine, engine);
+                std::process::exit(1);
+            });
+        }
+        config.filter = match matches.value_of("filter") {
+            Some("decay") => {
+                let half_life = matches.value_of("half-life")
+                    .map_or(DEFAULT_HALF_LIFE_MS, |ms| Config::parse_positive("half-life", ms));
+                Filter::Decay { half_life: half_life as f32 * FRAMES_PER_SECOND as f32 / 1000.0 }
+            },
+            Some("or")    => Filter::Or,
+            _             => Filter::None,
+        };
+        if let Some(effects) = matches.values_of("effects") {
+            config.effects = effects.map(|name| Effect::from_name(name).expect("effect should be validated by clap"))
+                .collect();
+        }
+        if let Some(dir) = matches.value_of("screenshot-dir") {
+            config.screenshot_dir = PathBuf::from(dir);
+        }
+        config.record = matches.is_present("record");
+        if let Some(format) = matches.value_of("record-format") {
+            config.record_format = VideoFormat::from_name(format).expect("format should be validated by clap");
+        }
+        if let Some(dir) = matches.value_of("record-dir") {
+            config.record_dir = PathBuf::from(dir);
+        }
+        config.stats = matches.is_present("stats");
+        config.profile = matches.value_of("profile").map(PathBuf::from);
+        config.trace = matches.value_of("trace").map(PathBuf::from);
+        if let Some(format) = matches.value_of("trace-format") {
+            config.trace_format = TraceFormat::from_name(format).expect("format should be validated by clap");
+        }
+        if let Some(range) = matches.value_of("trace-range") {
+            let (start, end) = Config::parse_range("trace range", range, 16, u16::MAX as u64);
+            config.trace_filter.addresses = Some(start.unwrap_or(0) as u16..=end.map_or(u16::MAX, |end| end as u16));
+        }
+        if let Some(range) = matches.value_of("trace-frames") {
+            let (start, end) = Config::parse_range("trace frames", range, 10, u64::MAX);
+            config.trace_filter.frames = Some(start.unwrap_or(0)..=end.unwrap_or(u64::MAX));
+        }
+        if let Some(speed) = matches.value_of("fast-forward") {
+            config.fast_forward = Config::parse_speed("fast-forward speed", speed);
+        }
+        if let Some(speed) = matches.value_of("turbo") {
+            config.turbo = Config::parse_speed("turbo speed", speed);
+        }
+        if let Some(speed) = matches.value_of("slow-motion") {
+            config.slow_motion = Config::parse_speed("slow motion speed", speed);
+        }
+        let peer = match (matches.value_of("host"), matches.value_of("join")) {
+            (Some(port), _)    => Some(Peer::Host(Config::parse_port(port))),
+            (_, Some(address)) => Some(Peer::Join(address.to_string())),
+            _                  => None,
+        };
+        let input_delay = matches.value_of("input-delay").expect("input delay should have a default");
+        config.netplay = peer.map(|peer| NetplayConfig {
+            peer,
+            input_delay: Config::parse_input_delay(input_delay),
+            keys: matches.values_of("netplay-keys").map(|keys| keys.fold(0, |mask, key| mask | Config::parse_key(key))),
+        });
+        config.rpc = matches.value_of("rpc").map(Address::parse);
+        config.headless = matches.is_present("headless");
+        config.script = matches.value_of("script").map(PathBuf::from);
+        if config.headless && config.rpc.is_none() && config.script.is_none() {
+            eprintln!("Nothing would drive the headless emulator: pass --rpc or --script");
+            std::process::exit(1);
+        }
+        if config.traces_to_stdout() && config.record_dir == Path::new(STDOUT) {
+            eprintln!("The trace and the recordings cannot both be written to stdout: pass a file to --trace");
+            std::process::exit(1);
+        }
+        config
+    }
+
+    /// Checks if the instruction trace is written to stdout, which then takes nothing else.
+    pub fn traces_to_stdout(&self) -> bool {
+        self.trace.as_ref().is_some_and(|path| path == Path::new(STDOUT))
+    }
+
+    /// Palette the display starts with.
+    pub fn palette(&self) -> Palette {
+        let mut palette = self.theme.palette();
+        if let Some(color) = self.foreground {
+            palette.set_foreground(color);
+        }
+        if let Some(color) = self.background {
+            palette.set_background(color);
+        }
+        palette
+    }
+
+    /// Parse a positive integer argument, exiting if it is malformed.
+    pub fn parse_positive(name: &str, value: &str) -> u32 {
+        match value.parse() {
+            Ok(number) if number > 0 => number,
+            _                        => {
+                eprintln!("Invalid {} '{}': expected a positive integer", name, value);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Parse a positive speed multiplier argument, exiting if it is malformed.
+    fn parse_speed(name: &str, value: &str) -> f32 {
+        match value.parse::<f32>() {
+            Ok(speed) if speed > 0.0 && speed.is_finite() => speed,
+            _                                             => {
+                eprintln!("Invalid {} '{}': expected a positive number", name, value);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Parse a range argument `START-END` with optional bounds up to `max`, exiting if it is malformed.
+    fn parse_range(name: &str, value: &str, radix: u32, max: u64) -> (Option<u64>, Option<u64>) {
+        match trace::parse_range(value, radix) {
+            Some((start, end)) if start.unwrap_or(0) <= max && end.unwrap_or(0) <= max => (start, end),
+            _                                                                        => {
+                let bounds = match radix {
+                    16 => format!("hex bounds up to {:X}", max),
+                    _  => format!("decimal bounds up to {}", max),
+                };
+                eprintln!("Invalid {} '{}': expected START-END with {}", name, value, bounds);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Parse a port argument, exiting if it is malformed.
+    fn parse_port(value: &str) -> u16 {
+        value.parse().unwrap_or_else(|_| {
+            eprintln!("Invalid port '{}': expected a number from 0 to 65535", value);
+            std::process::exit(1);
+        })
+    }
+
+    /// Parse a window scale argument, exiting if it is malformed or the window would be too large.
+    fn parse_scale(value: &str) -> u32 {
+        match value.parse() {
+            Ok(scale) if (1..=MAX_SCALE).contains(&scale) => scale,
+            _                                             => {
+                eprintln!("Invalid scale '{}': expected a positive integer up to {}", value, MAX_SCALE);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Parse an input delay argument in frames, exiting if it is malformed or too long.
+    fn parse_input_delay(value: &str) -> u8 {
+        match value.parse() {
+            Ok(frames) if frames <= MAX_INPUT_DELAY => frames,
+            _                                       => {
+                eprintln!("Invalid input delay '{}': expected at most {} frames", value, MAX_INPUT_DELAY);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Parse a hex key argument into its bit, exiting if it is malformed.
+    fn parse_key(value: &str) -> u16 {
+        match u8::from_str_radix(value, 16) {
+            Ok(key) if key < 16 => 1 << key,
+            _                   => {
+                eprintln!("Invalid key '{}': expected a hex digit", value);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    /// Parse a colour argument, exiting if it is malformed.
+    fn parse_color(name: &str, hex: &str) -> Rgb {
+        match Rgb::from_hex(hex) {
+            Some(color) => color,
+            None        => {
+                eprintln!("Invalid {} colour '{}': expected RRGGBB", name, hex);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_overrides_theme() {
+        let config = Config {
+            theme: Theme::Amber,
+            foreground: Some(Rgb::new(1, 2, 3)),
+            background: None,
+            ..Config::default()
+        };
+        let palette = config.palette();
+        assert_eq!(palette.foreground(), Rgb::new(1, 2, 3));
+        assert_eq!(palette.background(), Theme::Amber.palette().background());
+    }
+
+    #[test]
+    fn trace_to_stdout() {
+        assert!(!Config::default().traces_to_stdout());
+        assert!(!Config { trace: Some(PathBuf::from("trace.txt")), ..Config::default() }.traces_to_stdout());
+        assert!(Config { trace: Some(PathBuf::from("-")), ..Config::default() }.traces_to_stdout());
+    }
+}
@@ -1,10 +1,82 @@
 //! CPU module
 
-use rand::random;
+use std::time::Instant;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
 use prelude::*;
+use cache::BlockCache;
+use framebuffer::Framebuffer;
+use instruction::Instruction;
 use interconnect::Interconnect;
+#[cfg(feature = "jit")]
+use jit::{JitCache, Registers};
+use memory::{Memory, END_RESERVED, MEMORY_SIZE};
+use profiler::Profiler;
+use quirks::Quirks;
+use script::Script;
+use trace::{TraceEntry, Tracer};
+
+/// Number of instructions executed per frame unless configured otherwise, 600 per second at 60 frames per second.
+pub const DEFAULT_CYCLES_PER_FRAME: u32 = 10;
+/// Name of the emulated platform.
+pub const PLATFORM: &str = "CHIP-8";
+/// Frames per second. The timers count down and the display is presented once per frame.
+pub const FRAMES_PER_SECOND: u32 = 60;
+
+/// How instructions are executed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Engine {
+    /// Fetch and decode every instruction as it is executed.
+    #[default]
+    Interpreter,
+    /// Execute basic blocks that are decoded once and cached until the program overwrites them.
+    Cached,
+    /// Execute basic blocks translated to native code, interpreting the instructions native code does not cover.
+    #[cfg(feature = "jit")]
+    Jit,
+}
+
+impl Engine {
+    /// Look up an engine by its name.
+    pub fn from_name(name: &str) -> Option<Engine> {
+        match name {
+            "interpreter" => Some(Engine::Interpreter),
+            "cached"      => Some(Engine::Cached),
+            #[cfg(feature = "jit")]
+            "jit"         => Some(Engine::Jit),
+            _             => None,
+        }
+    }
+}
+
+/// Registers, stack and timers of a CPU.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct State {
+    pub pc: u16,
+    pub stack: [u16; 16],
+    pub sp: u8,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub dt: u8,
+    pub st: u8,
+}
 
+/// Everything a program can observe of the machine, to restore it later or on another machine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Registers, stack and timers.
+    pub state: State,
+    /// The whole memory.
+    pub memory: Vec<u8>,
+    /// The display.
+    pub framebuffer: Framebuffer,
+    /// Keys held down when `LD Vx, K` started waiting and not released since, which do not complete the wait.
+    pub held_keys: Option<u16>,
+    /// Key held down while `LD Vx, K` waits for its release.
+    pub pressed_key: Option<u8>,
+}
 
 /// A CHIP-8 CPU.
 pub struct Cpu<T, U> where T: Input, U: Graphics {
@@ -22,41 +94,370 @@ pub struct Cpu<T, U> where T: Input, U: Graphics {
     i: u16,
     // timer registers
     dt: u8,
+    st: u8,
+    // instructions executed per frame
+    cycles_per_frame: u32,
+    // behaviour of the instructions interpreters disagree on
+    quirks: Quirks,
+    // keys held down when `LD Vx, K` started waiting and not released since, the key pressed then that it waits for the
+    // release of, and when the wait started, only known when profiling
+    held_keys: Option<u16>,
+    pressed_key: Option<u8>,
+    key_wait_start: Option<Instant>,
+    // how instructions are executed, and the decoded blocks of the cached and JIT engines
+    engine: Engine,
+    cache: BlockCache,
+    #[cfg(feature = "jit")]
+    jit: JitCache,
+    // execution statistics, only collected when profiling
+    profiler: Option<Box<Profiler>>,
+    // log of the executed instructions, only written when tracing
+    tracer: Option<Box<Tracer>>,
+    // hooks run as the program executes, only when a script is loaded
+    script: Option<Box<Script>>,
+    // instructions executed and frames completed since power-on
+    cycles: u64,
+    frames: u64,
+    // random number generator of RND
+    rng: StdRng,
 }
 
 impl<T, U> Cpu<T, U> where T: Input, U: Graphics {
-    /// Constructor.
+    /// Constructor. Execution starts at the first instruction of the program, at 0x200.
     ///
     /// # Arguments
     /// - `interconnect`: the interconnect that the CPU will use to communicate with memory and peripherals.
     pub fn new(interconnect: Interconnect<T, U>) -> Cpu<T, U> {
         Cpu {
             interconnect,
-            pc: 0,
+            pc: END_RESERVED as u16,
             stack: [0; 16],
             sp: 0,
             v: [0; 16],
             i: 0,
-            dt: 0
+            dt: 0,
+            st: 0,
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            quirks: Quirks::default(),
+            held_keys: None,
+            pressed_key: None,
+            key_wait_start: None,
+            engine: Engine::default(),
+            cache: BlockCache::new(),
+            #[cfg(feature = "jit")]
+            jit: JitCache::new(),
+            profiler: None,
+            tracer: None,
+            script: None,
+            cycles: 0,
+            frames: 0,
+            rng: StdRng::from_entropy(),
         }
     }
 
-    /// Execute instructions from memory.
-    pub fn run(&mut self) {
-        loop {
-            if self.interconnect.input.close_requested() {
-                break
+    /// Reset the CPU to its power-on state with a freshly loaded ROM, keeping the peripherals.
+    ///
+    /// # Arguments
+    /// - `rom`: the program to load into memory.
+    ///
+    /// # Returns
+    /// Returns an error, leaving the machine untouched, if the program does not fit in memory.
+    pub fn reset(&mut self, rom: Vec<u8>) -> Result<(), String> {
+        self.interconnect.memory = Memory::new(rom)?;
+        self.clear_caches();
+        self.interconnect.graphics.cls();
+        self.pc = END_RESERVED as u16;
+        self.stack = [0; 16];
+        self.sp = 0;
+        self.v = [0; 16];
+        self.i = 0;
+        self.dt = 0;
+        self.st = 0;
+        self.held_keys = None;
+        self.pressed_key = None;
+        self.key_wait_start = None;
+        Ok(())
+    }
+
+    /// Seed the random number generator, making the results of `RND` reproducible.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Set the number of instructions executed per frame.
+    pub fn set_cycles_per_frame(&mut self, cycles_per_frame: u32) {
+        self.cycles_per_frame = cycles_per_frame;
+    }
+
+    /// Set the behaviour of the instructions interpreters disagree on.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        // native code has the quirks compiled in
+        self.clear_caches();
+    }
+
+    /// Set how instructions are executed.
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+        self.clear_caches();
+    }
+
+    /// Drop the decoded and compiled blocks.
+    fn clear_caches(&mut self) {
+        self.cache.clear();
+        #[cfg(feature = "jit")]
+        self.jit.clear();
+    }
+
+    /// Access the interconnect.
+    pub fn interconnect(&self) -> &Interconnect<T, U> {
+        &self.interconnect
+    }
+
+    /// Access the interconnect mutably.
+    pub fn interconnect_mut(&mut self) -> &mut Interconnect<T, U> {
+        &mut self.interconnect
+    }
+
+    /// Start collecting execution statistics.
+    pub fn enable_profiler(&mut self) {
+        self.profiler = Some(Box::new(Profiler::new()));
+    }
+
+    /// Execution statistics collected since the profiler was enabled.
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_deref()
+    }
+
+    /// Log every executed instruction from now on.
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    /// Stop logging instructions, flushing the log.
+    pub fn finish_trace(&mut self) {
+        if let Some(mut tracer) = self.tracer.take() {
+            if let Err(why) = tracer.flush() {
+                eprintln!("Cannot write trace: {}", why);
             }
-            self.execute_cycle();
-            self.interconnect.input.handle_inputs();
         }
     }
 
-    /// Execute a single cycle of the program.
-    fn execute_cycle(&mut self) {
+    /// Run the hooks of a script from now on, replacing the loaded one.
+    pub fn set_script(&mut self, script: Script) {
+        self.script = Some(Box::new(script));
+    }
+
+    /// The loaded script, unless there is none or it failed.
+    pub fn script_mut(&mut self) -> Option<&mut Script> {
+        self.script.as_deref_mut()
+    }
+
+    /// Program counter.
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// Address register.
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    /// Number of frames completed since power-on.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Registers, stack and timers.
+    pub fn state(&self) -> State {
+        State { pc: self.pc, stack: self.stack, sp: self.sp, v: self.v, i: self.i, dt: self.dt, st: self.st }
+    }
+
+    /// Overwrite the registers, stack and timers.
+    pub fn set_state(&mut self, state: State) {
+        self.pc = state.pc;
+        self.stack = state.stack;
+        self.sp = state.sp;
+        self.v = state.v;
+        self.i = state.i;
+        self.dt = state.dt;
+        self.st = state.st;
+    }
+
+    /// Save everything a program can observe of the machine.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            state: self.state(),
+            memory: self.interconnect.memory.ram().to_vec(),
+            framebuffer: self.interconnect.graphics.framebuffer().clone(),
+            held_keys: self.held_keys,
+            pressed_key: self.pressed_key,
+        }
+    }
+
+    /// Restore the machine from a snapshot, keeping the peripherals and the speed.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.set_state(snapshot.state);
+        for (addr, &byte) in snapshot.memory.iter().enumerate() {
+            self.interconnect.memory.write(addr, byte);
+        }
+        self.clear_caches();
+        *self.interconnect.graphics.framebuffer_mut() = snapshot.framebuffer.clone();
+        self.held_keys = snapshot.held_keys;
+        self.pressed_key = snapshot.pressed_key;
+        self.key_wait_start = None;
+    }
+
+    /// Number of instructions executed per frame.
+    pub fn cycles_per_frame(&self) -> u32 {
+        self.cycles_per_frame
+    }
+
+    /// Checks if the buzzer sounds, which it does while the sound timer is non-zero.
+    pub fn buzzer_on(&self) -> bool {
+        self.st > 0
+    }
+
+    /// Execute the instructions of a single frame and count down the timers.
+    pub fn execute_frame(&mut self) {
+        // hooks on instructions must see every one of them
+        let engine = if self.script.as_ref().is_some_and(|script| script.watches_instructions()) {
+            Engine::Interpreter
+        } else {
+            self.engine
+        };
+        match engine {
+            Engine::Interpreter => for _ in 0..self.cycles_per_frame {
+                self.execute_cycle();
+            },
+            Engine::Cached      => self.execute_cached(self.cycles_per_frame as usize),
+            #[cfg(feature = "jit")]
+            Engine::Jit         => self.execute_jit(self.cycles_per_frame as usize),
+        }
         self.handle_timers();
+        self.frames += 1;
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.record_frame();
+        }
+        self.run_script(Script::end_frame);
+        if self.tracer.as_ref().is_some_and(|tracer| tracer.is_done(self.frames)) {
+            self.finish_trace();
+        }
+    }
+
+    /// Execute a single instruction of the program, without counting down the timers.
+    pub fn execute_cycle(&mut self) {
+        self.wrap_pc();
         let opcode = self.interconnect.memory.read_word(self.pc as usize);
-        self.process_opcode(opcode);
+        self.observe(opcode);
+        if self.script.as_ref().is_some_and(|script| script.watches_instructions()) {
+            self.run_script(Script::execute);
+        } else {
+            self.process_opcode(opcode);
+        }
+        self.cycles += 1;
+    }
+
+    /// Run a hook of the loaded script, if any, dropping the script if the hook fails.
+    fn run_script<F: FnOnce(&mut Script, &mut Self) -> Result<(), String>>(&mut self, hook: F) {
+        if let Some(mut script) = self.script.take() {
+            match hook(&mut script, self) {
+                Ok(())   => self.script = Some(script),
+                Err(why) => eprintln!("Script stopped: {}", why),
+            }
+        }
+    }
+
+    /// Execute instructions from the block cache.
+    ///
+    /// # Arguments
+    /// - `cycles`: the number of instructions to execute, which may end in the middle of a block.
+    fn execute_cached(&mut self, cycles: usize) {
+        let mut remaining = cycles;
+        while remaining > 0 {
+            self.wrap_pc();
+            let block = self.cache.block(&mut self.interconnect.memory, self.pc);
+            for &(opcode, instruction) in block.instructions.iter().take(remaining) {
+                self.observe(opcode);
+                self.execute(instruction);
+                self.cycles += 1;
+                remaining -= 1;
+                // the rest of the block may just have been overwritten
+                if instruction.writes_memory() && self.cache.invalidate(&mut self.interconnect.memory) {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Execute instructions as native code, interpreting the instructions it does not cover. Everything is interpreted
+    /// while profiling or tracing, as native code does not report the instructions it executes.
+    ///
+    /// # Arguments
+    /// - `cycles`: the number of instructions to execute, which may end in the middle of a block.
+    #[cfg(feature = "jit")]
+    fn execute_jit(&mut self, cycles: usize) {
+        let mut remaining = cycles;
+        while remaining > 0 {
+            if self.profiler.is_none() && self.tracer.is_none() {
+                let mut registers = Registers { v: self.v, i: self.i, pc: self.pc, dt: self.dt };
+                while remaining > 0 {
+                    registers.pc %= MEMORY_SIZE as u16;
+                    let block = self.jit.block(&mut self.interconnect.memory, registers.pc, self.quirks);
+                    if block.is_empty() {
+                        break;
+                    }
+                    let executed = block.run(&mut registers, remaining);
+                    self.cycles += executed as u64;
+                    remaining -= executed;
+                }
+                self.v = registers.v;
+                self.i = registers.i;
+                self.pc = registers.pc;
+            }
+            if remaining > 0 {
+                self.execute_cycle();
+                remaining -= 1;
+            }
+        }
+    }
+
+    /// Bring a program counter that ran off the end of memory back to its start, as fetching wraps around.
+    fn wrap_pc(&mut self) {
+        self.pc %= MEMORY_SIZE as u16;
+    }
+
+    /// Let the profiler and the tracer see an instruction about to be executed, a waiting `LD Vx, K` only once.
+    fn observe(&mut self, opcode: u16) {
+        if self.waits_for_key() {
+            return;
+        }
+        if let Some(ref mut profiler) = self.profiler {
+            profiler.record(self.pc, opcode, self.dt);
+        }
+        if self.tracer.is_some() {
+            self.trace(opcode);
+        }
+    }
+
+    /// Log an instruction about to be executed, stopping the trace if it cannot be written.
+    fn trace(&mut self, opcode: u16) {
+        let entry = TraceEntry {
+            cycle: self.cycles,
+            frame: self.frames,
+            pc: self.pc,
+            opcode,
+            v: self.v,
+            i: self.i,
+            sp: self.sp,
+            dt: self.dt,
+        };
+        if let Some(ref mut tracer) = self.tracer {
+            if let Err(why) = tracer.trace(&entry) {
+                eprintln!("Cannot write trace: {}", why);
+                self.tracer = None;
+            }
+        }
     }
 
     /// Handle timers
@@ -64,134 +465,221 @@ impl<T, U> Cpu<T, U> where T: Input, U: Graphics {
         if self.dt > 0 {
             self.dt -= 1;
         }
+        if self.st > 0 {
+            self.st -= 1;
+        }
     }
 
-    /// Process an opcode.
-    fn process_opcode(&mut self, opcode: u16) {
-        // get potential register values and parameters
-        let x = ((opcode & 0x0F00) >> 8) as usize;
-        let y = ((opcode & 0x00F0) >> 4) as usize;
-        let vx = self.v[x];
-        let vy = self.v[y];
-        let nnn = opcode & 0x0FFF;
-        let kk = (opcode & 0x00FF) as u8;
-        let n = (opcode & 0x000F) as u8;
-
-        // beak opcode into nibbles
-        let op_1 = (opcode & 0xF000) >> 12;
-        let op_2 = (opcode & 0x0F00) >> 8;
-        let op_3 = (opcode & 0x00F0) >> 4;
-        let op_4 = opcode & 0x000F;
+    /// Process an opcode as if it was fetched at the program counter, without counting a cycle.
+    pub fn process_opcode(&mut self, opcode: u16) {
+        self.execute(Instruction::decode(opcode));
+    }
 
+    /// Execute a decoded instruction.
+    fn execute(&mut self, instruction: Instruction) {
         // increment program counter
         self.pc += 2;
 
-        match (op_1, op_2, op_3, op_4) {
+        match instruction {
             // CLS
-            (0, 0, 0xE, 0) => self.interconnect.graphics.cls(),
+            Instruction::Cls => self.interconnect.graphics.cls(),
             // RET
-            (0, 0, 0xE, 0xE) => {
-                self.sp = self.sp - 1;
+            Instruction::Ret => {
+                // returning with an empty stack returns to its bottom entry
+                self.sp = self.sp.saturating_sub(1);
                 self.pc = self.stack[self.sp as usize];
             },
+            Instruction::Nop => (),
             // JP
-            (0x1, _, _, _) => self.pc = nnn,
+            Instruction::Jp(nnn) => self.pc = nnn,
             // CALL
-            (0x2, _, _, _) => {
-                self.stack[self.sp as usize] = self.pc;
-                self.sp = self.sp + 1;
+            Instruction::Call(nnn) => {
+                // calling with a full stack loses the return address
+                if let Some(entry) = self.stack.get_mut(self.sp as usize) {
+                    *entry = self.pc;
+                    self.sp += 1;
+                }
                 self.pc = nnn;
             },
             // SE Vx KK
-            (0x3, _, _, _) => self.pc += if vx == kk { 2 } else { 0 },
+            Instruction::SeByte(x, kk) => self.pc += if self.v[x as usize] == kk { 2 } else { 0 },
             // SNE Vx KK
-            (0x4, _, _, _) => self.pc += if vx != kk { 2 } else { 0 },
+            Instruction::SneByte(x, kk) => self.pc += if self.v[x as usize] != kk { 2 } else { 0 },
             // SE Vx Vy
-            (0x5, _, _, _) => self.pc += if vx == vy { 2 } else { 0 },
+            Instruction::SeReg(x, y) => self.pc += if self.v[x as usize] == self.v[y as usize] { 2 } else { 0 },
             // LD Vx
-            (0x6, _, _, _) => self.v[x] = kk,
+            Instruction::LdByte(x, kk) => self.v[x as usize] = kk,
             // ADD Vx, byte
-            (0x7, _, _, _) => self.v[x] = vx.wrapping_add(kk),
+            Instruction::AddByte(x, kk) => self.v[x as usize] = self.v[x as usize].wrapping_add(kk),
             // LD Vx, Vy
-            (0x8, _, _, 0x0) => self.v[x] = self.v[y],
+            Instruction::LdReg(x, y) => self.v[x as usize] = self.v[y as usize],
             // OR Vx, Vy
-            (0x8, _, _, 0x1) => self.v[x] = self.v[x] | self.v[y],
+            Instruction::Or(x, y) => {
+                self.v[x as usize] = self.v[x as usize] | self.v[y as usize];
+                self.reset_vf();
+            },
             // AND Vx, Vy
-            (0x8, _, _, 0x2) => self.v[x] = self.v[x] & self.v[y],
+            Instruction::And(x, y) => {
+                self.v[x as usize] = self.v[x as usize] & self.v[y as usize];
+                self.reset_vf();
+            },
             // XOR Vx, Vy
-            (0x8, _, _, 0x3) => self.v[x] = self.v[x] ^ self.v[y],
+            Instruction::Xor(x, y) => {
+                self.v[x as usize] = self.v[x as usize] ^ self.v[y as usize];
+                self.reset_vf();
+            },
             // ADD Vx, Vy
-            (0x8, _, _, 0x4) => {
-                let res = self.v[x] as u16 + self.v[y] as u16;
+            Instruction::AddReg(x, y) => {
+                let res = self.v[x as usize] as u16 + self.v[y as usize] as u16;
                 self.v[0xF] = if res > 0xFF { 1 } else { 0 };
-                self.v[x] = (res & 0xFF) as u8;
+                self.v[x as usize] = (res & 0xFF) as u8;
             }
             // SUB Vx, Vy
-            (0x8, _, _, 0x5) => {
-                let res = self.v[x] as i8 - self.v[y] as i8;
-                self.v[x] = res as u8;
+            Instruction::Sub(x, y) => {
+                let res = (self.v[x as usize] as i8).wrapping_sub(self.v[y as usize] as i8);
+                self.v[x as usize] = res as u8;
                 self.v[0xF] = if res < 0 { 1 } else { 0 };
             }
             // SHR Vx
-            (0x8, _, _, 0x6) => {
-                self.v[0xF] = self.v[x] & 0x1;
-                self.v[x] >>= 1;
+            Instruction::Shr(x, y) => {
+                let value = self.v[if self.quirks.shift_vy { y } else { x } as usize];
+                self.v[0xF] = value & 0x1;
+                self.v[x as usize] = value >> 1;
             }
             // SUBN Vx, Vy
-            (0x8, _, _, 0x7) => {
-                let res = self.v[y] as i8 - self.v[x] as i8;
-                self.v[x] = res as u8;
+            Instruction::Subn(x, y) => {
+                let res = (self.v[y as usize] as i8).wrapping_sub(self.v[x as usize] as i8);
+                self.v[x as usize] = res as u8;
                 self.v[0xF] = if res < 0 { 1 } else { 0 };
             },
             // SHL Vx
-            (0x8, _, _, 0xE) => {
-                self.v[0xF] = self.v[x] & 0x80;
-                self.v[x] <<= 1;
+            Instruction::Shl(x, y) => {
+                let value = self.v[if self.quirks.shift_vy { y } else { x } as usize];
+                self.v[0xF] = value & 0x80;
+                self.v[x as usize] = value << 1;
             }
             // SNE Vx Vy
-            (0x9, _, _, _) => self.pc += if vx != vy { 2 } else { 0 },
+            Instruction::SneReg(x, y) => self.pc += if self.v[x as usize] != self.v[y as usize] { 2 } else { 0 },
             // LD I
-            (0xA, _, _, _) => self.i = nnn,
+            Instruction::LdI(nnn) => self.i = nnn,
             // JP V0
-            (0xB, _, _, _) => self.pc = nnn + self.v[0] as u16,
+            Instruction::JpV0(nnn) => {
+                let x = if self.quirks.jump_vx { (nnn >> 8) as usize } else { 0 };
+                self.pc = nnn + self.v[x] as u16;
+            },
             // RND
-            (0xC, _, _, _) => self.v[x] = random::<u8>() & kk,
+            Instruction::Rnd(x, kk) => self.v[x as usize] = self.rng.gen::<u8>() & kk,
             // DRW
-            (0xD, _, _, _) => {
-                let sprite = self.interconnect.memory.get_slice(self.i as usize, n);
-                let collision = self.interconnect.graphics.draw(vx as usize, vy as usize, sprite);
+            Instruction::Drw(x, y, n) => {
+                let (vx, vy) = (self.v[x as usize], self.v[y as usize]);
+                let mut sprite = [0; 16];
+                for (row, byte) in sprite[..n as usize].iter_mut().enumerate() {
+                    *byte = self.interconnect.memory.read(self.i as usize + row);
+                }
+                let collision = self.interconnect.graphics.draw(vx as usize, vy as usize, &sprite[..n as usize],
+                                                                self.quirks.clip);
                 self.v[0xF] = if collision { 1 } else { 0 };
             }
-            // SKP Vx
-            (0xE, _, 0x9, 0xE) => self.pc += if self.interconnect.input.is_key_down(vx) { 2 } else { 0 },
+            // SKP Vx, the key being the low nibble of Vx as on the COSMAC VIP
+            Instruction::Skp(x) => {
+                self.pc += if self.interconnect.input.is_key_down(self.v[x as usize] & 0xF) { 2 } else { 0 }
+            },
             // SKNP Vx
-            (0xE, _, 0xA, 0x1) => self.pc += if self.interconnect.input.is_key_down(vx) { 0 } else { 2 },
+            Instruction::Sknp(x) => {
+                self.pc += if self.interconnect.input.is_key_down(self.v[x as usize] & 0xF) { 0 } else { 2 }
+            },
             // LD Vx, DT
-            (0xF, _, 0x0, 0x7) => self.v[x] = self.dt,
+            Instruction::LdVxDt(x) => self.v[x as usize] = self.dt,
             // LD Vx, K
-            (0xF, _, 0x0, 0xA) => {
-                let key = self.interconnect.input.wait_input();
-                self.v[x] = key;
+            Instruction::LdVxK(x) => {
+                if self.profiler.is_some() && self.key_wait_start.is_none() {
+                    self.key_wait_start = Some(Instant::now());
+                }
+                match self.key_input() {
+                    Some(key) => {
+                        self.v[x as usize] = key;
+                        if let (Some(profiler), Some(start)) = (self.profiler.as_mut(), self.key_wait_start.take()) {
+                            profiler.record_key_wait(start.elapsed());
+                        }
+                    },
+                    // keep waiting, executing this instruction again so that the timers and the frames go on
+                    None      => self.pc -= 2,
+                }
             },
             // LD DT, Vx
-            (0xF, _, 0x1, 0x5) => self.dt = self.v[x],
+            Instruction::LdDtVx(x) => self.dt = self.v[x as usize],
+            // LD ST, Vx
+            Instruction::LdStVx(x) => self.st = self.v[x as usize],
             // ADD I, Vx
-            (0xF, _, 0x1, 0xE) => self.i = self.i + self.v[x] as u16,
+            Instruction::AddI(x) => self.i = self.i.wrapping_add(self.v[x as usize] as u16),
             // LD F, Vx
-            (0xF, _, 0x2, 0x9) => self.i = vx as u16 * 5,
+            Instruction::LdF(x) => self.i = self.v[x as usize] as u16 * 5,
             // LD B, Vx
-            (0xF, _, 0x3, 0x3) => {
+            Instruction::LdB(x) => {
+                let vx = self.v[x as usize];
                 self.interconnect.memory.write(self.i as usize, vx / 100);
                 self.interconnect.memory.write(self.i as usize + 1, (vx / 10) % 10);
                 self.interconnect.memory.write(self.i as usize + 2, (vx % 100) % 10);
             },
             // LD [I], Vx
-            (0xF, _, 0x5, 0x5) => self.interconnect.memory.get_slice_mut(self.i as usize, x as u8 + 1)
-                        .copy_from_slice(&self.v[0..(x as usize + 1)]),
+            Instruction::LdMemVx(x) => {
+                for reg in 0..=x as usize {
+                    self.interconnect.memory.write(self.i as usize + reg, self.v[reg]);
+                }
+                self.increment_i(x as usize);
+            },
             // LD Vx, [I]
-            (0xF, _, 0x6, 0x5) =>  self.v[0..(x as usize + 1)]
-                        .copy_from_slice(&self.interconnect.memory.get_slice_mut(self.i as usize, x as u8 + 1)),
-            (_, _, _, _) => ()
+            Instruction::LdVxMem(x) => {
+                for reg in 0..=x as usize {
+                    self.v[reg] = self.interconnect.memory.read(self.i as usize + reg);
+                }
+                self.increment_i(x as usize);
+            },
+        }
+    }
+
+    /// Check for the key `LD Vx, K` waits for, the lowest one pressed when several are.
+    ///
+    /// # Returns
+    /// Returns the key once it is pressed after the wait started, or released with the `key_release` quirk.
+    fn key_input(&mut self) -> Option<u8> {
+        let input = &self.interconnect.input;
+        let down = (0..16).filter(|&key| input.is_key_down(key)).fold(0u16, |keys, key| keys | 1 << key);
+        if let Some(key) = self.pressed_key {
+            return if down & 1 << key == 0 { self.pressed_key.take() } else { None };
+        }
+        // a key held down when the wait started only counts once released and pressed again
+        let held = self.held_keys.unwrap_or(down) & down;
+        let pressed = down & !held;
+        if pressed == 0 {
+            self.held_keys = Some(held);
+            return None;
+        }
+        self.held_keys = None;
+        let key = pressed.trailing_zeros() as u8;
+        if self.quirks.key_release {
+            self.pressed_key = Some(key);
+            return None;
+        }
+        Some(key)
+    }
+
+    /// Checks if `LD Vx, K` started waiting for a key and is executed again until it gets one.
+    fn waits_for_key(&self) -> bool {
+        self.held_keys.is_some() || self.pressed_key.is_some()
+    }
+
+    /// Reset VF after a logical operation if the quirk is enabled.
+    fn reset_vf(&mut self) {
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
+    }
+
+    /// Move I past the registers stored or loaded up to VX if the quirk is enabled.
+    fn increment_i(&mut self, x: usize) {
+        if self.quirks.load_store_increment {
+            self.i = self.i.wrapping_add(x as u16 + 1);
         }
     }
 }
@@ -210,6 +698,189 @@ mod tests {
         cpu
     }
 
+    #[test]
+    fn timers_count_down_once_per_frame() {
+        let mut cpu = get_cpu();
+        cpu.dt = 5;
+
+        cpu.execute_frame();
+        assert_eq!(cpu.dt, 4, "the delay timer is decremented once");
+        assert_eq!(cpu.pc, 0x200 + 2 * DEFAULT_CYCLES_PER_FRAME as u16, "a frame worth of instructions is executed");
+    }
+
+    #[test]
+    fn profiler_records_instructions() {
+        let mut cpu = get_cpu();
+        assert!(cpu.profiler().is_none(), "profiling is disabled by default");
+        cpu.enable_profiler();
+        cpu.execute_frame();
+        let profiler = cpu.profiler().unwrap();
+        assert_eq!(profiler.count(0x200), 1);
+        assert_eq!(profiler.count(0x200 + 2 * (DEFAULT_CYCLES_PER_FRAME as usize - 1)), 1);
+    }
+
+    #[test]
+    fn seeded_rng_is_reproducible() {
+        let run = |seed| {
+            let mut cpu = get_cpu();
+            cpu.seed_rng(seed);
+            (0..8).map(|_| { cpu.process_opcode(0xC0FF); cpu.v[0] }).collect::<Vec<u8>>()
+        };
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+    }
+
+    #[test]
+    fn reset_reloads_rom() {
+        let mut cpu = get_cpu();
+        cpu.pc = 0x204;
+        cpu.v[3] = 7;
+        cpu.sp = 1;
+        cpu.st = 5;
+        cpu.interconnect.memory.write(0x200, 0xFF);
+        cpu.reset(vec![0x12, 0x34]).unwrap();
+        assert_eq!(cpu.pc, 0x200, "the program counter is reset to the start of the program");
+        assert_eq!((cpu.v[3], cpu.sp, cpu.st), (0, 0, 0), "the registers are cleared");
+        assert_eq!(cpu.interconnect.memory.read_word(0x200), 0x1234, "the new ROM is loaded");
+
+        cpu.v[3] = 7;
+        assert!(cpu.reset(vec![0; 4096]).is_err(), "a ROM larger than memory is rejected");
+        assert_eq!((cpu.v[3], cpu.interconnect.memory.read_word(0x200)), (7, 0x1234), "the machine is untouched");
+    }
+
+    #[test]
+    fn restores_snapshots() {
+        let mut cpu = get_cpu();
+        cpu.set_quirks(Quirks::from_name("cosmac").unwrap());
+        cpu.v[4] = 9;
+        cpu.interconnect.memory.write(0x300, 0xAB);
+        cpu.interconnect.graphics.set_pixel(5, 6, true);
+        cpu.interconnect.input.press_key(0x1);
+        cpu.process_opcode(0xF10A);
+        cpu.interconnect.input.press_key(0x2);
+        cpu.process_opcode(0xF10A);
+        let snapshot = cpu.snapshot();
+
+        let mut other = get_cpu();
+        other.restore(&snapshot);
+        assert_eq!(other.snapshot(), snapshot);
+        assert!(other.interconnect.graphics.get_pixel(5, 6), "the display is restored");
+        assert_eq!(other.pressed_key, Some(0x2), "the key waited for is restored");
+        assert_eq!(other.held_keys, None, "the keys held before the wait are restored");
+    }
+
+    #[test]
+    fn cosmac_quirks() {
+        let mut cpu = get_cpu();
+        cpu.set_quirks(Quirks::from_name("cosmac").unwrap());
+        cpu.v[0xF] = 1;
+        cpu.process_opcode(0x8011);
+        assert_eq!(cpu.v[0xF], 0, "logical operations reset VF");
+
+        cpu.v[2] = 0x81;
+        cpu.process_opcode(0x8126);
+        assert_eq!((cpu.v[1], cpu.v[0xF]), (0x40, 1), "VY is shifted into VX");
+
+        cpu.i = 0x300;
+        cpu.process_opcode(0xF255);
+        assert_eq!(cpu.i, 0x303, "I points after the stored registers");
+    }
+
+    #[test]
+    fn key_wait_keeps_the_timers_running() {
+        // LD V1, K
+        let mut cpu = Cpu::new(Interconnect::new_test(vec![0xF1, 0x0A]));
+        cpu.dt = 5;
+        cpu.execute_frame();
+        assert_eq!((cpu.pc, cpu.dt), (0x200, 4), "the instruction waits while the timers count down");
+
+        cpu.interconnect.input.press_key(0xB);
+        cpu.interconnect.input.press_key(0x7);
+        cpu.execute_cycle();
+        assert_eq!((cpu.pc, cpu.v[1]), (0x202, 0x7), "the lowest key held down is taken");
+    }
+
+    #[test]
+    fn key_wait_takes_new_presses_only() {
+        // LD V1, K
+        let mut cpu = Cpu::new(Interconnect::new_test(vec![0xF1, 0x0A]));
+        cpu.interconnect.input.press_key(0x4);
+        cpu.execute_frame();
+        assert_eq!(cpu.pc, 0x200, "a key held down before the wait does not complete it");
+
+        cpu.interconnect.input.release_key(0x4);
+        cpu.execute_cycle();
+        cpu.interconnect.input.press_key(0x4);
+        cpu.execute_cycle();
+        assert_eq!((cpu.pc, cpu.v[1]), (0x202, 0x4), "the key completes the wait once pressed again");
+    }
+
+    #[test]
+    fn key_wait_is_observed_once() {
+        // LD V1, K
+        let mut cpu = Cpu::new(Interconnect::new_test(vec![0xF1, 0x0A]));
+        cpu.enable_profiler();
+        cpu.execute_frame();
+        cpu.interconnect.input.press_key(0x4);
+        cpu.execute_cycle();
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.profiler().unwrap().count(0x200), 1, "the waiting instruction is counted once");
+    }
+
+    #[test]
+    fn key_wait_on_release() {
+        let mut cpu = get_cpu();
+        cpu.set_quirks(Quirks::from_name("cosmac").unwrap());
+        cpu.process_opcode(0xF10A);
+        cpu.interconnect.input.press_key(0x3);
+        cpu.process_opcode(0xF10A);
+        cpu.interconnect.input.press_key(0x1);
+        cpu.process_opcode(0xF10A);
+        assert_eq!(cpu.pc, 0x200, "the instruction waits while the key is held down");
+
+        cpu.interconnect.input.release_key(0x3);
+        cpu.process_opcode(0xF10A);
+        assert_eq!((cpu.pc, cpu.v[1]), (0x202, 0x3), "the key pressed first completes the wait once released");
+    }
+
+    #[test]
+    fn cached_engine_runs_self_modifying_code() {
+        // LD V0, 0x61; LD V1, 0x2A; LD I, 0x208; LD [I], V1; LD V1, 0x00, the last instruction being overwritten by
+        // the one before to LD V1, 0x2A
+        let rom = vec![0x60, 0x61, 0x61, 0x2A, 0xA2, 0x08, 0xF1, 0x55, 0x61, 0x00];
+        let mut cpu = Cpu::new(Interconnect::new_test(rom));
+        cpu.set_engine(Engine::Cached);
+        cpu.execute_frame();
+        assert_eq!(cpu.v[1], 0x2A, "the overwritten instruction is executed");
+        assert_eq!(cpu.pc, 0x200 + 2 * DEFAULT_CYCLES_PER_FRAME as u16, "a frame worth of instructions is executed");
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn jit_engine_runs_self_modifying_code() {
+        // as above, with native code for all but LD [I], V1
+        let rom = vec![0x60, 0x61, 0x61, 0x2A, 0xA2, 0x08, 0xF1, 0x55, 0x61, 0x00];
+        let mut cpu = Cpu::new(Interconnect::new_test(rom));
+        cpu.set_engine(Engine::Jit);
+        cpu.execute_frame();
+        assert_eq!(cpu.v[1], 0x2A, "the overwritten instruction is executed");
+        assert_eq!(cpu.pc, 0x200 + 2 * DEFAULT_CYCLES_PER_FRAME as u16, "a frame worth of instructions is executed");
+    }
+
+    #[test]
+    fn opcode_ld_st_vx() {
+        let mut cpu = get_cpu();
+        cpu.v[3] = 2;
+
+        cpu.process_opcode(0xF318);
+        assert_eq!(cpu.st, 2, "the sound timer is set");
+        assert!(cpu.buzzer_on(), "the buzzer sounds while the sound timer runs");
+
+        cpu.handle_timers();
+        cpu.handle_timers();
+        assert!(!cpu.buzzer_on(), "the buzzer stops once the sound timer expired");
+    }
+
     #[test]
     fn opcode_jp() {
         let mut cpu = get_cpu();
@@ -237,11 +908,11 @@ mod tests {
 
         // vx == kk
         cpu.process_opcode(0x31FE);
-        assert_eq!(cpu.pc, 4, "the stack pointer skips");
+        assert_eq!(cpu.pc, 0x204, "the stack pointer skips");
 
         // vx != kk
         cpu.process_opcode(0x31FA);
-        assert_eq!(cpu.pc, 6, "the stack pointer is incremented");
+        assert_eq!(cpu.pc, 0x206, "the stack pointer is incremented");
     }
 
     #[test]
@@ -251,11 +922,11 @@ mod tests {
 
         // vx == kk
         cpu.process_opcode(0x41FE);
-        assert_eq!(cpu.pc, 2, "the stack pointer is incremented");
+        assert_eq!(cpu.pc, 0x202, "the stack pointer is incremented");
 
         // vx != kk
         cpu.process_opcode(0x41FA);
-        assert_eq!(cpu.pc, 6, "the stack pointer skips");
+        assert_eq!(cpu.pc, 0x206, "the stack pointer skips");
     }
 
     #[test]
@@ -267,11 +938,11 @@ mod tests {
 
         // vx == vy
         cpu.process_opcode(0x5230);
-        assert_eq!(cpu.pc, 4, "the stack pointer skips");
+        assert_eq!(cpu.pc, 0x204, "the stack pointer skips");
 
         // vx != vy
         cpu.process_opcode(0x5130);
-        assert_eq!(cpu.pc, 6, "the stack pointer is incremented");
+        assert_eq!(cpu.pc, 0x206, "the stack pointer is incremented");
     }
 
     #[test]
@@ -283,11 +954,11 @@ mod tests {
 
         // vx == vy
         cpu.process_opcode(0x9230);
-        assert_eq!(cpu.pc, 2, "the stack pointer is incremented");
+        assert_eq!(cpu.pc, 0x202, "the stack pointer is incremented");
 
         // vx != vy
         cpu.process_opcode(0x9130);
-        assert_eq!(cpu.pc, 6, "the stack pointer skips");
+        assert_eq!(cpu.pc, 0x206, "the stack pointer skips");
     }
 
     #[test]
@@ -425,15 +1096,15 @@ mod tests {
 
         cpu.process_opcode(0x61AA);
         assert_eq!(cpu.v[1], 0xAA, "V1 is set");
-        assert_eq!(cpu.pc, 2, "the program counter is advanced two bytes");
+        assert_eq!(cpu.pc, 0x202, "the program counter is advanced two bytes");
 
         cpu.process_opcode(0x621A);
         assert_eq!(cpu.v[2], 0x1A, "V2 is set");
-        assert_eq!(cpu.pc, 4, "the program counter is advanced two bytes");
+        assert_eq!(cpu.pc, 0x204, "the program counter is advanced two bytes");
 
         cpu.process_opcode(0x6A15);
         assert_eq!(cpu.v[10], 0x15, "V10 is set");
-        assert_eq!(cpu.pc, 6, "the program counter is advanced two bytes");
+        assert_eq!(cpu.pc, 0x206, "the program counter is advanced two bytes");
     }
 
     #[test]
@@ -442,6 +1113,6 @@ mod tests {
         cpu.process_opcode(0xAFAF);
 
         assert_eq!(cpu.i, 0x0FAF, "the 'i' register is updated");
-        assert_eq!(cpu.pc, 2, "the program counter is advanced two bytes");
+        assert_eq!(cpu.pc, 0x202, "the program counter is advanced two bytes");
     }
 }
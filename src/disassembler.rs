@@ -0,0 +1,115 @@
+//! Disassembler module. Translates opcodes to the mnemonics of Cowgod's CHIP-8 technical reference.
+
+/// Get the pattern of an opcode, such as `8XY4`, naming the instruction independently of its operands.
+pub fn pattern(opcode: u16) -> &'static str {
+    let (op_1, op_3, op_4) = ((opcode & 0xF000) >> 12, (opcode & 0x00F0) >> 4, opcode & 0x000F);
+    match (op_1, opcode & 0x0FFF, op_3, op_4) {
+        (0x0, 0x0E0, _, _)   => "00E0",
+        (0x0, 0x0EE, _, _)   => "00EE",
+        (0x0, _, _, _)       => "0NNN",
+        (0x1, _, _, _)       => "1NNN",
+        (0x2, _, _, _)       => "2NNN",
+        (0x3, _, _, _)       => "3XKK",
+        (0x4, _, _, _)       => "4XKK",
+        (0x5, _, _, 0x0)     => "5XY0",
+        (0x6, _, _, _)       => "6XKK",
+        (0x7, _, _, _)       => "7XKK",
+        (0x8, _, _, 0x0)     => "8XY0",
+        (0x8, _, _, 0x1)     => "8XY1",
+        (0x8, _, _, 0x2)     => "8XY2",
+        (0x8, _, _, 0x3)     => "8XY3",
+        (0x8, _, _, 0x4)     => "8XY4",
+        (0x8, _, _, 0x5)     => "8XY5",
+        (0x8, _, _, 0x6)     => "8XY6",
+        (0x8, _, _, 0x7)     => "8XY7",
+        (0x8, _, _, 0xE)     => "8XYE",
+        (0x9, _, _, 0x0)     => "9XY0",
+        (0xA, _, _, _)       => "ANNN",
+        (0xB, _, _, _)       => "BNNN",
+        (0xC, _, _, _)       => "CXKK",
+        (0xD, _, _, _)       => "DXYN",
+        (0xE, _, 0x9, 0xE)   => "EX9E",
+        (0xE, _, 0xA, 0x1)   => "EXA1",
+        (0xF, _, 0x0, 0x7)   => "FX07",
+        (0xF, _, 0x0, 0xA)   => "FX0A",
+        (0xF, _, 0x1, 0x5)   => "FX15",
+        (0xF, _, 0x1, 0x8)   => "FX18",
+        (0xF, _, 0x1, 0xE)   => "FX1E",
+        (0xF, _, 0x2, 0x9)   => "FX29",
+        (0xF, _, 0x3, 0x3)   => "FX33",
+        (0xF, _, 0x5, 0x5)   => "FX55",
+        (0xF, _, 0x6, 0x5)   => "FX65",
+        _                    => "????",
+    }
+}
+
+/// Disassemble an opcode, such as `ADD V1, V2` for `0x8124`. Unknown opcodes are shown as data words.
+pub fn disassemble(opcode: u16) -> String {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let nnn = opcode & 0x0FFF;
+    let kk = opcode & 0x00FF;
+    let n = opcode & 0x000F;
+    match pattern(opcode) {
+        "00E0" => "CLS".to_string(),
+        "00EE" => "RET".to_string(),
+        "0NNN" => format!("SYS 0x{:03X}", nnn),
+        "1NNN" => format!("JP 0x{:03X}", nnn),
+        "2NNN" => format!("CALL 0x{:03X}", nnn),
+        "3XKK" => format!("SE V{:X}, 0x{:02X}", x, kk),
+        "4XKK" => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        "5XY0" => format!("SE V{:X}, V{:X}", x, y),
+        "6XKK" => format!("LD V{:X}, 0x{:02X}", x, kk),
+        "7XKK" => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        "8XY0" => format!("LD V{:X}, V{:X}", x, y),
+        "8XY1" => format!("OR V{:X}, V{:X}", x, y),
+        "8XY2" => format!("AND V{:X}, V{:X}", x, y),
+        "8XY3" => format!("XOR V{:X}, V{:X}", x, y),
+        "8XY4" => format!("ADD V{:X}, V{:X}", x, y),
+        "8XY5" => format!("SUB V{:X}, V{:X}", x, y),
+        "8XY6" => format!("SHR V{:X}", x),
+        "8XY7" => format!("SUBN V{:X}, V{:X}", x, y),
+        "8XYE" => format!("SHL V{:X}", x),
+        "9XY0" => format!("SNE V{:X}, V{:X}", x, y),
+        "ANNN" => format!("LD I, 0x{:03X}", nnn),
+        "BNNN" => format!("JP V0, 0x{:03X}", nnn),
+        "CXKK" => format!("RND V{:X}, 0x{:02X}", x, kk),
+        "DXYN" => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+        "EX9E" => format!("SKP V{:X}", x),
+        "EXA1" => format!("SKNP V{:X}", x),
+        "FX07" => format!("LD V{:X}, DT", x),
+        "FX0A" => format!("LD V{:X}, K", x),
+        "FX15" => format!("LD DT, V{:X}", x),
+        "FX18" => format!("LD ST, V{:X}", x),
+        "FX1E" => format!("ADD I, V{:X}", x),
+        "FX29" => format!("LD F, V{:X}", x),
+        "FX33" => format!("LD B, V{:X}", x),
+        "FX55" => format!("LD [I], V{:X}", x),
+        "FX65" => format!("LD V{:X}, [I]", x),
+        _      => format!("DW 0x{:04X}", opcode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        assert_eq!(pattern(0x00E0), "00E0");
+        assert_eq!(pattern(0x0123), "0NNN");
+        assert_eq!(pattern(0x8AB4), "8XY4");
+        assert_eq!(pattern(0x8AB9), "????");
+        assert_eq!(pattern(0xF30A), "FX0A");
+    }
+
+    #[test]
+    fn mnemonics() {
+        assert_eq!(disassemble(0x00EE), "RET");
+        assert_eq!(disassemble(0x2ABC), "CALL 0xABC");
+        assert_eq!(disassemble(0x7A05), "ADD VA, 0x05");
+        assert_eq!(disassemble(0xD125), "DRW V1, V2, 5");
+        assert_eq!(disassemble(0xF155), "LD [I], V1");
+        assert_eq!(disassemble(0x5121), "DW 0x5121");
+    }
+}
@@ -0,0 +1,283 @@
+//! Effects module. Contains CPU post-processing effects imitating CRT and LCD screens.
+//!
+//! The effects operate on an upscaled copy of the display, so that each emulated pixel covers a block of
+//! `UPSCALE x UPSCALE` output pixels the effects can shape.
+
+use graphics::BYTES_PER_PIXEL;
+
+/// Factor the display is upscaled by before applying effects.
+pub const UPSCALE: usize = 6;
+/// Brightness of the dark lines between scanlines.
+const SCANLINE_BRIGHTNESS: f32 = 0.55;
+/// Brightness of the gaps between LCD cells.
+const GRID_BRIGHTNESS: f32 = 0.3;
+/// Weight of the blurred image added by the glow effect.
+const GLOW_STRENGTH: f32 = 0.6;
+/// Strength of the barrel distortion.
+const CURVATURE: f32 = 0.08;
+
+/// A post-processing effect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effect {
+    /// Darken every other output row, like the gaps between CRT scanlines.
+    Scanlines,
+    /// Darken the border of each emulated pixel, like the gaps between LCD cells.
+    Grid,
+    /// Add a blurred copy of the image, making bright pixels bleed into their surroundings.
+    Glow,
+    /// Bulge the image outwards, like the curved glass of a CRT.
+    Curvature,
+}
+
+impl Effect {
+    /// Look up an effect by its name.
+    pub fn from_name(name: &str) -> Option<Effect> {
+        match name {
+            "scanlines" => Some(Effect::Scanlines),
+            "grid"      => Some(Effect::Grid),
+            "glow"      => Some(Effect::Glow),
+            "curvature" => Some(Effect::Curvature),
+            _           => None,
+        }
+    }
+
+    /// Apply the effect to an image.
+    ///
+    /// # Arguments
+    /// - `image`: the upscaled image to modify.
+    /// - `scale`: the number of output pixels per emulated pixel along each axis.
+    pub fn apply(self, image: &mut Image, scale: usize) {
+        match self {
+            Effect::Scanlines => scanlines(image),
+            Effect::Grid      => grid(image, scale),
+            Effect::Glow      => glow(image, scale),
+            Effect::Curvature => curvature(image),
+        }
+    }
+}
+
+/// An image of packed RGB24 pixels.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Constructor for a black image.
+    pub fn new(width: usize, height: usize) -> Image {
+        Image { width, height, pixels: vec![0; width * height * BYTES_PER_PIXEL] }
+    }
+
+    /// Upscale packed RGB24 pixels by repeating each pixel `scale` times along both axes.
+    pub fn upscale(pixels: &[u8], width: usize, height: usize, scale: usize) -> Image {
+        let mut image = Image::new(width * scale, height * scale);
+        let row_len = image.width * BYTES_PER_PIXEL;
+        for y in 0..height {
+            let row_start = y * scale * row_len;
+            for x in 0..width {
+                let src = &pixels[(x + y * width) * BYTES_PER_PIXEL..(x + y * width + 1) * BYTES_PER_PIXEL];
+                for i in 0..scale {
+                    let dst = row_start + (x * scale + i) * BYTES_PER_PIXEL;
+                    image.pixels[dst..dst + BYTES_PER_PIXEL].copy_from_slice(src);
+                }
+            }
+            // the remaining rows of the block are copies of the first one
+            for j in 1..scale {
+                image.pixels.copy_within(row_start..row_start + row_len, row_start + j * row_len);
+            }
+        }
+        image
+    }
+
+    /// Get the pixel at a location.
+    #[inline(always)]
+    pub fn pixel(&self, x: usize, y: usize) -> &[u8] {
+        let idx = (x + y * self.width) * BYTES_PER_PIXEL;
+        &self.pixels[idx..idx + BYTES_PER_PIXEL]
+    }
+
+    /// Multiply the channels of the pixel at a location by `factor`.
+    #[inline(always)]
+    fn dim(&mut self, x: usize, y: usize, factor: f32) {
+        let idx = (x + y * self.width) * BYTES_PER_PIXEL;
+        for channel in &mut self.pixels[idx..idx + BYTES_PER_PIXEL] {
+            *channel = (*channel as f32 * factor) as u8;
+        }
+    }
+}
+
+/// Pipeline of effects applied to every presented frame.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PostProcessor {
+    effects: Vec<Effect>,
+}
+
+impl PostProcessor {
+    /// Constructor.
+    ///
+    /// # Arguments
+    /// - `effects`: the effects to apply, in order.
+    pub fn new(effects: Vec<Effect>) -> PostProcessor {
+        PostProcessor { effects }
+    }
+
+    /// Checks if no effects are applied.
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Upscale packed RGB24 pixels and apply all effects.
+    pub fn process(&self, pixels: &[u8], width: usize, height: usize) -> Image {
+        let mut image = Image::upscale(pixels, width, height, UPSCALE);
+        for effect in &self.effects {
+            effect.apply(&mut image, UPSCALE);
+        }
+        image
+    }
+}
+
+/// Darken every other row.
+fn scanlines(image: &mut Image) {
+    for y in (1..image.height).step_by(2) {
+        for x in 0..image.width {
+            image.dim(x, y, SCANLINE_BRIGHTNESS);
+        }
+    }
+}
+
+/// Darken the last row and column of each block of `scale x scale` pixels.
+fn grid(image: &mut Image, scale: usize) {
+    for y in 0..image.height {
+        for x in 0..image.width {
+            if x % scale == scale - 1 || y % scale == scale - 1 {
+                image.dim(x, y, GRID_BRIGHTNESS);
+            }
+        }
+    }
+}
+
+/// Add a box blurred copy of the image with a radius of half an emulated pixel.
+fn glow(image: &mut Image, scale: usize) {
+    let radius = (scale / 2).max(1);
+    let horizontal = box_blur(&image.pixels, image.width, image.height, radius, true);
+    let blurred = box_blur(&horizontal, image.width, image.height, radius, false);
+    for (channel, glow) in image.pixels.iter_mut().zip(blurred.iter()) {
+        *channel = (*channel as f32 + *glow as f32 * GLOW_STRENGTH).min(255.0) as u8;
+    }
+}
+
+/// Blur packed RGB24 pixels along one axis with a moving average over `2 * radius + 1` pixels.
+fn box_blur(pixels: &[u8], width: usize, height: usize, radius: usize, horizontal: bool) -> Vec<u8> {
+    let mut out = vec![0; pixels.len()];
+    let (lines, len) = if horizontal { (height, width) } else { (width, height) };
+    let index = |line: usize, pos: usize| {
+        let (x, y) = if horizontal { (pos, line) } else { (line, pos) };
+        (x + y * width) * BYTES_PER_PIXEL
+    };
+    let window = (2 * radius + 1) as u32;
+    for line in 0..lines {
+        for channel in 0..BYTES_PER_PIXEL {
+            // running sum over the window, treating pixels outside the image as black
+            let mut sum: u32 = (0..radius.min(len)).map(|pos| pixels[index(line, pos) + channel] as u32).sum();
+            for pos in 0..len {
+                if pos + radius < len {
+                    sum += pixels[index(line, pos + radius) + channel] as u32;
+                }
+                if pos > radius {
+                    sum -= pixels[index(line, pos - radius - 1) + channel] as u32;
+                }
+                out[index(line, pos) + channel] = (sum / window) as u8;
+            }
+        }
+    }
+    out
+}
+
+/// Apply a barrel distortion, leaving the corners black.
+fn curvature(image: &mut Image) {
+    let source = image.pixels.clone();
+    let (width, height) = (image.width as f32, image.height as f32);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            // coordinates relative to the centre, in [-1, 1]
+            let u = (x as f32 + 0.5) / width * 2.0 - 1.0;
+            let v = (y as f32 + 0.5) / height * 2.0 - 1.0;
+            let distortion = 1.0 + CURVATURE * (u * u + v * v);
+            let (su, sv) = (u * distortion, v * distortion);
+
+            let idx = (x + y * image.width) * BYTES_PER_PIXEL;
+            if su.abs() > 1.0 || sv.abs() > 1.0 {
+                image.pixels[idx..idx + BYTES_PER_PIXEL].copy_from_slice(&[0, 0, 0]);
+            } else {
+                let sx = (((su + 1.0) / 2.0 * width) as usize).min(image.width - 1);
+                let sy = (((sv + 1.0) / 2.0 * height) as usize).min(image.height - 1);
+                let src = (sx + sy * image.width) * BYTES_PER_PIXEL;
+                image.pixels[idx..idx + BYTES_PER_PIXEL].copy_from_slice(&source[src..src + BYTES_PER_PIXEL]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A white image of `size x size` emulated pixels, upscaled by `scale`.
+    fn white(size: usize, scale: usize) -> Image {
+        Image::upscale(&vec![255; size * size * BYTES_PER_PIXEL], size, size, scale)
+    }
+
+    #[test]
+    fn upscale_repeats_pixels() {
+        let image = Image::upscale(&[1, 2, 3, 4, 5, 6], 2, 1, 2);
+        assert_eq!((image.width, image.height), (4, 2));
+        assert_eq!(image.pixels, vec![1, 2, 3, 1, 2, 3, 4, 5, 6, 4, 5, 6, 1, 2, 3, 1, 2, 3, 4, 5, 6, 4, 5, 6]);
+    }
+
+    #[test]
+    fn scanlines_dim_odd_rows() {
+        let mut image = white(1, 4);
+        Effect::Scanlines.apply(&mut image, 4);
+        assert_eq!(image.pixel(0, 0), [255, 255, 255]);
+        assert!(image.pixel(0, 1)[0] < 255);
+        assert_eq!(image.pixel(0, 2), [255, 255, 255]);
+    }
+
+    #[test]
+    fn grid_dims_cell_borders() {
+        let mut image = white(2, 3);
+        Effect::Grid.apply(&mut image, 3);
+        assert_eq!(image.pixel(0, 0), [255, 255, 255]);
+        assert!(image.pixel(2, 0)[0] < 255, "right border of the first cell");
+        assert!(image.pixel(0, 2)[0] < 255, "bottom border of the first cell");
+        assert_eq!(image.pixel(3, 3), [255, 255, 255], "inside of the last cell");
+    }
+
+    #[test]
+    fn glow_bleeds_into_neighbours() {
+        let mut pixels = vec![0; 3 * BYTES_PER_PIXEL];
+        pixels[BYTES_PER_PIXEL..2 * BYTES_PER_PIXEL].copy_from_slice(&[200, 200, 200]);
+        let mut image = Image::upscale(&pixels, 3, 1, 2);
+        Effect::Glow.apply(&mut image, 2);
+        assert!(image.pixel(1, 0)[0] > 0, "dark pixel next to a lit one glows");
+        assert_eq!(image.pixel(0, 0), [0, 0, 0], "far away pixels stay dark");
+        assert!(image.pixel(2, 0)[0] > 200, "lit pixels get brighter");
+    }
+
+    #[test]
+    fn curvature_blackens_corners() {
+        let mut image = white(4, 4);
+        Effect::Curvature.apply(&mut image, 4);
+        assert_eq!(image.pixel(0, 0), [0, 0, 0]);
+        assert_eq!(image.pixel(8, 8), [255, 255, 255], "centre is unchanged");
+    }
+
+    #[test]
+    fn post_processor_upscales() {
+        let processor = PostProcessor::new(vec![Effect::Scanlines, Effect::Grid]);
+        let image = processor.process(&[255; 2 * BYTES_PER_PIXEL], 2, 1);
+        assert_eq!((image.width, image.height), (2 * UPSCALE, UPSCALE));
+    }
+}
@@ -0,0 +1,676 @@
+//! Emulator module. Runs the CPU on a thread of its own, so that presenting the display and polling the window never
+//! hold up the emulation, and the window stays responsive while the program waits for a key.
+//!
+//! The emulation thread owns the machine and talks to the frontend over two channels: keys, hotkeys and memory edits
+//! come in as `Command`s, completed frames with the state of the buzzer and the messages to show go out as `Output`s.
+//! It keeps its own 60 Hz pace and stops when the frontend sends `Command::Quit` or hangs up. In a two-player game
+//! over the network, the pace is the one of the slower player and the speed controls are unavailable.
+//!
+//! The requests of the remote control clients are answered between two ticks, so that they always see the machine
+//! between two frames, or between two instructions after stepping through them.
+
+use std;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, sleep, JoinHandle};
+use std::time::Instant;
+
+use prelude::*;
+use config::Config;
+use cpu::Cpu;
+use framebuffer::Framebuffer;
+use graphics::RemoteDisplay;
+use input::RemoteKeyboard;
+use interconnect::Interconnect;
+use memory::Memory;
+use memory_viewer::Registers;
+use netplay::Session;
+use rpc::{self, Call, Client, Event, MAX_STEP_FRAMES, MAX_STEP_INSTRUCTIONS};
+use screenshot;
+use script::Script;
+use speed::SpeedControl;
+use trace::Tracer;
+use vm::FRAME_DURATION;
+
+use serde_json::Value;
+
+/// Requests of the frontend to the emulation thread.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// A key of the keypad was pressed, or released.
+    Key(u8, bool),
+    /// A hotkey controlling the emulation was pressed: pause, frame advance, turbo, slow motion or reset.
+    Hotkey(Hotkey),
+    /// The fast-forward key was pressed, or released.
+    FastForward(bool),
+    /// Start or stop reporting the memory writes, for the memory viewer.
+    WatchMemory(bool),
+    /// Write a byte edited in the memory viewer.
+    WriteMemory(usize, u8),
+    /// Stop the emulation.
+    Quit,
+}
+
+/// A completed frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    /// Display at the end of the frame.
+    pub framebuffer: Framebuffer,
+    /// Whether the buzzer sounds at the end of the frame.
+    pub buzzer: bool,
+}
+
+/// Summary of a 60 Hz tick of the emulation thread, sent after the frames run during it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tick {
+    /// Number of frames run during the tick.
+    pub frames: u32,
+    /// Number of instructions run per frame.
+    pub cycles_per_frame: u32,
+    /// Whether the emulation is paused.
+    pub paused: bool,
+    /// Registers highlighted by the memory viewer.
+    pub registers: Registers,
+    /// Bytes written during the tick while the memory is watched, with their new values.
+    pub writes: Vec<(usize, u8)>,
+}
+
+/// Reports of the emulation thread to the frontend.
+pub enum Output {
+    /// A frame was completed.
+    Frame(Frame),
+    /// A tick ended.
+    Tick(Tick),
+    /// The whole memory, sent when it starts being watched and after a reset while it is.
+    Memory(Box<Memory>),
+    /// A transient message to show over the output.
+    Message(String),
+    /// The lines of text the script draws over the output, sent when they change.
+    ScriptText(Vec<String>),
+}
+
+/// The machine running on the emulation thread.
+pub struct Emulator {
+    cpu: Cpu<RemoteKeyboard, RemoteDisplay>,
+    config: Config,
+    rom: String,
+    speed: SpeedControl,
+    outputs: Sender<Output>,
+    // two-player game over the network, if any
+    session: Option<Session>,
+    // whether the frontend still listens
+    connected: bool,
+    watching_memory: bool,
+    // requests of the remote control clients, if the server runs
+    calls: Option<Receiver<Call>>,
+    // events the remote control clients subscribed to
+    subscriptions: Vec<(Client, Event)>,
+    // whether a remote control client asked to stop the emulation
+    quit_requested: bool,
+}
+
+impl Emulator {
+    /// Constructor.
+    ///
+    /// # Arguments
+    /// - `rom`: the path of the ROM, reloaded on reset.
+    /// - `memory`: the memory with the ROM loaded.
+    /// - `config`: the options of the emulator.
+    /// - `commands`: the requests of the frontend.
+    /// - `outputs`: the reports to the frontend.
+    /// - `session`: the two-player game over the network, if any.
+    /// - `calls`: the requests of the remote control clients, if the server runs.
+    pub fn new(rom: &str, memory: Memory, config: &Config, commands: Receiver<Command>, outputs: Sender<Output>,
+               session: Option<Session>, calls: Option<Receiver<Call>>) -> Emulator {
+        let mut cpu = Cpu::new(Interconnect::new_remote(memory, commands));
+        cpu.set_cycles_per_frame(config.cycles_per_frame);
+        cpu.set_quirks(config.quirks);
+        cpu.set_engine(config.engine);
+        if let Some(ref session) = session {
+            cpu.seed_rng(session.seed());
+        }
+        if config.profile.is_some() {
+            cpu.enable_profiler();
+        }
+        if let Some(ref path) = config.trace {
+            cpu.set_tracer(Emulator::open_trace(path, config));
+        }
+        if let Some(ref path) = config.script {
+            cpu.set_script(Script::load(path).unwrap_or_else(|why| {
+                eprintln!("Cannot load script '{}': {}", path.display(), why);
+                std::process::exit(1);
+            }));
+        }
+        Emulator {
+            cpu,
+            config: config.clone(),
+            rom: rom.to_string(),
+            speed: SpeedControl::new(config.fast_forward, config.turbo, config.slow_motion),
+            outputs,
+            session,
+            connected: true,
+            watching_memory: false,
+            calls,
+            subscriptions: Vec::new(),
+            quit_requested: false,
+        }
+    }
+
+    /// Start the emulation on a thread of its own.
+    ///
+    /// # Arguments
+    /// - `rom`: the path of the ROM, reloaded on reset.
+    /// - `memory`: the memory with the ROM loaded.
+    /// - `config`: the options of the emulator.
+    /// - `session`: the two-player game over the network, if any.
+    /// - `calls`: the requests of the remote control clients, if the server runs.
+    ///
+    /// # Returns
+    /// Returns the channel to send commands on, the channel the outputs arrive on, and the thread, which finishes once
+    /// the emulation stopped and the profile and trace were written.
+    pub fn spawn(rom: &str, memory: Memory, config: &Config, session: Option<Session>, calls: Option<Receiver<Call>>)
+        -> (Sender<Command>, Receiver<Output>, JoinHandle<()>) {
+        let (commands, command_receiver) = channel();
+        let (output_sender, outputs) = channel();
+        let (rom, config) = (rom.to_string(), config.clone());
+        let thread = thread::Builder::new()
+            .name("emulation".to_string())
+            .spawn(move || {
+                Emulator::new(&rom, memory, &config, command_receiver, output_sender, session, calls).run()
+            })
+            .expect("the emulation thread should start");
+        (commands, outputs, thread)
+    }
+
+    /// Run at 60 ticks per second until the frontend, a remote control client or the script stops the emulation.
+    pub fn run(&mut self) {
+        let mut next_frame = Instant::now();
+        while self.connected {
+            self.cpu.interconnect_mut().input.handle_inputs();
+            self.handle_commands();
+            self.handle_calls();
+            if self.cpu.interconnect().input.close_requested() || self.quit_requested {
+                break;
+            }
+            let frames = if self.session.is_some() {
+                self.run_netplay_frame()
+            } else {
+                let frames = self.speed.frames_to_run();
+                for _ in 0..frames {
+                    self.cpu.execute_frame();
+                    self.send_frame();
+                }
+                frames
+            };
+            self.handle_script();
+            self.send_tick(frames);
+
+            // wait for the start of the next frame, without catching up on frames that took too long
+            next_frame += FRAME_DURATION;
+            let now = Instant::now();
+            if next_frame > now {
+                sleep(next_frame - now);
+            } else {
+                next_frame = now;
+            }
+        }
+        self.save_profile();
+        self.cpu.finish_trace();
+    }
+
+    /// Run the next frame of the two-player game, if the keys of the other player arrive within a frame, ending the
+    /// game when the connection fails.
+    ///
+    /// # Returns
+    /// Returns the number of frames run.
+    fn run_netplay_frame(&mut self) -> u32 {
+        let session = self.session.as_mut().expect("a two-player game should be running");
+        let keys = self.cpu.interconnect().input.held_keys();
+        let resyncs = session.resyncs();
+        match session.step(&mut self.cpu, keys, FRAME_DURATION) {
+            Ok(ran)  => {
+                if session.resyncs() != resyncs {
+                    self.show_message("Resynchronised");
+                }
+                if ran {
+                    self.send_frame();
+                }
+                ran as u32
+            },
+            Err(why) => {
+                self.session = None;
+                self.cpu.interconnect_mut().input.set_keys(keys);
+                self.show_message(&format!("Netplay ended: {}", why));
+                0
+            },
+        }
+    }
+
+    /// Send the text the script draws over the output if it changed, and stop if the script asked to.
+    fn handle_script(&mut self) {
+        let text = match self.cpu.script_mut() {
+            Some(script) => {
+                self.quit_requested |= script.quit_requested();
+                script.take_text()
+            },
+            None         => None,
+        };
+        if let Some(lines) = text {
+            self.send(Output::ScriptText(lines));
+        }
+    }
+
+    /// Send the display and the buzzer at the end of a frame.
+    fn send_frame(&mut self) {
+        let frame = Frame {
+            framebuffer: self.cpu.interconnect().graphics.framebuffer().clone(),
+            buzzer: self.cpu.buzzer_on(),
+        };
+        let (number, buzzer) = (self.cpu.frames(), frame.buzzer);
+        self.notify(Event::Frame, json!({"frame": number, "buzzer": buzzer}));
+        self.send(Output::Frame(frame));
+    }
+
+    /// Open the instruction trace, exiting if the file cannot be created.
+    fn open_trace(path: &Path, config: &Config) -> Tracer {
+        let writer: Box<dyn io::Write> = if path == Path::new("-") {
+            Box::new(BufWriter::new(io::stdout()))
+        } else {
+            match File::create(path) {
+                Ok(file)  => Box::new(BufWriter::new(file)),
+                Err(why)  => {
+                    eprintln!("Cannot create trace '{}': {}", path.display(), why);
+                    std::process::exit(1);
+                },
+            }
+        };
+        Tracer::new(writer, config.trace_format, config.trace_filter.clone())
+    }
+
+    /// Save the profile reports if profiling is enabled.
+    fn save_profile(&self) {
+        if let (Some(path), Some(profiler)) = (&self.config.profile, self.cpu.profiler()) {
+            let memory = &self.cpu.interconnect().memory;
+            match profiler.save(path, &self.config.rom_name, memory, self.cpu.cycles_per_frame()) {
+                Ok((text, json)) => eprintln!("Saved profile to '{}' and '{}'", text.display(), json.display()),
+                Err(why)         => eprintln!("Cannot save profile: {}", why),
+            }
+        }
+    }
+
+    /// Send an output, noting when the frontend no longer listens.
+    fn send(&mut self, output: Output) {
+        self.connected &= self.outputs.send(output).is_ok();
+    }
+
+    /// Report the end of a tick, with the memory writes if the memory is watched.
+    fn send_tick(&mut self, frames: u32) {
+        let memory = &mut self.cpu.interconnect_mut().memory;
+        let writes = if self.watching_memory {
+            memory.take_writes().into_iter().map(|addr| (addr, memory.read(addr))).collect()
+        } else {
+            Vec::new()
+        };
+        let tick = Tick {
+            frames,
+            cycles_per_frame: self.cpu.cycles_per_frame(),
+            paused: self.speed.is_paused(),
+            registers: Registers { pc: self.cpu.pc(), i: self.cpu.i() },
+            writes,
+        };
+        self.send(Output::Tick(tick));
+    }
+
+    /// Handle the commands received since the last tick.
+    fn handle_commands(&mut self) {
+        while let Some(command) = self.cpu.interconnect_mut().input.next_command() {
+            match command {
+                // both players must run the same frames on the same machine
+                Command::Hotkey(Hotkey::TogglePause) | Command::Hotkey(Hotkey::AdvanceFrame)
+                    | Command::Hotkey(Hotkey::ToggleTurbo) | Command::Hotkey(Hotkey::ToggleSlowMotion)
+                    | Command::Hotkey(Hotkey::Reset) | Command::WriteMemory(..) if self.session.is_some() => {
+                    self.show_message("Unavailable during netplay")
+                },
+                Command::FastForward(_) if self.session.is_some() => (),
+                Command::Hotkey(Hotkey::TogglePause)      => self.toggle_pause(),
+                Command::Hotkey(Hotkey::AdvanceFrame)     => self.speed.advance_frame(),
+                Command::Hotkey(Hotkey::ToggleTurbo)      => self.change_speed(SpeedControl::toggle_turbo),
+                Command::Hotkey(Hotkey::ToggleSlowMotion) => self.change_speed(SpeedControl::toggle_slow_motion),
+                Command::Hotkey(Hotkey::Reset)            => self.reset(),
+                Command::Hotkey(_)                        => (),
+                Command::FastForward(held)                => self.change_speed(|speed| speed.set_fast_forward(held)),
+                Command::WatchMemory(watch)               => self.watch_memory(watch),
+                Command::WriteMemory(addr, byte)          => self.cpu.interconnect_mut().memory.write(addr, byte),
+                // keys and quitting are handled by the keyboard
+                Command::Key(..) | Command::Quit          => (),
+            }
+        }
+    }
+
+    /// Pause or resume the emulation.
+    fn toggle_pause(&mut self) {
+        self.speed.toggle_pause();
+        let message = if self.speed.is_paused() { "Paused" } else { "Resumed" };
+        self.show_message(message);
+    }
+
+    /// Apply a change to the speed controls, showing the new speed if it differs from the old one.
+    fn change_speed<F: FnOnce(&mut SpeedControl)>(&mut self, change: F) {
+        let old = self.speed.multiplier();
+        change(&mut self.speed);
+        let new = self.speed.multiplier();
+        if new != old {
+            self.show_message(&format!("Speed {:.0}%", new * 100.0));
+        }
+    }
+
+    /// Start or stop reporting the memory writes, starting with the whole memory.
+    fn watch_memory(&mut self, watch: bool) {
+        self.watching_memory = watch;
+        if watch {
+            self.send_memory();
+        }
+    }
+
+    /// Send the whole memory, only reporting the bytes written from now on.
+    fn send_memory(&mut self) {
+        let memory = &mut self.cpu.interconnect_mut().memory;
+        memory.take_writes();
+        let memory = Box::new(memory.clone());
+        self.send(Output::Memory(memory));
+    }
+
+    /// Show a transient message over the output.
+    fn show_message(&mut self, text: &str) {
+        self.notify(Event::Message, json!({"text": text}));
+        self.send(Output::Message(text.to_string()));
+    }
+
+    /// Notify the remote control clients subscribed to an event, forgetting the ones that are gone.
+    fn notify(&mut self, event: Event, params: Value) {
+        self.subscriptions.retain(|&(ref client, subscribed)| {
+            subscribed != event || client.notify(event.name(), params.clone())
+        });
+    }
+
+    /// Reload the ROM from storage and restart it.
+    fn reset(&mut self) {
+        match fs::read(&self.rom).map_err(|why| why.to_string()).and_then(|rom| self.restart(rom)) {
+            Ok(())   => self.show_message("Reset"),
+            Err(why) => eprintln!("Cannot reload '{}': {}", self.rom, why),
+        }
+    }
+
+    /// Restart the machine with a ROM, leaving it untouched if the ROM does not fit in memory.
+    fn restart(&mut self, rom: Vec<u8>) -> Result<(), String> {
+        self.cpu.reset(rom)?;
+        if self.watching_memory {
+            self.send_memory();
+        }
+        Ok(())
+    }
+
+    /// Answer the requests of the remote control clients received since the last tick.
+    fn handle_calls(&mut self) {
+        let calls: Vec<Call> = match self.calls {
+            Some(ref calls) => calls.try_iter().collect(),
+            None            => return,
+        };
+        for call in calls {
+            let result = self.call(&call);
+            call.answer(result);
+        }
+    }
+
+    /// Carry out a request of a remote control client.
+    ///
+    /// # Returns
+    /// Returns the result of the method, or why it failed.
+    fn call(&mut self, call: &Call) -> Result<Value, rpc::Error> {
+        let params = &call.params;
+        match call.method.as_str() {
+            // both players must run the same frames on the same machine
+            "step" | "set_registers" | "write_memory" | "load_rom" | "pause" | "resume" if self.session.is_some() => {
+                Err(rpc::Error::failed("unavailable during netplay"))
+            },
+            "status"          => Ok(self.status()),
+            "pause"           => {
+                if !self.speed.is_paused() {
+                    self.toggle_pause();
+                }
+                Ok(self.status())
+            },
+            "resume"          => {
+                if self.speed.is_paused() {
+                    self.toggle_pause();
+                }
+                Ok(self.status())
+            },
+            "step"            => self.step(params),
+            "get_registers"   => Ok(rpc::registers(&self.cpu)),
+            "set_registers"   => rpc::set_registers(&mut self.cpu, params),
+            "read_memory"     => rpc::read_memory(&self.cpu, params),
+            "write_memory"    => rpc::write_memory(&mut self.cpu, params),
+            "press_key"       => rpc::key(params).map(|key| {
+                self.cpu.interconnect_mut().input.set_key(key, true);
+                Value::Null
+            }),
+            "release_key"     => rpc::key(params).map(|key| {
+                self.cpu.interconnect_mut().input.set_key(key, false);
+                Value::Null
+            }),
+            "load_rom"        => self.load_rom(params),
+            "screenshot"      => self.screenshot(params),
+            "subscribe"       => rpc::events(params).map(|events| {
+                for event in events {
+                    if !self.subscriptions.iter().any(|&(ref client, subscribed)| {
+                        client.id() == call.client.id() && subscribed == event
+                    }) {
+                        self.subscriptions.push((call.client.clone(), event));
+                    }
+                }
+                Value::Null
+            }),
+            "unsubscribe"     => rpc::events(params).map(|events| {
+                self.subscriptions.retain(|&(ref client, subscribed)| {
+                    client.id() != call.client.id() || !events.contains(&subscribed)
+                });
+                Value::Null
+            }),
+            "quit"            => {
+                self.quit_requested = true;
+                Ok(Value::Null)
+            },
+            method            => Err(rpc::Error::method_not_found(method)),
+        }
+    }
+
+    /// State of the emulation.
+    fn status(&self) -> Value {
+        json!({
+            "rom": self.config.rom_name,
+            "frame": self.cpu.frames(),
+            "paused": self.speed.is_paused(),
+            "speed": self.speed.multiplier(),
+            "cycles_per_frame": self.cpu.cycles_per_frame(),
+            "netplay": self.session.is_some(),
+        })
+    }
+
+    /// Pause the emulation and run some `frames` or some `instructions`, answering with the registers.
+    fn step(&mut self, params: &Value) -> Result<Value, rpc::Error> {
+        let frames = rpc::optional(params, "frames", MAX_STEP_FRAMES)?;
+        let instructions = rpc::optional(params, "instructions", MAX_STEP_INSTRUCTIONS)?;
+        if !self.speed.is_paused() {
+            self.toggle_pause();
+        }
+        match (frames, instructions) {
+            (Some(frames), None)       => for _ in 0..frames {
+                self.cpu.execute_frame();
+                self.send_frame();
+            },
+            (None, Some(instructions)) => {
+                for _ in 0..instructions {
+                    self.cpu.execute_cycle();
+                }
+                self.send_frame();
+            },
+            _                          => {
+                return Err(rpc::Error::invalid_params("expected either frames or instructions"));
+            },
+        }
+        Ok(rpc::registers(&self.cpu))
+    }
+
+    /// Load the ROM at `path` and restart the machine with it, reloading it from there on reset.
+    fn load_rom(&mut self, params: &Value) -> Result<Value, rpc::Error> {
+        let path = params.get("path").and_then(Value::as_str)
+            .ok_or_else(|| rpc::Error::invalid_params("expected the path of the ROM"))?;
+        let rom = fs::read(path).map_err(|why| rpc::Error::failed(&format!("cannot read '{}': {}", path, why)))?;
+        let size = rom.len();
+        self.restart(rom).map_err(|why| rpc::Error::invalid_params(&format!("cannot load '{}': {}", path, why)))?;
+        self.rom = path.to_string();
+        self.config.rom_name = Path::new(path).file_name().map_or_else(|| path.to_string(), |name| {
+            name.to_string_lossy().into_owned()
+        });
+        let message = format!("Loaded {}", self.config.rom_name);
+        self.show_message(&message);
+        Ok(json!({"rom": self.config.rom_name, "size": size}))
+    }
+
+    /// Save the display to `path` as a PNG scaled by `scale`, or to the screenshot directory as the screenshot hotkey
+    /// does if no path is given.
+    fn screenshot(&mut self, params: &Value) -> Result<Value, rpc::Error> {
+        let graphics = &self.cpu.interconnect().graphics;
+        let failed = |why: io::Error| rpc::Error::failed(&format!("cannot save screenshot: {}", why));
+        let paths = match params.get("path").map(Value::as_str) {
+            Some(Some(path)) => {
+                let scale = rpc::optional(params, "scale", 64)?.unwrap_or(1).max(1) as usize;
+                let file = File::create(path).map_err(failed)?;
+                screenshot::write_png(BufWriter::new(file), &graphics.to_image(scale)).map_err(failed)?;
+                vec![PathBuf::from(path)]
+            },
+            Some(None)       => return Err(rpc::Error::invalid_params("the path should be a string")),
+            None             => {
+                let config = &self.config;
+                let (native, scaled) = screenshot::save(graphics, &config.screenshot_dir, &config.rom_name,
+                                                        config.scale as usize).map_err(failed)?;
+                vec![native, scaled]
+            },
+        };
+        Ok(json!({"paths": paths.iter().map(|path| path.display().to_string()).collect::<Vec<String>>()}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+
+    /// Time to wait for an output before considering the emulation stuck.
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Start the emulation of a program from 0x200.
+    fn spawn(rom: &[u8]) -> (Sender<Command>, Receiver<Output>, JoinHandle<()>) {
+        let rom = rom.to_vec();
+        let (commands, command_receiver) = channel();
+        let (output_sender, outputs) = channel();
+        let thread = thread::spawn(move || {
+            let memory = Memory::new(rom).unwrap();
+            let config = Config::default();
+            Emulator::new("", memory, &config, command_receiver, output_sender, None, None).run();
+        });
+        (commands, outputs, thread)
+    }
+
+    /// Wait for the next tick, collecting the frames completed before it.
+    fn next_tick(outputs: &Receiver<Output>) -> (Vec<Frame>, Tick) {
+        let mut frames = Vec::new();
+        loop {
+            match outputs.recv_timeout(TIMEOUT).expect("the emulation should report") {
+                Output::Frame(frame) => frames.push(frame),
+                Output::Tick(tick)   => return (frames, tick),
+                _                    => (),
+            }
+        }
+    }
+
+    #[test]
+    fn sends_frames_and_stops_on_quit() {
+        let (commands, outputs, thread) = spawn(&[0x12, 0x00]);
+        let (frames, tick) = next_tick(&outputs);
+        assert_eq!(frames.len() as u32, tick.frames);
+        commands.send(Command::Quit).unwrap();
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn keys_reach_the_waiting_program() {
+        // LD V1, K; LD F, V1; DRW V0, V0, 5; JP 0x206
+        let (commands, outputs, thread) = spawn(&[0xF1, 0x0A, 0xF1, 0x29, 0xD0, 0x05, 0x12, 0x06]);
+        let start = Instant::now();
+        // the program only takes keys pressed while it waits, so press and release every frame until it drew one
+        let mut down = false;
+        let digit = loop {
+            assert!(start.elapsed() < TIMEOUT, "the key should reach the program");
+            match outputs.recv_timeout(FRAME_DURATION * 5) {
+                Ok(Output::Frame(frame)) if frame.framebuffer.rows()[0] != 0 => break frame,
+                Ok(Output::Frame(_))                                         => {
+                    down = !down;
+                    commands.send(Command::Key(0x1, down)).unwrap();
+                },
+                _                                                            => (),
+            }
+        };
+        assert_eq!(digit.framebuffer.rows()[0], 0x20 << 56, "the top row of the digit 1 is drawn");
+        commands.send(Command::Quit).unwrap();
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn quits_while_the_program_waits_for_a_key() {
+        // LD V0, K
+        let (commands, _outputs, thread) = spawn(&[0xF0, 0x0A]);
+        commands.send(Command::Quit).unwrap();
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn stops_when_the_frontend_hangs_up() {
+        let (commands, outputs, thread) = spawn(&[0x12, 0x00]);
+        drop(outputs);
+        thread.join().unwrap();
+        drop(commands);
+    }
+
+    #[test]
+    fn pauses_and_reports_memory_writes() {
+        let (commands, outputs, thread) = spawn(&[0x12, 0x00]);
+        commands.send(Command::Hotkey(Hotkey::TogglePause)).unwrap();
+        commands.send(Command::WatchMemory(true)).unwrap();
+        commands.send(Command::WriteMemory(0x300, 0xAB)).unwrap();
+        let mut messages = Vec::new();
+        let mut memory = None;
+        let tick = loop {
+            match outputs.recv_timeout(TIMEOUT).unwrap() {
+                Output::Message(text) => messages.push(text),
+                Output::Memory(full)  => memory = Some(full),
+                Output::Tick(tick)    => if memory.is_some() {
+                    break tick;
+                },
+                Output::Frame(_)      => (),
+                Output::ScriptText(_) => (),
+            }
+        };
+        assert_eq!(messages, vec!["Paused".to_string()]);
+        assert_eq!((tick.frames, tick.paused), (0, true));
+        assert_eq!(tick.writes, vec![(0x300, 0xAB)]);
+        assert_eq!(memory.unwrap().read(0x200), 0x12);
+        commands.send(Command::Quit).unwrap();
+        thread.join().unwrap();
+    }
+}
@@ -0,0 +1,166 @@
+//! Filter module. Contains display filters reducing the flicker of sprites that are erased and redrawn with XOR.
+//!
+//! Filters only change how the display memory is shown, never the display memory itself.
+
+use graphics::{render_rgb, BYTES_PER_PIXEL};
+use palette::{Palette, Rgb};
+
+/// Intensity below which a fading pixel is considered off.
+const MIN_INTENSITY: f32 = 1.0 / 255.0;
+
+/// Display filter applied when presenting frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Filter {
+    /// Show every frame as it is.
+    #[default]
+    None,
+    /// Let pixels fade out like phosphor, halving their intensity every `half_life` frames.
+    Decay { half_life: f32 },
+    /// Show a pixel if it is lit in the current or in the previous frame.
+    Or,
+}
+
+/// State of a filter across frames.
+pub struct FlickerFilter {
+    filter: Filter,
+    // intensity of each pixel, 1.0 while lit
+    intensity: Vec<f32>,
+    // palette index each pixel was last lit with
+    last_lit: Vec<u8>,
+    // display memory of the previous frame
+    previous: Vec<u8>,
+    // whether presenting unchanged display memory again would produce the same output
+    settled: bool,
+}
+
+impl FlickerFilter {
+    /// Constructor.
+    ///
+    /// # Arguments
+    /// - `filter`: the filter to apply.
+    /// - `size`: the number of pixels of the display.
+    pub fn new(filter: Filter, size: usize) -> FlickerFilter {
+        FlickerFilter {
+            filter,
+            intensity: vec![0.0; size],
+            last_lit: vec![0; size],
+            previous: vec![0; size],
+            settled: true,
+        }
+    }
+
+    /// Checks if the output only changes when the display memory changes. Otherwise pixels are still fading out
+    /// and the display should be presented again even if nothing was drawn.
+    pub fn is_settled(&self) -> bool {
+        self.settled
+    }
+
+    /// Advance the filter by one frame and render the display memory to packed RGB24 pixels.
+    ///
+    /// # Arguments
+    /// - `memory`: the display memory, one palette index per pixel.
+    /// - `palette`: the colours of the palette indices.
+    /// - `pixels`: the buffer to write to, three bytes per pixel of `memory`.
+    pub fn render(&mut self, memory: &[u8], palette: &Palette, pixels: &mut [u8]) {
+        if self.intensity.len() != memory.len() {
+            *self = FlickerFilter::new(self.filter, memory.len());
+        }
+        match self.filter {
+            Filter::None                => render_rgb(memory, palette, pixels),
+            Filter::Decay { half_life } => self.render_decay(memory, palette, pixels, half_life),
+            Filter::Or                  => self.render_or(memory, palette, pixels),
+        }
+    }
+
+    /// Render with pixels fading out exponentially once they are turned off.
+    fn render_decay(&mut self, memory: &[u8], palette: &Palette, pixels: &mut [u8], half_life: f32) {
+        let factor = 0.5_f32.powf(1.0 / half_life.max(f32::EPSILON));
+        let background = palette.background();
+        self.settled = true;
+        for (idx, pixel) in pixels.chunks_mut(BYTES_PER_PIXEL).enumerate().take(memory.len()) {
+            if memory[idx] != 0 {
+                self.intensity[idx] = 1.0;
+                self.last_lit[idx] = memory[idx];
+            } else if self.intensity[idx] > 0.0 {
+                self.intensity[idx] *= factor;
+                if self.intensity[idx] < MIN_INTENSITY {
+                    self.intensity[idx] = 0.0;
+                } else {
+                    self.settled = false;
+                }
+            }
+            let color = blend(background, palette.color(self.last_lit[idx]), self.intensity[idx]);
+            pixel.copy_from_slice(&[color.r, color.g, color.b]);
+        }
+    }
+
+    /// Render the union of the current and the previous frame.
+    fn render_or(&mut self, memory: &[u8], palette: &Palette, pixels: &mut [u8]) {
+        self.settled = self.previous[..] == memory[..];
+        for (idx, pixel) in pixels.chunks_mut(BYTES_PER_PIXEL).enumerate().take(memory.len()) {
+            let value = if memory[idx] != 0 { memory[idx] } else { self.previous[idx] };
+            let color = palette.color(value);
+            pixel.copy_from_slice(&[color.r, color.g, color.b]);
+        }
+        self.previous.copy_from_slice(memory);
+    }
+}
+
+/// Blend two colours linearly, `amount` being the weight of `to`.
+fn blend(from: Rgb, to: Rgb, amount: f32) -> Rgb {
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+    Rgb::new(channel(from.r, to.r), channel(from.g, to.g), channel(from.b, to.b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use palette::Theme;
+
+    fn render(filter: &mut FlickerFilter, memory: &[u8]) -> Vec<u8> {
+        let mut pixels = vec![0; memory.len() * BYTES_PER_PIXEL];
+        filter.render(memory, &Theme::Classic.palette(), &mut pixels);
+        pixels
+    }
+
+    #[test]
+    fn none_shows_frame() {
+        let mut filter = FlickerFilter::new(Filter::None, 2);
+        assert_eq!(render(&mut filter, &[1, 0]), vec![255, 255, 255, 0, 0, 0]);
+        assert_eq!(render(&mut filter, &[0, 0]), vec![0; 6]);
+        assert!(filter.is_settled());
+    }
+
+    #[test]
+    fn decay_halves_per_half_life() {
+        let mut filter = FlickerFilter::new(Filter::Decay { half_life: 1.0 }, 1);
+        assert_eq!(render(&mut filter, &[1]), vec![255, 255, 255], "lit pixels have full intensity");
+        assert!(filter.is_settled());
+
+        assert_eq!(render(&mut filter, &[0]), vec![128, 128, 128], "half intensity after one half-life");
+        assert!(!filter.is_settled(), "the pixel is fading out");
+        assert_eq!(render(&mut filter, &[0]), vec![64, 64, 64], "quarter intensity after two half-lives");
+    }
+
+    #[test]
+    fn decay_settles_once_faded() {
+        let mut filter = FlickerFilter::new(Filter::Decay { half_life: 1.0 }, 1);
+        render(&mut filter, &[1]);
+        for _ in 0..8 {
+            render(&mut filter, &[0]);
+        }
+        assert_eq!(render(&mut filter, &[0]), vec![0, 0, 0]);
+        assert!(filter.is_settled());
+    }
+
+    #[test]
+    fn or_shows_previous_frame() {
+        let mut filter = FlickerFilter::new(Filter::Or, 2);
+        render(&mut filter, &[1, 0]);
+        assert_eq!(render(&mut filter, &[0, 1]), vec![255; 6], "pixels of both frames are shown");
+        assert!(!filter.is_settled());
+        assert_eq!(render(&mut filter, &[0, 1]), vec![0, 0, 0, 255, 255, 255]);
+        assert!(filter.is_settled());
+    }
+}
@@ -0,0 +1,196 @@
+//! Framebuffer module. The pixels of the display, each row packed into an integer with the leftmost pixel in its most
+//! significant bit. A sprite row is drawn by shifting its byte into place and XOR-ing it into the display row, and a
+//! collision is a non-zero AND of the two.
+
+use std::fmt::Debug;
+use std::ops::{BitAnd, BitXorAssign, Not};
+
+/// An integer holding the pixels of a row, one bit each.
+pub trait Row: Copy + Debug + Default + Eq + BitAnd<Output = Self> + BitXorAssign + Not<Output = Self> {
+    /// Number of pixels of a row.
+    const WIDTH: usize;
+
+    /// A sprite row drawn at a column.
+    ///
+    /// # Arguments
+    /// - `byte`: the pixels of the sprite row, the leftmost in the most significant bit.
+    /// - `x`: the column of the leftmost pixel, less than `WIDTH`.
+    /// - `clip`: cut off the pixels crossing the right edge instead of wrapping them around to the left.
+    fn sprite(byte: u8, x: usize, clip: bool) -> Self;
+
+    /// The pixel at a column.
+    fn pixel(x: usize) -> Self;
+}
+
+macro_rules! impl_row {
+    ($int:ty) => {
+        impl Row for $int {
+            const WIDTH: usize = <$int>::BITS as usize;
+
+            #[inline(always)]
+            fn sprite(byte: u8, x: usize, clip: bool) -> $int {
+                let row = <$int>::from(byte) << (Self::WIDTH - 8);
+                if clip {
+                    row >> x
+                } else {
+                    row.rotate_right(x as u32)
+                }
+            }
+
+            #[inline(always)]
+            fn pixel(x: usize) -> $int {
+                1 << (Self::WIDTH - 1 - x)
+            }
+        }
+    };
+}
+
+impl_row!(u64);
+impl_row!(u128);
+
+/// Pixels of a display as wide as its row type, 64 pixels by default.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Framebuffer<R: Row = u64> {
+    rows: Vec<R>,
+}
+
+impl<R: Row> Framebuffer<R> {
+    /// Constructor of a blank framebuffer.
+    ///
+    /// # Arguments
+    /// - `height`: the number of rows.
+    pub fn new(height: usize) -> Framebuffer<R> {
+        Framebuffer { rows: vec![R::default(); height] }
+    }
+
+    /// Constructor of a framebuffer holding packed rows, from top to bottom.
+    pub fn from_rows(rows: Vec<R>) -> Framebuffer<R> {
+        Framebuffer { rows }
+    }
+
+    /// Number of pixels per row.
+    pub fn width(&self) -> usize {
+        R::WIDTH
+    }
+
+    /// Number of rows.
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Packed pixels of the rows, from top to bottom.
+    pub fn rows(&self) -> &[R] {
+        &self.rows
+    }
+
+    /// Turn every pixel off.
+    pub fn clear(&mut self) {
+        for row in &mut self.rows {
+            *row = R::default();
+        }
+    }
+
+    /// Checks if a pixel is on.
+    #[inline(always)]
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & R::pixel(x) != R::default()
+    }
+
+    /// Turn a pixel on or off.
+    #[inline(always)]
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        let pixel = R::pixel(x);
+        let row = &mut self.rows[y];
+        *row = *row & !pixel;
+        if on {
+            *row ^= pixel;
+        }
+    }
+
+    /// XOR a sprite onto the display. The location wraps around the display, the pixels of the sprite crossing its
+    /// edges wrap around as well unless `clip` is set, in which case they are cut off.
+    ///
+    /// # Returns
+    /// Returns `true` if the sprite turned off a pixel that was on.
+    pub fn draw(&mut self, x: usize, y: usize, sprite: &[u8], clip: bool) -> bool {
+        let (x, y) = (x % R::WIDTH, y % self.height());
+        let mut collision = false;
+        for (j, &byte) in sprite.iter().enumerate() {
+            if clip && y + j >= self.height() {
+                break;
+            }
+            let yj = (y + j) % self.height();
+            let sprite_row = R::sprite(byte, x, clip);
+            collision |= self.rows[yj] & sprite_row != R::default();
+            self.rows[yj] ^= sprite_row;
+        }
+        collision
+    }
+
+    /// Write the pixels as palette indices, 1 for pixels that are on and 0 for pixels that are off.
+    ///
+    /// # Arguments
+    /// - `memory`: the buffer to write to, one byte per pixel, row by row.
+    pub fn unpack(&self, memory: &mut [u8]) {
+        for (row, bytes) in self.rows.iter().zip(memory.chunks_mut(R::WIDTH)) {
+            for (x, byte) in bytes.iter_mut().enumerate() {
+                *byte = (*row & R::pixel(x) != R::default()) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_get() {
+        let mut framebuffer: Framebuffer = Framebuffer::new(32);
+        framebuffer.set(0, 0, true);
+        framebuffer.set(63, 31, true);
+        assert_eq!(framebuffer.rows()[0], 1 << 63);
+        assert_eq!(framebuffer.rows()[31], 1);
+        framebuffer.set(0, 0, false);
+        assert!(!framebuffer.get(0, 0));
+        assert!(framebuffer.get(63, 31));
+    }
+
+    #[test]
+    fn draw_wraps_around_the_right_edge() {
+        let mut framebuffer: Framebuffer = Framebuffer::new(32);
+        assert!(!framebuffer.draw(60, 0, &[0b1111_0011], false));
+        assert_eq!(framebuffer.rows()[0], 0b1111 | 0b11 << 60);
+        assert!(framebuffer.draw(60, 0, &[0b0000_0001], false), "turning off pixel 3 is a collision");
+        assert!(!framebuffer.get(3, 0));
+    }
+
+    #[test]
+    fn draw_clips_at_the_edges() {
+        let mut framebuffer: Framebuffer = Framebuffer::new(32);
+        framebuffer.draw(60, 31, &[0xFF, 0xFF], true);
+        assert_eq!(framebuffer.rows()[31], 0b1111);
+        assert_eq!(framebuffer.rows()[0], 0);
+    }
+
+    #[test]
+    fn wide_rows() {
+        let mut framebuffer: Framebuffer<u128> = Framebuffer::new(64);
+        assert_eq!((framebuffer.width(), framebuffer.height()), (128, 64));
+        framebuffer.draw(124, 63, &[0xFF, 0xFF], false);
+        assert_eq!(framebuffer.rows()[63], 0xF | 0xF << 124);
+        assert_eq!(framebuffer.rows()[0], 0xF | 0xF << 124);
+    }
+
+    #[test]
+    fn unpack() {
+        let mut framebuffer: Framebuffer = Framebuffer::new(2);
+        framebuffer.set(1, 0, true);
+        framebuffer.set(0, 1, true);
+        let mut memory = [0xFF; 128];
+        framebuffer.unpack(&mut memory);
+        assert_eq!(&memory[0..3], &[0, 1, 0]);
+        assert_eq!(&memory[64..66], &[1, 0]);
+        assert_eq!(memory.iter().map(|&byte| byte as usize).sum::<usize>(), 2);
+    }
+}
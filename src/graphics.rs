@@ -1,180 +1,397 @@
 //! Graphics module.
 
-use sdl2;
-use sdl2::pixels::Color;
-use sdl2::rect::Point;
+use std::path::PathBuf;
 
-const WIDTH: usize = 64;
-const HEIGHT: usize = 32;
+use sdl2;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::Texture;
+use sdl2::video::FullscreenType;
+
+use config::Config;
+use effects::{Image, PostProcessor, UPSCALE};
+use filter::FlickerFilter;
+use framebuffer::Framebuffer;
+use osd::{Osd, Stats};
+use palette::{Palette, Theme};
+use screenshot;
+use viewport::{Fit, Viewport};
+
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
 const DISPLAY_SIZE: usize = WIDTH * HEIGHT;
+/// Bytes per pixel of the RGB24 texture the display is uploaded to.
+pub const BYTES_PER_PIXEL: usize = 3;
 
 pub trait Graphics {
     /// Constructor.
-    fn new(context: &sdl2::Sdl) -> Self;
+    fn new(context: &sdl2::Sdl, config: &Config) -> Self;
+
+    /// Pixels of the display.
+    fn framebuffer(&self) -> &Framebuffer;
+
+    /// Pixels of the display, mutably.
+    fn framebuffer_mut(&mut self) -> &mut Framebuffer;
 
     /// Clears the display.
     fn cls(&mut self) {
-        for x in 0..WIDTH {
-            for y in 0..HEIGHT {
-                self.set_pixel(x, y, false);
-            }
-        }
+        self.framebuffer_mut().clear();
     }
 
     /// "Turns on" a pixel on the screen
-    fn set_pixel(&mut self, x: usize, y: usize, on: bool);
+    #[inline(always)]
+    fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        self.framebuffer_mut().set(x, y, on);
+    }
 
     /// Checks if a pixel is "turned on"
-    fn get_pixel(&self, x: usize, y: usize) -> bool;
+    #[inline(always)]
+    fn get_pixel(&self, x: usize, y: usize) -> bool {
+        self.framebuffer().get(x, y)
+    }
 
-    /// Draw a sprite at the given location.
+    /// Draw a sprite at the given location. The location wraps around the display, the pixels of the sprite crossing
+    /// its edges wrap around as well unless `clip` is set, in which case they are cut off.
     ///
     /// # Returns
     /// Returns `true` if the sprite collides with an existing sprite on the display.
-    fn draw(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool;
+    fn draw(&mut self, x: usize, y: usize, sprite: &[u8], clip: bool) -> bool {
+        self.framebuffer_mut().draw(x, y, sprite, clip)
+    }
+
+    /// Present the display state at the end of a frame.
+    fn present(&mut self) {}
+
+    /// Emulated resolution as `(width, height)`.
+    fn resolution(&self) -> (usize, usize) {
+        (self.framebuffer().width(), self.framebuffer().height())
+    }
+
+    /// Colours the display is rendered with.
+    fn palette(&self) -> Palette {
+        Theme::default().palette()
+    }
+
+    /// Render the display with the active palette, each pixel covering `scale x scale` pixels of the image.
+    fn to_image(&self, scale: usize) -> Image {
+        let (width, height) = self.resolution();
+        let palette = self.palette();
+        let mut pixels = Vec::with_capacity(width * height * BYTES_PER_PIXEL);
+        for y in 0..height {
+            for x in 0..width {
+                let color = palette.color(self.get_pixel(x, y) as u8);
+                pixels.extend_from_slice(&[color.r, color.g, color.b]);
+            }
+        }
+        Image::upscale(&pixels, width, height, scale)
+    }
+
+    /// Save a screenshot of the display.
+    fn screenshot(&mut self) {}
+
+    /// Switch to the next colour theme.
+    fn cycle_theme(&mut self) {}
+
+    /// Toggle between windowed and fullscreen output.
+    fn toggle_fullscreen(&mut self) {}
+
+    /// Redraw the output, for instance after the window was resized.
+    fn refresh(&mut self) {}
+
+    /// Show a transient message over the output.
+    fn show_message(&mut self, _text: &str) {}
+
+    /// Show or hide the emulation statistics over the output.
+    fn toggle_stats(&mut self) {}
+
+    /// Update the emulation statistics shown over the output.
+    fn set_stats(&mut self, _stats: Stats) {}
+
+    /// Replace the lines of text the script draws over the output.
+    fn set_script_text(&mut self, _lines: &[String]) {}
+}
 
+/// Convert display memory to packed RGB24 pixels.
+///
+/// # Arguments
+/// - `memory`: the display memory, one palette index per pixel.
+/// - `palette`: the colours of the palette indices.
+/// - `pixels`: the buffer to write to, three bytes per pixel of `memory`.
+pub fn render_rgb(memory: &[u8], palette: &Palette, pixels: &mut [u8]) {
+    for (value, pixel) in memory.iter().zip(pixels.chunks_mut(BYTES_PER_PIXEL)) {
+        let color = palette.color(*value);
+        pixel[0] = color.r;
+        pixel[1] = color.g;
+        pixel[2] = color.b;
+    }
 }
 
+/// Display rendering to an SDL window.
+///
+/// Drawing only updates the framebuffer. The window is updated once per frame by `present`, which passes the
+/// framebuffer through the flicker filter and the post-processing effects, composites the on-screen display and
+/// uploads it to a streaming texture if the output changed since the last frame.
 pub struct Display {
     canvas: sdl2::render::WindowCanvas,
+    texture: Texture,
+    pixels: Vec<u8>,
+    framebuffer: Framebuffer,
+    // the framebuffer last presented, and the same unpacked to palette indices
+    presented: Framebuffer,
     memory: [u8; DISPLAY_SIZE],
+    // set when the output changes other than by drawing
+    dirty: bool,
+    filter: FlickerFilter,
+    effects: PostProcessor,
+    osd: Osd,
+    theme: Theme,
+    palette: Palette,
+    fit: Fit,
+    scale: u32,
+    screenshot_dir: PathBuf,
+    rom_name: String,
 }
 
 impl Display {
+    /// Set the colours used to render the display.
+    ///
+    /// # Arguments
+    /// - `theme`: the theme to continue from when cycling themes.
+    /// - `palette`: the colours to render with, possibly differing from the palette of `theme`.
+    pub fn set_palette(&mut self, theme: Theme, palette: Palette) {
+        self.theme = theme;
+        self.palette = palette;
+        self.dirty = true;
+    }
+
+    /// Create a streaming texture of the given size.
+    fn create_texture(canvas: &sdl2::render::WindowCanvas, width: usize, height: usize) -> Texture {
+        canvas.texture_creator()
+            .create_texture_streaming(PixelFormatEnum::RGB24, width as u32, height as u32)
+            .unwrap()
+    }
+
+    /// Upload packed RGB24 pixels to the texture, recreating it if the size changed.
+    fn upload(canvas: &sdl2::render::WindowCanvas, texture: &mut Texture, pixels: &[u8], width: usize, height: usize) {
+        let query = texture.query();
+        if (query.width as usize, query.height as usize) != (width, height) {
+            *texture = Display::create_texture(canvas, width, height);
+        }
+        let _ = texture.update(None, pixels, width * BYTES_PER_PIXEL);
+    }
+
     /// Draw the display state to the `WindowCanvas`.
     fn draw_display(&mut self) {
-        // Clear canvas in black
+        let (width, height) = self.resolution();
+        self.presented.clone_from(&self.framebuffer);
+        self.framebuffer.unpack(&mut self.memory);
+        self.filter.render(&self.memory[..width * height], &self.palette, &mut self.pixels);
+        if !self.effects.is_empty() || self.osd.is_visible() {
+            // the overlay needs a higher resolution than the emulated display for its text to be legible
+            let mut image = if self.effects.is_empty() {
+                Image::upscale(&self.pixels, width, height, UPSCALE)
+            } else {
+                self.effects.process(&self.pixels, width, height)
+            };
+            self.osd.render(&mut image);
+            Display::upload(&self.canvas, &mut self.texture, &image.pixels, image.width, image.height);
+        } else {
+            Display::upload(&self.canvas, &mut self.texture, &self.pixels, width, height);
+        }
+
+        let window = self.canvas.output_size().unwrap_or((width as u32, height as u32));
+        let viewport = Viewport::new(window, (width as u32, height as u32), self.fit);
+
+        // Clear the letterbox in black and copy the display into the viewport
         self.canvas.set_draw_color(Color::RGB(0, 0, 0));
         self.canvas.clear();
-
-        // Draw the state to the display
-        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-        for x in 0..WIDTH {
-            for y in 0..HEIGHT {
-                if self.get_pixel(x, y) {
-                    let _ = self.canvas.draw_point(Point::new(x as i32, y as i32));
-                }
-            }
-        }
+        let dst = Rect::new(viewport.x, viewport.y, viewport.width, viewport.height);
+        let _ = self.canvas.copy(&self.texture, None, dst);
         self.canvas.present();
     }
 }
 
 impl Graphics for Display {
-    /// Constructor
-    fn new(context: &sdl2::Sdl) -> Display {
+    /// Constructor.
+    ///
+    /// # Arguments
+    /// - `context`: the SDL context to open the window in.
+    /// - `config`: the options of the emulator, defining the window size, fit and colours.
+    fn new(context: &sdl2::Sdl, config: &Config) -> Display {
         let video_subsystem = context.video().unwrap();
-        let window = video_subsystem.window("CHIP-8", (WIDTH * 10) as u32, (HEIGHT * 10) as u32)
+        let mut window = video_subsystem.window("CHIP-8", WIDTH as u32 * config.scale, HEIGHT as u32 * config.scale)
             .position_centered()
+            .resizable()
             .build()
             .unwrap();
+        let _ = window.set_minimum_size(WIDTH as u32, HEIGHT as u32);
+        if config.fullscreen {
+            let _ = window.set_fullscreen(FullscreenType::Desktop);
+        }
 
-        let mut canvas = window.into_canvas().software().build().unwrap();
-        let _ = canvas.set_scale(10.0, 10.0);
+        let canvas = window.into_canvas().software().build().unwrap();
+        let texture = Display::create_texture(&canvas, WIDTH, HEIGHT);
+        let mut display = Display {
+            canvas,
+            texture,
+            pixels: vec![0; DISPLAY_SIZE * BYTES_PER_PIXEL],
+            framebuffer: Framebuffer::new(HEIGHT),
+            presented: Framebuffer::new(HEIGHT),
+            memory: [0; DISPLAY_SIZE],
+            dirty: false,
+            filter: FlickerFilter::new(config.filter, DISPLAY_SIZE),
+            effects: PostProcessor::new(config.effects.clone()),
+            osd: Osd::new(config.stats),
+            theme: config.theme,
+            palette: config.palette(),
+            fit: config.fit,
+            scale: config.scale,
+            screenshot_dir: config.screenshot_dir.clone(),
+            rom_name: config.rom_name.clone(),
+        };
+        display.draw_display();
+        display
+    }
 
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-        canvas.present();
+    /// Pixels of the display.
+    fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
 
-        Display {
-            canvas,
-            memory: [0; DISPLAY_SIZE]
-        }
+    /// Pixels of the display, mutably.
+    fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
     }
 
-    /// "Turns on" a pixel on the screen
-    #[inline(always)]
-    fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
-        self.memory[x + y * WIDTH] = on as u8;
+    /// Present the display in the window if it or the overlay changed since the last frame or pixels are still fading
+    /// out.
+    fn present(&mut self) {
+        let overlay_changed = self.osd.tick();
+        let changed = self.framebuffer != self.presented;
+        if self.dirty || changed || overlay_changed || !self.filter.is_settled() {
+            self.draw_display();
+            self.dirty = false;
+        }
     }
 
-    /// Checks if a pixel is "turned on"
-    #[inline(always)]
-    fn get_pixel(&self, x: usize, y: usize) -> bool {
-        self.memory[x + y * WIDTH] == 1
+    /// Colours the display is rendered with.
+    fn palette(&self) -> Palette {
+        self.palette
     }
 
-    /// Draw a sprite at the given location.
-    ///
-    /// # Returns
-    /// Returns `true` if the sprite collides with an existing sprite on the display.
-    fn draw(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
-        let rows = sprite.len();
-        let mut collision = false;
-        for j in 0..rows {
-          let row = sprite[j];
-          for i in 0..8 {
-            let curr = row >> (7 - i) & 0x01;
-            if curr == 1 {
-              let xi = (x + i) % WIDTH;
-              let yj = (y + j) % HEIGHT;
-              let prev = self.get_pixel(xi, yj);
-              if prev {
-                collision = true;
-              }
-              self.set_pixel(xi, yj, (curr == 1) ^ prev);
-            }
-          }
+    /// Save a screenshot of the display at native resolution and at the window scale to the screenshot directory.
+    fn screenshot(&mut self) {
+        match screenshot::save(self, &self.screenshot_dir, &self.rom_name, self.scale as usize) {
+            Ok((native, scaled)) => {
+                eprintln!("Saved screenshots '{}' and '{}'", native.display(), scaled.display());
+                self.osd.message("Screenshot saved");
+            },
+            Err(why)             => {
+                eprintln!("Cannot save screenshot: {}", why);
+                self.osd.message("Screenshot failed");
+            },
         }
-        self.draw_display();
-        collision
+    }
+
+    /// Switch to the next colour theme.
+    fn cycle_theme(&mut self) {
+        let theme = self.theme.next();
+        self.set_palette(theme, theme.palette());
+        self.osd.message(&format!("Theme {}", theme.name()));
+    }
+
+    /// Toggle between windowed and borderless fullscreen output.
+    fn toggle_fullscreen(&mut self) {
+        let state = match self.canvas.window().fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _                   => FullscreenType::Off,
+        };
+        let _ = self.canvas.window_mut().set_fullscreen(state);
+        self.dirty = true;
+    }
+
+    /// Redraw the output, for instance after the window was resized.
+    fn refresh(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Show a transient message over the output.
+    fn show_message(&mut self, text: &str) {
+        self.osd.message(text);
+    }
+
+    /// Show or hide the emulation statistics over the output.
+    fn toggle_stats(&mut self) {
+        self.osd.toggle_stats();
+    }
+
+    /// Update the emulation statistics shown over the output.
+    fn set_stats(&mut self, stats: Stats) {
+        self.osd.set_stats(stats);
+    }
+
+    /// Replace the lines of text the script draws over the output.
+    fn set_script_text(&mut self, lines: &[String]) {
+        self.osd.set_script_text(lines);
+    }
+}
+
+/// Display of the emulation thread, only holding the framebuffer, which the frontend presents.
+pub struct RemoteDisplay {
+    framebuffer: Framebuffer,
+}
+
+impl RemoteDisplay {
+    /// Constructor of a blank display.
+    pub fn new_remote() -> RemoteDisplay {
+        RemoteDisplay { framebuffer: Framebuffer::new(HEIGHT) }
+    }
+}
+
+impl Graphics for RemoteDisplay {
+    /// Constructor.
+    fn new(_context: &sdl2::Sdl, _config: &Config) -> RemoteDisplay {
+        RemoteDisplay::new_remote()
+    }
+
+    /// Pixels of the display.
+    fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    /// Pixels of the display, mutably.
+    fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
     }
 }
 
 /// Display used for testing.
 #[allow(dead_code)]
 pub struct TestDisplay {
-    memory: [u8; DISPLAY_SIZE],
+    framebuffer: Framebuffer,
 }
 
 #[allow(dead_code)]
 impl TestDisplay {
     pub fn new_test() -> Self {
-        Self { memory: [0; DISPLAY_SIZE] }
+        Self { framebuffer: Framebuffer::new(HEIGHT) }
     }
 }
 
 impl Graphics for TestDisplay {
     /// Constructor.
-    fn new(_context: &sdl2::Sdl) -> Self {
+    fn new(_context: &sdl2::Sdl, _config: &Config) -> Self {
         panic!("No SDL context should be initialised for testing");
     }
 
-    /// "Turns on" a pixel on the screen
-    #[inline(always)]
-    fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
-        self.memory[x + y * WIDTH] = on as u8;
+    /// Pixels of the display.
+    fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
     }
 
-    /// Checks if a pixel is "turned on"
-    #[inline(always)]
-    fn get_pixel(&self, x: usize, y: usize) -> bool {
-        self.memory[x + y * WIDTH] == 1
-    }
-
-    /// Draw a sprite at the given location.
-    ///
-    /// # Returns
-    /// Returns `true` if the sprite collides with an existing sprite on the display.
-    fn draw(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
-        let rows = sprite.len();
-        let mut collision = false;
-        for j in 0..rows {
-          let row = sprite[j];
-          for i in 0..8 {
-            let curr = row >> (7 - i) & 0x01;
-            if curr == 1 {
-              let xi = (x + i) % WIDTH;
-              let yj = (y + j) % HEIGHT;
-              let prev = self.get_pixel(xi, yj);
-              if prev {
-                collision = true;
-              }
-              self.set_pixel(xi, yj, (curr == 1) ^ prev);
-            }
-          }
-        }
-        collision
+    /// Pixels of the display, mutably.
+    fn framebuffer_mut(&mut self) -> &mut Framebuffer {
+        &mut self.framebuffer
     }
 }
 
@@ -207,7 +424,7 @@ mod tests {
         let mut display = get_display();
         let sprite: [u8; 2] = [0b00110011, 0b11001010];
 
-        display.draw(0, 0, &sprite);
+        display.draw(0, 0, &sprite, false);
 
         assert_eq!(false, display.get_pixel(0, 0));
         assert_eq!(false, display.get_pixel(1, 0));
@@ -228,20 +445,55 @@ mod tests {
         assert_eq!(false, display.get_pixel(7, 1));
     }
 
+    #[test]
+    fn render_rgb_uses_palette() {
+        let palette = Theme::Amber.palette();
+        let mut pixels = [0; 2 * BYTES_PER_PIXEL];
+        render_rgb(&[0, 1], &palette, &mut pixels);
+        let (bg, fg) = (palette.background(), palette.foreground());
+        assert_eq!(pixels, [bg.r, bg.g, bg.b, fg.r, fg.g, fg.b]);
+    }
+
+    #[test]
+    fn to_image_scales() {
+        let mut display = get_display();
+        display.set_pixel(1, 0, true);
+        let image = display.to_image(2);
+        assert_eq!((image.width, image.height), (WIDTH * 2, HEIGHT * 2));
+        assert_eq!(image.pixel(0, 0), [0, 0, 0]);
+        assert_eq!(image.pixel(2, 1), [255, 255, 255]);
+        assert_eq!(image.pixel(4, 0), [0, 0, 0]);
+    }
+
     #[test]
     fn draw_detects_collisions() {
         let mut display = get_display();
 
         let mut sprite: [u8; 1] = [0b00110000];
-        let mut collision = display.draw(0, 0, &sprite);
+        let mut collision = display.draw(0, 0, &sprite, false);
         assert_eq!(false, collision);
 
         sprite = [0b00000011];
-        collision = display.draw(0, 0, &sprite);
+        collision = display.draw(0, 0, &sprite, false);
         assert_eq!(false, collision);
 
         sprite = [0b00000001];
-        collision = display.draw(0, 0, &sprite);
+        collision = display.draw(0, 0, &sprite, false);
         assert_eq!(true, collision);
     }
+
+    #[test]
+    fn draw_wraps_or_clips() {
+        let mut display = get_display();
+        display.draw(WIDTH - 4, HEIGHT - 1, &[0xFF, 0xFF], false);
+        assert!(display.get_pixel(WIDTH - 1, HEIGHT - 1));
+        assert!(display.get_pixel(3, HEIGHT - 1));
+        assert!(display.get_pixel(0, 0));
+
+        display.cls();
+        display.draw(WIDTH + WIDTH - 4, HEIGHT - 1, &[0xFF, 0xFF], true);
+        assert!(display.get_pixel(WIDTH - 1, HEIGHT - 1));
+        assert!(!display.get_pixel(3, HEIGHT - 1));
+        assert!(!display.get_pixel(0, 0));
+    }
 }
@@ -0,0 +1,332 @@
+//! Reinforcement learning module. Wraps a game in an environment with the interface of
+//! [Gym](https://gymnasium.farama.org): `reset` starts an episode, `step` holds down keys for a few frames and answers
+//! with the display, the reward earned and whether the game is over.
+//!
+//! The environment runs on the headless peripherals, without window nor pacing, as fast as the CPU goes. What counts
+//! as reward and as the end of the game is defined per game, from the bytes the game keeps in memory. Definitions are
+//! text files with one rule per line, blank lines and lines starting with `#` being ignored:
+//! - `reward byte ADDR [WEIGHT]`: the reward is the increase of the byte at `ADDR`, times `WEIGHT`, 1 by default.
+//! - `reward bcd ADDR [WEIGHT]`: the same with the number of three BCD digits from `ADDR` on, as written by `LD B, Vx`.
+//! - `done byte ADDR VALUE` or `done bcd ADDR VALUE`: the game is over once the number equals `VALUE`.
+//! - `done pc ADDR`: the game is over once the program reaches `ADDR`.
+//! - `keys K...`: the hex keys the game is played with, which make up the actions of `Environment::actions`.
+//!
+//! The rewards of all the `reward` rules add up, and any of the `done` rules ends the game. Addresses and values are
+//! hex with a `0x` prefix or decimal.
+
+use std::fs;
+use std::path::Path;
+
+use cpu::{Cpu, Engine};
+use framebuffer::Framebuffer;
+use graphics::TestDisplay;
+use input::TestKeyboard;
+use interconnect::Interconnect;
+use memory::{Memory, MEMORY_SIZE};
+use prelude::*;
+use quirks::Quirks;
+
+/// Default number of frames an action is held for.
+pub const DEFAULT_FRAMES_PER_STEP: u32 = 4;
+
+/// A number the game keeps in memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// The byte at an address.
+    Byte(usize),
+    /// Three BCD digits from an address on, hundreds first.
+    Bcd(usize),
+}
+
+impl Source {
+    /// Read the number from memory.
+    pub fn read(self, memory: &[u8]) -> i64 {
+        match self {
+            Source::Byte(addr) => memory[addr] as i64,
+            Source::Bcd(addr)  => memory[addr..addr + 3].iter().fold(0, |number, &digit| number * 10 + digit as i64),
+        }
+    }
+}
+
+/// Condition ending the game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Done {
+    /// A number in memory equals a value.
+    Equals(Source, i64),
+    /// The program reached an address.
+    Pc(u16),
+}
+
+/// What counts as reward and as the end of a game.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Game {
+    /// Numbers whose increase is rewarded, with their weights.
+    pub rewards: Vec<(Source, f32)>,
+    /// Conditions ending the game, any of them.
+    pub done: Vec<Done>,
+    /// Keys the game is played with, the lowest bit for key 0.
+    pub keys: u16,
+}
+
+impl Game {
+    /// Load the definition of a game from a file.
+    pub fn load(path: &Path) -> Result<Game, String> {
+        Game::parse(&fs::read_to_string(path).map_err(|why| why.to_string())?)
+    }
+
+    /// Parse the definition of a game.
+    ///
+    /// # Returns
+    /// Returns the game, or the line that is malformed and why.
+    pub fn parse(text: &str) -> Result<Game, String> {
+        let mut game = Game::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            game.parse_rule(line).map_err(|why| format!("line {}: {}", number + 1, why))?;
+        }
+        Ok(game)
+    }
+
+    /// Parse a rule, adding it to the game.
+    fn parse_rule(&mut self, line: &str) -> Result<(), String> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[..] {
+            ["reward", kind, addr]         => self.rewards.push((parse_source(kind, addr)?, 1.0)),
+            ["reward", kind, addr, weight] => {
+                let weight = weight.parse().map_err(|_| format!("malformed weight '{}'", weight))?;
+                self.rewards.push((parse_source(kind, addr)?, weight));
+            },
+            ["done", "pc", addr]           => {
+                self.done.push(Done::Pc(parse_number(addr, MEMORY_SIZE as i64 - 2)? as u16));
+            },
+            ["done", kind, addr, value]    => {
+                self.done.push(Done::Equals(parse_source(kind, addr)?, parse_number(value, 999)?));
+            },
+            ["keys", ref keys @ ..] if !keys.is_empty() => for key in keys {
+                match u8::from_str_radix(key, 16) {
+                    Ok(key) if key < 16 => self.keys |= 1 << key,
+                    _                   => return Err(format!("malformed key '{}'", key)),
+                }
+            },
+            _                              => return Err(format!("malformed rule '{}'", line)),
+        }
+        Ok(())
+    }
+
+    /// Checks if the game is over.
+    fn is_done(&self, pc: u16, memory: &[u8]) -> bool {
+        self.done.iter().any(|&done| match done {
+            Done::Equals(source, value) => source.read(memory) == value,
+            Done::Pc(addr)              => pc == addr,
+        })
+    }
+
+    /// Sum of the weighted numbers, whose increase is the reward.
+    fn score(&self, memory: &[u8]) -> f32 {
+        self.rewards.iter().map(|&(source, weight)| source.read(memory) as f32 * weight).sum()
+    }
+}
+
+/// Parse the source of a number, `byte` or `bcd`, and its address.
+fn parse_source(kind: &str, addr: &str) -> Result<Source, String> {
+    match kind {
+        "byte" => Ok(Source::Byte(parse_number(addr, MEMORY_SIZE as i64 - 1)? as usize)),
+        "bcd"  => Ok(Source::Bcd(parse_number(addr, MEMORY_SIZE as i64 - 3)? as usize)),
+        _      => Err(format!("unknown source '{}', expected byte or bcd", kind)),
+    }
+}
+
+/// Parse a number from 0 to `max`, hex with a `0x` prefix or decimal.
+fn parse_number(text: &str, max: i64) -> Result<i64, String> {
+    let number = match text.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16),
+        None      => text.parse(),
+    };
+    number.ok()
+        .filter(|number| (0..=max).contains(number))
+        .ok_or_else(|| format!("expected a number from 0 to {:#X} but got '{}'", max, text))
+}
+
+/// A game to play step by step.
+pub struct Environment {
+    cpu: Cpu<TestKeyboard, TestDisplay>,
+    rom: Vec<u8>,
+    game: Game,
+    frames_per_step: u32,
+    // score at the end of the last step, and whether the game is over
+    score: f32,
+    done: bool,
+}
+
+impl Environment {
+    /// Constructor. The first episode starts on `reset`.
+    ///
+    /// # Arguments
+    /// - `rom`: the program of the game.
+    /// - `game`: what counts as reward and as the end of the game.
+    ///
+    /// # Returns
+    /// Returns the environment, or an error if the program does not fit in memory.
+    pub fn new(rom: Vec<u8>, game: Game) -> Result<Environment, String> {
+        let mut interconnect = Interconnect::new_test(Vec::new());
+        interconnect.memory = Memory::new(rom.clone())?;
+        Ok(Environment {
+            cpu: Cpu::new(interconnect),
+            rom,
+            game,
+            frames_per_step: DEFAULT_FRAMES_PER_STEP,
+            score: 0.0,
+            done: true,
+        })
+    }
+
+    /// Set the number of frames an action is held for.
+    pub fn set_frames_per_step(&mut self, frames: u32) {
+        self.frames_per_step = frames;
+    }
+
+    /// Set the number of instructions executed per frame.
+    pub fn set_cycles_per_frame(&mut self, cycles: u32) {
+        self.cpu.set_cycles_per_frame(cycles);
+    }
+
+    /// Set the behaviour of the instructions interpreters disagree on.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.cpu.set_quirks(quirks);
+    }
+
+    /// Set how instructions are executed, the cached engine being the fastest one available without the JIT.
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.cpu.set_engine(engine);
+    }
+
+    /// The actions worth trying: every combination of the keys of the game, starting with none.
+    pub fn actions(&self) -> Vec<u16> {
+        let keys = self.game.keys;
+        // every subset of the keys, counting through them
+        let mut actions = vec![0];
+        let mut action: u16 = 0;
+        while action != keys {
+            action = action.wrapping_sub(keys) & keys;
+            actions.push(action);
+        }
+        actions
+    }
+
+    /// The machine, to look into the state of the game.
+    pub fn cpu(&self) -> &Cpu<TestKeyboard, TestDisplay> {
+        &self.cpu
+    }
+
+    /// Start a new episode, running the program from its start with fresh memory and registers.
+    ///
+    /// # Arguments
+    /// - `seed`: the seed of the random number generator, the same seed and actions playing the same episode.
+    ///
+    /// # Returns
+    /// Returns the display.
+    pub fn reset(&mut self, seed: u64) -> Framebuffer {
+        self.cpu.reset(self.rom.clone()).expect("the program fit in memory when the environment was made");
+        self.cpu.seed_rng(seed);
+        self.cpu.interconnect_mut().input.set_keys(0);
+        self.score = self.game.score(self.cpu.interconnect().memory.ram());
+        self.done = false;
+        self.observation()
+    }
+
+    /// Hold down keys for the frames of a step, stopping early if the game ends. Stepping once the game is over runs
+    /// nothing until the next `reset`.
+    ///
+    /// # Arguments
+    /// - `action`: the keys held down, the lowest bit for key 0.
+    ///
+    /// # Returns
+    /// Returns the display, the reward earned during the step and whether the game is over.
+    pub fn step(&mut self, action: u16) -> (Framebuffer, f32, bool) {
+        if self.done {
+            return (self.observation(), 0.0, true);
+        }
+        self.cpu.interconnect_mut().input.set_keys(action);
+        for _ in 0..self.frames_per_step {
+            self.cpu.execute_frame();
+            if self.game.is_done(self.cpu.pc(), self.cpu.interconnect().memory.ram()) {
+                self.done = true;
+                break;
+            }
+        }
+        let score = self.game.score(self.cpu.interconnect().memory.ram());
+        let reward = score - self.score;
+        self.score = score;
+        (self.observation(), reward, self.done)
+    }
+
+    /// The display, one bit per pixel.
+    pub fn observation(&self) -> Framebuffer {
+        self.cpu.interconnect().graphics.framebuffer().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_games() {
+        let text = "# BRIX\nreward bcd 0x314\nreward byte 20 -0.5\n\ndone pc 0x2DE\ndone byte 0x1F 0\nkeys 4 6\n";
+        let game = Game::parse(text).unwrap();
+        assert_eq!(game.rewards, vec![(Source::Bcd(0x314), 1.0), (Source::Byte(20), -0.5)]);
+        assert_eq!(game.done, vec![Done::Pc(0x2DE), Done::Equals(Source::Byte(0x1F), 0)]);
+        assert_eq!(game.keys, 0b0101_0000);
+    }
+
+    #[test]
+    fn rejects_malformed_games() {
+        assert_eq!(Game::parse("reward\n").unwrap_err(), "line 1: malformed rule 'reward'");
+        assert!(Game::parse("reward word 0x300").is_err());
+        assert!(Game::parse("reward bcd 0xFFE").is_err(), "the digits do not fit in memory");
+        assert!(Game::parse("done pc 0x1000").is_err());
+        assert!(Game::parse("keys 4 G").is_err());
+    }
+
+    #[test]
+    fn reads_numbers() {
+        let memory = [1, 2, 3, 250];
+        assert_eq!(Source::Bcd(0).read(&memory), 123);
+        assert_eq!(Source::Byte(3).read(&memory), 250);
+    }
+
+    #[test]
+    fn rewards_increases_until_done() {
+        // LD V0, 0; loop: ADD V0, 1; LD I, 0x300; LD [I], V0; JP loop
+        let rom = vec![0x60, 0x00, 0x70, 0x01, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x02];
+        let game = Game::parse("reward byte 0x300 2\ndone byte 0x300 30").unwrap();
+        let mut env = Environment::new(rom, game).unwrap();
+        env.set_cycles_per_frame(4);
+        env.set_frames_per_step(1);
+        env.reset(0);
+        let (_, reward, done) = env.step(0);
+        assert_eq!((reward, done), (2.0, false), "a loop of four instructions runs once per frame");
+        let mut total = reward;
+        loop {
+            let (_, reward, done) = env.step(0);
+            total += reward;
+            if done {
+                break;
+            }
+        }
+        assert_eq!(env.cpu.interconnect().memory.read(0x300), 30);
+        assert_eq!(env.step(0).1, 0.0, "nothing runs once the game is over");
+        assert_eq!(total, 60.0, "every increase of the byte should be rewarded");
+    }
+
+    #[test]
+    fn actions_are_combinations_of_the_keys() {
+        let env = Environment::new(Vec::new(), Game::parse("keys 4 6").unwrap()).unwrap();
+        assert_eq!(env.actions(), vec![0, 1 << 4, 1 << 6, 1 << 4 | 1 << 6]);
+        let env = Environment::new(Vec::new(), Game::default()).unwrap();
+        assert_eq!(env.actions(), vec![0]);
+    }
+}
@@ -1,14 +1,49 @@
 //! Input module
 
-use std::thread::sleep;
-use std::time::Duration;
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, TryRecvError};
 
 use sdl2;
-use sdl2::event::Event;
+use sdl2::event::{Event, WindowEvent};
 use sdl2::keyboard::Keycode;
 
-// Wait for the duration it takes for an instruction to execute.
-const INPUT_WAIT_DELAY: u64 = 2;
+use emulator::Command;
+
+/// Keys held down according to a bitmask, the lowest bit for key 0.
+fn unpack_keys(keys: u16) -> [bool; 16] {
+    let mut state = [false; 16];
+    for (key, held) in state.iter_mut().enumerate() {
+        *held = keys >> key & 1 != 0;
+    }
+    state
+}
+
+/// Emulator controls, independent of the emulated keypad.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hotkey {
+    /// Switch to the next colour theme.
+    CycleTheme,
+    /// Toggle between windowed and fullscreen output.
+    ToggleFullscreen,
+    /// Save a screenshot of the display.
+    Screenshot,
+    /// Start or stop recording gameplay.
+    ToggleRecording,
+    /// Pause or resume the emulation.
+    TogglePause,
+    /// Run a single frame while paused.
+    AdvanceFrame,
+    /// Switch turbo speed on or off.
+    ToggleTurbo,
+    /// Switch slow motion on or off.
+    ToggleSlowMotion,
+    /// Reload the ROM and restart it.
+    Reset,
+    /// Show or hide the emulation statistics.
+    ToggleStats,
+    /// Open or close the memory viewer.
+    ToggleMemoryViewer,
+}
 
 /// Trait implemented by all input devices
 pub trait Input {
@@ -18,31 +53,68 @@ pub trait Input {
     /// Input handling.
     fn handle_inputs(&mut self);
 
-    /// Wait for an input event.
-    fn wait_input(&mut self) -> u8;
-
     /// Checks if a key is pressed.
     fn is_key_down(&self, key: u8) -> bool;
 
+    /// Hold down exactly the keys of a bitmask, the lowest bit for key 0, until the next key event.
+    fn set_keys(&mut self, keys: u16);
+
     /// Checks if a close was requested.
     fn close_requested(&self) -> bool;
+
+    /// Pops the next emulator hotkey that was pressed, if any.
+    fn next_hotkey(&mut self) -> Option<Hotkey> {
+        None
+    }
+
+    /// Checks if the output needs to be redrawn, clearing the request.
+    fn take_redraw_request(&mut self) -> bool {
+        false
+    }
+
+    /// Checks if the fast-forward key is held.
+    fn fast_forward_held(&self) -> bool {
+        false
+    }
+
+    /// Set the secondary window whose keys are passed on as debug keys instead of controlling the keypad.
+    fn set_debug_window(&mut self, _window_id: Option<u32>) {}
+
+    /// Pops the next key pressed in the secondary window, if any.
+    fn next_debug_key(&mut self) -> Option<Keycode> {
+        None
+    }
 }
 
 /// A keyboard
 pub struct Keyboard {
     event_pump: sdl2::EventPump,
     state: [bool; 16],
-    last_input: u8,
-    input_dirty: bool,
-    close_requested: bool
+    close_requested: bool,
+    redraw_requested: bool,
+    fast_forward_held: bool,
+    hotkeys: VecDeque<Hotkey>,
+    debug_window: Option<u32>,
+    debug_keys: VecDeque<Keycode>,
+    key_events: VecDeque<(u8, bool)>,
 }
 
 impl Keyboard {
     /// Set an input.
     fn set_input(&mut self, key: u8, value: bool) {
         self.state[key as usize] = value;
-        self.last_input = key;
-        self.input_dirty = true;
+        self.key_events.push_back((key, value));
+    }
+
+    /// Pops the next press, or release, of a keypad key, if any.
+    pub fn next_key_event(&mut self) -> Option<(u8, bool)> {
+        self.key_events.pop_front()
+    }
+
+    /// Checks if a key is an emulator hotkey rather than a keypad key.
+    fn is_hotkey(keycode: Keycode) -> bool {
+        matches!(keycode, Keycode::F2 | Keycode::F3 | Keycode::F4 | Keycode::F5 | Keycode::F9 | Keycode::F11 |
+                          Keycode::F12 | Keycode::P | Keycode::N | Keycode::T | Keycode::M | Keycode::Tab)
     }
 }
 
@@ -54,9 +126,13 @@ impl Input for Keyboard {
         Self {
             event_pump,
             state: [false; 16],
-            last_input: 0,
-            input_dirty: false,
-            close_requested: false
+            close_requested: false,
+            redraw_requested: false,
+            fast_forward_held: false,
+            hotkeys: VecDeque::new(),
+            debug_window: None,
+            debug_keys: VecDeque::new(),
+            key_events: VecDeque::new(),
         }
     }
 
@@ -67,6 +143,23 @@ impl Input for Keyboard {
         for event in events {
             match event {
                 Event::Quit {..}                                    => self.close_requested = true,
+                // closing the secondary window only closes that window
+                Event::Window { win_event: WindowEvent::Close, window_id, .. } => {
+                    if Some(window_id) == self.debug_window {
+                        self.hotkeys.push_back(Hotkey::ToggleMemoryViewer);
+                    } else {
+                        self.close_requested = true;
+                    }
+                },
+                // keys of the secondary window other than the hotkeys do not reach the keypad
+                Event::KeyDown { keycode: Some(keycode), window_id, .. }
+                    if Some(window_id) == self.debug_window && !Keyboard::is_hotkey(keycode) => {
+                    self.debug_keys.push_back(keycode)
+                },
+                Event::KeyUp { keycode: Some(keycode), window_id, .. }
+                    if Some(window_id) == self.debug_window && !Keyboard::is_hotkey(keycode) => {},
+                Event::Window { win_event: WindowEvent::SizeChanged(..), .. } |
+                Event::Window { win_event: WindowEvent::Exposed, .. } => self.redraw_requested = true,
                 Event::KeyDown { keycode: Some(Keycode::Num0), .. } => self.set_input(0x0, true ),
                 Event::KeyUp   { keycode: Some(Keycode::Num0), .. } => self.set_input(0x0, false),
                 Event::KeyDown { keycode: Some(Keycode::Num1), .. } => self.set_input(0x1, true ),
@@ -99,24 +192,162 @@ impl Input for Keyboard {
                 Event::KeyUp   { keycode: Some(Keycode::E),    .. } => self.set_input(0xe, false),
                 Event::KeyDown { keycode: Some(Keycode::F),    .. } => self.set_input(0xf, true ),
                 Event::KeyUp   { keycode: Some(Keycode::F),    .. } => self.set_input(0xf, false),
+                Event::KeyDown { keycode: Some(Keycode::F2), repeat: false, .. } => {
+                    self.hotkeys.push_back(Hotkey::CycleTheme)
+                },
+                Event::KeyDown { keycode: Some(Keycode::F11), repeat: false, .. } => {
+                    self.hotkeys.push_back(Hotkey::ToggleFullscreen)
+                },
+                Event::KeyDown { keycode: Some(Keycode::F9), repeat: false, .. } => {
+                    self.hotkeys.push_back(Hotkey::ToggleRecording)
+                },
+                Event::KeyDown { keycode: Some(Keycode::F12), repeat: false, .. } => {
+                    self.hotkeys.push_back(Hotkey::Screenshot)
+                },
+                Event::KeyDown { keycode: Some(Keycode::P), repeat: false, .. } => {
+                    self.hotkeys.push_back(Hotkey::TogglePause)
+                },
+                Event::KeyDown { keycode: Some(Keycode::N), .. } => self.hotkeys.push_back(Hotkey::AdvanceFrame),
+                Event::KeyDown { keycode: Some(Keycode::T), repeat: false, .. } => {
+                    self.hotkeys.push_back(Hotkey::ToggleTurbo)
+                },
+                Event::KeyDown { keycode: Some(Keycode::M), repeat: false, .. } => {
+                    self.hotkeys.push_back(Hotkey::ToggleSlowMotion)
+                },
+                Event::KeyDown { keycode: Some(Keycode::F3), repeat: false, .. } => {
+                    self.hotkeys.push_back(Hotkey::ToggleStats)
+                },
+                Event::KeyDown { keycode: Some(Keycode::F4), repeat: false, .. } => {
+                    self.hotkeys.push_back(Hotkey::ToggleMemoryViewer)
+                },
+                Event::KeyDown { keycode: Some(Keycode::F5), repeat: false, .. } => {
+                    self.hotkeys.push_back(Hotkey::Reset)
+                },
+                Event::KeyDown { keycode: Some(Keycode::Tab), .. }  => self.fast_forward_held = true,
+                Event::KeyUp   { keycode: Some(Keycode::Tab), .. }  => self.fast_forward_held = false,
                 _                                                   => {}
             }
         }
     }
 
-    /// Wait until an input event comes through and return the key for that input event.
-    fn wait_input(&mut self) -> u8 {
-        self.input_dirty = false;
+    /// Checks if a key is pressed.
+    #[inline(always)]
+    fn is_key_down(&self, key: u8) -> bool {
+        self.state[key as usize]
+    }
+
+    /// Hold down exactly the keys of a bitmask.
+    fn set_keys(&mut self, keys: u16) {
+        self.state = unpack_keys(keys);
+    }
+
+    /// Checks if a close was requested.
+    #[inline(always)]
+    fn close_requested(&self) -> bool {
+        self.close_requested
+    }
+
+    /// Pops the next emulator hotkey that was pressed, if any.
+    fn next_hotkey(&mut self) -> Option<Hotkey> {
+        self.hotkeys.pop_front()
+    }
+
+    /// Checks if the window was resized or exposed, clearing the request.
+    fn take_redraw_request(&mut self) -> bool {
+        let requested = self.redraw_requested;
+        self.redraw_requested = false;
+        requested
+    }
+
+    /// Checks if the fast-forward key is held.
+    #[inline(always)]
+    fn fast_forward_held(&self) -> bool {
+        self.fast_forward_held
+    }
+
+    /// Set the secondary window whose keys are passed on as debug keys instead of controlling the keypad.
+    fn set_debug_window(&mut self, window_id: Option<u32>) {
+        self.debug_window = window_id;
+        self.debug_keys.clear();
+    }
+
+    /// Pops the next key pressed in the secondary window, if any.
+    fn next_debug_key(&mut self) -> Option<Keycode> {
+        self.debug_keys.pop_front()
+    }
+}
+
+
+/// Keyboard of the emulation thread, fed with the keys pressed in the frontend. The other commands of the frontend
+/// are queued for the emulation thread.
+pub struct RemoteKeyboard {
+    commands: Receiver<Command>,
+    state: [bool; 16],
+    // keys held down in the frontend, which only differ from the keypad when it is set from elsewhere
+    held: u16,
+    close_requested: bool,
+    pending: VecDeque<Command>,
+}
+
+impl RemoteKeyboard {
+    /// Constructor.
+    ///
+    /// # Arguments
+    /// - `commands`: the requests of the frontend.
+    pub fn new_remote(commands: Receiver<Command>) -> RemoteKeyboard {
+        RemoteKeyboard {
+            commands,
+            state: [false; 16],
+            held: 0,
+            close_requested: false,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Keys held down in the frontend, the lowest bit for key 0.
+    pub fn held_keys(&self) -> u16 {
+        self.held
+    }
+
+    /// Pops the next command other than a key or a close, if any.
+    pub fn next_command(&mut self) -> Option<Command> {
+        self.pending.pop_front()
+    }
+
+    /// Press or release a key of the keypad, as if it was in the frontend.
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        self.state[key as usize] = pressed;
+        self.held = if pressed { self.held | 1 << key } else { self.held & !(1 << key) };
+    }
+
+    /// Apply a command.
+    fn receive(&mut self, command: Command) {
+        match command {
+            Command::Key(key, pressed) => self.set_key(key, pressed),
+            Command::Quit              => self.close_requested = true,
+            command                    => self.pending.push_back(command),
+        }
+    }
+}
+
+impl Input for RemoteKeyboard {
+    /// Constructor.
+    fn new(_context: &sdl2::Sdl) -> Self {
+        panic!("The keyboard of the emulation thread is fed by the frontend");
+    }
+
+    /// Apply the commands received since the last call.
+    fn handle_inputs(&mut self) {
         loop {
-            self.handle_inputs();
-            // return last key pressed if key was pressed
-            if self.input_dirty {
-                break;
+            match self.commands.try_recv() {
+                Ok(command)                     => self.receive(command),
+                Err(TryRecvError::Empty)        => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.close_requested = true;
+                    break;
+                },
             }
-            sleep(Duration::from_millis(INPUT_WAIT_DELAY));
         }
-
-        self.last_input
     }
 
     /// Checks if a key is pressed.
@@ -125,6 +356,11 @@ impl Input for Keyboard {
         self.state[key as usize]
     }
 
+    /// Hold down exactly the keys of a bitmask.
+    fn set_keys(&mut self, keys: u16) {
+        self.state = unpack_keys(keys);
+    }
+
     /// Checks if a close was requested.
     #[inline(always)]
     fn close_requested(&self) -> bool {
@@ -132,7 +368,6 @@ impl Input for Keyboard {
     }
 }
 
-
 /// Keyboard used for testing.
 #[allow(dead_code)]
 pub struct TestKeyboard {
@@ -152,6 +387,11 @@ impl TestKeyboard {
         self.state[key as usize] = true;
     }
 
+    /// Simulate key release event.
+    pub fn release_key(&mut self, key: u8) {
+        self.state[key as usize] = false;
+    }
+
     pub fn close(&mut self) {
         self.close_requested = true;
     }
@@ -168,18 +408,17 @@ impl Input for TestKeyboard {
         // input handling is not tested hence this function does nothing
     }
 
-    /// Wait for an input event.
-    fn wait_input(&mut self) -> u8 {
-        // input handling is not tested hence this fucntion does nothing
-        0
-    }
-
     /// Checks if a key is pressed.
     #[inline(always)]
     fn is_key_down(&self, key: u8) -> bool {
         self.state[key as usize]
     }
 
+    /// Hold down exactly the keys of a bitmask.
+    fn set_keys(&mut self, keys: u16) {
+        self.state = unpack_keys(keys);
+    }
+
     /// Checks if a close was requested.
     #[inline(always)]
     fn close_requested(&self) -> bool {
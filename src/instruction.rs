@@ -0,0 +1,164 @@
+//! Instruction module. Decodes opcodes into instructions with their operands extracted, so that an instruction can be
+//! decoded once and executed many times.
+
+/// A decoded instruction, named after Cowgod's mnemonics. `x` and `y` are register numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// `00E0`
+    Cls,
+    /// `00EE`
+    Ret,
+    /// `0NNN` and opcodes that are not instructions, which are ignored.
+    Nop,
+    /// `1NNN`
+    Jp(u16),
+    /// `2NNN`
+    Call(u16),
+    /// `3XKK`
+    SeByte(u8, u8),
+    /// `4XKK`
+    SneByte(u8, u8),
+    /// `5XYN`, N being ignored.
+    SeReg(u8, u8),
+    /// `6XKK`
+    LdByte(u8, u8),
+    /// `7XKK`
+    AddByte(u8, u8),
+    /// `8XY0`
+    LdReg(u8, u8),
+    /// `8XY1`
+    Or(u8, u8),
+    /// `8XY2`
+    And(u8, u8),
+    /// `8XY3`
+    Xor(u8, u8),
+    /// `8XY4`
+    AddReg(u8, u8),
+    /// `8XY5`
+    Sub(u8, u8),
+    /// `8XY6`
+    Shr(u8, u8),
+    /// `8XY7`
+    Subn(u8, u8),
+    /// `8XYE`
+    Shl(u8, u8),
+    /// `9XYN`, N being ignored.
+    SneReg(u8, u8),
+    /// `ANNN`
+    LdI(u16),
+    /// `BNNN`
+    JpV0(u16),
+    /// `CXKK`
+    Rnd(u8, u8),
+    /// `DXYN`
+    Drw(u8, u8, u8),
+    /// `EX9E`
+    Skp(u8),
+    /// `EXA1`
+    Sknp(u8),
+    /// `FX07`
+    LdVxDt(u8),
+    /// `FX0A`
+    LdVxK(u8),
+    /// `FX15`
+    LdDtVx(u8),
+    /// `FX18`
+    LdStVx(u8),
+    /// `FX1E`
+    AddI(u8),
+    /// `FX29`
+    LdF(u8),
+    /// `FX33`
+    LdB(u8),
+    /// `FX55`
+    LdMemVx(u8),
+    /// `FX65`
+    LdVxMem(u8),
+}
+
+impl Instruction {
+    /// Decode an opcode.
+    pub fn decode(opcode: u16) -> Instruction {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let nnn = opcode & 0x0FFF;
+        let kk = (opcode & 0x00FF) as u8;
+        let n = (opcode & 0x000F) as u8;
+
+        match (opcode >> 12, x, y, n) {
+            (0x0, 0, 0xE, 0)   => Instruction::Cls,
+            (0x0, 0, 0xE, 0xE) => Instruction::Ret,
+            (0x1, _, _, _)     => Instruction::Jp(nnn),
+            (0x2, _, _, _)     => Instruction::Call(nnn),
+            (0x3, _, _, _)     => Instruction::SeByte(x, kk),
+            (0x4, _, _, _)     => Instruction::SneByte(x, kk),
+            (0x5, _, _, _)     => Instruction::SeReg(x, y),
+            (0x6, _, _, _)     => Instruction::LdByte(x, kk),
+            (0x7, _, _, _)     => Instruction::AddByte(x, kk),
+            (0x8, _, _, 0x0)   => Instruction::LdReg(x, y),
+            (0x8, _, _, 0x1)   => Instruction::Or(x, y),
+            (0x8, _, _, 0x2)   => Instruction::And(x, y),
+            (0x8, _, _, 0x3)   => Instruction::Xor(x, y),
+            (0x8, _, _, 0x4)   => Instruction::AddReg(x, y),
+            (0x8, _, _, 0x5)   => Instruction::Sub(x, y),
+            (0x8, _, _, 0x6)   => Instruction::Shr(x, y),
+            (0x8, _, _, 0x7)   => Instruction::Subn(x, y),
+            (0x8, _, _, 0xE)   => Instruction::Shl(x, y),
+            (0x9, _, _, _)     => Instruction::SneReg(x, y),
+            (0xA, _, _, _)     => Instruction::LdI(nnn),
+            (0xB, _, _, _)     => Instruction::JpV0(nnn),
+            (0xC, _, _, _)     => Instruction::Rnd(x, kk),
+            (0xD, _, _, _)     => Instruction::Drw(x, y, n),
+            (0xE, _, 0x9, 0xE) => Instruction::Skp(x),
+            (0xE, _, 0xA, 0x1) => Instruction::Sknp(x),
+            (0xF, _, 0x0, 0x7) => Instruction::LdVxDt(x),
+            (0xF, _, 0x0, 0xA) => Instruction::LdVxK(x),
+            (0xF, _, 0x1, 0x5) => Instruction::LdDtVx(x),
+            (0xF, _, 0x1, 0x8) => Instruction::LdStVx(x),
+            (0xF, _, 0x1, 0xE) => Instruction::AddI(x),
+            (0xF, _, 0x2, 0x9) => Instruction::LdF(x),
+            (0xF, _, 0x3, 0x3) => Instruction::LdB(x),
+            (0xF, _, 0x5, 0x5) => Instruction::LdMemVx(x),
+            (0xF, _, 0x6, 0x5) => Instruction::LdVxMem(x),
+            _                  => Instruction::Nop,
+        }
+    }
+
+    /// Checks if the instruction may continue somewhere else than at the next instruction, ending a basic block.
+    /// `LD Vx, K` is one, as it executes again until a key is pressed.
+    pub fn is_branch(self) -> bool {
+        matches!(self, Instruction::Ret | Instruction::Jp(_) | Instruction::Call(_) | Instruction::JpV0(_)
+                 | Instruction::SeByte(..) | Instruction::SneByte(..) | Instruction::SeReg(..)
+                 | Instruction::SneReg(..) | Instruction::Skp(_) | Instruction::Sknp(_) | Instruction::LdVxK(_))
+    }
+
+    /// Checks if the instruction writes to memory, possibly modifying the program.
+    pub fn writes_memory(self) -> bool {
+        matches!(self, Instruction::LdB(_) | Instruction::LdMemVx(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_operands() {
+        assert_eq!(Instruction::decode(0x00E0), Instruction::Cls);
+        assert_eq!(Instruction::decode(0x0123), Instruction::Nop);
+        assert_eq!(Instruction::decode(0x2ABC), Instruction::Call(0xABC));
+        assert_eq!(Instruction::decode(0x8AB5), Instruction::Sub(0xA, 0xB));
+        assert_eq!(Instruction::decode(0x8AB9), Instruction::Nop);
+        assert_eq!(Instruction::decode(0xD125), Instruction::Drw(1, 2, 5));
+        assert_eq!(Instruction::decode(0xF355), Instruction::LdMemVx(3));
+    }
+
+    #[test]
+    fn classifies_instructions() {
+        assert!(Instruction::decode(0x3A05).is_branch());
+        assert!(!Instruction::decode(0x7A05).is_branch());
+        assert!(Instruction::decode(0xFA0A).is_branch());
+        assert!(Instruction::decode(0xF233).writes_memory());
+        assert!(!Instruction::decode(0xF265).writes_memory());
+    }
+}
@@ -1,11 +1,15 @@
 //! Interconnect module
 
+use std::sync::mpsc::Receiver;
+
 use sdl2;
 
 use prelude::*;
+use config::Config;
 use memory::Memory;
-use graphics::{Display, TestDisplay};
-use input::{Keyboard, TestKeyboard};
+use emulator::Command;
+use graphics::{Display, RemoteDisplay, TestDisplay};
+use input::{Keyboard, RemoteKeyboard, TestKeyboard};
 
 /// An interconnect allowing access to memory, peripherals, etc.
 pub struct Interconnect<T, U> where T: Input, U: Graphics {
@@ -19,11 +23,32 @@ pub struct Interconnect<T, U> where T: Input, U: Graphics {
 
 impl Interconnect<Keyboard, Display> {
     /// Constructor.
-    pub fn new(rom: Vec<u8>) -> Interconnect<Keyboard, Display> {
-        let context = sdl2::init().unwrap();
-        let memory = Memory::new(rom);
-        let graphics = Display::new(&context);
-        let input = Keyboard::new(&context);
+    ///
+    /// # Arguments
+    /// - `memory`: the memory with the program loaded.
+    /// - `context`: the SDL context to open the window in and read the keyboard from.
+    /// - `config`: the options of the emulator.
+    pub fn new(memory: Memory, context: &sdl2::Sdl, config: &Config) -> Interconnect<Keyboard, Display> {
+        let graphics = Display::new(context, config);
+        let input = Keyboard::new(context);
+
+        Interconnect {
+            memory,
+            graphics,
+            input
+        }
+    }
+}
+
+impl Interconnect<RemoteKeyboard, RemoteDisplay> {
+    /// Constructor for the emulation thread, with a keyboard fed by the frontend and a display it presents.
+    ///
+    /// # Arguments
+    /// - `memory`: the memory with the program loaded.
+    /// - `commands`: the requests of the frontend.
+    pub fn new_remote(memory: Memory, commands: Receiver<Command>) -> Interconnect<RemoteKeyboard, RemoteDisplay> {
+        let graphics = RemoteDisplay::new_remote();
+        let input = RemoteKeyboard::new_remote(commands);
 
         Interconnect {
             memory,
@@ -37,7 +62,7 @@ impl Interconnect<TestKeyboard, TestDisplay> {
     /// Constructor for a testing interconnect with fake keyboard and fake display.
     #[allow(dead_code)]
     pub fn new_test(rom: Vec<u8>) -> Interconnect<TestKeyboard, TestDisplay> {
-        let memory = Memory::new(rom);
+        let memory = Memory::new(rom).expect("the test program should fit in memory");
         let graphics = TestDisplay::new_test();
         let input = TestKeyboard::new_test();
 
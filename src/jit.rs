@@ -0,0 +1,436 @@
+//! JIT module. Backs the JIT engine, which translates basic blocks into native x86-64 code, only available with the
+//! `jit` feature.
+//!
+//! Native code works on the registers V0 to VF, I and the program counter, copied into `Registers` along with the delay
+//! timer, which it only reads. It covers the register and jump instructions and reading the delay timer; the others,
+//! drawing, keys, setting timers, memory, the stack and random numbers, exit to the host, which interprets them. A
+//! compiled block is the longest run of covered instructions starting at an address, so a block starting at an
+//! instruction that is not covered is empty and its instruction is interpreted.
+//!
+//! Native code never writes memory, the interpreted instructions do. As with the block cache, compiled instructions
+//! are marked as code in memory and blocks holding overwritten instructions are dropped.
+
+use std::mem;
+use std::ops::Range;
+
+use dynasmrt::{dynasm, AssemblyOffset, DynasmApi, DynasmLabelApi, ExecutableBuffer};
+use dynasmrt::x64::Assembler;
+
+use cache::Block;
+use instruction::Instruction;
+use memory::{Memory, MEMORY_SIZE};
+use quirks::Quirks;
+
+/// Offsets of the fields of `Registers`.
+const OFFSET_VF: i32 = 0xF;
+const OFFSET_I: i32 = 16;
+const OFFSET_PC: i32 = 18;
+const OFFSET_DT: i32 = 20;
+
+/// Registers native code works on.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Registers {
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub dt: u8,
+}
+
+/// Signature of a compiled block: takes the registers and the maximum number of instructions to execute, and returns
+/// the number of instructions executed with the program counter pointing to the next one.
+type Entry = extern "sysv64" fn(*mut Registers, u64) -> u64;
+
+/// A block translated to native code.
+pub struct CompiledBlock {
+    /// Addresses of the compiled instructions, or of the first instruction if it is left to the interpreter.
+    pub range: Range<usize>,
+    /// Number of compiled instructions.
+    len: usize,
+    code: Option<(ExecutableBuffer, AssemblyOffset)>,
+}
+
+impl CompiledBlock {
+    /// Compile the longest run of covered instructions at the start of a block.
+    ///
+    /// # Arguments
+    /// - `block`: the decoded block.
+    /// - `quirks`: the behaviour of the instructions interpreters disagree on, which is compiled in.
+    fn compile(block: &Block, quirks: Quirks) -> CompiledBlock {
+        let len = block.instructions.iter().take_while(|&&(_, instruction)| is_covered(instruction)).count();
+        let start = block.start as usize;
+        let range = start..start + 2 * len.max(1);
+        if len == 0 {
+            return CompiledBlock { range, len, code: None };
+        }
+
+        let mut ops = Assembler::new().expect("cannot allocate executable memory");
+        let entry = ops.offset();
+        // rdi: registers, rsi: maximum number of instructions, rax: instructions executed
+        dynasm!(ops
+            ; .arch x64
+            ; xor eax, eax
+        );
+        let mut exits = Vec::new();
+        let mut addr = block.start;
+        for &(_, instruction) in block.instructions.iter().take(len) {
+            if addr != block.start {
+                let exit = ops.new_dynamic_label();
+                exits.push((exit, addr));
+                dynasm!(ops
+                    ; .arch x64
+                    ; cmp rax, rsi
+                    ; jae =>exit
+                );
+            }
+            emit(&mut ops, addr, instruction, quirks);
+            dynasm!(ops
+                ; .arch x64
+                ; inc rax
+            );
+            addr += 2;
+        }
+        if !block.instructions[len - 1].1.is_branch() {
+            dynasm!(ops
+                ; .arch x64
+                ; mov WORD [rdi + OFFSET_PC], addr as i16
+            );
+        }
+        dynasm!(ops
+            ; .arch x64
+            ; ret
+        );
+        // out of instructions, continue at the next one later
+        for (exit, addr) in exits {
+            dynasm!(ops
+                ; .arch x64
+                ; =>exit
+                ; mov WORD [rdi + OFFSET_PC], addr as i16
+                ; ret
+            );
+        }
+        let buffer = ops.finalize().unwrap_or_else(|_| panic!("cannot finalize native code"));
+        CompiledBlock { range, len, code: Some((buffer, entry)) }
+    }
+
+    /// Number of compiled instructions, zero if the first instruction is left to the interpreter.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Checks if the first instruction is left to the interpreter.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Run the native code.
+    ///
+    /// # Arguments
+    /// - `registers`: the registers, the program counter pointing to the start of the block.
+    /// - `cycles`: the maximum number of instructions to execute, at least one.
+    ///
+    /// # Returns
+    /// Returns the number of instructions executed.
+    pub fn run(&self, registers: &mut Registers, cycles: usize) -> usize {
+        let (ref buffer, entry) = *self.code.as_ref().expect("empty blocks are interpreted");
+        // safety: the buffer holds a function of this signature at the entry, which only accesses the registers
+        let entry: Entry = unsafe { mem::transmute(buffer.ptr(entry)) };
+        entry(registers, cycles as u64) as usize
+    }
+}
+
+/// Checks if native code covers an instruction. `SUB`, `SUBN` and `ADD I` are not covered, as they overflow in the
+/// interpreter, which panics in debug builds.
+fn is_covered(instruction: Instruction) -> bool {
+    matches!(instruction, Instruction::Nop | Instruction::Jp(_) | Instruction::SeByte(..) | Instruction::SneByte(..)
+             | Instruction::SeReg(..) | Instruction::LdByte(..) | Instruction::AddByte(..) | Instruction::LdReg(..)
+             | Instruction::Or(..) | Instruction::And(..) | Instruction::Xor(..) | Instruction::AddReg(..)
+             | Instruction::Shr(..) | Instruction::Shl(..) | Instruction::SneReg(..) | Instruction::LdI(_)
+             | Instruction::JpV0(_) | Instruction::LdF(_) | Instruction::LdVxDt(_))
+}
+
+/// Emit the native code of an instruction.
+///
+/// # Arguments
+/// - `ops`: the assembler.
+/// - `addr`: the address of the instruction.
+/// - `instruction`: a covered instruction.
+/// - `quirks`: the behaviour of the instructions interpreters disagree on.
+fn emit(ops: &mut Assembler, addr: u16, instruction: Instruction, quirks: Quirks) {
+    let next = addr.wrapping_add(2) as i16;
+    let skip = addr.wrapping_add(4) as i16;
+    match instruction {
+        Instruction::Nop            => (),
+        Instruction::Jp(nnn)        => dynasm!(ops
+            ; .arch x64
+            ; mov WORD [rdi + OFFSET_PC], nnn as i16
+        ),
+        Instruction::SeByte(x, kk)  => dynasm!(ops
+            ; .arch x64
+            ; mov WORD [rdi + OFFSET_PC], next
+            ; cmp BYTE [rdi + x as i32], kk as i8
+            ; jne >done
+            ; mov WORD [rdi + OFFSET_PC], skip
+            ; done:
+        ),
+        Instruction::SneByte(x, kk) => dynasm!(ops
+            ; .arch x64
+            ; mov WORD [rdi + OFFSET_PC], next
+            ; cmp BYTE [rdi + x as i32], kk as i8
+            ; je >done
+            ; mov WORD [rdi + OFFSET_PC], skip
+            ; done:
+        ),
+        Instruction::SeReg(x, y)    => dynasm!(ops
+            ; .arch x64
+            ; mov WORD [rdi + OFFSET_PC], next
+            ; mov cl, BYTE [rdi + y as i32]
+            ; cmp BYTE [rdi + x as i32], cl
+            ; jne >done
+            ; mov WORD [rdi + OFFSET_PC], skip
+            ; done:
+        ),
+        Instruction::SneReg(x, y)   => dynasm!(ops
+            ; .arch x64
+            ; mov WORD [rdi + OFFSET_PC], next
+            ; mov cl, BYTE [rdi + y as i32]
+            ; cmp BYTE [rdi + x as i32], cl
+            ; je >done
+            ; mov WORD [rdi + OFFSET_PC], skip
+            ; done:
+        ),
+        Instruction::LdByte(x, kk)  => dynasm!(ops
+            ; .arch x64
+            ; mov BYTE [rdi + x as i32], kk as i8
+        ),
+        Instruction::AddByte(x, kk) => dynasm!(ops
+            ; .arch x64
+            ; add BYTE [rdi + x as i32], kk as i8
+        ),
+        Instruction::LdReg(x, y)    => dynasm!(ops
+            ; .arch x64
+            ; mov cl, BYTE [rdi + y as i32]
+            ; mov BYTE [rdi + x as i32], cl
+        ),
+        Instruction::Or(x, y)       => {
+            dynasm!(ops
+                ; .arch x64
+                ; mov cl, BYTE [rdi + y as i32]
+                ; or BYTE [rdi + x as i32], cl
+            );
+            emit_vf_reset(ops, quirks);
+        },
+        Instruction::And(x, y)      => {
+            dynasm!(ops
+                ; .arch x64
+                ; mov cl, BYTE [rdi + y as i32]
+                ; and BYTE [rdi + x as i32], cl
+            );
+            emit_vf_reset(ops, quirks);
+        },
+        Instruction::Xor(x, y)      => {
+            dynasm!(ops
+                ; .arch x64
+                ; mov cl, BYTE [rdi + y as i32]
+                ; xor BYTE [rdi + x as i32], cl
+            );
+            emit_vf_reset(ops, quirks);
+        },
+        // the carry is written before the sum, as the interpreter does
+        Instruction::AddReg(x, y)   => dynasm!(ops
+            ; .arch x64
+            ; movzx ecx, BYTE [rdi + x as i32]
+            ; movzx edx, BYTE [rdi + y as i32]
+            ; add ecx, edx
+            ; cmp ecx, 0xFF
+            ; seta dl
+            ; mov BYTE [rdi + OFFSET_VF], dl
+            ; mov BYTE [rdi + x as i32], cl
+        ),
+        Instruction::Shr(x, y)      => {
+            let source = if quirks.shift_vy { y } else { x };
+            dynasm!(ops
+                ; .arch x64
+                ; mov cl, BYTE [rdi + source as i32]
+                ; mov dl, cl
+                ; and dl, 0x1
+                ; mov BYTE [rdi + OFFSET_VF], dl
+                ; shr cl, 1
+                ; mov BYTE [rdi + x as i32], cl
+            );
+        },
+        // VF is set to the bit shifted out in place, as the interpreter does
+        Instruction::Shl(x, y)      => {
+            let source = if quirks.shift_vy { y } else { x };
+            dynasm!(ops
+                ; .arch x64
+                ; mov cl, BYTE [rdi + source as i32]
+                ; mov dl, cl
+                ; and dl, 0x80_u8 as i8
+                ; mov BYTE [rdi + OFFSET_VF], dl
+                ; shl cl, 1
+                ; mov BYTE [rdi + x as i32], cl
+            );
+        },
+        Instruction::LdI(nnn)       => dynasm!(ops
+            ; .arch x64
+            ; mov WORD [rdi + OFFSET_I], nnn as i16
+        ),
+        Instruction::JpV0(nnn)      => {
+            let x = if quirks.jump_vx { nnn >> 8 } else { 0 };
+            dynasm!(ops
+                ; .arch x64
+                ; movzx ecx, BYTE [rdi + x as i32]
+                ; add ecx, nnn as i32
+                ; mov WORD [rdi + OFFSET_PC], cx
+            );
+        },
+        Instruction::LdF(x)         => dynasm!(ops
+            ; .arch x64
+            ; movzx ecx, BYTE [rdi + x as i32]
+            ; lea ecx, [rcx + rcx * 4]
+            ; mov WORD [rdi + OFFSET_I], cx
+        ),
+        Instruction::LdVxDt(x)      => dynasm!(ops
+            ; .arch x64
+            ; mov cl, BYTE [rdi + OFFSET_DT]
+            ; mov BYTE [rdi + x as i32], cl
+        ),
+        _                           => unreachable!("{:?} is not covered by native code", instruction),
+    }
+}
+
+/// Emit resetting VF after a logical operation if the quirk is enabled.
+fn emit_vf_reset(ops: &mut Assembler, quirks: Quirks) {
+    if quirks.vf_reset {
+        dynasm!(ops
+            ; .arch x64
+            ; mov BYTE [rdi + OFFSET_VF], 0
+        );
+    }
+}
+
+/// Compiled blocks indexed by their start address.
+pub struct JitCache {
+    blocks: Vec<Option<CompiledBlock>>,
+}
+
+impl Default for JitCache {
+    fn default() -> JitCache {
+        JitCache { blocks: (0..MEMORY_SIZE).map(|_| None).collect() }
+    }
+}
+
+impl JitCache {
+    /// Constructor.
+    pub fn new() -> JitCache {
+        JitCache::default()
+    }
+
+    /// Drop all blocks, as when another program is loaded or the quirks change.
+    pub fn clear(&mut self) {
+        self.blocks.iter_mut().for_each(|block| *block = None);
+    }
+
+    /// Number of cached blocks.
+    pub fn len(&self) -> usize {
+        self.blocks.iter().filter(|block| block.is_some()).count()
+    }
+
+    /// Checks if no block is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the block starting at an address, compiling it unless it is cached. Blocks holding instructions written to
+    /// since they were compiled are dropped first.
+    ///
+    /// # Arguments
+    /// - `memory`: the memory holding the program.
+    /// - `start`: the address of the first instruction.
+    /// - `quirks`: the behaviour of the instructions interpreters disagree on.
+    pub fn block(&mut self, memory: &mut Memory, start: u16, quirks: Quirks) -> &CompiledBlock {
+        let writes = memory.take_code_writes();
+        if !writes.is_empty() {
+            for entry in self.blocks.iter_mut() {
+                if entry.as_ref().is_some_and(|block| writes.iter().any(|addr| block.range.contains(addr))) {
+                    *entry = None;
+                }
+            }
+        }
+        let entry = &mut self.blocks[start as usize];
+        if entry.is_none() {
+            let block = CompiledBlock::compile(&Block::decode(memory, start), quirks);
+            memory.mark_code(block.range.clone());
+            *entry = Some(block);
+        }
+        entry.as_ref().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(program: &[u8], quirks: Quirks) -> CompiledBlock {
+        let memory = Memory::new(program.to_vec()).unwrap();
+        CompiledBlock::compile(&Block::decode(&memory, 0x200), quirks)
+    }
+
+    #[test]
+    fn compiles_register_instructions() {
+        // LD V0, 0xF0; LD V1, 0x20; ADD V0, V1; SHR V1; LD F, V1; SE V0, 0x10
+        let program = [0x60, 0xF0, 0x61, 0x20, 0x80, 0x14, 0x81, 0x16, 0xF1, 0x29, 0x30, 0x10];
+        let block = compile(&program, Quirks::default());
+        assert_eq!(block.len(), 6);
+        let mut registers = Registers { pc: 0x200, ..Registers::default() };
+        assert_eq!(block.run(&mut registers, 32), 6);
+        assert_eq!((registers.v[0], registers.v[1], registers.v[0xF]), (0x10, 0x10, 0));
+        assert_eq!(registers.i, 0x50, "I points to the sprite of the digit");
+        assert_eq!(registers.pc, 0x20E, "the next instruction is skipped");
+    }
+
+    #[test]
+    fn stops_after_the_given_number_of_instructions() {
+        // LD V0, 0x01; ADD V0, 0x01; ADD V0, 0x01
+        let block = compile(&[0x60, 0x01, 0x70, 0x01, 0x70, 0x01], Quirks::default());
+        let mut registers = Registers { pc: 0x200, ..Registers::default() };
+        assert_eq!(block.run(&mut registers, 2), 2);
+        assert_eq!((registers.v[0], registers.pc), (2, 0x204));
+    }
+
+    #[test]
+    fn leaves_other_instructions_to_the_interpreter() {
+        // LD V0, 0x01; DRW V0, V0, 1
+        assert_eq!(compile(&[0x60, 0x01, 0xD0, 0x01], Quirks::default()).len(), 1);
+        assert!(compile(&[0xD0, 0x01], Quirks::default()).is_empty());
+    }
+
+    #[test]
+    fn compiles_quirks_in() {
+        // LD VF, 0x01; LD V3, 0x01; OR V0, V1; JP V0, 0x300
+        let program = [0x6F, 0x01, 0x63, 0x01, 0x80, 0x11, 0xB3, 0x00];
+        let mut registers = Registers { pc: 0x200, ..Registers::default() };
+        compile(&program, Quirks::from_name("cosmac").unwrap()).run(&mut registers, 32);
+        assert_eq!((registers.v[0xF], registers.pc), (0, 0x300), "VF is reset");
+        let mut registers = Registers { pc: 0x200, ..Registers::default() };
+        compile(&program, Quirks::from_name("schip").unwrap()).run(&mut registers, 32);
+        assert_eq!((registers.v[0xF], registers.pc), (1, 0x301), "the jump is offset by V3");
+    }
+
+    #[test]
+    fn writes_to_code_drop_blocks() {
+        // LD V0, 0x05; ADD V0, 0x01; DRW V0, V0, 1
+        let mut memory = Memory::new(vec![0x60, 0x05, 0x70, 0x01, 0xD0, 0x01]).unwrap();
+        let mut cache = JitCache::new();
+        cache.block(&mut memory, 0x200, Quirks::default());
+        memory.write(0x205, 0x02);
+        cache.block(&mut memory, 0x200, Quirks::default());
+        assert_eq!(cache.len(), 1, "the interpreted instruction is not compiled code");
+
+        memory.write(0x203, 0x02);
+        let mut registers = Registers { pc: 0x200, ..Registers::default() };
+        cache.block(&mut memory, 0x200, Quirks::default()).run(&mut registers, 32);
+        assert_eq!(registers.v[0], 0x07, "the overwritten instruction is compiled again");
+    }
+}
@@ -0,0 +1,55 @@
+//! Implementation of CHIP-8 emulator. I implemented this to understand how emulators work and learn more about
+//! computer architecture.
+//!
+//! Some of the code contained in this implementation is based on the following:
+//! - https://github.com/ColinEberhardt/wasm-rust-chip8
+//! - https://github.com/mikezaby/chip-8.rs
+//! - https://github.com/Reshurum/notch
+
+extern crate clap;
+extern crate sdl2;
+extern crate rand;
+extern crate png;
+extern crate gif;
+extern crate rhai;
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "jit")]
+extern crate dynasmrt;
+
+#[cfg(all(feature = "jit", not(target_arch = "x86_64")))]
+compile_error!("the jit feature requires an x86-64 target");
+
+pub mod cache;
+pub mod config;
+pub mod filter;
+pub mod framebuffer;
+pub mod cpu;
+pub mod disassembler;
+pub mod effects;
+pub mod emulator;
+pub mod interconnect;
+pub mod vm;
+pub mod memory;
+pub mod memory_viewer;
+pub mod netplay;
+pub mod osd;
+pub mod input;
+pub mod instruction;
+#[cfg(feature = "jit")]
+pub mod jit;
+pub mod graphics;
+pub mod gym;
+pub mod palette;
+pub mod prelude;
+pub mod profiler;
+pub mod quirks;
+pub mod recompiled;
+pub mod recompiler;
+pub mod recorder;
+pub mod rpc;
+pub mod screenshot;
+pub mod script;
+pub mod speed;
+pub mod trace;
+pub mod viewport;
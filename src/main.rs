@@ -1,31 +1,66 @@
-//! Implementation of CHIP-8 emulator. I implemented this to understand how emulators work and learn more about
-//! computer architecture.
-//!
-//! Some of the code contained in this implementation is based on the following:
-//! - https://github.com/ColinEberhardt/wasm-rust-chip8
-//! - https://github.com/mikezaby/chip-8.rs
-//! - https://github.com/Reshurum/notch
+//! Command line entry point of the CHIP-8 emulator.
 
 #[macro_use] extern crate clap;
-extern crate sdl2;
-extern crate rand;
+extern crate chip_8;
 
-use clap::App;
+use std::fs;
+use std::path::PathBuf;
 
-mod cpu;
-mod interconnect;
-mod vm;
-mod memory;
-mod input;
-mod graphics;
-mod prelude;
+use clap::{App, ArgMatches};
+
+use chip_8::config::Config;
+use chip_8::recompiler::{self, Program};
+use chip_8::vm::VirtualMachine;
 
 
 fn main() {
     let yaml = load_yaml!("../static/cli.yml");
-    let matches = App::from_yaml(yaml).version(env!("CARGO_PKG_VERSION")).get_matches();
+    let matches = App::from_yaml(yaml)
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand_negates_reqs(true)
+        .get_matches();
+    if let Some(matches) = matches.subcommand_matches("recompile") {
+        recompile(matches);
+        return;
+    }
     let rom = matches.value_of("ROM").expect("ROM should be supplied");
     let rom_path = format!("{}/static/roms/{}", env!("CARGO_MANIFEST_DIR"), rom);
-    let mut vm = vm::VirtualMachine::new(&rom_path);
+    let config = Config::from_matches(&matches);
+    if config.headless {
+        VirtualMachine::run_headless(&rom_path, &config);
+        return;
+    }
+    let mut vm = VirtualMachine::new(&rom_path, &config);
     vm.run();
 }
+
+/// Recompile a bundled ROM into a cargo project.
+fn recompile(matches: &ArgMatches) {
+    let name = matches.value_of("ROM").expect("ROM should be supplied");
+    let rom_path = format!("{}/static/roms/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let rom = fs::read(&rom_path).unwrap_or_else(|why| {
+        eprintln!("Cannot read ROM {}: {}", rom_path, why);
+        std::process::exit(1);
+    });
+    let cycles = matches.value_of("cycles-per-frame").expect("cycles per frame should have a default");
+    let cycles_per_frame = Config::parse_positive("cycles per frame", cycles);
+    let quirks = matches.value_of("quirks").expect("quirks should have a default");
+    let out = match matches.value_of("out") {
+        Some(out) => PathBuf::from(out),
+        None      => PathBuf::from("recompiled").join(name.to_lowercase()),
+    };
+
+    let program = Program::recover(name, &rom).unwrap_or_else(|why| {
+        eprintln!("Cannot recompile ROM {}: {}", rom_path, why);
+        std::process::exit(1);
+    });
+    let source = program.to_rust(quirks, cycles_per_frame);
+    let package = format!("chip8-{}", name.to_lowercase());
+    if let Err(why) = recompiler::write_project(&out, &package, &source) {
+        eprintln!("Cannot write {}: {}", out.display(), why);
+        std::process::exit(1);
+    }
+    println!("Recompiled {} into {}: {} routines, {} instructions", name, out.display(), program.routines(),
+             program.instructions());
+    println!("Run it with: cargo run --release --manifest-path {}", out.join("Cargo.toml").display());
+}
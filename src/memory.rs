@@ -1,7 +1,10 @@
 //! Memory module.
 
+use std::mem;
+use std::ops::Range;
+
 /// Font set of the CHIP-8
-static FONTSET: [u8; 80] = [
+pub static FONTSET: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0,   // 0
     0x20, 0x60, 0x20, 0x20, 0x70,   // 1
     0xF0, 0x10, 0xF0, 0x80, 0xF0,   // 2
@@ -24,37 +27,106 @@ static FONTSET: [u8; 80] = [
 /// Initial offset of program memory
 pub const END_RESERVED: usize = 0x200;
 
+/// Size of the memory in bytes
+pub const MEMORY_SIZE: usize = 4096;
+
+/// Size of the largest program, which fills the memory after the reserved area.
+pub const MAX_PROGRAM_SIZE: usize = MEMORY_SIZE - END_RESERVED;
+
 /// Memory of the program
+#[derive(Clone)]
 pub struct Memory {
-    ram: [u8; 4096]
+    ram: [u8; MEMORY_SIZE],
+    // length of the loaded program
+    program_len: usize,
+    // bytes written since the writes were last taken
+    written: [bool; MEMORY_SIZE],
+    // bytes holding instructions that were decoded ahead of execution
+    code: [bool; MEMORY_SIZE],
+    // addresses of such bytes written since the code writes were last taken
+    code_writes: Vec<usize>,
 }
 
 #[allow(dead_code)]
 impl Memory {
     /// Constructor
-    pub fn new(rom: Vec<u8>) -> Memory {
-        let mut memory = [0; 4096];
+    ///
+    /// # Returns
+    /// Returns the memory with the fontset and the program loaded, or an error if the program does not fit.
+    pub fn new(rom: Vec<u8>) -> Result<Memory, String> {
+        if rom.len() > MAX_PROGRAM_SIZE {
+            return Err(format!("the program is {} bytes, more than the {} bytes that fit in memory", rom.len(),
+                               MAX_PROGRAM_SIZE));
+        }
+        let mut memory = [0; MEMORY_SIZE];
+        let program_len = rom.len();
         Memory::dump_fontset(&mut memory);
         Memory::dump_program(&mut memory, rom);
-        Memory { ram: memory }
+        Ok(Memory {
+            ram: memory,
+            program_len,
+            written: [false; MEMORY_SIZE],
+            code: [false; MEMORY_SIZE],
+            code_writes: Vec::new(),
+        })
+    }
+
+    /// Range of addresses the program was loaded to.
+    pub fn program_range(&self) -> Range<usize> {
+        END_RESERVED..END_RESERVED + self.program_len
+    }
+
+    /// Take the addresses written to since the last call, in ascending order.
+    pub fn take_writes(&mut self) -> Vec<usize> {
+        let mut writes = Vec::new();
+        for (addr, written) in self.written.iter_mut().enumerate() {
+            if *written {
+                writes.push(addr);
+                *written = false;
+            }
+        }
+        writes
     }
 
-    /// Read from memory at address `addr`
+    /// Mark bytes as holding decoded instructions, so that writing to them is reported by `take_code_writes`. The
+    /// part of the range past the end of memory is ignored.
+    pub fn mark_code(&mut self, range: Range<usize>) {
+        for code in &mut self.code[range.start.min(MEMORY_SIZE)..range.end.min(MEMORY_SIZE)] {
+            *code = true;
+        }
+    }
+
+    /// Take the addresses of decoded instructions written to since the last call.
+    pub fn take_code_writes(&mut self) -> Vec<usize> {
+        mem::take(&mut self.code_writes)
+    }
+
+    /// The whole memory.
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    /// Read from memory at address `addr`, wrapping around past the end of memory
     #[inline(always)]
     pub fn read(&self, addr: usize) -> u8 {
-        self.ram[addr]
+        self.ram[addr % MEMORY_SIZE]
     }
 
-    /// Write to memory at address `addr`
+    /// Write to memory at address `addr`, wrapping around past the end of memory
     #[inline(always)]
     pub fn write(&mut self, addr: usize, byte: u8) {
+        let addr = addr % MEMORY_SIZE;
         self.ram[addr] = byte;
+        self.written[addr] = true;
+        if self.code[addr] {
+            self.code_writes.push(addr);
+        }
     }
 
-    /// Read a word from memory
+    /// Read a word from memory, wrapping around past the end of memory
     #[inline(always)]
     pub fn read_word(&self, addr: usize) -> u16 {
-        (self.ram[addr] as u16) << 8 | (self.ram[addr + 1] as u16)
+        (self.read(addr) as u16) << 8 | (self.read(addr + 1) as u16)
     }
 
     /// Reads a slice from memory.
@@ -66,6 +138,15 @@ impl Memory {
     /// Gets a mutable reference to a slice from memory.
     #[inline(always)]
     pub fn get_slice_mut(&mut self, addr: usize, length: u8) -> &mut [u8] {
+        // the whole slice is assumed to be written to
+        for written in &mut self.written[addr..(addr + length as usize)] {
+            *written = true;
+        }
+        for code_addr in addr..(addr + length as usize) {
+            if self.code[code_addr] {
+                self.code_writes.push(code_addr);
+            }
+        }
         &mut self.ram[addr..(addr + length as usize)]
     }
 
@@ -90,8 +171,13 @@ mod tests {
 
     fn get_mem() -> Memory {
         let rom = vec![1, 2, 3, 4, 5, 6, 7];
-        let memory = Memory::new(rom);
-        memory
+        Memory::new(rom).unwrap()
+    }
+
+    #[test]
+    fn rejects_programs_too_large() {
+        assert!(Memory::new(vec![0; MAX_PROGRAM_SIZE]).is_ok(), "a program may fill the memory");
+        assert!(Memory::new(vec![0; MAX_PROGRAM_SIZE + 1]).is_err());
     }
 
     #[test]
@@ -120,6 +206,33 @@ mod tests {
         assert_eq!(memory.read(0x207), 8);
     }
 
+    #[test]
+    fn writes_are_tracked() {
+        let mut memory = get_mem();
+        assert!(memory.take_writes().is_empty(), "loading the program is not a write");
+        memory.write(0x300, 1);
+        memory.get_slice_mut(0x210, 2);
+        assert_eq!(memory.take_writes(), vec![0x210, 0x211, 0x300]);
+        assert!(memory.take_writes().is_empty(), "taking the writes clears them");
+    }
+
+    #[test]
+    fn code_writes_are_tracked() {
+        let mut memory = get_mem();
+        memory.mark_code(0x200..0x204);
+        memory.write(0x204, 1);
+        assert!(memory.take_code_writes().is_empty(), "only writes to code are reported");
+        memory.write(0x201, 1);
+        memory.get_slice_mut(0x1FF, 4);
+        assert_eq!(memory.take_code_writes(), vec![0x201, 0x200, 0x201, 0x202]);
+        assert!(memory.take_code_writes().is_empty());
+    }
+
+    #[test]
+    fn program_range() {
+        assert_eq!(get_mem().program_range(), 0x200..0x207);
+    }
+
     #[test]
     fn read_word() {
         let memory = get_mem();
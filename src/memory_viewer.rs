@@ -0,0 +1,391 @@
+//! Memory viewer module. Shows the memory as a live hex dump in a secondary window and lets bytes be edited while the
+//! emulation is paused.
+
+use sdl2;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Texture, WindowCanvas};
+
+use effects::Image;
+use graphics::BYTES_PER_PIXEL;
+use memory::{Memory, MEMORY_SIZE};
+use osd::{draw_char, GLYPH_HEIGHT, GLYPH_WIDTH, SPACING};
+
+/// Number of bytes shown per row.
+pub const BYTES_PER_ROW: usize = 16;
+/// Number of rows shown at once.
+const ROWS: usize = 32;
+/// Number of window pixels per text pixel.
+const TEXT_SIZE: usize = 2;
+/// Number of characters per row: the address followed by a colon, then the bytes separated by spaces.
+const COLUMNS: usize = 5 + 3 * BYTES_PER_ROW;
+/// Number of frames until the highlight of a written byte has faded to half intensity.
+const WRITE_HALF_LIFE: f32 = 20.0;
+/// Number of bytes highlighted from `I` on, the most a sprite or a register dump reads or writes.
+const I_RANGE: usize = 16;
+/// Size of the font area at the start of the memory.
+const FONT_SIZE: usize = 80;
+
+/// Colours of the hex dump.
+const BACKGROUND: [u8; 3] = [16, 16, 16];
+const TEXT: [u8; 3] = [150, 150, 150];
+const FONT_TEXT: [u8; 3] = [110, 160, 255];
+const PROGRAM_TEXT: [u8; 3] = [120, 220, 120];
+const HEADER_TEXT: [u8; 3] = [255, 255, 255];
+const PC_BACKGROUND: [u8; 3] = [130, 110, 0];
+const I_BACKGROUND: [u8; 3] = [0, 60, 120];
+const WRITE_BACKGROUND: [u8; 3] = [220, 30, 30];
+const CURSOR_BACKGROUND: [u8; 3] = [230, 230, 230];
+const CURSOR_TEXT: [u8; 3] = [0, 0, 0];
+
+/// A key controlling the hex dump.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditKey {
+    Up,
+    Down,
+    Left,
+    Right,
+    PageUp,
+    PageDown,
+    /// Jump to the program counter.
+    Home,
+    /// Type a hex digit into the byte under the cursor.
+    Digit(u8),
+}
+
+impl EditKey {
+    /// Map an SDL key to an edit key.
+    pub fn from_keycode(keycode: Keycode) -> Option<EditKey> {
+        let digit = match keycode {
+            Keycode::Up       => return Some(EditKey::Up),
+            Keycode::Down     => return Some(EditKey::Down),
+            Keycode::Left     => return Some(EditKey::Left),
+            Keycode::Right    => return Some(EditKey::Right),
+            Keycode::PageUp   => return Some(EditKey::PageUp),
+            Keycode::PageDown => return Some(EditKey::PageDown),
+            Keycode::Home     => return Some(EditKey::Home),
+            Keycode::Num0     => 0x0,
+            Keycode::Num1     => 0x1,
+            Keycode::Num2     => 0x2,
+            Keycode::Num3     => 0x3,
+            Keycode::Num4     => 0x4,
+            Keycode::Num5     => 0x5,
+            Keycode::Num6     => 0x6,
+            Keycode::Num7     => 0x7,
+            Keycode::Num8     => 0x8,
+            Keycode::Num9     => 0x9,
+            Keycode::A        => 0xa,
+            Keycode::B        => 0xb,
+            Keycode::C        => 0xc,
+            Keycode::D        => 0xd,
+            Keycode::E        => 0xe,
+            Keycode::F        => 0xf,
+            _                 => return None,
+        };
+        Some(EditKey::Digit(digit))
+    }
+}
+
+/// Registers highlighted in the hex dump.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Registers {
+    pub pc: u16,
+    pub i: u16,
+}
+
+/// State of the hex dump: the scroll position, the cursor and the highlights of recently written bytes.
+pub struct HexView {
+    top_row: usize,
+    cursor: usize,
+    // high nibble typed into the byte under the cursor
+    pending: Option<u8>,
+    // highlight intensity of each byte, 1.0 when just written
+    heat: Vec<f32>,
+}
+
+impl HexView {
+    /// Constructor.
+    pub fn new() -> HexView {
+        HexView { top_row: 0, cursor: 0, pending: None, heat: vec![0.0; MEMORY_SIZE] }
+    }
+
+    /// Advance by one frame, fading out old writes and highlighting new ones.
+    pub fn update(&mut self, writes: &[usize]) {
+        let factor = 0.5_f32.powf(1.0 / WRITE_HALF_LIFE);
+        for heat in &mut self.heat {
+            *heat *= factor;
+        }
+        for &addr in writes {
+            self.heat[addr] = 1.0;
+        }
+    }
+
+    /// Handle a key.
+    ///
+    /// # Arguments
+    /// - `key`: the key that was pressed.
+    /// - `memory`: the memory to edit.
+    /// - `registers`: the registers, used to jump to the program counter.
+    /// - `editable`: whether digits edit the memory, which is only allowed while paused.
+    pub fn handle_key(&mut self, key: EditKey, memory: &mut Memory, registers: Registers, editable: bool) {
+        let cursor = self.cursor as isize;
+        let target = match key {
+            EditKey::Up       => cursor - BYTES_PER_ROW as isize,
+            EditKey::Down     => cursor + BYTES_PER_ROW as isize,
+            EditKey::Left     => cursor - 1,
+            EditKey::Right    => cursor + 1,
+            EditKey::PageUp   => cursor - (ROWS * BYTES_PER_ROW) as isize,
+            EditKey::PageDown => cursor + (ROWS * BYTES_PER_ROW) as isize,
+            EditKey::Home     => registers.pc as isize,
+            EditKey::Digit(digit) => {
+                if editable {
+                    self.type_digit(digit, memory);
+                }
+                return;
+            },
+        };
+        self.move_cursor(target.max(0).min(MEMORY_SIZE as isize - 1) as usize);
+    }
+
+    /// Type a digit, writing the byte once both nibbles are typed.
+    fn type_digit(&mut self, digit: u8, memory: &mut Memory) {
+        match self.pending.take() {
+            None       => self.pending = Some(digit),
+            Some(high) => {
+                memory.write(self.cursor, high << 4 | digit);
+                if self.cursor + 1 < MEMORY_SIZE {
+                    self.move_cursor(self.cursor + 1);
+                }
+            },
+        }
+    }
+
+    /// Move the cursor, scrolling to keep it visible and dropping a half typed byte.
+    fn move_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor;
+        self.pending = None;
+        let row = cursor / BYTES_PER_ROW;
+        if row < self.top_row {
+            self.top_row = row;
+        } else if row >= self.top_row + ROWS {
+            self.top_row = row + 1 - ROWS;
+        }
+    }
+
+    /// Render the hex dump.
+    ///
+    /// # Arguments
+    /// - `memory`: the memory to show.
+    /// - `registers`: the registers to highlight.
+    /// - `editable`: whether the memory can be edited, shown in the header.
+    pub fn render(&self, memory: &Memory, registers: Registers, editable: bool) -> Image {
+        let (width, height) = HexView::size();
+        let mut image = Image::new(width, height);
+        fill(&mut image, 0, 0, width, height, BACKGROUND);
+
+        let mode = if editable { "EDIT" } else { "PAUSE TO EDIT" };
+        let header = format!("PC {:04X}  I {:04X}  {}", registers.pc, registers.i, mode);
+        draw_string(&mut image, 0, 0, &header, HEADER_TEXT);
+
+        let program = memory.program_range();
+        let i_range = registers.i as usize..registers.i as usize + I_RANGE;
+        for line in 0..ROWS {
+            let row_addr = (self.top_row + line) * BYTES_PER_ROW;
+            if row_addr >= MEMORY_SIZE {
+                break;
+            }
+            draw_string(&mut image, 0, line + 1, &format!("{:03X}:", row_addr), HEADER_TEXT);
+            for offset in 0..BYTES_PER_ROW {
+                let addr = row_addr + offset;
+                let column = 5 + 3 * offset;
+                let is_pc = addr == registers.pc as usize || addr == registers.pc as usize + 1;
+                let mut background = if is_pc {
+                    PC_BACKGROUND
+                } else if i_range.contains(&addr) {
+                    I_BACKGROUND
+                } else {
+                    BACKGROUND
+                };
+                background = blend(background, WRITE_BACKGROUND, self.heat[addr]);
+                let mut text = if addr < FONT_SIZE {
+                    FONT_TEXT
+                } else if program.contains(&addr) {
+                    PROGRAM_TEXT
+                } else {
+                    TEXT
+                };
+                if addr == self.cursor {
+                    background = CURSOR_BACKGROUND;
+                    text = CURSOR_TEXT;
+                }
+                let byte = match (addr == self.cursor, self.pending) {
+                    (true, Some(high)) => format!("{:X}_", high),
+                    _                  => format!("{:02X}", memory.read(addr)),
+                };
+                let (x, y) = cell(column, line + 1);
+                fill(&mut image, x, y, 2 * cell_width() + SPACING * TEXT_SIZE, cell_height(), background);
+                draw_string(&mut image, column, line + 1, &byte, text);
+            }
+        }
+        image
+    }
+
+    /// Size of the rendered hex dump in pixels, one header row above the rows of bytes.
+    pub fn size() -> (usize, usize) {
+        (COLUMNS * cell_width() + SPACING * TEXT_SIZE, (ROWS + 1) * cell_height() + SPACING * TEXT_SIZE)
+    }
+}
+
+/// Width of a character cell in pixels.
+fn cell_width() -> usize {
+    (GLYPH_WIDTH + SPACING) * TEXT_SIZE
+}
+
+/// Height of a character cell in pixels.
+fn cell_height() -> usize {
+    (GLYPH_HEIGHT + SPACING) * TEXT_SIZE
+}
+
+/// Top left corner of a character cell in pixels.
+fn cell(column: usize, line: usize) -> (usize, usize) {
+    (column * cell_width(), line * cell_height())
+}
+
+/// Draw a string starting at a character cell.
+fn draw_string(image: &mut Image, column: usize, line: usize, text: &str, color: [u8; 3]) {
+    for (n, c) in text.chars().enumerate() {
+        let (x, y) = cell(column + n, line);
+        draw_char(image, x + SPACING * TEXT_SIZE, y + SPACING * TEXT_SIZE, TEXT_SIZE, c, color);
+    }
+}
+
+/// Fill a rectangle, clipped to the image.
+fn fill(image: &mut Image, x: usize, y: usize, width: usize, height: usize, color: [u8; 3]) {
+    for py in y..(y + height).min(image.height) {
+        for px in x..(x + width).min(image.width) {
+            let idx = (px + py * image.width) * BYTES_PER_PIXEL;
+            image.pixels[idx..idx + BYTES_PER_PIXEL].copy_from_slice(&color);
+        }
+    }
+}
+
+/// Blend two colours linearly, `amount` being the weight of `to`.
+fn blend(from: [u8; 3], to: [u8; 3], amount: f32) -> [u8; 3] {
+    let channel = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * amount).round() as u8;
+    [channel(from[0], to[0]), channel(from[1], to[1]), channel(from[2], to[2])]
+}
+
+impl Default for HexView {
+    fn default() -> HexView {
+        HexView::new()
+    }
+}
+
+/// Secondary window showing the hex dump.
+pub struct MemoryViewer {
+    canvas: WindowCanvas,
+    texture: Texture,
+    view: HexView,
+}
+
+impl MemoryViewer {
+    /// Open the window.
+    pub fn open(video: &sdl2::VideoSubsystem) -> Result<MemoryViewer, String> {
+        let (width, height) = HexView::size();
+        let window = video.window("CHIP-8 Memory", width as u32, height as u32)
+            .build()
+            .map_err(|why| why.to_string())?;
+        let canvas = window.into_canvas().software().build().map_err(|why| why.to_string())?;
+        let texture = canvas.texture_creator()
+            .create_texture_streaming(PixelFormatEnum::RGB24, width as u32, height as u32)
+            .map_err(|why| why.to_string())?;
+        Ok(MemoryViewer { canvas, texture, view: HexView::new() })
+    }
+
+    /// Identifier of the window, used to route its keys.
+    pub fn window_id(&self) -> u32 {
+        self.canvas.window().id()
+    }
+
+    /// Handle a key pressed in the window.
+    pub fn handle_key(&mut self, keycode: Keycode, memory: &mut Memory, registers: Registers, editable: bool) {
+        if let Some(key) = EditKey::from_keycode(keycode) {
+            self.view.handle_key(key, memory, registers, editable);
+        }
+    }
+
+    /// Advance by one frame and present the hex dump.
+    pub fn present(&mut self, memory: &mut Memory, registers: Registers, editable: bool) {
+        self.view.update(&memory.take_writes());
+        let image = self.view.render(memory, registers, editable);
+        let _ = self.texture.update(None, &image.pixels, image.width * BYTES_PER_PIXEL);
+        let _ = self.canvas.copy(&self.texture, None, None);
+        self.canvas.present();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REGISTERS: Registers = Registers { pc: 0x200, i: 0x300 };
+
+    fn get_mem() -> Memory {
+        Memory::new(vec![0x12, 0x34]).unwrap()
+    }
+
+    #[test]
+    fn typing_edits_only_while_editable() {
+        let mut memory = get_mem();
+        let mut view = HexView::new();
+        view.handle_key(EditKey::Home, &mut memory, REGISTERS, true);
+        view.handle_key(EditKey::Digit(0xA), &mut memory, REGISTERS, false);
+        view.handle_key(EditKey::Digit(0xB), &mut memory, REGISTERS, false);
+        assert_eq!(memory.read(0x200), 0x12, "memory is read-only while running");
+
+        view.handle_key(EditKey::Digit(0xA), &mut memory, REGISTERS, true);
+        assert_eq!(memory.read(0x200), 0x12, "a single nibble does not write");
+        view.handle_key(EditKey::Digit(0xB), &mut memory, REGISTERS, true);
+        assert_eq!(memory.read(0x200), 0xAB);
+        assert_eq!(view.cursor, 0x201, "the cursor moves to the next byte");
+    }
+
+    #[test]
+    fn cursor_scrolls_and_clamps() {
+        let mut memory = get_mem();
+        let mut view = HexView::new();
+        view.handle_key(EditKey::Up, &mut memory, REGISTERS, false);
+        assert_eq!(view.cursor, 0);
+        view.handle_key(EditKey::PageDown, &mut memory, REGISTERS, false);
+        assert_eq!(view.cursor, ROWS * BYTES_PER_ROW);
+        assert_eq!(view.top_row, 1, "the view scrolls to keep the cursor visible");
+        for _ in 0..MEMORY_SIZE / (ROWS * BYTES_PER_ROW) {
+            view.handle_key(EditKey::PageDown, &mut memory, REGISTERS, false);
+        }
+        assert_eq!(view.cursor, MEMORY_SIZE - 1);
+    }
+
+    #[test]
+    fn writes_fade_out() {
+        let mut view = HexView::new();
+        view.update(&[0x300]);
+        assert_eq!(view.heat[0x300], 1.0);
+        for _ in 0..WRITE_HALF_LIFE as usize {
+            view.update(&[]);
+        }
+        assert!((view.heat[0x300] - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn render_highlights_pc() {
+        let memory = get_mem();
+        let mut view = HexView::new();
+        view.move_cursor(0x200);
+        let image = view.render(&memory, Registers { pc: 0x202, i: 0 }, false);
+        assert_eq!((image.width, image.height), HexView::size());
+        let (x, y) = cell(5 + 3 * 2, 0x200 / BYTES_PER_ROW - view.top_row + 1);
+        assert_eq!(image.pixel(x, y), PC_BACKGROUND, "the byte at the program counter is highlighted");
+        let (x, y) = cell(5, 0x200 / BYTES_PER_ROW - view.top_row + 1);
+        assert_eq!(image.pixel(x, y), CURSOR_BACKGROUND, "the byte under the cursor is highlighted");
+    }
+}
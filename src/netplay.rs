@@ -0,0 +1,684 @@
+//! Netplay module. Two emulators play together over TCP in deterministic lockstep: every frame, each player sends the
+//! keys it holds down to the other, and a frame only runs once the keys of both players for it are known. Each player
+//! owns a subset of the keypad, the keys of the other player being ignored.
+//!
+//! The keys are sent a few frames ahead, the input delay, so that they usually arrive before they are needed. Every
+//! second the players exchange a hash of their machine. When the hashes differ the host sends its whole machine, which
+//! the guest takes over.
+//!
+//! Messages are a tag byte followed by big-endian fields.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand;
+
+use cpu::{Cpu, Snapshot, State};
+use framebuffer::Framebuffer;
+use graphics::{Graphics, HEIGHT};
+use input::Input;
+use memory::MEMORY_SIZE;
+use quirks::Quirks;
+
+/// Frames the keys are sent ahead unless configured otherwise.
+pub const DEFAULT_INPUT_DELAY: u8 = 2;
+/// Largest input delay, half a second.
+pub const MAX_INPUT_DELAY: u8 = 30;
+/// Keys owned by the host and by the guest unless configured otherwise: 0 to 7 and 8 to F, which split the keypad
+/// of two-player games like PONG between the left and the right player.
+pub const DEFAULT_KEYS: [u16; 2] = [0x00FF, 0xFF00];
+/// Frames between two comparisons of the machines.
+const HASH_INTERVAL: u64 = 60;
+/// Frames the keys are kept for after they were used, as a resync may rewind the guest by up to the input delay.
+const HISTORY: u64 = 2 * MAX_INPUT_DELAY as u64 + 2;
+/// Version of the protocol, both players must speak the same.
+const VERSION: u8 = 2;
+/// Tags of the messages.
+const HELLO: u8 = 0;
+const INPUT: u8 = 1;
+const HASH: u8 = 2;
+const RESYNC: u8 = 3;
+/// Encoding of no key being waited for in a snapshot.
+const NO_KEY: u8 = 0xFF;
+/// Parameters of the FNV-1a hash.
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// How to reach the other player.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Peer {
+    /// Wait for the other player on a port, playing as the host.
+    Host(u16),
+    /// Connect to the host at an address, `HOST:PORT`.
+    Join(String),
+}
+
+/// Options of a netplay session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetplayConfig {
+    /// How to reach the other player.
+    pub peer: Peer,
+    /// Frames the keys are sent ahead, only the one of the host being used.
+    pub input_delay: u8,
+    /// Keys owned by this player, one bit per key, the default ones of its role if absent.
+    pub keys: Option<u16>,
+}
+
+/// Description of the machine of a player, sent when connecting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hello {
+    /// Hash of the ROM.
+    pub rom: u64,
+    /// Behaviour of the instructions interpreters disagree on.
+    pub quirks: Quirks,
+    /// Number of instructions executed per frame.
+    pub cycles_per_frame: u32,
+    /// Seed of the random number generators, only the one of the host being used.
+    pub seed: u64,
+    /// Frames the keys are sent ahead, only the one of the host being used.
+    pub input_delay: u8,
+    /// Keys owned by the player, one bit per key.
+    pub keys: u16,
+}
+
+impl Hello {
+    /// Constructor, with a random seed.
+    ///
+    /// # Arguments
+    /// - `rom`: the ROM.
+    /// - `quirks`: the behaviour of the instructions interpreters disagree on.
+    /// - `cycles_per_frame`: the number of instructions executed per frame.
+    /// - `input_delay`: the frames the keys are sent ahead.
+    /// - `keys`: the keys owned by the player.
+    pub fn new(rom: &[u8], quirks: Quirks, cycles_per_frame: u32, input_delay: u8, keys: u16) -> Hello {
+        Hello { rom: fnv(rom), quirks, cycles_per_frame, seed: rand::random(), input_delay, keys }
+    }
+
+    /// Check that the machine of the other player runs like this one, and that the players own different keys.
+    fn check(&self, other: &Hello) -> io::Result<()> {
+        if self.rom != other.rom {
+            return Err(invalid("the other player runs a different ROM"));
+        }
+        if self.quirks != other.quirks {
+            return Err(invalid("the other player runs with different quirks"));
+        }
+        if self.cycles_per_frame != other.cycles_per_frame {
+            return Err(invalid("the other player runs a different number of cycles per frame"));
+        }
+        if self.keys & other.keys != 0 {
+            return Err(invalid(&format!("both players own the keys {}", key_list(self.keys & other.keys))));
+        }
+        if other.input_delay > MAX_INPUT_DELAY {
+            return Err(invalid("the input delay of the other player is too long"));
+        }
+        Ok(())
+    }
+}
+
+/// Messages exchanged by the players.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    /// The machine of the player, sent first.
+    Hello(Hello),
+    /// The keys of the player held down in a frame.
+    Input { frame: u64, keys: u16 },
+    /// Hash of the machine of the player before a frame.
+    Hash { frame: u64, hash: u64 },
+    /// The machine of the host before a frame, which the guest takes over after a desync, with the seed both players
+    /// reseed their random number generators with.
+    Resync { frame: u64, seed: u64, snapshot: Box<Snapshot> },
+}
+
+impl Message {
+    /// Encode the message.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match *self {
+            Message::Hello(ref hello)                       => {
+                writer.write_all(&[HELLO, VERSION])?;
+                write_u64(writer, hello.rom)?;
+                writer.write_all(&[quirk_bits(hello.quirks)])?;
+                write_u32(writer, hello.cycles_per_frame)?;
+                write_u64(writer, hello.seed)?;
+                writer.write_all(&[hello.input_delay])?;
+                write_u16(writer, hello.keys)
+            },
+            Message::Input { frame, keys }                  => {
+                writer.write_all(&[INPUT])?;
+                write_u64(writer, frame)?;
+                write_u16(writer, keys)
+            },
+            Message::Hash { frame, hash }                   => {
+                writer.write_all(&[HASH])?;
+                write_u64(writer, frame)?;
+                write_u64(writer, hash)
+            },
+            Message::Resync { frame, seed, ref snapshot }   => {
+                writer.write_all(&[RESYNC])?;
+                write_u64(writer, frame)?;
+                write_u64(writer, seed)?;
+                write_snapshot(writer, snapshot)
+            },
+        }
+    }
+
+    /// Decode the next message.
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Message> {
+        match read_u8(reader)? {
+            HELLO  => {
+                if read_u8(reader)? != VERSION {
+                    return Err(invalid("the other player runs an incompatible version"));
+                }
+                Ok(Message::Hello(Hello {
+                    rom: read_u64(reader)?,
+                    quirks: quirks_from_bits(read_u8(reader)?),
+                    cycles_per_frame: read_u32(reader)?,
+                    seed: read_u64(reader)?,
+                    input_delay: read_u8(reader)?,
+                    keys: read_u16(reader)?,
+                }))
+            },
+            INPUT  => Ok(Message::Input { frame: read_u64(reader)?, keys: read_u16(reader)? }),
+            HASH   => Ok(Message::Hash { frame: read_u64(reader)?, hash: read_u64(reader)? }),
+            RESYNC => Ok(Message::Resync {
+                frame: read_u64(reader)?,
+                seed: read_u64(reader)?,
+                snapshot: Box::new(read_snapshot(reader)?),
+            }),
+            tag    => Err(invalid(&format!("unknown message {}", tag))),
+        }
+    }
+}
+
+/// A game in lockstep with another player.
+pub struct Session {
+    writer: BufWriter<TcpStream>,
+    // messages of the other player, decoded on a thread of their own
+    messages: Receiver<io::Result<Message>>,
+    host: bool,
+    seed: u64,
+    input_delay: u64,
+    keys: u16,
+    other_keys: u16,
+    // next frame to run, and next frame to send the keys of
+    frame: u64,
+    sent: u64,
+    // keys of both players by frame
+    inputs: BTreeMap<u64, u16>,
+    other_inputs: BTreeMap<u64, u16>,
+    // hashes by frame, until both are known
+    hashes: BTreeMap<u64, u64>,
+    other_hashes: BTreeMap<u64, u64>,
+    // hashes of the other player before this frame predate the last resync
+    hashes_valid_from: u64,
+    resyncs: u32,
+}
+
+impl Session {
+    /// Start a session as configured, waiting for the other player to connect when hosting.
+    ///
+    /// # Arguments
+    /// - `config`: the options of the session.
+    /// - `rom`: the ROM.
+    /// - `quirks`: the behaviour of the instructions interpreters disagree on.
+    /// - `cycles_per_frame`: the number of instructions executed per frame.
+    pub fn connect(config: &NetplayConfig, rom: &[u8], quirks: Quirks, cycles_per_frame: u32) -> io::Result<Session> {
+        let hello = |keys| Hello::new(rom, quirks, cycles_per_frame, config.input_delay, config.keys.unwrap_or(keys));
+        match config.peer {
+            Peer::Host(port)        => Session::host(&TcpListener::bind(("0.0.0.0", port))?, hello(DEFAULT_KEYS[0])),
+            Peer::Join(ref address) => Session::join(address.as_str(), hello(DEFAULT_KEYS[1])),
+        }
+    }
+
+    /// Start a session as the host, waiting for the other player to connect.
+    pub fn host(listener: &TcpListener, hello: Hello) -> io::Result<Session> {
+        let (stream, _) = listener.accept()?;
+        Session::start(stream, true, hello)
+    }
+
+    /// Start a session as the guest, connecting to the host.
+    pub fn join<A: ToSocketAddrs>(address: A, hello: Hello) -> io::Result<Session> {
+        Session::start(TcpStream::connect(address)?, false, hello)
+    }
+
+    /// Exchange the machines of the players and start receiving messages.
+    fn start(stream: TcpStream, host: bool, hello: Hello) -> io::Result<Session> {
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+        Message::Hello(hello.clone()).write(&mut writer)?;
+        writer.flush()?;
+        let other = match Message::read(&mut reader)? {
+            Message::Hello(other) => other,
+            _                     => return Err(invalid("the other player did not introduce itself")),
+        };
+        hello.check(&other)?;
+        let (seed, input_delay) = if host { (hello.seed, hello.input_delay) } else { (other.seed, other.input_delay) };
+
+        let (sender, messages) = channel();
+        thread::Builder::new()
+            .name("netplay".to_string())
+            .spawn(move || loop {
+                let message = Message::read(&mut reader);
+                let failed = message.is_err();
+                if sender.send(message).is_err() || failed {
+                    break;
+                }
+            })?;
+        // nobody holds a key before the first keys arrive
+        let input_delay = input_delay as u64;
+        let none: BTreeMap<u64, u16> = (0..input_delay).map(|frame| (frame, 0)).collect();
+        Ok(Session {
+            writer,
+            messages,
+            host,
+            seed,
+            input_delay,
+            keys: hello.keys,
+            other_keys: other.keys,
+            frame: 0,
+            sent: input_delay,
+            inputs: none.clone(),
+            other_inputs: none,
+            hashes: BTreeMap::new(),
+            other_hashes: BTreeMap::new(),
+            hashes_valid_from: 0,
+            resyncs: 0,
+        })
+    }
+
+    /// Checks if this player hosts the session.
+    pub fn is_host(&self) -> bool {
+        self.host
+    }
+
+    /// Seed both players start their random number generators with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Next frame to run.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Number of times the guest took over the machine of the host.
+    pub fn resyncs(&self) -> u32 {
+        self.resyncs
+    }
+
+    /// Run the next frame once the keys of both players for it are known.
+    ///
+    /// # Arguments
+    /// - `cpu`: the machine, started like the one of the other player, with the seed of the session.
+    /// - `keys`: the keys held down by this player, one bit per key, the ones it does not own being ignored.
+    /// - `timeout`: how long to wait for the keys of the other player.
+    ///
+    /// # Returns
+    /// Returns `true` if the frame ran, `false` if the keys of the other player did not arrive in time, or the error
+    /// ending the session.
+    pub fn step<T: Input, U: Graphics>(&mut self, cpu: &mut Cpu<T, U>, keys: u16, timeout: Duration)
+        -> io::Result<bool> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            self.send_inputs(keys)?;
+            if self.other_inputs.contains_key(&self.frame) {
+                break;
+            }
+            let message = match self.messages.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(message)                         => message?,
+                Err(RecvTimeoutError::Timeout)      => return Ok(false),
+                Err(RecvTimeoutError::Disconnected) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            };
+            self.receive(cpu, message)?;
+        }
+
+        let keys = self.inputs[&self.frame] | self.other_inputs[&self.frame];
+        cpu.interconnect_mut().input.set_keys(keys);
+        cpu.execute_frame();
+        self.frame += 1;
+        if self.frame.is_multiple_of(HASH_INTERVAL) {
+            let hash = hash(&cpu.snapshot());
+            self.hashes.insert(self.frame, hash);
+            self.send(Message::Hash { frame: self.frame, hash })?;
+            self.compare_hashes(cpu)?;
+        }
+        let oldest = self.frame.saturating_sub(HISTORY);
+        self.inputs = self.inputs.split_off(&oldest);
+        self.other_inputs = self.other_inputs.split_off(&oldest);
+        Ok(true)
+    }
+
+    /// Send the keys of this player up to the input delay ahead of the next frame.
+    fn send_inputs(&mut self, keys: u16) -> io::Result<()> {
+        let keys = keys & self.keys;
+        while self.sent <= self.frame + self.input_delay {
+            self.inputs.insert(self.sent, keys);
+            let frame = self.sent;
+            self.sent += 1;
+            self.send(Message::Input { frame, keys })?;
+        }
+        Ok(())
+    }
+
+    /// Send a message to the other player.
+    fn send(&mut self, message: Message) -> io::Result<()> {
+        message.write(&mut self.writer)?;
+        self.writer.flush()
+    }
+
+    /// Handle a message of the other player.
+    fn receive<T: Input, U: Graphics>(&mut self, cpu: &mut Cpu<T, U>, message: Message) -> io::Result<()> {
+        match message {
+            Message::Hello(_)                        => return Err(invalid("the other player introduced itself again")),
+            Message::Input { frame, keys }           => {
+                self.other_inputs.insert(frame, keys & self.other_keys);
+            },
+            Message::Hash { frame, hash }            => if frame >= self.hashes_valid_from {
+                self.other_hashes.insert(frame, hash);
+                self.compare_hashes(cpu)?;
+            },
+            Message::Resync { frame, seed, snapshot } => {
+                if self.host {
+                    return Err(invalid("the guest cannot resync the host"));
+                }
+                cpu.restore(&snapshot);
+                cpu.seed_rng(seed);
+                self.frame = frame;
+                self.hashes.clear();
+                self.other_hashes.clear();
+                self.resyncs += 1;
+            },
+        }
+        Ok(())
+    }
+
+    /// Compare the hashes known of both players, the host sending its machine if they differ.
+    fn compare_hashes<T: Input, U: Graphics>(&mut self, cpu: &mut Cpu<T, U>) -> io::Result<()> {
+        let frames: Vec<u64> = self.other_hashes.keys().filter(|frame| self.hashes.contains_key(frame)).cloned()
+            .collect();
+        let mut desync = false;
+        for frame in frames {
+            desync |= self.hashes.remove(&frame) != self.other_hashes.remove(&frame);
+        }
+        if desync && self.host {
+            self.resync(cpu)?;
+        }
+        Ok(())
+    }
+
+    /// Send the machine of the host to the guest, both players reseeding their random number generators.
+    fn resync<T: Input, U: Graphics>(&mut self, cpu: &mut Cpu<T, U>) -> io::Result<()> {
+        let seed = rand::random();
+        cpu.seed_rng(seed);
+        let snapshot = Box::new(cpu.snapshot());
+        self.send(Message::Resync { frame: self.frame, seed, snapshot })?;
+        self.hashes.clear();
+        self.other_hashes.clear();
+        // the guest may have run ahead by up to the input delay before taking over the machine
+        self.hashes_valid_from = self.frame + self.input_delay + 2;
+        self.resyncs += 1;
+        Ok(())
+    }
+}
+
+impl Drop for Session {
+    /// Hang up, which also stops the thread receiving the messages.
+    fn drop(&mut self) {
+        let _ = self.writer.get_ref().shutdown(Shutdown::Both);
+    }
+}
+
+/// Hash of everything a program can observe of a machine.
+pub fn hash(snapshot: &Snapshot) -> u64 {
+    let mut bytes = Vec::new();
+    write_snapshot(&mut bytes, snapshot).expect("writing to memory should not fail");
+    fnv(&bytes)
+}
+
+/// FNV-1a hash of bytes.
+fn fnv(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET, |hash, &byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// Keys of a bitmask as hex digits.
+fn key_list(keys: u16) -> String {
+    (0..16).filter(|key| keys >> key & 1 != 0).map(|key| format!("{:X}", key)).collect::<Vec<_>>().join(", ")
+}
+
+/// Error of a malformed or unexpected message.
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// The quirks as one bit each.
+fn quirk_bits(quirks: Quirks) -> u8 {
+    [quirks.vf_reset, quirks.shift_vy, quirks.load_store_increment, quirks.jump_vx, quirks.clip, quirks.key_release]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (bit, &on)| bits | (on as u8) << bit)
+}
+
+/// The quirks encoded by `quirk_bits`.
+fn quirks_from_bits(bits: u8) -> Quirks {
+    let on = |bit: u8| bits >> bit & 1 != 0;
+    Quirks {
+        vf_reset: on(0),
+        shift_vy: on(1),
+        load_store_increment: on(2),
+        jump_vx: on(3),
+        clip: on(4),
+        key_release: on(5),
+    }
+}
+
+fn write_snapshot<W: Write>(writer: &mut W, snapshot: &Snapshot) -> io::Result<()> {
+    let state = &snapshot.state;
+    write_u16(writer, state.pc)?;
+    for &addr in state.stack.iter() {
+        write_u16(writer, addr)?;
+    }
+    writer.write_all(&[state.sp])?;
+    writer.write_all(&state.v)?;
+    write_u16(writer, state.i)?;
+    writer.write_all(&[state.dt, state.st, snapshot.pressed_key.unwrap_or(NO_KEY)])?;
+    writer.write_all(&[snapshot.held_keys.is_some() as u8])?;
+    write_u16(writer, snapshot.held_keys.unwrap_or(0))?;
+    writer.write_all(&snapshot.memory)?;
+    for &row in snapshot.framebuffer.rows() {
+        write_u64(writer, row)?;
+    }
+    Ok(())
+}
+
+fn read_snapshot<R: Read>(reader: &mut R) -> io::Result<Snapshot> {
+    let mut state = State { pc: read_u16(reader)?, ..State::default() };
+    for addr in state.stack.iter_mut() {
+        *addr = read_u16(reader)?;
+    }
+    state.sp = read_u8(reader)?;
+    reader.read_exact(&mut state.v)?;
+    state.i = read_u16(reader)?;
+    state.dt = read_u8(reader)?;
+    state.st = read_u8(reader)?;
+    let pressed_key = match read_u8(reader)? {
+        NO_KEY           => None,
+        key if key < 16  => Some(key),
+        key              => return Err(invalid(&format!("key {} in snapshot", key))),
+    };
+    let held_keys = match (read_u8(reader)?, read_u16(reader)?) {
+        (0, _)    => None,
+        (1, keys) => Some(keys),
+        (flag, _) => return Err(invalid(&format!("held keys flag {} in snapshot", flag))),
+    };
+    let mut memory = vec![0; MEMORY_SIZE];
+    reader.read_exact(&mut memory)?;
+    let rows = (0..HEIGHT).map(|_| read_u64(reader)).collect::<io::Result<Vec<u64>>>()?;
+    Ok(Snapshot { state, memory, framebuffer: Framebuffer::from_rows(rows), held_keys, pressed_key })
+}
+
+fn write_u16<W: Write>(writer: &mut W, value: u16) -> io::Result<()> {
+    writer.write_all(&value.to_be_bytes())
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&value.to_be_bytes())
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    writer.write_all(&value.to_be_bytes())
+}
+
+fn read_bytes<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    Ok(read_bytes::<R, 1>(reader)?[0])
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    Ok(u16::from_be_bytes(read_bytes(reader)?))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    Ok(u32::from_be_bytes(read_bytes(reader)?))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    Ok(u64::from_be_bytes(read_bytes(reader)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+    use std::thread::JoinHandle;
+
+    use graphics::TestDisplay;
+    use input::TestKeyboard;
+    use interconnect::Interconnect;
+
+    /// Time to wait for the other player before considering the session stuck.
+    const TIMEOUT: Duration = Duration::from_secs(5);
+    /// A program drawing random bytes along a path steered by the keys 1 and C.
+    const PROGRAM: [u8; 24] = [
+        0xC2, 0xFF, // 0x200: RND V2, 0xFF
+        0xA3, 0x00, // 0x202: LD I, 0x300
+        0xF2, 0x55, // 0x204: LD [I], V2
+        0x63, 0x01, // 0x206: LD V3, 0x01
+        0xE3, 0x9E, // 0x208: SKP V3
+        0x71, 0x01, // 0x20A: ADD V1, 0x01
+        0x63, 0x0C, // 0x20C: LD V3, 0x0C
+        0xE3, 0xA1, // 0x20E: SKNP V3
+        0x71, 0x02, // 0x210: ADD V1, 0x02
+        0xD0, 0x11, // 0x212: DRW V0, V1, 1
+        0x70, 0x01, // 0x214: ADD V0, 0x01
+        0x12, 0x00, // 0x216: JP 0x200
+    ];
+
+    fn cpu(seed: u64) -> Cpu<TestKeyboard, TestDisplay> {
+        let mut cpu = Cpu::new(Interconnect::new_test(PROGRAM.to_vec()));
+        cpu.seed_rng(seed);
+        cpu
+    }
+
+    fn hello(keys: u16) -> Hello {
+        Hello::new(&PROGRAM, Quirks::default(), 10, 3, keys)
+    }
+
+    /// Run a player for a number of frames, holding down a key every other frame, with a hook called before each frame.
+    ///
+    /// # Returns
+    /// Returns the machine at the end, the number of resyncs, and the session, which is only dropped once both players
+    /// are done so that neither hangs up on the other.
+    fn play<F>(session: io::Result<Session>, frames: u64, key: u8, mut hook: F) -> (Snapshot, u32, Session)
+        where F: FnMut(u64, &mut Cpu<TestKeyboard, TestDisplay>) {
+        let mut session = session.unwrap();
+        let mut cpu = cpu(session.seed());
+        while session.frame() < frames {
+            hook(session.frame(), &mut cpu);
+            let keys = if session.frame().is_multiple_of(2) { 1 << key } else { 0 };
+            assert!(session.step(&mut cpu, keys, TIMEOUT).unwrap(), "the keys of the other player should arrive");
+        }
+        (cpu.snapshot(), session.resyncs(), session)
+    }
+
+    /// Play a game on loopback, the guest corrupting its machine at a frame if given.
+    ///
+    /// # Returns
+    /// Returns the machines of the host and the guest at the end, and the number of resyncs of each.
+    fn game(frames: u64, corrupt: Option<u64>) -> ((Snapshot, u32), (Snapshot, u32)) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let host: JoinHandle<_> = thread::spawn(move || {
+            play(Session::host(&listener, hello(DEFAULT_KEYS[0])), frames, 0x1, |_, _| ())
+        });
+        let guest = play(Session::join(address, hello(DEFAULT_KEYS[1])), frames, 0xC, |frame, cpu| {
+            if Some(frame) == corrupt {
+                cpu.interconnect_mut().memory.write(0x300, 0xEE);
+                cpu.seed_rng(0);
+            }
+        });
+        let host = host.join().unwrap();
+        ((host.0, host.1), (guest.0, guest.1))
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let mut snapshot = cpu(1).snapshot();
+        snapshot.held_keys = Some(0x0041);
+        snapshot.pressed_key = Some(0xA);
+        snapshot.framebuffer.set(3, 4, true);
+        let messages = vec![
+            Message::Hello(hello(0x0F0F)),
+            Message::Input { frame: 12, keys: 0x8001 },
+            Message::Hash { frame: 60, hash: 0x0123_4567_89AB_CDEF },
+            Message::Resync { frame: 7, seed: 42, snapshot: Box::new(snapshot) },
+        ];
+        let mut bytes = Vec::new();
+        for message in &messages {
+            message.write(&mut bytes).unwrap();
+        }
+        let mut reader = Cursor::new(bytes);
+        for message in messages {
+            assert_eq!(Message::read(&mut reader).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn quirks_round_trip() {
+        for profile in ["default", "cosmac", "schip"].iter() {
+            let quirks = Quirks::from_name(profile).unwrap();
+            assert_eq!(quirks_from_bits(quirk_bits(quirks)), quirks);
+        }
+    }
+
+    #[test]
+    fn players_must_run_the_same_rom_with_their_own_keys() {
+        let other_rom = Hello { rom: fnv(&[0x12, 0x00]), ..hello(0xFF00) };
+        assert!(hello(0x00FF).check(&other_rom).is_err());
+        assert!(hello(0x00FF).check(&hello(0x0180)).is_err());
+        assert!(hello(0x00FF).check(&hello(0xFF00)).is_ok());
+    }
+
+    #[test]
+    fn players_stay_in_lockstep() {
+        let ((host, host_resyncs), (guest, guest_resyncs)) = game(150, None);
+        assert_eq!(host, guest);
+        assert_eq!((host_resyncs, guest_resyncs), (0, 0));
+    }
+
+    #[test]
+    fn guest_takes_over_the_host_machine_after_a_desync() {
+        let ((host, host_resyncs), (guest, guest_resyncs)) = game(300, Some(100));
+        assert_eq!(host, guest);
+        assert_eq!((host_resyncs, guest_resyncs), (1, 1));
+    }
+}
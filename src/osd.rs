@@ -0,0 +1,377 @@
+//! On-screen display module. Draws transient messages, emulation statistics and the text of the script over the
+//! output.
+//!
+//! The overlay is composited onto the upscaled output image and never touches the emulated display memory, so it
+//! shows up neither in screenshots nor in recordings.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use effects::Image;
+use graphics::BYTES_PER_PIXEL;
+use memory::FONTSET;
+
+/// Number of frames a message stays on screen.
+const MESSAGE_FRAMES: u32 = 120;
+/// Maximum number of messages shown at once, older ones are dropped.
+const MAX_MESSAGES: usize = 3;
+/// Number of text lines fitting on the output, defining the size of the text.
+const LINES: usize = 16;
+/// Width and height of a glyph in text pixels.
+pub const GLYPH_WIDTH: usize = 4;
+pub const GLYPH_HEIGHT: usize = 5;
+/// Empty text pixels around each glyph.
+pub const SPACING: usize = 1;
+/// Brightness of the output behind text.
+const BACKDROP_BRIGHTNESS: f32 = 0.25;
+/// Colour of the text.
+const TEXT_COLOR: [u8; 3] = [255, 255, 255];
+
+/// Glyphs of the characters missing from the font set, in the same format of one row per byte, using the high
+/// nibble.
+static EXTRA_GLYPHS: [(char, [u8; 5]); 32] = [
+    ('G', [0xF0, 0x80, 0xB0, 0x90, 0xF0]),
+    ('H', [0x90, 0x90, 0xF0, 0x90, 0x90]),
+    ('I', [0xE0, 0x40, 0x40, 0x40, 0xE0]),
+    ('J', [0x70, 0x20, 0x20, 0xA0, 0x40]),
+    ('K', [0x90, 0xA0, 0xC0, 0xA0, 0x90]),
+    ('L', [0x80, 0x80, 0x80, 0x80, 0xF0]),
+    ('M', [0x90, 0xF0, 0xF0, 0x90, 0x90]),
+    ('N', [0x90, 0xD0, 0xB0, 0x90, 0x90]),
+    ('O', [0x60, 0x90, 0x90, 0x90, 0x60]),
+    ('P', [0xE0, 0x90, 0xE0, 0x80, 0x80]),
+    ('Q', [0x60, 0x90, 0x90, 0xB0, 0x70]),
+    ('R', [0xE0, 0x90, 0xE0, 0xA0, 0x90]),
+    ('S', [0x70, 0x80, 0x60, 0x10, 0xE0]),
+    ('T', [0xE0, 0x40, 0x40, 0x40, 0x40]),
+    ('U', [0x90, 0x90, 0x90, 0x90, 0xF0]),
+    ('V', [0x90, 0x90, 0x90, 0x90, 0x60]),
+    ('W', [0x90, 0x90, 0xF0, 0xF0, 0x90]),
+    ('X', [0x90, 0x90, 0x60, 0x90, 0x90]),
+    ('Y', [0xA0, 0xA0, 0x40, 0x40, 0x40]),
+    ('Z', [0xF0, 0x10, 0x60, 0x80, 0xF0]),
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x40]),
+    (',', [0x00, 0x00, 0x00, 0x40, 0x80]),
+    (':', [0x00, 0x40, 0x00, 0x40, 0x00]),
+    ('%', [0x90, 0x10, 0x60, 0x80, 0x90]),
+    ('-', [0x00, 0x00, 0xF0, 0x00, 0x00]),
+    ('+', [0x00, 0x40, 0xE0, 0x40, 0x00]),
+    ('/', [0x10, 0x20, 0x20, 0x40, 0x80]),
+    ('!', [0x40, 0x40, 0x40, 0x00, 0x40]),
+    ('\'', [0x40, 0x40, 0x00, 0x00, 0x00]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0xF0]),
+    ('?', [0xE0, 0x10, 0x60, 0x00, 0x40]),
+];
+
+/// Get the glyph of a character, case insensitive. Unknown characters are shown as `?`.
+pub fn glyph(c: char) -> [u8; 5] {
+    let c = c.to_ascii_uppercase();
+    if let Some(digit) = c.to_digit(16) {
+        let start = digit as usize * GLYPH_HEIGHT;
+        let mut glyph = [0; 5];
+        glyph.copy_from_slice(&FONTSET[start..start + GLYPH_HEIGHT]);
+        return glyph;
+    }
+    EXTRA_GLYPHS.iter()
+        .find(|&&(extra, _)| extra == c)
+        .or_else(|| EXTRA_GLYPHS.iter().find(|&&(extra, _)| extra == '?'))
+        .map(|&(_, glyph)| glyph)
+        .unwrap()
+}
+
+/// Emulation statistics shown in the corner of the output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    /// Frames presented per second.
+    pub fps: f32,
+    /// Instructions executed per second.
+    pub ips: f32,
+    /// Name of the emulated platform.
+    pub platform: &'static str,
+}
+
+impl Stats {
+    /// Lines of text showing the statistics.
+    fn lines(&self) -> Vec<String> {
+        vec![
+            format!("{:.0} FPS", self.fps),
+            format!("{:.0} IPS", self.ips),
+            self.platform.to_string(),
+        ]
+    }
+}
+
+/// Measures the statistics over windows of one second.
+pub struct StatsMeter {
+    start: Instant,
+    frames: u32,
+    instructions: u64,
+}
+
+impl StatsMeter {
+    /// Constructor.
+    pub fn new(now: Instant) -> StatsMeter {
+        StatsMeter { start: now, frames: 0, instructions: 0 }
+    }
+
+    /// Count a presented frame.
+    ///
+    /// # Arguments
+    /// - `now`: the time the frame was presented at.
+    /// - `instructions`: the number of instructions executed since the last frame.
+    ///
+    /// # Returns
+    /// Returns the frames and instructions per second once a second has passed since the last measurement.
+    pub fn tick(&mut self, now: Instant, instructions: u64) -> Option<(f32, f32)> {
+        self.frames += 1;
+        self.instructions += instructions;
+        let elapsed = now.duration_since(self.start);
+        if elapsed < Duration::from_secs(1) {
+            return None;
+        }
+        let secs = elapsed.as_secs_f32();
+        let rates = (self.frames as f32 / secs, self.instructions as f32 / secs);
+        *self = StatsMeter::new(now);
+        Some(rates)
+    }
+}
+
+/// A message shown for a limited number of frames.
+struct Message {
+    text: String,
+    frames_left: u32,
+}
+
+/// Overlay of messages, statistics and script text.
+pub struct Osd {
+    messages: VecDeque<Message>,
+    show_stats: bool,
+    stats: Option<Stats>,
+    // lines of text drawn by the script
+    script_text: Vec<String>,
+    // whether the overlay changed since the last tick
+    changed: bool,
+}
+
+impl Osd {
+    /// Constructor.
+    ///
+    /// # Arguments
+    /// - `show_stats`: whether the statistics are shown.
+    pub fn new(show_stats: bool) -> Osd {
+        Osd { messages: VecDeque::new(), show_stats, stats: None, script_text: Vec::new(), changed: false }
+    }
+
+    /// Show a message for a couple of seconds.
+    pub fn message(&mut self, text: &str) {
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(Message { text: text.to_string(), frames_left: MESSAGE_FRAMES });
+        self.changed = true;
+    }
+
+    /// Show or hide the statistics.
+    pub fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+        self.changed = true;
+    }
+
+    /// Update the statistics.
+    pub fn set_stats(&mut self, stats: Stats) {
+        if self.stats != Some(stats) {
+            self.stats = Some(stats);
+            self.changed |= self.show_stats;
+        }
+    }
+
+    /// Replace the lines of text drawn by the script.
+    pub fn set_script_text(&mut self, lines: &[String]) {
+        if self.script_text != lines {
+            self.script_text = lines.to_vec();
+            self.changed = true;
+        }
+    }
+
+    /// Checks if anything is drawn over the output.
+    pub fn is_visible(&self) -> bool {
+        !self.messages.is_empty() || (self.show_stats && self.stats.is_some()) || !self.script_text.is_empty()
+    }
+
+    /// Advance by one frame, expiring old messages.
+    ///
+    /// # Returns
+    /// Returns `true` if the overlay changed since the last frame and the output should be drawn again.
+    pub fn tick(&mut self) -> bool {
+        for message in &mut self.messages {
+            message.frames_left = message.frames_left.saturating_sub(1);
+        }
+        while self.messages.front().is_some_and(|message| message.frames_left == 0) {
+            self.messages.pop_front();
+            self.changed = true;
+        }
+        let changed = self.changed;
+        self.changed = false;
+        changed
+    }
+
+    /// Draw the overlay onto an image: the text of the script in the top left corner, the statistics in the top right
+    /// one and the messages in the bottom left one, newest at the bottom.
+    pub fn render(&self, image: &mut Image) {
+        let size = (image.height / (LINES * (GLYPH_HEIGHT + SPACING))).max(1);
+        let line_height = (GLYPH_HEIGHT + SPACING) * size;
+        for (row, line) in self.script_text.iter().enumerate().filter(|(_, line)| !line.is_empty()) {
+            draw_text(image, 0, row * line_height, size, line);
+        }
+        if let (true, Some(stats)) = (self.show_stats, self.stats) {
+            for (row, line) in stats.lines().iter().enumerate() {
+                let width = text_width(line, size);
+                draw_text(image, image.width.saturating_sub(width), row * line_height, size, line);
+            }
+        }
+        let top = image.height.saturating_sub(self.messages.len() * line_height);
+        for (row, message) in self.messages.iter().enumerate() {
+            draw_text(image, 0, top + row * line_height, size, &message.text);
+        }
+    }
+}
+
+/// Width of a line of text including the spacing around it.
+fn text_width(text: &str, size: usize) -> usize {
+    (text.chars().count() * (GLYPH_WIDTH + SPACING) + SPACING) * size
+}
+
+/// Draw a line of text over a darkened backdrop, clipped to the image.
+///
+/// # Arguments
+/// - `image`: the image to draw onto.
+/// - `x`, `y`: the top left corner of the backdrop.
+/// - `size`: the number of image pixels per text pixel along each axis.
+/// - `text`: the text to draw.
+fn draw_text(image: &mut Image, x: usize, y: usize, size: usize, text: &str) {
+    let width = text_width(text, size);
+    let height = (GLYPH_HEIGHT + 2 * SPACING) * size;
+    for py in y..(y + height).min(image.height) {
+        for px in x..(x + width).min(image.width) {
+            let idx = (px + py * image.width) * BYTES_PER_PIXEL;
+            for channel in &mut image.pixels[idx..idx + BYTES_PER_PIXEL] {
+                *channel = (*channel as f32 * BACKDROP_BRIGHTNESS) as u8;
+            }
+        }
+    }
+    for (n, c) in text.chars().enumerate() {
+        let left = x + (SPACING + n * (GLYPH_WIDTH + SPACING)) * size;
+        draw_char(image, left, y + SPACING * size, size, c, TEXT_COLOR);
+    }
+}
+
+/// Draw the glyph of a character, clipped to the image.
+///
+/// # Arguments
+/// - `image`: the image to draw onto.
+/// - `x`, `y`: the top left corner of the glyph.
+/// - `size`: the number of image pixels per text pixel along each axis.
+/// - `c`: the character to draw.
+/// - `color`: the colour of the glyph, the pixels around it are left untouched.
+pub fn draw_char(image: &mut Image, x: usize, y: usize, size: usize, c: char, color: [u8; 3]) {
+    for (row, bits) in glyph(c).iter().enumerate() {
+        for col in 0..GLYPH_WIDTH {
+            if bits >> (7 - col) & 0x01 == 0 {
+                continue;
+            }
+            let top = y + row * size;
+            let left = x + col * size;
+            for py in top..(top + size).min(image.height) {
+                for px in left..(left + size).min(image.width) {
+                    let idx = (px + py * image.width) * BYTES_PER_PIXEL;
+                    image.pixels[idx..idx + BYTES_PER_PIXEL].copy_from_slice(&color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs_reuse_font_set() {
+        assert_eq!(glyph('0'), [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+        assert_eq!(glyph('a'), glyph('A'), "glyphs are case insensitive");
+        assert_eq!(glyph('~'), glyph('?'), "unknown characters are replaced");
+    }
+
+    #[test]
+    fn messages_expire() {
+        let mut osd = Osd::new(false);
+        assert!(!osd.is_visible());
+        osd.message("PAUSED");
+        assert!(osd.is_visible());
+        assert!(osd.tick(), "a new message changes the overlay");
+        for _ in 1..MESSAGE_FRAMES - 1 {
+            assert!(!osd.tick());
+        }
+        assert!(osd.tick(), "the expiring message changes the overlay");
+        assert!(!osd.is_visible());
+    }
+
+    #[test]
+    fn old_messages_are_dropped() {
+        let mut osd = Osd::new(false);
+        for n in 0..MAX_MESSAGES + 1 {
+            osd.message(&n.to_string());
+        }
+        assert_eq!(osd.messages.len(), MAX_MESSAGES);
+        assert_eq!(osd.messages[0].text, "1");
+    }
+
+    #[test]
+    fn hidden_stats_do_not_change_overlay() {
+        let mut osd = Osd::new(false);
+        osd.set_stats(Stats { fps: 60.0, ips: 600.0, platform: "CHIP-8" });
+        assert!(!osd.tick());
+        assert!(!osd.is_visible());
+        osd.toggle_stats();
+        assert!(osd.tick());
+        assert!(osd.is_visible());
+    }
+
+    #[test]
+    fn script_text_stays_until_replaced() {
+        let mut osd = Osd::new(false);
+        osd.set_script_text(&["LIVES 3".to_string()]);
+        assert!(osd.tick());
+        for _ in 0..MESSAGE_FRAMES {
+            assert!(!osd.tick());
+        }
+        assert!(osd.is_visible());
+        osd.set_script_text(&[]);
+        assert!(osd.tick());
+        assert!(!osd.is_visible());
+    }
+
+    #[test]
+    fn render_draws_text_over_backdrop() {
+        let mut image = Image::upscale(&[100; 4 * 4 * BYTES_PER_PIXEL], 4, 4, 8);
+        let mut osd = Osd::new(false);
+        osd.message("1");
+        osd.render(&mut image);
+        // the text pixel size is 1 on a 32 pixel high image, the message is on the last line
+        let top = image.height - (GLYPH_HEIGHT + SPACING);
+        assert_eq!(image.pixel(SPACING + 2, top + SPACING), TEXT_COLOR, "the stem of the 1 is drawn");
+        assert_eq!(image.pixel(SPACING, top + SPACING), [25, 25, 25], "the backdrop darkens the output");
+        assert_eq!(image.pixel(20, 0), [100, 100, 100], "the rest of the output is untouched");
+    }
+
+    #[test]
+    fn stats_meter_measures_per_second() {
+        let start = Instant::now();
+        let mut meter = StatsMeter::new(start);
+        for frame in 1..60 {
+            assert_eq!(meter.tick(start + Duration::from_millis(frame * 10), 10), None);
+        }
+        assert_eq!(meter.tick(start + Duration::from_secs(2), 10), Some((30.0, 300.0)));
+    }
+}
@@ -0,0 +1,221 @@
+//! Palette module. Contains the colours used to render the display.
+
+use sdl2::pixels::Color;
+
+/// Number of entries in a palette. Index 0 is the background, index 1 the first plane, index 2 the second plane and
+/// index 3 pixels set on both planes.
+pub const PALETTE_SIZE: usize = 4;
+
+/// A colour in RGB space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    /// Constructor.
+    pub const fn new(r: u8, g: u8, b: u8) -> Rgb {
+        Rgb { r, g, b }
+    }
+
+    /// Parse a colour in `RRGGBB` notation, optionally prefixed with `#`.
+    pub fn from_hex(hex: &str) -> Option<Rgb> {
+        let hex = hex.trim_start_matches('#');
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).ok();
+        Some(Rgb::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl From<Rgb> for Color {
+    fn from(rgb: Rgb) -> Color {
+        Color::RGB(rgb.r, rgb.g, rgb.b)
+    }
+}
+
+/// Colours used to render the pixels of the display.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    colors: [Rgb; PALETTE_SIZE],
+}
+
+impl Palette {
+    /// Constructor.
+    pub const fn new(colors: [Rgb; PALETTE_SIZE]) -> Palette {
+        Palette { colors }
+    }
+
+    /// Get the colour for a pixel value. Values outside the palette wrap around.
+    #[inline(always)]
+    pub fn color(&self, value: u8) -> Rgb {
+        self.colors[value as usize % PALETTE_SIZE]
+    }
+
+    /// Background colour.
+    pub fn background(&self) -> Rgb {
+        self.colors[0]
+    }
+
+    /// Foreground colour of the first plane.
+    #[allow(dead_code)]
+    pub fn foreground(&self) -> Rgb {
+        self.colors[1]
+    }
+
+    /// Replace the background colour.
+    pub fn set_background(&mut self, color: Rgb) {
+        self.colors[0] = color;
+    }
+
+    /// Replace the foreground colour of the first plane.
+    pub fn set_foreground(&mut self, color: Rgb) {
+        self.colors[1] = color;
+    }
+}
+
+/// Built-in colour themes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Theme {
+    /// White on black.
+    #[default]
+    Classic,
+    /// Green phosphor monitor.
+    Green,
+    /// Amber phosphor monitor.
+    Amber,
+    /// Greenish liquid crystal display.
+    Lcd,
+    /// Yellow on black, for maximal contrast.
+    HighContrast,
+    /// Colours distinguishable with the common forms of colour blindness.
+    Colorblind,
+}
+
+impl Theme {
+    /// All themes, in the order they are cycled through.
+    pub const ALL: [Theme; 6] = [
+        Theme::Classic,
+        Theme::Green,
+        Theme::Amber,
+        Theme::Lcd,
+        Theme::HighContrast,
+        Theme::Colorblind,
+    ];
+
+    /// Name of the theme as used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Classic      => "classic",
+            Theme::Green        => "green",
+            Theme::Amber        => "amber",
+            Theme::Lcd          => "lcd",
+            Theme::HighContrast => "high-contrast",
+            Theme::Colorblind   => "colorblind",
+        }
+    }
+
+    /// Look up a theme by its name.
+    pub fn from_name(name: &str) -> Option<Theme> {
+        Theme::ALL.iter().cloned().find(|theme| theme.name() == name)
+    }
+
+    /// The theme following this one.
+    pub fn next(self) -> Theme {
+        let idx = Theme::ALL.iter().position(|&theme| theme == self).unwrap();
+        Theme::ALL[(idx + 1) % Theme::ALL.len()]
+    }
+
+    /// Palette of the theme.
+    pub fn palette(self) -> Palette {
+        match self {
+            Theme::Classic => Palette::new([
+                Rgb::new(0x00, 0x00, 0x00),
+                Rgb::new(0xFF, 0xFF, 0xFF),
+                Rgb::new(0xAA, 0xAA, 0xAA),
+                Rgb::new(0x55, 0x55, 0x55),
+            ]),
+            Theme::Green => Palette::new([
+                Rgb::new(0x0A, 0x14, 0x0A),
+                Rgb::new(0x33, 0xFF, 0x66),
+                Rgb::new(0x1A, 0x99, 0x3D),
+                Rgb::new(0xB3, 0xFF, 0xC6),
+            ]),
+            Theme::Amber => Palette::new([
+                Rgb::new(0x1A, 0x0F, 0x00),
+                Rgb::new(0xFF, 0xB0, 0x00),
+                Rgb::new(0x99, 0x66, 0x00),
+                Rgb::new(0xFF, 0xDD, 0x88),
+            ]),
+            Theme::Lcd => Palette::new([
+                Rgb::new(0x9B, 0xBC, 0x0F),
+                Rgb::new(0x0F, 0x38, 0x0F),
+                Rgb::new(0x30, 0x62, 0x30),
+                Rgb::new(0x8B, 0xAC, 0x0F),
+            ]),
+            Theme::HighContrast => Palette::new([
+                Rgb::new(0x00, 0x00, 0x00),
+                Rgb::new(0xFF, 0xFF, 0x00),
+                Rgb::new(0x00, 0xFF, 0xFF),
+                Rgb::new(0xFF, 0xFF, 0xFF),
+            ]),
+            // Okabe-Ito colours
+            Theme::Colorblind => Palette::new([
+                Rgb::new(0x00, 0x00, 0x00),
+                Rgb::new(0xE6, 0x9F, 0x00),
+                Rgb::new(0x56, 0xB4, 0xE9),
+                Rgb::new(0xF0, 0xE4, 0x42),
+            ]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgb_from_hex() {
+        assert_eq!(Rgb::from_hex("#33FF66"), Some(Rgb::new(0x33, 0xFF, 0x66)));
+        assert_eq!(Rgb::from_hex("0a0b0c"), Some(Rgb::new(0x0A, 0x0B, 0x0C)));
+        assert_eq!(Rgb::from_hex("#33FF6"), None, "too short");
+        assert_eq!(Rgb::from_hex("#33FFGG"), None, "not hexadecimal");
+    }
+
+    #[test]
+    fn palette_color_wraps() {
+        let palette = Theme::Classic.palette();
+        assert_eq!(palette.color(0), palette.background());
+        assert_eq!(palette.color(1), palette.foreground());
+        assert_eq!(palette.color(5), palette.foreground());
+    }
+
+    #[test]
+    fn palette_overrides() {
+        let mut palette = Theme::Classic.palette();
+        palette.set_foreground(Rgb::new(1, 2, 3));
+        palette.set_background(Rgb::new(4, 5, 6));
+        assert_eq!(palette.color(1), Rgb::new(1, 2, 3));
+        assert_eq!(palette.color(0), Rgb::new(4, 5, 6));
+    }
+
+    #[test]
+    fn theme_names_round_trip() {
+        for &theme in Theme::ALL.iter() {
+            assert_eq!(Theme::from_name(theme.name()), Some(theme));
+        }
+        assert_eq!(Theme::from_name("rainbow"), None);
+    }
+
+    #[test]
+    fn theme_cycle_wraps() {
+        let mut theme = Theme::default();
+        for _ in 0..Theme::ALL.len() {
+            theme = theme.next();
+        }
+        assert_eq!(theme, Theme::default());
+    }
+}
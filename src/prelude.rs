@@ -1,4 +1,4 @@
 //! Useful imports
 
-pub use input::Input;
+pub use input::{Input, Hotkey};
 pub use graphics::Graphics;
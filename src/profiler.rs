@@ -0,0 +1,327 @@
+//! Profiler module. Collects execution statistics of the running program and reports its hotspots.
+//!
+//! The CPU only holds a profiler when profiling is enabled, so the cost of a disabled profiler is a single check per
+//! instruction.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde_json::Value;
+
+use cpu::FRAMES_PER_SECOND;
+use disassembler::{disassemble, pattern};
+use memory::{Memory, MEMORY_SIZE};
+
+/// Number of hotspots listed in the text report.
+const TEXT_HOTSPOTS: usize = 20;
+/// Maximum number of instructions between two reads of the delay timer at the same address for them to be
+/// considered a loop waiting on the timer.
+const DELAY_LOOP_LENGTH: u64 = 8;
+
+/// Counts of an edge of the call graph.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CallEdge {
+    /// Number of times the subroutine was called from the call site.
+    pub calls: u64,
+    /// Number of times the subroutine returned to the call site.
+    pub returns: u64,
+}
+
+/// Execution statistics of a program.
+pub struct Profiler {
+    // number of executions of each address
+    counts: Vec<u64>,
+    // number of executions of each instruction pattern
+    patterns: BTreeMap<&'static str, u64>,
+    // call graph, keyed by call site and subroutine address
+    calls: BTreeMap<(u16, u16), CallEdge>,
+    // call sites and subroutines of the calls that have not returned yet
+    stack: Vec<(u16, u16)>,
+    instructions: u64,
+    frames: u64,
+    key_waits: u64,
+    key_wait: Duration,
+    delay_wait_instructions: u64,
+    // address and instruction number of the last read of a running delay timer
+    last_delay_read: Option<(u16, u64)>,
+}
+
+impl Profiler {
+    /// Constructor.
+    pub fn new() -> Profiler {
+        Profiler {
+            counts: vec![0; MEMORY_SIZE],
+            patterns: BTreeMap::new(),
+            calls: BTreeMap::new(),
+            stack: Vec::new(),
+            instructions: 0,
+            frames: 0,
+            key_waits: 0,
+            key_wait: Duration::from_secs(0),
+            delay_wait_instructions: 0,
+            last_delay_read: None,
+        }
+    }
+
+    /// Record an instruction about to be executed.
+    ///
+    /// # Arguments
+    /// - `pc`: the address of the instruction.
+    /// - `opcode`: the instruction.
+    /// - `dt`: the value of the delay timer.
+    pub fn record(&mut self, pc: u16, opcode: u16, dt: u8) {
+        self.counts[pc as usize % MEMORY_SIZE] += 1;
+        let pattern = pattern(opcode);
+        *self.patterns.entry(pattern).or_insert(0) += 1;
+        match pattern {
+            "2NNN" => {
+                let target = opcode & 0x0FFF;
+                self.calls.entry((pc, target)).or_default().calls += 1;
+                self.stack.push((pc, target));
+            },
+            "00EE" => {
+                if let Some(edge) = self.stack.pop() {
+                    self.calls.entry(edge).or_default().returns += 1;
+                }
+            },
+            "FX07" if dt > 0 => {
+                // a short loop reading the running timer at the same address is waiting for it to run out
+                if let Some((last_pc, last)) = self.last_delay_read {
+                    let distance = self.instructions - last;
+                    if last_pc == pc && distance <= DELAY_LOOP_LENGTH {
+                        self.delay_wait_instructions += distance;
+                    }
+                }
+                self.last_delay_read = Some((pc, self.instructions));
+            },
+            _      => {},
+        }
+        self.instructions += 1;
+    }
+
+    /// Record the end of a frame.
+    pub fn record_frame(&mut self) {
+        self.frames += 1;
+    }
+
+    /// Record the time spent waiting for a key press in `FX0A`.
+    pub fn record_key_wait(&mut self, duration: Duration) {
+        self.key_waits += 1;
+        self.key_wait += duration;
+    }
+
+    /// Number of executions of an address.
+    pub fn count(&self, addr: usize) -> u64 {
+        self.counts[addr]
+    }
+
+    /// Edges of the call graph, keyed by call site and subroutine address.
+    pub fn calls(&self) -> &BTreeMap<(u16, u16), CallEdge> {
+        &self.calls
+    }
+
+    /// Number of instructions spent in loops waiting on the delay timer.
+    pub fn delay_wait_instructions(&self) -> u64 {
+        self.delay_wait_instructions
+    }
+
+    /// Executed addresses with their counts, most executed first.
+    fn hotspots(&self) -> Vec<(usize, u64)> {
+        let mut hotspots: Vec<(usize, u64)> = self.counts.iter().cloned().enumerate()
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        hotspots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hotspots
+    }
+
+    /// Share of all instructions in percent.
+    fn share(&self, count: u64) -> f64 {
+        if self.instructions == 0 { 0.0 } else { count as f64 * 100.0 / self.instructions as f64 }
+    }
+
+    /// Estimated time spent waiting on the delay timer, given the instructions executed per frame.
+    fn delay_wait_secs(&self, cycles_per_frame: u32) -> f64 {
+        self.delay_wait_instructions as f64 / (cycles_per_frame as f64 * FRAMES_PER_SECOND as f64)
+    }
+
+    /// Human readable report.
+    ///
+    /// # Arguments
+    /// - `rom`: the name of the profiled ROM.
+    /// - `memory`: the memory the instructions are disassembled from.
+    /// - `cycles_per_frame`: the number of instructions executed per frame, used to estimate waiting times.
+    pub fn text_report(&self, rom: &str, memory: &Memory, cycles_per_frame: u32) -> String {
+        let mut report = String::new();
+        report += &format!("Profile of {}\n\n", rom);
+        report += &format!("Instructions:          {} in {} frames\n", self.instructions, self.frames);
+        report += &format!("Waiting for keys:      {:.2} s in {} waits (FX0A)\n",
+                           self.key_wait.as_secs_f64(), self.key_waits);
+        report += &format!("Waiting on delay timer: {} instructions, about {:.2} s (FX07 loops)\n",
+                           self.delay_wait_instructions, self.delay_wait_secs(cycles_per_frame));
+
+        report += "\nHotspots\n";
+        report += "  Address      Count   Share  Instruction\n";
+        for (addr, count) in self.hotspots().into_iter().take(TEXT_HOTSPOTS) {
+            report += &format!("  0x{:03X}  {:>10}  {:>5.1}%  {}\n", addr, count, self.share(count),
+                               disassemble(read_opcode(memory, addr)));
+        }
+
+        report += "\nInstruction classes\n";
+        let mut patterns: Vec<(&&str, &u64)> = self.patterns.iter().collect();
+        patterns.sort_by(|a, b| b.1.cmp(a.1));
+        for (pattern, &count) in patterns {
+            report += &format!("  {}  {:>10}  {:>5.1}%\n", pattern, count, self.share(count));
+        }
+
+        report += "\nCall graph\n";
+        for (&(site, target), edge) in &self.calls {
+            report += &format!("  0x{:03X} -> 0x{:03X}  {} calls, {} returns\n", site, target, edge.calls, edge.returns);
+        }
+        report
+    }
+
+    /// Machine readable report with the same content as the text report, listing all executed addresses.
+    pub fn json_report(&self, rom: &str, memory: &Memory, cycles_per_frame: u32) -> String {
+        let hotspots: Vec<Value> = self.hotspots().into_iter()
+            .map(|(addr, count)| json!({
+                "address": addr,
+                "count": count,
+                "instruction": disassemble(read_opcode(memory, addr)),
+            }))
+            .collect();
+        let calls: Vec<Value> = self.calls.iter()
+            .map(|(&(site, target), edge)| json!({
+                "from": site,
+                "to": target,
+                "calls": edge.calls,
+                "returns": edge.returns,
+            }))
+            .collect();
+        let report = json!({
+            "rom": rom,
+            "instructions": self.instructions,
+            "frames": self.frames,
+            "key_waits": self.key_waits,
+            "key_wait_secs": self.key_wait.as_secs_f64(),
+            "delay_wait_instructions": self.delay_wait_instructions,
+            "delay_wait_secs": self.delay_wait_secs(cycles_per_frame),
+            "hotspots": hotspots,
+            "classes": self.patterns,
+            "calls": calls,
+        });
+        format!("{:#}\n", report)
+    }
+
+    /// Write the text report and the JSON report next to each other.
+    ///
+    /// # Arguments
+    /// - `path`: the path of the reports, `.txt` and `.json` are appended to it.
+    /// - `rom`: the name of the profiled ROM.
+    /// - `memory`: the memory the instructions are disassembled from.
+    /// - `cycles_per_frame`: the number of instructions executed per frame.
+    ///
+    /// # Returns
+    /// Returns the paths of the text and the JSON report.
+    pub fn save(&self, path: &Path, rom: &str, memory: &Memory, cycles_per_frame: u32)
+            -> io::Result<(PathBuf, PathBuf)> {
+        let text = with_suffix(path, ".txt");
+        let json = with_suffix(path, ".json");
+        BufWriter::new(File::create(&text)?).write_all(self.text_report(rom, memory, cycles_per_frame).as_bytes())?;
+        BufWriter::new(File::create(&json)?).write_all(self.json_report(rom, memory, cycles_per_frame).as_bytes())?;
+        Ok((text, json))
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler::new()
+    }
+}
+
+/// Read the opcode at an address, treating bytes past the end of memory as zero.
+fn read_opcode(memory: &Memory, addr: usize) -> u16 {
+    let high = memory.read(addr) as u16;
+    let low = if addr + 1 < MEMORY_SIZE { memory.read(addr + 1) as u16 } else { 0 };
+    high << 8 | low
+}
+
+/// A path with a suffix appended to its file name, keeping any extension it has.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_addresses_and_classes() {
+        let mut profiler = Profiler::new();
+        profiler.record(0x200, 0x6001, 0);
+        profiler.record(0x202, 0x1200, 0);
+        profiler.record(0x200, 0x6001, 0);
+        assert_eq!(profiler.count(0x200), 2);
+        assert_eq!(profiler.hotspots(), vec![(0x200, 2), (0x202, 1)]);
+        assert_eq!(profiler.patterns["6XKK"], 2);
+    }
+
+    #[test]
+    fn pairs_calls_and_returns() {
+        let mut profiler = Profiler::new();
+        profiler.record(0x200, 0x2300, 0);
+        profiler.record(0x300, 0x2400, 0);
+        profiler.record(0x400, 0x00EE, 0);
+        profiler.record(0x302, 0x00EE, 0);
+        profiler.record(0x202, 0x2300, 0);
+        assert_eq!(profiler.calls()[&(0x200, 0x300)], CallEdge { calls: 1, returns: 1 });
+        assert_eq!(profiler.calls()[&(0x300, 0x400)], CallEdge { calls: 1, returns: 1 });
+        assert_eq!(profiler.calls()[&(0x202, 0x300)], CallEdge { calls: 1, returns: 0 });
+    }
+
+    #[test]
+    fn detects_delay_timer_loops() {
+        let mut profiler = Profiler::new();
+        // LD V0, DT / SE V0, 0 / JP back, while the timer runs
+        for dt in (1..4).rev() {
+            profiler.record(0x200, 0xF007, dt);
+            profiler.record(0x202, 0x3000, dt);
+            profiler.record(0x204, 0x1200, dt);
+        }
+        assert_eq!(profiler.delay_wait_instructions(), 6, "two loop iterations after the first read");
+
+        profiler.record(0x200, 0xF007, 0);
+        assert_eq!(profiler.delay_wait_instructions(), 6, "reading an expired timer is not waiting");
+    }
+
+    #[test]
+    fn reports_disassemble_hotspots() {
+        let memory = Memory::new(vec![0x60, 0x2A]).unwrap();
+        let mut profiler = Profiler::new();
+        profiler.record(0x200, 0x602A, 0);
+        profiler.record_frame();
+        let text = profiler.text_report("TEST", &memory, 10);
+        assert!(text.contains("0x200           1  100.0%  LD V0, 0x2A"), "{}", text);
+        let json: Value = serde_json::from_str(&profiler.json_report("TEST", &memory, 10)).unwrap();
+        assert_eq!(json["hotspots"], json!([{"address": 512, "count": 1, "instruction": "LD V0, 0x2A"}]));
+        assert_eq!(json["frames"], json!(1));
+    }
+
+    #[test]
+    fn json_report_is_valid_json() {
+        let memory = Memory::new(Vec::new()).unwrap();
+        let json = Profiler::new().json_report("a\"b\\c\n", &memory, 10);
+        let json: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["rom"], json!("a\"b\\c\n"));
+    }
+
+    #[test]
+    fn suffixes_are_appended() {
+        assert_eq!(with_suffix(Path::new("out.prof"), ".json"), PathBuf::from("out.prof.json"));
+        assert_eq!(with_suffix(Path::new("dir/out"), ".txt"), PathBuf::from("dir/out.txt"));
+    }
+}
@@ -0,0 +1,66 @@
+//! Quirks module. CHIP-8 interpreters disagree on a handful of instructions, programs written for one of them may
+//! misbehave on another. A quirk profile selects the behaviour of each of these instructions.
+
+/// Behaviour of the instructions interpreters disagree on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8XY1`, `8XY2` and `8XY3` reset VF to 0.
+    pub vf_reset: bool,
+    /// `8XY6` and `8XYE` shift VY into VX instead of shifting VX in place.
+    pub shift_vy: bool,
+    /// `FX55` and `FX65` leave I pointing after the last register stored or loaded.
+    pub load_store_increment: bool,
+    /// `BNNN` jumps to NNN plus VX, X being the highest nibble of NNN, instead of plus V0.
+    pub jump_vx: bool,
+    /// Sprites are clipped at the edges of the display instead of wrapping around.
+    pub clip: bool,
+    /// `FX0A` completes when the key is released instead of when it is pressed.
+    pub key_release: bool,
+}
+
+/// Names of the available profiles.
+pub const PROFILES: [&str; 3] = ["default", "cosmac", "schip"];
+
+impl Quirks {
+    /// Look up a profile by its name.
+    ///
+    /// - `default`: the behaviour of this emulator, shifting in place and wrapping sprites around.
+    /// - `cosmac`: the original COSMAC VIP interpreter.
+    /// - `schip`: SUPER-CHIP 1.1 on the HP 48.
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        match name {
+            "default" => Some(Quirks::default()),
+            "cosmac"  => Some(Quirks {
+                vf_reset: true,
+                shift_vy: true,
+                load_store_increment: true,
+                jump_vx: false,
+                clip: true,
+                key_release: true,
+            }),
+            "schip"   => Some(Quirks {
+                vf_reset: false,
+                shift_vy: false,
+                load_store_increment: false,
+                jump_vx: true,
+                clip: true,
+                key_release: false,
+            }),
+            _         => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles() {
+        for name in PROFILES.iter() {
+            assert!(Quirks::from_name(name).is_some(), "{} should be a profile", name);
+        }
+        assert_eq!(Quirks::from_name("default"), Some(Quirks::default()));
+        assert_eq!(Quirks::from_name("octo"), None);
+    }
+}
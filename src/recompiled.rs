@@ -0,0 +1,306 @@
+//! Recompiled module. Runtime of the programs the recompiler translates ROMs into.
+//!
+//! A recompiled routine runs on a `Machine`, executing the register and jump instructions itself on the registers of
+//! the machine and leaving drawing, keys, random numbers and memory to the CPU. The machine counts the instructions
+//! into frames like the CPU does, so a recompiled program runs exactly like the interpreted ROM.
+//!
+//! Routines only cover the code found by control-flow recovery. The machine interprets the instructions at other
+//! addresses until the program counter reaches recovered code again, and every instruction once the program has
+//! overwritten recovered code.
+
+use std::ops::Range;
+use std::thread::sleep;
+use std::time::Instant;
+
+use sdl2;
+
+use prelude::*;
+use config::Config;
+use cpu::{Cpu, State};
+use graphics::Display;
+use input::Keyboard;
+use interconnect::Interconnect;
+use memory::Memory;
+use quirks::Quirks;
+use vm::FRAME_DURATION;
+
+/// Why a routine stopped before returning.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exit {
+    /// The program counter left the routine, to an address it does not cover.
+    Leave,
+    /// The program overwrote recovered code.
+    CodeWritten,
+    /// The frame handler stopped the machine.
+    Quit,
+}
+
+/// A recompiled routine, running from the program counter until it returns with `RET`.
+pub type Routine<T, U> = fn(&mut Machine<T, U>) -> Result<(), Exit>;
+/// Look up the routine with a basic block starting at an address.
+pub type Dispatch<T, U> = fn(u16) -> Option<Routine<T, U>>;
+/// Called at the end of every frame, stopping the machine by returning `false`.
+pub type FrameHandler<T, U> = Box<dyn FnMut(&mut Machine<T, U>) -> bool>;
+
+/// A machine running recompiled routines.
+pub struct Machine<T, U> where T: Input, U: Graphics {
+    /// Registers, stack and timers, which routines work on directly.
+    pub state: State,
+    // the CPU executing the instructions routines leave to it
+    cpu: Cpu<T, U>,
+    // instructions executed in the current frame, and per frame
+    cycles: u32,
+    cycles_per_frame: u32,
+    // frames completed
+    frames: u64,
+    // set once the program overwrites recovered code
+    code_written: bool,
+    frame_handler: Option<FrameHandler<T, U>>,
+}
+
+impl<T, U> Machine<T, U> where T: Input, U: Graphics {
+    /// Constructor.
+    ///
+    /// # Arguments
+    /// - `cpu`: the CPU with the ROM loaded, whose registers, quirks and cycles per frame the machine starts with.
+    /// - `code`: the addresses of the recovered code.
+    pub fn new(mut cpu: Cpu<T, U>, code: &[Range<u16>]) -> Machine<T, U> {
+        for range in code {
+            cpu.interconnect_mut().memory.mark_code(range.start as usize..range.end as usize);
+        }
+        Machine {
+            state: cpu.state(),
+            cycles_per_frame: cpu.cycles_per_frame(),
+            cpu,
+            cycles: 0,
+            frames: 0,
+            code_written: false,
+            frame_handler: None,
+        }
+    }
+
+    /// Set the handler called at the end of every frame.
+    pub fn set_frame_handler(&mut self, handler: FrameHandler<T, U>) {
+        self.frame_handler = Some(handler);
+    }
+
+    /// Access the interconnect.
+    pub fn interconnect(&self) -> &Interconnect<T, U> {
+        self.cpu.interconnect()
+    }
+
+    /// Access the interconnect mutably.
+    pub fn interconnect_mut(&mut self) -> &mut Interconnect<T, U> {
+        self.cpu.interconnect_mut()
+    }
+
+    /// Number of frames completed.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Run the program from the program counter until the frame handler stops it.
+    ///
+    /// # Arguments
+    /// - `dispatch`: the lookup of the recompiled routines.
+    pub fn run(&mut self, dispatch: Dispatch<T, U>) {
+        loop {
+            let result = match dispatch(self.state.pc) {
+                Some(routine) if !self.code_written => routine(self),
+                _                                   => self.interpret(),
+            };
+            if result == Err(Exit::Quit) {
+                return;
+            }
+        }
+    }
+
+    /// Count an executed instruction, ending the frame after a frame worth of instructions.
+    ///
+    /// # Arguments
+    /// - `pc`: the address of the next instruction.
+    #[inline(always)]
+    pub fn tick(&mut self, pc: u16) -> Result<(), Exit> {
+        self.state.pc = pc;
+        self.cycles += 1;
+        if self.cycles < self.cycles_per_frame {
+            Ok(())
+        } else {
+            self.end_frame()
+        }
+    }
+
+    /// Execute an instruction on the CPU, which updates the program counter.
+    ///
+    /// # Arguments
+    /// - `pc`: the address of the instruction.
+    /// - `opcode`: the instruction.
+    pub fn host(&mut self, pc: u16, opcode: u16) -> Result<(), Exit> {
+        self.state.pc = pc;
+        self.cpu.set_state(self.state);
+        self.cpu.process_opcode(opcode);
+        self.state = self.cpu.state();
+        self.finish_cpu_instruction()
+    }
+
+    /// Execute `CALL`, pushing the return address.
+    ///
+    /// # Arguments
+    /// - `ret`: the address to return to.
+    /// - `nnn`: the address of the routine called.
+    pub fn call(&mut self, ret: u16, nnn: u16) -> Result<(), Exit> {
+        // a full stack loses the return address, as on the CPU
+        if let Some(entry) = self.state.stack.get_mut(self.state.sp as usize) {
+            *entry = ret;
+            self.state.sp += 1;
+        }
+        self.tick(nnn)
+    }
+
+    /// Execute `RET`, popping the return address into the program counter.
+    pub fn ret(&mut self) -> Result<(), Exit> {
+        self.state.sp = self.state.sp.saturating_sub(1);
+        let pc = self.state.stack[self.state.sp as usize];
+        self.tick(pc)
+    }
+
+    /// Interpret the instruction at the program counter.
+    fn interpret(&mut self) -> Result<(), Exit> {
+        self.cpu.set_state(self.state);
+        self.cpu.execute_cycle();
+        self.state = self.cpu.state();
+        self.finish_cpu_instruction()
+    }
+
+    /// Count an instruction executed by the CPU, stopping the routine if it overwrote recovered code.
+    fn finish_cpu_instruction(&mut self) -> Result<(), Exit> {
+        let code_written = !self.cpu.interconnect_mut().memory.take_code_writes().is_empty();
+        self.code_written |= code_written;
+        let pc = self.state.pc;
+        self.tick(pc)?;
+        if code_written {
+            Err(Exit::CodeWritten)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Count down the timers and call the frame handler.
+    fn end_frame(&mut self) -> Result<(), Exit> {
+        self.cycles = 0;
+        if self.state.dt > 0 {
+            self.state.dt -= 1;
+        }
+        if self.state.st > 0 {
+            self.state.st -= 1;
+        }
+        self.frames += 1;
+        if let Some(mut handler) = self.frame_handler.take() {
+            let resume = handler(self);
+            self.frame_handler = Some(handler);
+            if !resume {
+                return Err(Exit::Quit);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Run a recompiled program in a window at 60 frames per second until it is closed.
+///
+/// # Arguments
+/// - `rom`: the ROM the program was recompiled from.
+/// - `code`: the addresses of the recovered code.
+/// - `quirks`: the name of the quirk profile the routines were recompiled for.
+/// - `cycles_per_frame`: the number of instructions executed per frame.
+/// - `dispatch`: the lookup of the recompiled routines.
+pub fn run(rom: &[u8], code: &[Range<u16>], quirks: &str, cycles_per_frame: u32,
+           dispatch: Dispatch<Keyboard, Display>) {
+    let quirks = Quirks::from_name(quirks).expect("recompiled for an unknown quirk profile");
+    let config = Config { quirks, ..Config::default() };
+    let context = sdl2::init().unwrap();
+    let memory = Memory::new(rom.to_vec()).expect("the program was recovered from memory");
+    let mut cpu = Cpu::new(Interconnect::new(memory, &context, &config));
+    cpu.set_quirks(config.quirks);
+    cpu.set_cycles_per_frame(cycles_per_frame);
+    let mut machine = Machine::new(cpu, code);
+    let mut next_frame = Instant::now();
+    machine.set_frame_handler(Box::new(move |machine| {
+        let interconnect = machine.interconnect_mut();
+        interconnect.input.handle_inputs();
+        // the emulator hotkeys are not supported, and the keypad is read directly
+        while interconnect.input.next_hotkey().is_some() {}
+        while interconnect.input.next_key_event().is_some() {}
+        if interconnect.input.take_redraw_request() {
+            interconnect.graphics.refresh();
+        }
+        if interconnect.input.close_requested() {
+            return false;
+        }
+        interconnect.graphics.present();
+
+        next_frame += FRAME_DURATION;
+        let now = Instant::now();
+        if next_frame > now {
+            sleep(next_frame - now);
+        } else {
+            next_frame = now;
+        }
+        true
+    }));
+    machine.run(dispatch);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use graphics::TestDisplay;
+    use input::TestKeyboard;
+
+    type TestMachine = Machine<TestKeyboard, TestDisplay>;
+
+    /// LD V0, 0x01 and LD V1, 0x02 at 0x200, returning to the interpreter for the rest.
+    fn sub_200(m: &mut TestMachine) -> Result<(), Exit> {
+        m.state.v[0x0] = 0x01;
+        m.tick(0x202)?;
+        m.state.v[0x1] = 0x02;
+        m.tick(0x204)?;
+        m.state.pc = 0x204;
+        Err(Exit::Leave)
+    }
+
+    fn dispatch(pc: u16) -> Option<Routine<TestKeyboard, TestDisplay>> {
+        match pc {
+            0x200 => Some(sub_200),
+            _     => None,
+        }
+    }
+
+    /// A machine running for a number of frames.
+    fn machine(rom: Vec<u8>, frames: u64) -> TestMachine {
+        let cpu = Cpu::new(Interconnect::new_test(rom));
+        // the code of sub_200, instruction by instruction
+        let mut machine = Machine::new(cpu, &[0x200..0x202, 0x202..0x204]);
+        machine.set_frame_handler(Box::new(move |machine| machine.frames() < frames));
+        machine
+    }
+
+    #[test]
+    fn interprets_code_without_routine() {
+        // LD V0, 0x01; LD V1, 0x02; LD V2, 0x03; JP 0x200
+        let mut machine = machine(vec![0x60, 0x01, 0x61, 0x02, 0x62, 0x03, 0x12, 0x00], 1);
+        machine.run(dispatch);
+        assert_eq!(&machine.state.v[0..3], &[1, 2, 3]);
+        assert_eq!(machine.state.pc, 0x204, "a frame worth of instructions is executed");
+    }
+
+    #[test]
+    fn interprets_everything_once_code_is_written() {
+        // LD V0, 0x01; LD V1, 0x02; LD I, 0x200; LD [I], V0; JP 0x200, turning LD V0, 0x01 into SYS 0x161
+        let mut machine = machine(vec![0x60, 0x01, 0x61, 0x02, 0xA2, 0x00, 0xF0, 0x55, 0x12, 0x00], 2);
+        machine.run(dispatch);
+        assert!(machine.code_written);
+        assert_eq!(machine.interconnect().memory.read(0x200), 0x01);
+    }
+}
@@ -0,0 +1,551 @@
+//! Recompiler module. Translates a ROM ahead of time into a Rust program running on the `recompiled` runtime.
+//!
+//! Control-flow recovery follows the program from its start at 0x200 and from every `CALL` target, each of which
+//! starts a routine. A routine holds every instruction reachable from its start without following calls, and becomes
+//! one Rust function looping over a `match` on the program counter, with an arm per basic block. The targets of a
+//! `JP V0` are only known at run time; the jump tables it usually indexes, runs of `JP` instructions at its base
+//! address, are recovered as blocks of the routine. Any other address the program counter reaches leaves the routine,
+//! and the runtime interprets the code there until it reaches a recovered block again.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+use disassembler::disassemble;
+use instruction::Instruction;
+use memory::{Memory, MEMORY_SIZE};
+use quirks::Quirks;
+
+/// Address the program starts at.
+const START: u16 = 0x200;
+/// Maximum number of entries of a jump table.
+const MAX_JUMP_TABLE_LEN: u16 = 128;
+/// Directory of the library vendored into recompiled projects.
+const LIBRARY_DIR: &str = "chip-8";
+/// Manifest of the library, whose package, dependencies and features are vendored.
+const LIBRARY_MANIFEST: &str = include_str!("../Cargo.toml");
+/// Sources of the library, vendored into recompiled projects so that they build on their own.
+const LIBRARY_SOURCES: [(&str, &str); 33] = [
+    ("cache.rs", include_str!("cache.rs")),
+    ("config.rs", include_str!("config.rs")),
+    ("cpu.rs", include_str!("cpu.rs")),
+    ("disassembler.rs", include_str!("disassembler.rs")),
+    ("effects.rs", include_str!("effects.rs")),
+    ("emulator.rs", include_str!("emulator.rs")),
+    ("filter.rs", include_str!("filter.rs")),
+    ("framebuffer.rs", include_str!("framebuffer.rs")),
+    ("graphics.rs", include_str!("graphics.rs")),
+    ("gym.rs", include_str!("gym.rs")),
+    ("input.rs", include_str!("input.rs")),
+    ("instruction.rs", include_str!("instruction.rs")),
+    ("interconnect.rs", include_str!("interconnect.rs")),
+    ("jit.rs", include_str!("jit.rs")),
+    ("lib.rs", include_str!("lib.rs")),
+    ("memory.rs", include_str!("memory.rs")),
+    ("memory_viewer.rs", include_str!("memory_viewer.rs")),
+    ("netplay.rs", include_str!("netplay.rs")),
+    ("osd.rs", include_str!("osd.rs")),
+    ("palette.rs", include_str!("palette.rs")),
+    ("prelude.rs", include_str!("prelude.rs")),
+    ("profiler.rs", include_str!("profiler.rs")),
+    ("quirks.rs", include_str!("quirks.rs")),
+    ("recompiled.rs", include_str!("recompiled.rs")),
+    ("recompiler.rs", include_str!("recompiler.rs")),
+    ("recorder.rs", include_str!("recorder.rs")),
+    ("rpc.rs", include_str!("rpc.rs")),
+    ("screenshot.rs", include_str!("screenshot.rs")),
+    ("script.rs", include_str!("script.rs")),
+    ("speed.rs", include_str!("speed.rs")),
+    ("trace.rs", include_str!("trace.rs")),
+    ("viewport.rs", include_str!("viewport.rs")),
+    ("vm.rs", include_str!("vm.rs")),
+];
+
+/// A routine found by control-flow recovery.
+struct Routine {
+    /// Instructions by their address.
+    instructions: BTreeMap<u16, (u16, Instruction)>,
+    /// Addresses the program counter may jump to.
+    targets: BTreeSet<u16>,
+}
+
+impl Routine {
+    /// Follow the instructions reachable from the start of a routine.
+    ///
+    /// # Arguments
+    /// - `memory`: the memory with the ROM loaded.
+    /// - `start`: the address of the first instruction.
+    /// - `calls`: collects the routines called.
+    fn recover(memory: &Memory, start: u16, calls: &mut Vec<u16>) -> Routine {
+        let mut routine = Routine { instructions: BTreeMap::new(), targets: BTreeSet::new() };
+        routine.targets.insert(start);
+        let mut work = vec![start];
+        while let Some(addr) = work.pop() {
+            if routine.instructions.contains_key(&addr) || !is_in_memory(addr) {
+                continue;
+            }
+            let opcode = memory.read_word(addr as usize);
+            let instruction = Instruction::decode(opcode);
+            routine.instructions.insert(addr, (opcode, instruction));
+            let (next, skip) = (addr + 2, addr + 4);
+            let targets = match instruction {
+                Instruction::Ret                                              => vec![],
+                Instruction::Jp(nnn)                                          => vec![nnn],
+                Instruction::Call(nnn)                                        => {
+                    calls.push(nnn);
+                    vec![next]
+                },
+                Instruction::SeByte(..) | Instruction::SneByte(..) | Instruction::SeReg(..)
+                    | Instruction::SneReg(..) | Instruction::Skp(_) | Instruction::Sknp(_) => vec![next, skip],
+                Instruction::JpV0(nnn)                                        => jump_table(memory, nnn),
+                // executed again until a key is pressed
+                Instruction::LdVxK(_)                                         => vec![addr, next],
+                _                                                             => {
+                    work.push(next);
+                    continue;
+                },
+            };
+            routine.targets.extend(targets.iter().cloned());
+            work.extend(targets);
+        }
+        routine
+    }
+
+    /// Checks if a basic block starts at an address: a jump target, or an instruction not following on from the one
+    /// before.
+    fn is_block_start(&self, addr: u16) -> bool {
+        self.targets.contains(&addr) || addr < 2 || match self.instructions.get(&(addr - 2)) {
+            Some(&(_, instruction)) => ends_block(instruction),
+            None                    => true,
+        }
+    }
+}
+
+/// Checks if an instruction can be fetched from an address.
+fn is_in_memory(addr: u16) -> bool {
+    (addr as usize) + 1 < MEMORY_SIZE
+}
+
+/// Checks if an instruction ends a basic block, as it may continue elsewhere than at the next instruction.
+fn ends_block(instruction: Instruction) -> bool {
+    instruction.is_branch()
+}
+
+/// Find the jump table indexed by a `JP V0`: the `JP` instructions from its base address on.
+fn jump_table(memory: &Memory, base: u16) -> Vec<u16> {
+    (0..MAX_JUMP_TABLE_LEN)
+        .map(|idx| base + 2 * idx)
+        .take_while(|&addr| is_in_memory(addr) && memory.read_word(addr as usize) >> 12 == 0x1)
+        .collect()
+}
+
+/// A ROM after control-flow recovery.
+pub struct Program {
+    name: String,
+    rom: Vec<u8>,
+    /// Routines by their start address.
+    routines: BTreeMap<u16, Routine>,
+}
+
+impl Program {
+    /// Recover the routines of a ROM.
+    ///
+    /// # Arguments
+    /// - `name`: the name of the ROM.
+    /// - `rom`: the ROM.
+    ///
+    /// # Returns
+    /// Returns the program, or an error if the ROM does not fit in memory.
+    pub fn recover(name: &str, rom: &[u8]) -> Result<Program, String> {
+        let memory = Memory::new(rom.to_vec())?;
+        let mut routines = BTreeMap::new();
+        let mut calls = vec![START];
+        while let Some(start) = calls.pop() {
+            if !routines.contains_key(&start) && is_in_memory(start) {
+                let routine = Routine::recover(&memory, start, &mut calls);
+                routines.insert(start, routine);
+            }
+        }
+        Ok(Program { name: name.to_string(), rom: rom.to_vec(), routines })
+    }
+
+    /// Number of routines.
+    pub fn routines(&self) -> usize {
+        self.routines.len()
+    }
+
+    /// Number of distinct instructions recovered.
+    pub fn instructions(&self) -> usize {
+        self.code_addresses().len()
+    }
+
+    /// Addresses of the recovered instructions.
+    fn code_addresses(&self) -> BTreeSet<u16> {
+        self.routines.values().flat_map(|routine| routine.instructions.keys().cloned()).collect()
+    }
+
+    /// Address ranges of the recovered code.
+    pub fn code(&self) -> Vec<Range<u16>> {
+        let mut ranges: Vec<Range<u16>> = Vec::new();
+        for addr in self.code_addresses() {
+            match ranges.last_mut() {
+                Some(range) if range.end >= addr => range.end = addr + 2,
+                _                                => ranges.push(addr..addr + 2),
+            }
+        }
+        ranges
+    }
+
+    /// Generate the Rust program.
+    ///
+    /// # Arguments
+    /// - `quirks`: the name of the quirk profile to recompile for.
+    /// - `cycles_per_frame`: the number of instructions executed per frame.
+    pub fn to_rust(&self, quirks: &str, cycles_per_frame: u32) -> String {
+        let profile = Quirks::from_name(quirks).expect("unknown quirk profile");
+        let mut out = String::new();
+        let _ = writeln!(out, "// CHIP-8 ROM {} recompiled to Rust with the {} quirks: {} routines, {} instructions.",
+                         self.name, quirks, self.routines(), self.instructions());
+        let _ = writeln!(out, "//");
+        let _ = writeln!(out, "// Generated by `chip-8 recompile`.");
+        let _ = writeln!(out);
+        let _ = writeln!(out, "extern crate chip_8;");
+        let _ = writeln!(out);
+        let _ = writeln!(out, "use std::ops::Range;");
+        let _ = writeln!(out);
+        let _ = writeln!(out, "use chip_8::prelude::*;");
+        let _ = writeln!(out, "use chip_8::recompiled::{{self, Exit, Machine, Routine}};");
+        let _ = writeln!(out);
+        let _ = writeln!(out, "/// Quirk profile the routines were recompiled for.");
+        let _ = writeln!(out, "pub const QUIRKS: &str = {:?};", quirks);
+        let _ = writeln!(out, "/// Instructions executed per frame.");
+        let _ = writeln!(out, "pub const CYCLES_PER_FRAME: u32 = {};", cycles_per_frame);
+        let _ = writeln!(out, "/// The ROM.");
+        let _ = writeln!(out, "pub const ROM: &[u8] = &[");
+        for chunk in self.rom.chunks(16) {
+            let bytes: Vec<String> = chunk.iter().map(|byte| format!("0x{:02X},", byte)).collect();
+            let _ = writeln!(out, "    {}", bytes.join(" "));
+        }
+        let _ = writeln!(out, "];");
+        let _ = writeln!(out, "/// Addresses of the recovered code.");
+        let _ = writeln!(out, "#[allow(clippy::single_range_in_vec_init)]");
+        let ranges: Vec<String> = self.code().iter().map(|range| format!("0x{:03X}..0x{:03X}", range.start, range.end))
+            .collect();
+        write_list(&mut out, "pub const CODE: &[Range<u16>] = &[", ",", &ranges, "];");
+        let _ = writeln!(out);
+        let _ = writeln!(out, "fn main() {{");
+        let _ = writeln!(out, "    recompiled::run(ROM, CODE, QUIRKS, CYCLES_PER_FRAME, dispatch);");
+        let _ = writeln!(out, "}}");
+        self.write_dispatch(&mut out);
+        for (&start, routine) in &self.routines {
+            self.write_routine(&mut out, start, routine, profile);
+        }
+        out
+    }
+
+    /// Write the lookup of the routine with a block starting at an address. Blocks shared by routines are looked up
+    /// in the first one.
+    fn write_dispatch(&self, out: &mut String) {
+        let mut owners: BTreeMap<u16, Vec<u16>> = BTreeMap::new();
+        let mut seen = BTreeSet::new();
+        for (&start, routine) in &self.routines {
+            for &addr in routine.instructions.keys() {
+                if routine.is_block_start(addr) && seen.insert(addr) {
+                    owners.entry(start).or_default().push(addr);
+                }
+            }
+        }
+        let _ = writeln!(out);
+        let _ = writeln!(out, "/// Look up the routine with a basic block starting at an address.");
+        let _ = writeln!(out, "pub fn dispatch<T: Input, U: Graphics>(pc: u16) -> Option<Routine<T, U>> {{");
+        let _ = writeln!(out, "    let routine: Routine<T, U> = match pc {{");
+        for (start, blocks) in owners {
+            let blocks: Vec<String> = blocks.iter().map(|addr| format!("0x{:03X}", addr)).collect();
+            write_list(out, "        ", " |", &blocks, &format!("=> {},", function(start)));
+        }
+        let _ = writeln!(out, "        _ => return None,");
+        let _ = writeln!(out, "    }};");
+        let _ = writeln!(out, "    Some(routine)");
+        let _ = writeln!(out, "}}");
+    }
+
+    /// Write the function of a routine.
+    fn write_routine(&self, out: &mut String, start: u16, routine: &Routine, quirks: Quirks) {
+        let mut arms = String::new();
+        let mut loops = false;
+        for (&addr, _) in routine.instructions.iter().filter(|&(&addr, _)| routine.is_block_start(addr)) {
+            let _ = writeln!(arms, "            0x{:03X} => {{", addr);
+            let mut pc = addr;
+            loop {
+                let (opcode, instruction) = routine.instructions[&pc];
+                let _ = writeln!(arms, "                // 0x{:03X}: {}", pc, disassemble(opcode));
+                for line in self.translate(pc, opcode, instruction, quirks) {
+                    loops |= line.starts_with("pc = ");
+                    let _ = writeln!(arms, "                {}", line);
+                }
+                pc += 2;
+                if ends_block(instruction) {
+                    break;
+                }
+                if !routine.instructions.contains_key(&pc) || routine.is_block_start(pc) {
+                    let _ = writeln!(arms, "                pc = 0x{:03X};", pc);
+                    loops = true;
+                    break;
+                }
+            }
+            let _ = writeln!(arms, "            }},");
+        }
+        let _ = writeln!(arms, "            _ => {{");
+        let _ = writeln!(arms, "                m.state.pc = pc;");
+        let _ = writeln!(arms, "                return Err(Exit::Leave);");
+        let _ = writeln!(arms, "            }},");
+
+        let _ = writeln!(out);
+        let _ = writeln!(out, "/// Routine at 0x{:03X}.", start);
+        let _ = writeln!(out, "pub fn {}<T: Input, U: Graphics>(m: &mut Machine<T, U>) -> Result<(), Exit> {{",
+                         function(start));
+        if loops {
+            let _ = writeln!(out, "    let mut pc = m.state.pc;");
+            let _ = writeln!(out, "    loop {{");
+            let _ = writeln!(out, "        match pc {{");
+            out.push_str(&arms);
+            let _ = writeln!(out, "        }}");
+            let _ = writeln!(out, "    }}");
+        } else {
+            // every block returns, so the match is the value of the function
+            let arms = arms.replace("return Ok(());", "Ok(())").replace("                m.state.pc = pc;\n", "")
+                .replace("return Err(Exit::Leave);", "Err(Exit::Leave)");
+            let _ = writeln!(out, "    let pc = m.state.pc;");
+            let _ = writeln!(out, "    match pc {{");
+            for line in arms.lines() {
+                let _ = writeln!(out, "{}", &line[4..]);
+            }
+            let _ = writeln!(out, "    }}");
+        }
+        let _ = writeln!(out, "}}");
+    }
+
+    /// Translate an instruction into Rust statements.
+    ///
+    /// # Arguments
+    /// - `addr`: the address of the instruction.
+    /// - `opcode`: the instruction.
+    /// - `instruction`: the decoded instruction.
+    /// - `quirks`: the behaviour of the instructions interpreters disagree on.
+    fn translate(&self, addr: u16, opcode: u16, instruction: Instruction, quirks: Quirks) -> Vec<String> {
+        let (next, skip) = (addr + 2, addr + 4);
+        let v = |x: u8| format!("m.state.v[0x{:X}]", x);
+        let tick = format!("m.tick(0x{:03X})?;", next);
+        let vf_reset = if quirks.vf_reset { vec![format!("{} = 0;", v(0xF))] } else { vec![] };
+        let skip_if = |condition: String| vec![
+            format!("pc = if {} {{ 0x{:03X} }} else {{ 0x{:03X} }};", condition, skip, next),
+            "m.tick(pc)?;".to_string(),
+        ];
+        let mut lines = match instruction {
+            Instruction::Cls | Instruction::Rnd(..) | Instruction::Drw(..) | Instruction::LdB(_)
+                | Instruction::LdMemVx(_) | Instruction::LdVxMem(_) => {
+                vec![format!("m.host(0x{:03X}, 0x{:04X})?;", addr, opcode)]
+            },
+            Instruction::Skp(_) | Instruction::Sknp(_) | Instruction::LdVxK(_) => vec![
+                format!("m.host(0x{:03X}, 0x{:04X})?;", addr, opcode),
+                "pc = m.state.pc;".to_string(),
+            ],
+            Instruction::Nop            => vec![tick],
+            Instruction::Ret            => vec!["m.ret()?;".to_string(), "return Ok(());".to_string()],
+            Instruction::Jp(nnn)        => vec![format!("m.tick(0x{:03X})?;", nnn), format!("pc = 0x{:03X};", nnn)],
+            Instruction::Call(nnn)      => {
+                let mut lines = vec![format!("m.call(0x{:03X}, 0x{:03X})?;", next, nnn)];
+                if self.routines.contains_key(&nnn) {
+                    lines.push(format!("{}(m)?;", function(nnn)));
+                    lines.push("pc = m.state.pc;".to_string());
+                } else {
+                    lines.push(format!("pc = 0x{:03X};", nnn));
+                }
+                lines
+            },
+            Instruction::SeByte(x, kk)  => skip_if(format!("{} == 0x{:02X}", v(x), kk)),
+            Instruction::SneByte(x, kk) => skip_if(format!("{} != 0x{:02X}", v(x), kk)),
+            Instruction::SeReg(x, y)    => {
+                skip_if(if x == y { "true".to_string() } else { format!("{} == {}", v(x), v(y)) })
+            },
+            Instruction::SneReg(x, y)   => {
+                skip_if(if x == y { "false".to_string() } else { format!("{} != {}", v(x), v(y)) })
+            },
+            Instruction::LdByte(x, kk)  => vec![format!("{} = 0x{:02X};", v(x), kk), tick],
+            Instruction::AddByte(x, kk) => vec![format!("{0} = {0}.wrapping_add(0x{1:02X});", v(x), kk), tick],
+            Instruction::LdReg(x, y) if x == y => vec![tick],
+            Instruction::LdReg(x, y)    => vec![format!("{} = {};", v(x), v(y)), tick],
+            Instruction::Or(x, y) | Instruction::And(x, y) if x == y => [vf_reset, vec![tick]].concat(),
+            Instruction::Xor(x, y) if x == y => [vec![format!("{} = 0;", v(x))], vf_reset, vec![tick]].concat(),
+            Instruction::Or(x, y)       => [vec![format!("{} |= {};", v(x), v(y))], vf_reset, vec![tick]].concat(),
+            Instruction::And(x, y)      => [vec![format!("{} &= {};", v(x), v(y))], vf_reset, vec![tick]].concat(),
+            Instruction::Xor(x, y)      => [vec![format!("{} ^= {};", v(x), v(y))], vf_reset, vec![tick]].concat(),
+            // the carry is written before the sum, as the CPU does
+            Instruction::AddReg(x, y)   => vec![
+                format!("let sum = u16::from({}) + u16::from({});", v(x), v(y)),
+                format!("{} = u8::from(sum > 0xFF);", v(0xF)),
+                format!("{} = sum as u8;", v(x)),
+                tick,
+            ],
+            Instruction::Sub(x, y) | Instruction::Subn(x, y) if x == y => {
+                vec![format!("{} = 0;", v(x)), format!("{} = 0;", v(0xF)), tick]
+            },
+            // the difference of signed bytes with the borrow in VF, as the CPU computes them
+            Instruction::Sub(x, y)      => vec![
+                format!("let difference = ({} as i8).wrapping_sub({} as i8);", v(x), v(y)),
+                format!("{} = difference as u8;", v(x)),
+                format!("{} = u8::from(difference < 0);", v(0xF)),
+                tick,
+            ],
+            Instruction::Subn(x, y)     => vec![
+                format!("let difference = ({} as i8).wrapping_sub({} as i8);", v(y), v(x)),
+                format!("{} = difference as u8;", v(x)),
+                format!("{} = u8::from(difference < 0);", v(0xF)),
+                tick,
+            ],
+            Instruction::Shr(x, y)      => vec![
+                format!("let value = {};", v(if quirks.shift_vy { y } else { x })),
+                format!("{} = value & 0x1;", v(0xF)),
+                format!("{} = value >> 1;", v(x)),
+                tick,
+            ],
+            // VF is set to the bit shifted out in place, as the CPU does
+            Instruction::Shl(x, y)      => vec![
+                format!("let value = {};", v(if quirks.shift_vy { y } else { x })),
+                format!("{} = value & 0x80;", v(0xF)),
+                format!("{} = value << 1;", v(x)),
+                tick,
+            ],
+            Instruction::LdI(nnn)       => vec![format!("m.state.i = 0x{:03X};", nnn), tick],
+            Instruction::JpV0(nnn)      => {
+                let x = if quirks.jump_vx { (nnn >> 8) as u8 } else { 0 };
+                vec![format!("pc = 0x{:03X} + u16::from({});", nnn, v(x)), "m.tick(pc)?;".to_string()]
+            },
+            Instruction::LdVxDt(x)      => vec![format!("{} = m.state.dt;", v(x)), tick],
+            Instruction::LdDtVx(x)      => vec![format!("m.state.dt = {};", v(x)), tick],
+            Instruction::LdStVx(x)      => vec![format!("m.state.st = {};", v(x)), tick],
+            Instruction::AddI(x)        => {
+                vec![format!("m.state.i = m.state.i.wrapping_add(u16::from({}));", v(x)), tick]
+            },
+            Instruction::LdF(x)         => vec![format!("m.state.i = u16::from({}) * 5;", v(x)), tick],
+        };
+        lines.retain(|line| !line.is_empty());
+        lines
+    }
+}
+
+/// Name of the function of the routine at an address.
+fn function(start: u16) -> String {
+    format!("sub_{:03x}", start)
+}
+
+/// Write items separated by a separator, wrapping lines at 120 characters.
+///
+/// # Arguments
+/// - `out`: the output.
+/// - `open`: the text before the items, ending with the indentation of the first line.
+/// - `separator`: the text after every item but the last.
+/// - `items`: the items.
+/// - `close`: the text after the last item.
+fn write_list(out: &mut String, open: &str, separator: &str, items: &[String], close: &str) {
+    let indent = " ".repeat(open.len() - open.trim_start().len() + 4);
+    let mut line = open.to_string();
+    let wraps = open.trim_start().ends_with('[');
+    if wraps {
+        let _ = writeln!(out, "{}", line);
+        line = indent.clone();
+    }
+    for (idx, item) in items.iter().enumerate() {
+        let last = idx + 1 == items.len();
+        let text = if !last || wraps { format!("{}{}", item, separator) } else { item.to_string() };
+        let width = if last && !wraps { text.len() + close.len() + 1 } else { text.len() };
+        if line.trim().is_empty() || line.len() + width < 120 {
+            if !line.trim().is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&text);
+        } else {
+            let _ = writeln!(out, "{}", line);
+            line = format!("{}{}", indent, text);
+        }
+    }
+    if wraps {
+        let _ = writeln!(out, "{}", line);
+        let _ = writeln!(out, "{}", close);
+    } else {
+        let _ = writeln!(out, "{} {}", line, close);
+    }
+}
+
+/// Write a recompiled program as a cargo project, along with a copy of the library it runs on.
+///
+/// # Arguments
+/// - `dir`: the directory of the project.
+/// - `package`: the name of the package.
+/// - `source`: the Rust program.
+pub fn write_project(dir: &Path, package: &str, source: &str) -> io::Result<()> {
+    let library = dir.join(LIBRARY_DIR);
+    fs::create_dir_all(library.join("src"))?;
+    // the library without its tests and benchmarks, which are not vendored
+    let library_manifest = LIBRARY_MANIFEST.split("\n[dev-dependencies]").next().unwrap_or(LIBRARY_MANIFEST);
+    fs::write(library.join("Cargo.toml"), library_manifest.trim_end().to_string() + "\n")?;
+    for (name, source) in LIBRARY_SOURCES.iter() {
+        fs::write(library.join("src").join(name), source)?;
+    }
+
+    fs::create_dir_all(dir.join("src"))?;
+    let manifest = format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2015\"\n\n[dependencies]\n\
+                            chip-8 = {{path = {:?}}}\n\n[workspace]\n", package, LIBRARY_DIR);
+    fs::write(dir.join("Cargo.toml"), manifest)?;
+    fs::write(dir.join("src").join("main.rs"), source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vendors_every_library_source() {
+        let mut sources: Vec<String> = fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("src")).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name != "main.rs")
+            .collect();
+        sources.sort();
+        let vendored: Vec<&str> = LIBRARY_SOURCES.iter().map(|&(name, _)| name).collect();
+        assert_eq!(sources, vendored, "recompiled projects should build with a copy of every library module");
+    }
+
+    #[test]
+    fn recovers_routines_and_blocks() {
+        // 0x200: CALL 0x206; JP 0x204
+        // 0x206: SE V0, 0x00; RET; RET
+        let program = Program::recover("TEST", &[0x22, 0x06, 0x12, 0x04, 0x00, 0x00, 0x30, 0x00, 0x00, 0xEE,
+                                                 0x00, 0xEE]).unwrap();
+        assert_eq!(program.routines.keys().cloned().collect::<Vec<u16>>(), vec![0x200, 0x206]);
+        let routine = &program.routines[&0x206];
+        assert_eq!(routine.instructions.len(), 3, "both sides of the skip are followed");
+        assert!(routine.is_block_start(0x208) && routine.is_block_start(0x20A));
+        assert_eq!(program.code(), vec![0x200..0x20C]);
+    }
+
+    #[test]
+    fn recovers_jump_tables() {
+        // 0x200: JP V0, 0x202; JP 0x208; JP 0x20A; 0x206: data
+        let program = Program::recover("TEST", &[0xB2, 0x02, 0x12, 0x08, 0x12, 0x0A, 0xFF, 0xFF, 0x00, 0xEE,
+                                                 0x00, 0xEE]).unwrap();
+        let targets: Vec<u16> = program.routines[&0x200].targets.iter().cloned().collect();
+        assert_eq!(targets, vec![0x200, 0x202, 0x204, 0x208, 0x20A]);
+        assert!(program.to_rust("default", 10).contains("pc = 0x202 + u16::from(m.state.v[0x0]);"));
+    }
+
+    #[test]
+    fn translates_quirks() {
+        // SHR V1, V2
+        let program = Program::recover("TEST", &[0x81, 0x26, 0x00, 0xEE]).unwrap();
+        assert!(program.to_rust("default", 10).contains("let value = m.state.v[0x1];"));
+        assert!(program.to_rust("cosmac", 10).contains("let value = m.state.v[0x2];"));
+    }
+}
@@ -0,0 +1,465 @@
+//! Recorder module. Records gameplay as video, with the buzzer written to a separate WAV file.
+//!
+//! Frames are recorded at the native resolution once per 60 Hz frame.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use gif;
+use png;
+
+use cpu::FRAMES_PER_SECOND;
+use effects::Image;
+use graphics::BYTES_PER_PIXEL;
+use screenshot;
+
+/// Sample rate of the recorded audio.
+pub const SAMPLE_RATE: u32 = 44_100;
+/// Frequency of the buzzer tone.
+const BUZZER_FREQUENCY: u32 = 440;
+/// Amplitude of the buzzer tone.
+const BUZZER_AMPLITUDE: i16 = 8_000;
+/// Directory name that makes the video be written to stdout.
+pub const STDOUT: &str = "-";
+
+/// Container the video is recorded to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// Animated GIF.
+    #[default]
+    Gif,
+    /// Animated PNG.
+    Apng,
+    /// Uncompressed YUV4MPEG2 stream, as understood by most video encoders.
+    Y4m,
+}
+
+impl VideoFormat {
+    /// Look up a format by its name.
+    pub fn from_name(name: &str) -> Option<VideoFormat> {
+        match name {
+            "gif"  => Some(VideoFormat::Gif),
+            "apng" => Some(VideoFormat::Apng),
+            "y4m"  => Some(VideoFormat::Y4m),
+            _      => None,
+        }
+    }
+
+    /// File extension of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            VideoFormat::Gif  => "gif",
+            VideoFormat::Apng => "png",
+            VideoFormat::Y4m  => "y4m",
+        }
+    }
+}
+
+/// Encoder of video frames.
+trait VideoEncoder {
+    /// Encode the next frame.
+    fn frame(&mut self, image: &Image) -> io::Result<()>;
+
+    /// Flush all frames and close the video.
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+/// A running recording.
+pub struct Recorder {
+    video: Box<dyn VideoEncoder>,
+    audio: WavWriter<BufWriter<File>>,
+    video_path: Option<PathBuf>,
+    audio_path: PathBuf,
+}
+
+impl Recorder {
+    /// Start a recording.
+    ///
+    /// # Arguments
+    /// - `format`: the container of the video.
+    /// - `dir`: the directory to write to, created if missing. With `-` the video is written to stdout and the audio
+    ///   to the current directory.
+    /// - `rom`: the name of the running ROM, used as prefix of the file names.
+    pub fn start(format: VideoFormat, dir: &Path, rom: &str) -> io::Result<Recorder> {
+        let stem = screenshot::file_stem(rom, SystemTime::now());
+        let (video, video_path): (Box<dyn VideoEncoder>, _) = if dir == Path::new(STDOUT) {
+            (new_encoder(format, Box::new(io::stdout())), None)
+        } else {
+            fs::create_dir_all(dir)?;
+            let path = dir.join(format!("{}.{}", stem, format.extension()));
+            let file = Box::new(BufWriter::new(File::create(&path)?));
+            (new_encoder(format, file), Some(path))
+        };
+        let audio_dir = if dir == Path::new(STDOUT) { Path::new(".") } else { dir };
+        let audio_path = audio_dir.join(format!("{}.wav", stem));
+        let audio = WavWriter::new(BufWriter::new(File::create(&audio_path)?))?;
+        Ok(Recorder { video, audio, video_path, audio_path })
+    }
+
+    /// Record a frame.
+    ///
+    /// # Arguments
+    /// - `image`: the display at native resolution.
+    /// - `buzzer`: whether the buzzer sounds during the frame.
+    pub fn record_frame(&mut self, image: &Image, buzzer: bool) -> io::Result<()> {
+        self.video.frame(image)?;
+        self.audio.frame(buzzer)
+    }
+
+    /// Stop the recording, flushing all files.
+    ///
+    /// # Returns
+    /// Returns a description of where the recording was written to.
+    pub fn finish(self) -> io::Result<String> {
+        self.video.finish()?;
+        self.audio.finish()?;
+        let video = self.video_path.map_or_else(|| String::from("stdout"), |path| format!("'{}'", path.display()));
+        Ok(format!("{} and '{}'", video, self.audio_path.display()))
+    }
+}
+
+/// Create the encoder of a format.
+fn new_encoder(format: VideoFormat, writer: Box<dyn Write>) -> Box<dyn VideoEncoder> {
+    match format {
+        VideoFormat::Gif  => Box::new(GifEncoder::new(writer)),
+        VideoFormat::Apng => Box::new(ApngEncoder::new(writer)),
+        VideoFormat::Y4m  => Box::new(Y4mEncoder::new(writer)),
+    }
+}
+
+/// Duration of a frame in hundredths of a second, distributing the rounding error so that the total stays exact.
+fn gif_delay(frame: u32) -> u16 {
+    let end = ((frame + 1) * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND;
+    let start = (frame * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND;
+    (end - start) as u16
+}
+
+/// Convert an image to palette indices and a palette of at most 256 colours.
+fn index_colors(image: &Image) -> (Vec<u8>, Vec<u8>) {
+    let mut lookup: HashMap<&[u8], u8> = HashMap::new();
+    let mut palette = Vec::new();
+    let mut indices = Vec::with_capacity(image.width * image.height);
+    for pixel in image.pixels.chunks(BYTES_PER_PIXEL) {
+        let idx = match lookup.get(pixel) {
+            Some(&idx)                  => idx,
+            None if lookup.len() < 256  => {
+                let idx = lookup.len() as u8;
+                lookup.insert(pixel, idx);
+                palette.extend_from_slice(pixel);
+                idx
+            },
+            // further colours are replaced by the last one of the palette
+            None                        => 255,
+        };
+        indices.push(idx);
+    }
+    (indices, palette)
+}
+
+/// Animated GIF encoder. Identical consecutive frames are merged into a single longer frame.
+struct GifEncoder {
+    writer: Option<Box<dyn Write>>,
+    encoder: Option<gif::Encoder<Box<dyn Write>>>,
+    // last frame, written once it is known for how long it is shown
+    pending: Option<Image>,
+    delay: u16,
+    frames: u32,
+}
+
+impl GifEncoder {
+    fn new(writer: Box<dyn Write>) -> GifEncoder {
+        GifEncoder { writer: Some(writer), encoder: None, pending: None, delay: 0, frames: 0 }
+    }
+
+    /// Write the pending frame, starting the file on the first frame.
+    fn flush_pending(&mut self) -> io::Result<()> {
+        let image = match self.pending.take() {
+            Some(image) => image,
+            None        => return Ok(()),
+        };
+        if self.encoder.is_none() {
+            let writer = self.writer.take().expect("writer is kept until the first frame");
+            let mut encoder = gif::Encoder::new(writer, image.width as u16, image.height as u16, &[])
+                .map_err(to_io_error)?;
+            encoder.set_repeat(gif::Repeat::Infinite).map_err(to_io_error)?;
+            self.encoder = Some(encoder);
+        }
+        let (indices, palette) = index_colors(&image);
+        let mut frame = gif::Frame::from_palette_pixels(image.width as u16, image.height as u16, indices, palette, None);
+        frame.delay = self.delay;
+        self.encoder.as_mut().unwrap().write_frame(&frame).map_err(to_io_error)
+    }
+}
+
+impl VideoEncoder for GifEncoder {
+    fn frame(&mut self, image: &Image) -> io::Result<()> {
+        if self.pending.as_ref() != Some(image) || self.delay > u16::MAX - 2 {
+            self.flush_pending()?;
+            self.pending = Some(image.clone());
+            self.delay = 0;
+        }
+        self.delay += gif_delay(self.frames);
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.flush_pending()?;
+        match self.encoder.take() {
+            Some(encoder) => encoder.into_inner()?.flush(),
+            None          => Ok(()),
+        }
+    }
+}
+
+/// Animated PNG encoder. The frame count is part of the header, so frames are kept in memory until the end.
+struct ApngEncoder {
+    writer: Box<dyn Write>,
+    // distinct consecutive frames with the number of 60 Hz frames they are shown
+    frames: Vec<(Image, u16)>,
+}
+
+impl ApngEncoder {
+    fn new(writer: Box<dyn Write>) -> ApngEncoder {
+        ApngEncoder { writer, frames: Vec::new() }
+    }
+}
+
+impl VideoEncoder for ApngEncoder {
+    fn frame(&mut self, image: &Image) -> io::Result<()> {
+        match self.frames.last_mut() {
+            Some((last, duration)) if last == image && *duration < u16::MAX => *duration += 1,
+            _                                                                 => self.frames.push((image.clone(), 1)),
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        let (width, height) = match self.frames.first() {
+            Some((image, _)) => (image.width, image.height),
+            None             => return Ok(()),
+        };
+        let mut encoder = png::Encoder::new(self.writer, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(self.frames.len() as u32, 0)?;
+        let mut writer = encoder.write_header()?;
+        for (image, duration) in &self.frames {
+            writer.set_frame_delay(*duration, FRAMES_PER_SECOND as u16)?;
+            writer.write_image_data(&image.pixels)?;
+        }
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// YUV4MPEG2 encoder writing full resolution 4:4:4 frames.
+struct Y4mEncoder {
+    writer: Box<dyn Write>,
+    header_written: bool,
+}
+
+impl Y4mEncoder {
+    fn new(writer: Box<dyn Write>) -> Y4mEncoder {
+        Y4mEncoder { writer, header_written: false }
+    }
+}
+
+impl VideoEncoder for Y4mEncoder {
+    fn frame(&mut self, image: &Image) -> io::Result<()> {
+        if !self.header_written {
+            writeln!(self.writer, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", image.width, image.height, FRAMES_PER_SECOND)?;
+            self.header_written = true;
+        }
+        let size = image.width * image.height;
+        let mut planes = vec![0; 3 * size];
+        for (idx, pixel) in image.pixels.chunks(BYTES_PER_PIXEL).enumerate() {
+            let (y, u, v) = rgb_to_yuv(pixel[0], pixel[1], pixel[2]);
+            planes[idx] = y;
+            planes[size + idx] = u;
+            planes[2 * size + idx] = v;
+        }
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&planes)
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Convert a colour to studio range BT.601 YCbCr.
+fn rgb_to_yuv(r: u8, g: u8, b: u8) -> (u8, u8, u8) {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let y = 16.0 + 0.257 * r + 0.504 * g + 0.098 * b;
+    let u = 128.0 - 0.148 * r - 0.291 * g + 0.439 * b;
+    let v = 128.0 + 0.439 * r - 0.368 * g - 0.071 * b;
+    (y.round() as u8, u.round() as u8, v.round() as u8)
+}
+
+/// Writer of 16 bit mono PCM WAV files containing the buzzer tone.
+struct WavWriter<W: Write + Seek> {
+    writer: W,
+    samples: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    /// Size of the header preceding the samples.
+    const HEADER_SIZE: u32 = 44;
+
+    /// Constructor, writing a header to be completed by `finish`.
+    fn new(mut writer: W) -> io::Result<WavWriter<W>> {
+        writer.write_all(&WavWriter::<W>::header(0))?;
+        Ok(WavWriter { writer, samples: 0 })
+    }
+
+    /// Header of a file containing `samples` samples.
+    fn header(samples: u32) -> Vec<u8> {
+        let data_size = samples * 2;
+        let mut header = Vec::with_capacity(WavWriter::<W>::HEADER_SIZE as usize);
+        header.extend_from_slice(b"RIFF");
+        header.extend_from_slice(&(WavWriter::<W>::HEADER_SIZE - 8 + data_size).to_le_bytes());
+        header.extend_from_slice(b"WAVEfmt ");
+        header.extend_from_slice(&16_u32.to_le_bytes());
+        header.extend_from_slice(&1_u16.to_le_bytes());
+        header.extend_from_slice(&1_u16.to_le_bytes());
+        header.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+        header.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+        header.extend_from_slice(&2_u16.to_le_bytes());
+        header.extend_from_slice(&16_u16.to_le_bytes());
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&data_size.to_le_bytes());
+        header
+    }
+
+    /// Write the samples of one frame, a square wave if the buzzer sounds and silence otherwise.
+    fn frame(&mut self, buzzer: bool) -> io::Result<()> {
+        let count = SAMPLE_RATE / FRAMES_PER_SECOND;
+        let half_period = SAMPLE_RATE / BUZZER_FREQUENCY / 2;
+        let mut bytes = Vec::with_capacity(count as usize * 2);
+        for idx in self.samples..self.samples + count {
+            let sample = match (buzzer, (idx / half_period) % 2) {
+                (false, _) => 0,
+                (true, 0)  => BUZZER_AMPLITUDE,
+                (true, _)  => -BUZZER_AMPLITUDE,
+            };
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        self.samples += count;
+        self.writer.write_all(&bytes)
+    }
+
+    /// Complete the header and flush the file.
+    fn finish(mut self) -> io::Result<()> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer.write_all(&WavWriter::<W>::header(self.samples))?;
+        self.writer.flush()
+    }
+}
+
+/// Convert a GIF encoding error to an IO error.
+fn to_io_error(error: gif::EncodingError) -> io::Error {
+    match error {
+        gif::EncodingError::Io(error) => error,
+        error                         => io::Error::other(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+    use std::rc::Rc;
+    use std::cell::RefCell;
+
+    /// Writer sharing its output, so it can be inspected after the encoder consumed it.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn image(value: u8) -> Image {
+        Image { width: 2, height: 1, pixels: vec![value; 2 * BYTES_PER_PIXEL] }
+    }
+
+    #[test]
+    fn gif_delays_add_up() {
+        let total: u32 = (0..FRAMES_PER_SECOND).map(|frame| gif_delay(frame) as u32).sum();
+        assert_eq!(total, 100, "a second of frames lasts 100 hundredths");
+        assert!((0..FRAMES_PER_SECOND).all(|frame| gif_delay(frame) == 1 || gif_delay(frame) == 2));
+    }
+
+    #[test]
+    fn index_colors_builds_palette() {
+        let image = Image { width: 3, height: 1, pixels: vec![0, 0, 0, 9, 9, 9, 0, 0, 0] };
+        assert_eq!(index_colors(&image), (vec![0, 1, 0], vec![0, 0, 0, 9, 9, 9]));
+    }
+
+    #[test]
+    fn gif_merges_identical_frames() {
+        let buffer = SharedBuffer::default();
+        let mut encoder = Box::new(GifEncoder::new(Box::new(buffer.clone())));
+        for value in &[0, 0, 0, 255] {
+            encoder.frame(&image(*value)).unwrap();
+        }
+        assert_eq!(encoder.delay, gif_delay(3), "the last frame is pending");
+        encoder.finish().unwrap();
+        assert_eq!(&buffer.0.borrow()[..6], b"GIF89a");
+    }
+
+    #[test]
+    fn apng_merges_identical_frames() {
+        let buffer = SharedBuffer::default();
+        let mut encoder = Box::new(ApngEncoder::new(Box::new(buffer.clone())));
+        for value in &[0, 0, 255] {
+            encoder.frame(&image(*value)).unwrap();
+        }
+        assert_eq!(encoder.frames.iter().map(|&(_, duration)| duration).collect::<Vec<_>>(), vec![2, 1]);
+        encoder.finish().unwrap();
+        let bytes = buffer.0.borrow();
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+        assert!(bytes.windows(4).any(|chunk| chunk == b"acTL"), "animation control chunk is written");
+    }
+
+    #[test]
+    fn y4m_frames() {
+        let buffer = SharedBuffer::default();
+        let mut encoder = Box::new(Y4mEncoder::new(Box::new(buffer.clone())));
+        encoder.frame(&image(255)).unwrap();
+        encoder.frame(&image(0)).unwrap();
+        encoder.finish().unwrap();
+
+        let header = b"YUV4MPEG2 W2 H1 F60:1 Ip A1:1 C444\n";
+        let bytes = buffer.0.borrow();
+        assert_eq!(&bytes[..header.len()], &header[..]);
+        assert_eq!(bytes.len(), header.len() + 2 * (b"FRAME\n".len() + 6));
+        assert_eq!(&bytes[header.len() + 6..header.len() + 8], &[235, 235], "white is full luma");
+    }
+
+    #[test]
+    fn wav_sizes() {
+        let mut cursor = Cursor::new(Vec::new());
+        {
+            let mut wav = WavWriter::new(&mut cursor).unwrap();
+            wav.frame(true).unwrap();
+            wav.finish().unwrap();
+        }
+        let bytes = cursor.into_inner();
+        let data_size = 2 * SAMPLE_RATE / FRAMES_PER_SECOND;
+        assert_eq!(bytes.len() as u32, 44 + data_size);
+        assert_eq!(&bytes[40..44], &data_size.to_le_bytes());
+        assert_eq!(&bytes[44..46], &BUZZER_AMPLITUDE.to_le_bytes(), "the tone starts high");
+    }
+}
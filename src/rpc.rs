@@ -0,0 +1,494 @@
+//! Remote control module. A JSON-RPC 2.0 server lets external tools drive the emulation: a client sends one request
+//! per line and receives one response per line, along with notifications of the events it subscribed to. The server
+//! listens on TCP, `HOST:PORT`, or on a Unix socket, `unix:PATH`.
+//!
+//! Every client is served on a thread of its own, which forwards the requests to the emulation thread as `Call`s. The
+//! emulation thread answers them between two frames, through the methods of this module that work on the machine and
+//! through its own for the ones controlling the emulation.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+
+use serde_json::{Map, Value};
+
+use prelude::*;
+use cpu::Cpu;
+use memory::MEMORY_SIZE;
+
+/// Largest number of instructions a single request may step, about a minute of emulated time at the default speed.
+pub const MAX_STEP_INSTRUCTIONS: u64 = 36_000;
+/// Largest number of frames a single request may step, a minute of emulated time.
+pub const MAX_STEP_FRAMES: u64 = 3_600;
+
+/// Address the server listens on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Address {
+    /// A TCP address, `HOST:PORT`.
+    Tcp(String),
+    /// The path of a Unix socket.
+    Unix(PathBuf),
+}
+
+impl Address {
+    /// Parse an address, `unix:PATH` for a Unix socket and `HOST:PORT` for TCP.
+    pub fn parse(address: &str) -> Address {
+        match address.strip_prefix("unix:") {
+            Some(path) => Address::Unix(PathBuf::from(path)),
+            None       => Address::Tcp(address.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Address::Tcp(ref address) => write!(f, "{}", address),
+            Address::Unix(ref path)   => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Events a client may subscribe to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A frame was completed, notified with its number and the state of the buzzer.
+    Frame,
+    /// A message was shown over the output, notified with its text.
+    Message,
+}
+
+impl Event {
+    /// Look up an event by its name.
+    pub fn from_name(name: &str) -> Option<Event> {
+        match name {
+            "frame"   => Some(Event::Frame),
+            "message" => Some(Event::Message),
+            _         => None,
+        }
+    }
+
+    /// Name of the event, which is also the method of its notifications.
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Frame   => "frame",
+            Event::Message => "message",
+        }
+    }
+}
+
+/// Error answering a request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    pub code: i64,
+    pub message: String,
+}
+
+impl Error {
+    /// The request is not valid JSON.
+    pub fn parse(why: &str) -> Error {
+        Error { code: -32700, message: format!("parse error: {}", why) }
+    }
+
+    /// The request is not a JSON-RPC request.
+    pub fn invalid_request(why: &str) -> Error {
+        Error { code: -32600, message: format!("invalid request: {}", why) }
+    }
+
+    /// The method does not exist.
+    pub fn method_not_found(method: &str) -> Error {
+        Error { code: -32601, message: format!("method not found: {}", method) }
+    }
+
+    /// The parameters of the method are missing or malformed.
+    pub fn invalid_params(why: &str) -> Error {
+        Error { code: -32602, message: format!("invalid params: {}", why) }
+    }
+
+    /// The method cannot be carried out.
+    pub fn failed(why: &str) -> Error {
+        Error { code: -32000, message: why.to_string() }
+    }
+}
+
+/// A connected client.
+#[derive(Clone)]
+pub struct Client {
+    id: u64,
+    // lines to write to the client
+    lines: Sender<String>,
+}
+
+impl Client {
+    /// Number identifying the client among the clients of the server.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Send a notification.
+    ///
+    /// # Returns
+    /// Returns `false` if the client is gone.
+    pub fn notify(&self, method: &str, params: Value) -> bool {
+        self.send(json!({"jsonrpc": "2.0", "method": method, "params": params}))
+    }
+
+    /// Send a response.
+    fn respond(&self, id: Value, result: Result<Value, Error>) -> bool {
+        self.send(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(error) => json!({"jsonrpc": "2.0", "id": id, "error": {"code": error.code, "message": error.message}}),
+        })
+    }
+
+    fn send(&self, message: Value) -> bool {
+        self.lines.send(message.to_string()).is_ok()
+    }
+}
+
+/// A request of a client, to be answered by the emulation thread.
+pub struct Call {
+    /// The client that sent the request.
+    pub client: Client,
+    /// Name of the method.
+    pub method: String,
+    /// Parameters of the method, an empty object if there are none.
+    pub params: Value,
+    // identifier of the request, absent for notifications, which are not answered
+    id: Option<Value>,
+}
+
+impl Call {
+    /// Parse a request.
+    ///
+    /// # Arguments
+    /// - `client`: the client that sent the request.
+    /// - `line`: the request.
+    ///
+    /// # Returns
+    /// Returns the call, or the error to answer with the identifier of the request if it is known.
+    fn parse(client: &Client, line: &str) -> Result<Call, (Value, Error)> {
+        let request: Value = serde_json::from_str(line).map_err(|why| (Value::Null, Error::parse(&why.to_string())))?;
+        let id = request.get("id").cloned();
+        let invalid = |why: &str| (id.clone().unwrap_or(Value::Null), Error::invalid_request(why));
+        if request.get("jsonrpc") != Some(&json!("2.0")) {
+            return Err(invalid(r#"expected "jsonrpc": "2.0""#));
+        }
+        let method = match request.get("method") {
+            Some(Value::String(method)) => method.clone(),
+            _                           => return Err(invalid("expected a method name")),
+        };
+        let params = match request.get("params") {
+            None                               => Value::Object(Map::new()),
+            Some(params) if params.is_object() => params.clone(),
+            Some(_)                            => return Err(invalid("expected named params")),
+        };
+        Ok(Call { client: client.clone(), method, params, id })
+    }
+
+    /// Answer the request, unless it is a notification.
+    pub fn answer(self, result: Result<Value, Error>) {
+        if let Some(id) = self.id {
+            self.client.respond(id, result);
+        }
+    }
+}
+
+/// Start listening, each client being served on a thread of its own.
+///
+/// # Returns
+/// Returns the channel the requests of the clients arrive on, and the address listened on, which tells the port when
+/// listening on port 0.
+pub fn serve(address: &Address) -> io::Result<(Receiver<Call>, Address)> {
+    let (calls, receiver) = channel();
+    let local = match *address {
+        Address::Tcp(ref address) => {
+            let listener = TcpListener::bind(address.as_str())?;
+            let local = Address::Tcp(listener.local_addr()?.to_string());
+            spawn("rpc", move || {
+                for (id, stream) in listener.incoming().filter_map(Result::ok).enumerate() {
+                    if let Ok(writer) = stream.try_clone() {
+                        accept(id as u64, Box::new(stream), Box::new(writer), calls.clone());
+                    }
+                }
+            })?;
+            local
+        },
+        #[cfg(unix)]
+        Address::Unix(ref path)   => {
+            // a socket left behind by an earlier server would prevent binding
+            if path.symlink_metadata().map(|metadata| metadata.file_type().is_socket()).unwrap_or(false) {
+                std::fs::remove_file(path)?;
+            }
+            let listener = UnixListener::bind(path)?;
+            spawn("rpc", move || {
+                for (id, stream) in listener.incoming().filter_map(Result::ok).enumerate() {
+                    if let Ok(writer) = stream.try_clone() {
+                        accept(id as u64, Box::new(stream), Box::new(writer), calls.clone());
+                    }
+                }
+            })?;
+            address.clone()
+        },
+        #[cfg(not(unix))]
+        Address::Unix(_)          => {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "Unix sockets are unavailable on this platform"));
+        },
+    };
+    Ok((receiver, local))
+}
+
+/// Serve a client: one thread reads its requests and forwards them, another writes the responses and notifications.
+fn accept(id: u64, reader: Box<dyn io::Read + Send>, mut writer: Box<dyn Write + Send>, calls: Sender<Call>) {
+    let (lines, outgoing) = channel::<String>();
+    let client = Client { id, lines };
+    let _ = spawn("rpc client", move || {
+        for line in outgoing {
+            if writeln!(writer, "{}", line).and_then(|_| writer.flush()).is_err() {
+                break;
+            }
+        }
+    });
+    let _ = spawn("rpc client", move || {
+        for line in BufReader::new(reader).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_)   => break,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Call::parse(&client, &line) {
+                Ok(call)         => if calls.send(call).is_err() {
+                    break;
+                },
+                Err((id, error)) => {
+                    client.respond(id, Err(error));
+                },
+            }
+        }
+    });
+}
+
+fn spawn<F: FnOnce() + Send + 'static>(name: &str, run: F) -> io::Result<()> {
+    thread::Builder::new().name(name.to_string()).spawn(run).map(|_| ())
+}
+
+/// Registers, stack and timers of the machine.
+pub fn registers<T: Input, U: Graphics>(cpu: &Cpu<T, U>) -> Value {
+    let state = cpu.state();
+    json!({
+        "pc": state.pc,
+        "i": state.i,
+        "v": state.v.to_vec(),
+        "sp": state.sp,
+        "stack": state.stack[..state.sp as usize].to_vec(),
+        "dt": state.dt,
+        "st": state.st,
+    })
+}
+
+/// Overwrite the registers given among `pc`, `i`, `v` (all sixteen, or an object of index to value), `dt` and `st`.
+pub fn set_registers<T: Input, U: Graphics>(cpu: &mut Cpu<T, U>, params: &Value) -> Result<Value, Error> {
+    let mut state = cpu.state();
+    if let Some(pc) = optional(params, "pc", MEMORY_SIZE as u64 - 2)? {
+        state.pc = pc as u16;
+    }
+    if let Some(i) = optional(params, "i", MEMORY_SIZE as u64 - 1)? {
+        state.i = i as u16;
+    }
+    match params.get("v") {
+        None                   => (),
+        Some(Value::Array(v))  => {
+            if v.len() != 16 {
+                return Err(Error::invalid_params("v should list all sixteen registers"));
+            }
+            for (x, value) in v.iter().enumerate() {
+                state.v[x] = byte(value, "v")?;
+            }
+        },
+        Some(Value::Object(v))  => for (x, value) in v {
+            let x = usize::from_str_radix(x.trim_start_matches('V').trim_start_matches('v'), 16)
+                .ok()
+                .filter(|&x| x < 16)
+                .ok_or_else(|| Error::invalid_params(&format!("unknown register V{}", x)))?;
+            state.v[x] = byte(value, "v")?;
+        },
+        Some(_)                => return Err(Error::invalid_params("v should be an array or an object")),
+    }
+    if let Some(dt) = optional(params, "dt", 0xFF)? {
+        state.dt = dt as u8;
+    }
+    if let Some(st) = optional(params, "st", 0xFF)? {
+        state.st = st as u8;
+    }
+    cpu.set_state(state);
+    Ok(registers(cpu))
+}
+
+/// Bytes of memory from `address` on, `length` of them.
+pub fn read_memory<T: Input, U: Graphics>(cpu: &Cpu<T, U>, params: &Value) -> Result<Value, Error> {
+    let address = required(params, "address", MEMORY_SIZE as u64 - 1)? as usize;
+    let length = optional(params, "length", (MEMORY_SIZE - address) as u64)?.unwrap_or(1) as usize;
+    let memory = &cpu.interconnect().memory;
+    let bytes: Vec<u8> = (address..address + length).map(|addr| memory.read(addr)).collect();
+    Ok(json!({"address": address, "bytes": bytes}))
+}
+
+/// Write `bytes` to memory from `address` on.
+pub fn write_memory<T: Input, U: Graphics>(cpu: &mut Cpu<T, U>, params: &Value) -> Result<Value, Error> {
+    let address = required(params, "address", MEMORY_SIZE as u64 - 1)? as usize;
+    let bytes = match params.get("bytes") {
+        Some(Value::Array(bytes)) => bytes.iter().map(|value| byte(value, "bytes")).collect::<Result<Vec<_>, _>>(),
+        _                         => Err(Error::invalid_params("expected bytes as an array")),
+    }?;
+    if address + bytes.len() > MEMORY_SIZE {
+        return Err(Error::invalid_params("the bytes do not fit in memory"));
+    }
+    let memory = &mut cpu.interconnect_mut().memory;
+    for (offset, &byte) in bytes.iter().enumerate() {
+        memory.write(address + offset, byte);
+    }
+    Ok(json!({"address": address, "written": bytes.len()}))
+}
+
+/// The keypad key of a request, `key`, as a number or a hex digit.
+pub fn key(params: &Value) -> Result<u8, Error> {
+    match params.get("key") {
+        Some(Value::String(key)) => u8::from_str_radix(key, 16).ok().filter(|&key| key < 16),
+        Some(key)                => key.as_u64().filter(|&key| key < 16).map(|key| key as u8),
+        None                     => None,
+    }.ok_or_else(|| Error::invalid_params("expected a key from 0 to F"))
+}
+
+/// The events of a subscription request, `events`.
+pub fn events(params: &Value) -> Result<Vec<Event>, Error> {
+    match params.get("events") {
+        Some(Value::Array(events)) => events.iter()
+            .map(|event| event.as_str().and_then(Event::from_name)
+                .ok_or_else(|| Error::invalid_params(&format!("unknown event {}", event))))
+            .collect(),
+        _                          => Err(Error::invalid_params("expected events as an array")),
+    }
+}
+
+/// A required integer parameter no greater than `max`.
+pub fn required(params: &Value, name: &str, max: u64) -> Result<u64, Error> {
+    optional(params, name, max)?.ok_or_else(|| Error::invalid_params(&format!("missing {}", name)))
+}
+
+/// An optional integer parameter no greater than `max`.
+pub fn optional(params: &Value, name: &str, max: u64) -> Result<Option<u64>, Error> {
+    match params.get(name) {
+        None        => Ok(None),
+        Some(value) => match value.as_u64() {
+            Some(number) if number <= max => Ok(Some(number)),
+            _                             => {
+                Err(Error::invalid_params(&format!("{} should be an integer from 0 to {}", name, max)))
+            },
+        },
+    }
+}
+
+/// A byte parameter.
+fn byte(value: &Value, name: &str) -> Result<u8, Error> {
+    value.as_u64()
+        .filter(|&byte| byte <= 0xFF)
+        .map(|byte| byte as u8)
+        .ok_or_else(|| Error::invalid_params(&format!("{} should hold bytes from 0 to 255", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use graphics::TestDisplay;
+    use input::TestKeyboard;
+    use interconnect::Interconnect;
+
+    fn client() -> (Client, Receiver<String>) {
+        let (lines, receiver) = channel();
+        (Client { id: 0, lines }, receiver)
+    }
+
+    fn cpu() -> Cpu<TestKeyboard, TestDisplay> {
+        Cpu::new(Interconnect::new_test(vec![0x12, 0x00]))
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(Address::parse("127.0.0.1:4000"), Address::Tcp("127.0.0.1:4000".to_string()));
+        assert_eq!(Address::parse("unix:/tmp/chip8.sock"), Address::Unix(PathBuf::from("/tmp/chip8.sock")));
+        assert_eq!(Address::parse("unix:/tmp/chip8.sock").to_string(), "unix:/tmp/chip8.sock");
+    }
+
+    #[test]
+    fn parses_requests() {
+        let (client, _) = client();
+        let call = Call::parse(&client, r#"{"jsonrpc": "2.0", "id": 3, "method": "step", "params": {"frames": 2}}"#)
+            .ok()
+            .unwrap();
+        assert_eq!((call.method.as_str(), &call.params, &call.id), ("step", &json!({"frames": 2}), &Some(json!(3))));
+        let call = Call::parse(&client, r#"{"jsonrpc": "2.0", "method": "pause"}"#).ok().unwrap();
+        assert_eq!((&call.params, &call.id), (&json!({}), &None));
+    }
+
+    #[test]
+    fn rejects_malformed_requests() {
+        let (client, _) = client();
+        let error = |line| Call::parse(&client, line).err().unwrap();
+        assert_eq!(error("{").1.code, -32700);
+        let expected = Error::invalid_request(r#"expected "jsonrpc": "2.0""#);
+        assert_eq!(error(r#"{"id": 1, "method": "pause"}"#), (json!(1), expected));
+        assert_eq!(error(r#"{"jsonrpc": "2.0", "id": 1, "method": "pause", "params": [1]}"#).1.code, -32600);
+    }
+
+    #[test]
+    fn answers_requests_but_not_notifications() {
+        let (client, lines) = client();
+        Call::parse(&client, r#"{"jsonrpc": "2.0", "id": "a", "method": "x"}"#).ok().unwrap()
+            .answer(Err(Error::method_not_found("x")));
+        Call::parse(&client, r#"{"jsonrpc": "2.0", "method": "x"}"#).ok().unwrap().answer(Ok(json!(null)));
+        let response: Value = serde_json::from_str(&lines.try_recv().unwrap()).unwrap();
+        let error = json!({"code": -32601, "message": "method not found: x"});
+        assert_eq!(response, json!({"jsonrpc": "2.0", "id": "a", "error": error}));
+        assert!(lines.try_recv().is_err(), "notifications are not answered");
+    }
+
+    #[test]
+    fn registers_round_trip() {
+        let mut cpu = cpu();
+        let registers = set_registers(&mut cpu, &json!({"pc": 0x200, "i": 0x300, "v": {"VA": 7}, "dt": 5})).unwrap();
+        assert_eq!((&registers["pc"], &registers["i"], &registers["dt"]), (&json!(0x200), &json!(0x300), &json!(5)));
+        assert_eq!(cpu.state().v[0xA], 7);
+        assert!(set_registers(&mut cpu, &json!({"pc": 0x1000})).is_err(), "the program counter stays in memory");
+        assert!(set_registers(&mut cpu, &json!({"i": 0x1000})).is_err(), "I stays in memory");
+        assert!(set_registers(&mut cpu, &json!({"v": [1, 2]})).is_err());
+    }
+
+    #[test]
+    fn memory_round_trip() {
+        let mut cpu = cpu();
+        write_memory(&mut cpu, &json!({"address": 0x300, "bytes": [1, 2, 3]})).unwrap();
+        let read = read_memory(&cpu, &json!({"address": 0x2FF, "length": 5})).unwrap();
+        assert_eq!(read["bytes"], json!([0, 1, 2, 3, 0]));
+        assert!(write_memory(&mut cpu, &json!({"address": 0xFFF, "bytes": [1, 2]})).is_err());
+        assert!(read_memory(&cpu, &json!({"address": 0xFFF, "length": 2})).is_err());
+    }
+
+    #[test]
+    fn keys_and_events() {
+        assert_eq!(key(&json!({"key": "c"})), Ok(0xC));
+        assert_eq!(key(&json!({"key": 4})), Ok(4));
+        assert!(key(&json!({"key": 16})).is_err());
+        assert_eq!(events(&json!({"events": ["frame", "message"]})), Ok(vec![Event::Frame, Event::Message]));
+        assert!(events(&json!({"events": ["draw"]})).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+//! Screenshot module. Exports the display as PNG images.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use png;
+
+use effects::Image;
+use prelude::*;
+
+/// Encode an image as PNG.
+pub fn write_png<W: Write>(writer: W, image: &Image) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(writer, image.width as u32, image.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.pixels)?;
+    Ok(())
+}
+
+/// Save the display as two PNG images, one at the native resolution and one scaled by `scale`.
+///
+/// # Arguments
+/// - `graphics`: the display to export, rendered with its active palette.
+/// - `dir`: the directory to write to, created if missing.
+/// - `rom`: the name of the running ROM, used as prefix of the file names.
+/// - `scale`: the factor the second image is scaled by.
+///
+/// # Returns
+/// Returns the paths of the native and the scaled image.
+pub fn save<G: Graphics + ?Sized>(graphics: &G, dir: &Path, rom: &str, scale: usize) -> io::Result<(PathBuf, PathBuf)> {
+    fs::create_dir_all(dir)?;
+    let stem = file_stem(rom, SystemTime::now());
+    let native = dir.join(format!("{}.png", stem));
+    let scaled = dir.join(format!("{}-x{}.png", stem, scale));
+    write_png(BufWriter::new(File::create(&native)?), &graphics.to_image(1))?;
+    write_png(BufWriter::new(File::create(&scaled)?), &graphics.to_image(scale))?;
+    Ok((native, scaled))
+}
+
+/// Name of a screenshot file without extension, made of the ROM name and the UTC time as `ROM-YYYYMMDD-HHMMSS-mmm`.
+pub fn file_stem(rom: &str, time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let secs_of_day = secs % 86_400;
+    format!("{}-{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}", rom, year, month, day,
+            secs_of_day / 3600, secs_of_day / 60 % 60, secs_of_day % 60, since_epoch.subsec_millis())
+}
+
+/// Convert days since 1970-01-01 to a `(year, month, day)` date of the proleptic Gregorian calendar.
+///
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use effects::Image;
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn file_stem_contains_rom_and_time() {
+        let time = UNIX_EPOCH + Duration::from_millis(951_782_400_000 + 3_723_042);
+        assert_eq!(file_stem("PONG", time), "PONG-20000229-010203-042");
+    }
+
+    #[test]
+    fn write_png_signature() {
+        let mut bytes = Vec::new();
+        write_png(&mut bytes, &Image::new(2, 2)).unwrap();
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+    }
+}
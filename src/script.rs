@@ -0,0 +1,429 @@
+//! Scripting module. Runs [Rhai](https://rhai.rs) scripts alongside the program, for tests, bots and trainers.
+//!
+//! A script defines any of the following hooks, called with `this` bound to the machine:
+//! - `on_frame()` at the end of every frame,
+//! - `on_pc(pc)` before every instruction,
+//! - `on_write(address, value)` after every byte an instruction wrote to memory,
+//! - `on_draw(x, y, rows, collision)` after every sprite drawn.
+//!
+//! Through `this`, the hooks read and write the registers with `this.v(x)`, `this.set_v(x, value)`, `this.i`,
+//! `this.pc`, `this.dt` and `this.st`, the memory with `this.read(address)` and `this.write(address, value)`, and the
+//! keypad with `this.key(k)`, `this.press(k)` and `this.release(k)`. They draw lines of text over the output with
+//! `this.text(row, text)` and `this.clear_text()`, keep values from one call to the next in the map `this.vars`, and
+//! stop the emulator with `this.quit()`. The statements outside of the hooks run once, when the script is loaded.
+//!
+//! Hooks on instructions make the CPU interpret every instruction, so that they see all of them.
+
+use std::fs;
+use std::mem;
+use std::path::Path;
+
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs, Map, Scope, AST, INT};
+
+use prelude::*;
+use cpu::{Cpu, State};
+use memory::MEMORY_SIZE;
+
+/// Largest number of operations a hook may run for, which stops scripts stuck in a loop.
+pub const MAX_OPERATIONS: u64 = 1_000_000;
+/// Number of lines of text a script can draw over the output.
+pub const TEXT_LINES: usize = 8;
+
+/// The hooks a script defines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Hooks {
+    frame: bool,
+    pc: bool,
+    write: bool,
+    draw: bool,
+}
+
+/// A loaded script.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    hooks: Hooks,
+    // whether a hook read memory, which is only copied for the hooks from then on
+    reads_memory: bool,
+    // values the hooks keep from one call to the next
+    vars: Map,
+    text: Vec<String>,
+    text_changed: bool,
+    quit_requested: bool,
+}
+
+impl Script {
+    /// Load a script from a file, running its statements outside of the hooks.
+    pub fn load(path: &Path) -> Result<Script, String> {
+        let source = fs::read_to_string(path).map_err(|why| why.to_string())?;
+        Script::compile(&source)
+    }
+
+    /// Compile a script, running its statements outside of the hooks.
+    pub fn compile(source: &str) -> Result<Script, String> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        Machine::register(&mut engine);
+        let ast = engine.compile(source).map_err(|why| why.to_string())?;
+        let mut hooks = Hooks::default();
+        for function in ast.iter_functions() {
+            let (hook, params) = match function.name {
+                "on_frame" => (&mut hooks.frame, 0),
+                "on_pc"    => (&mut hooks.pc, 1),
+                "on_write" => (&mut hooks.write, 2),
+                "on_draw"  => (&mut hooks.draw, 4),
+                _          => continue,
+            };
+            if function.params.len() != params {
+                return Err(format!("wrong number of parameters for {}, expected {}", function.name, params));
+            }
+            *hook = true;
+        }
+        let mut scope = Scope::new();
+        engine.run_ast_with_scope(&mut scope, &ast).map_err(|why| why.to_string())?;
+        Ok(Script {
+            engine,
+            ast,
+            scope,
+            hooks,
+            reads_memory: false,
+            vars: Map::new(),
+            text: Vec::new(),
+            text_changed: false,
+            quit_requested: false,
+        })
+    }
+
+    /// Checks if the script hooks into single instructions, which must then all be interpreted.
+    pub fn watches_instructions(&self) -> bool {
+        self.hooks.pc || self.hooks.write || self.hooks.draw
+    }
+
+    /// Checks if the script asked to stop the emulator.
+    pub fn quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+
+    /// Lines of text to draw over the output, if they changed since the last call.
+    pub fn take_text(&mut self) -> Option<Vec<String>> {
+        if mem::replace(&mut self.text_changed, false) {
+            Some(self.text.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Run the hook at the end of a frame.
+    pub fn end_frame<T: Input, U: Graphics>(&mut self, cpu: &mut Cpu<T, U>) -> Result<(), String> {
+        if self.hooks.frame {
+            self.call(cpu, "on_frame", ())?;
+        }
+        Ok(())
+    }
+
+    /// Execute the next instruction between the hooks on it. The instruction is executed even if a hook fails.
+    pub fn execute<T: Input, U: Graphics>(&mut self, cpu: &mut Cpu<T, U>) -> Result<(), String> {
+        let before = if self.hooks.pc { self.call(cpu, "on_pc", (cpu.pc() as INT,)) } else { Ok(()) };
+        // the hook may have moved the program counter
+        let opcode = cpu.interconnect().memory.read_word(cpu.pc() as usize);
+        let state = cpu.state();
+        cpu.process_opcode(opcode);
+        before?;
+        if self.hooks.write {
+            let x = (opcode >> 8 & 0xF) as usize;
+            let written = match opcode & 0xF0FF {
+                0xF033 => 3,
+                0xF055 => x + 1,
+                _      => 0,
+            };
+            for address in (state.i as usize..state.i as usize + written).filter(|&address| address < MEMORY_SIZE) {
+                let value = cpu.interconnect().memory.read(address);
+                self.call(cpu, "on_write", (address as INT, value as INT))?;
+            }
+        }
+        if self.hooks.draw && opcode & 0xF000 == 0xD000 {
+            let x = state.v[(opcode >> 8 & 0xF) as usize] as INT;
+            let y = state.v[(opcode >> 4 & 0xF) as usize] as INT;
+            let collision = cpu.state().v[0xF] == 1;
+            self.call(cpu, "on_draw", (x, y, (opcode & 0xF) as INT, collision))?;
+        }
+        Ok(())
+    }
+
+    /// Call a hook with `this` bound to the machine, applying its changes to the machine afterwards.
+    ///
+    /// Memory is only copied for the hook once a hook read it: until then, a hook reading memory is run again with a
+    /// copy, the changes of the first run being dropped.
+    fn call<T: Input, U: Graphics, A: FuncArgs + Clone>(&mut self, cpu: &mut Cpu<T, U>, hook: &str, args: A)
+        -> Result<(), String> {
+        let ram = if self.reads_memory { Some(cpu.interconnect().memory.ram().to_vec()) } else { None };
+        let vars = if self.reads_memory { mem::take(&mut self.vars) } else { self.vars.clone() };
+        let mut this = Dynamic::from(Machine::new(cpu, ram, vars, self.text.clone()));
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut this);
+        let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut self.scope, &self.ast, hook,
+                                                                 args.clone());
+        let mut machine = this.try_cast::<Machine>().ok_or_else(|| format!("{} replaced this", hook))?;
+        if machine.missed_read {
+            self.reads_memory = true;
+            return self.call(cpu, hook, args);
+        }
+        self.vars = mem::take(&mut machine.vars);
+        if machine.text != self.text {
+            self.text = mem::take(&mut machine.text);
+            self.text_changed = true;
+        }
+        self.quit_requested |= machine.quit;
+        machine.apply(cpu);
+        result.map(|_| ()).map_err(|why| format!("{}: {}", hook, why))
+    }
+}
+
+/// The machine as seen by a hook, a copy that is applied to the actual machine once the hook returns.
+#[derive(Clone)]
+struct Machine {
+    state: State,
+    // a copy of memory, only made for the scripts that read it
+    ram: Option<Vec<u8>>,
+    // whether the hook tried to read memory that was not copied
+    missed_read: bool,
+    keys: u16,
+    frame: u64,
+    // bytes written by the hook, in order
+    writes: Vec<(usize, u8)>,
+    vars: Map,
+    text: Vec<String>,
+    quit: bool,
+}
+
+impl Machine {
+    fn new<T: Input, U: Graphics>(cpu: &Cpu<T, U>, ram: Option<Vec<u8>>, vars: Map, text: Vec<String>) -> Machine {
+        Machine {
+            state: cpu.state(),
+            ram,
+            missed_read: false,
+            keys: Machine::keys(cpu),
+            frame: cpu.frames(),
+            writes: Vec::new(),
+            vars,
+            text,
+            quit: false,
+        }
+    }
+
+    /// Keys held down on the keypad, the lowest bit for key 0.
+    fn keys<T: Input, U: Graphics>(cpu: &Cpu<T, U>) -> u16 {
+        (0..16).filter(|&key| cpu.interconnect().input.is_key_down(key)).fold(0, |keys, key| keys | 1 << key)
+    }
+
+    /// Apply the changes of the hook to the actual machine.
+    fn apply<T: Input, U: Graphics>(self, cpu: &mut Cpu<T, U>) {
+        if self.state != cpu.state() {
+            cpu.set_state(self.state);
+        }
+        for (address, value) in self.writes {
+            cpu.interconnect_mut().memory.write(address, value);
+        }
+        if self.keys != Machine::keys(cpu) {
+            cpu.interconnect_mut().input.set_keys(self.keys);
+        }
+    }
+
+    /// Make the machine and its methods available to scripts.
+    fn register(engine: &mut Engine) {
+        engine.register_type_with_name::<Machine>("Machine")
+            .register_fn("v", |machine: &mut Machine, x: INT| -> Result<INT, Box<EvalAltResult>> {
+                Ok(machine.state.v[checked(x, 0xF, "register")?] as INT)
+            })
+            .register_fn("set_v", |machine: &mut Machine, x: INT, value: INT| -> Result<(), Box<EvalAltResult>> {
+                machine.state.v[checked(x, 0xF, "register")?] = checked(value, 0xFF, "value")? as u8;
+                Ok(())
+            })
+            .register_get_set("i", |machine: &mut Machine| machine.state.i as INT,
+                              |machine: &mut Machine, i: INT| -> Result<(), Box<EvalAltResult>> {
+                machine.state.i = checked(i, MEMORY_SIZE as INT - 1, "I")? as u16;
+                Ok(())
+            })
+            .register_get_set("pc", |machine: &mut Machine| machine.state.pc as INT,
+                              |machine: &mut Machine, pc: INT| -> Result<(), Box<EvalAltResult>> {
+                machine.state.pc = checked(pc, MEMORY_SIZE as INT - 2, "PC")? as u16;
+                Ok(())
+            })
+            .register_get_set("dt", |machine: &mut Machine| machine.state.dt as INT,
+                              |machine: &mut Machine, dt: INT| -> Result<(), Box<EvalAltResult>> {
+                machine.state.dt = checked(dt, 0xFF, "DT")? as u8;
+                Ok(())
+            })
+            .register_get_set("st", |machine: &mut Machine| machine.state.st as INT,
+                              |machine: &mut Machine, st: INT| -> Result<(), Box<EvalAltResult>> {
+                machine.state.st = checked(st, 0xFF, "ST")? as u8;
+                Ok(())
+            })
+            .register_get("frame", |machine: &mut Machine| machine.frame as INT)
+            .register_get_set("vars", |machine: &mut Machine| machine.vars.clone(),
+                              |machine: &mut Machine, vars: Map| machine.vars = vars)
+            .register_fn("read", |machine: &mut Machine, address: INT| -> Result<INT, Box<EvalAltResult>> {
+                let address = checked(address, MEMORY_SIZE as INT - 1, "address")?;
+                match machine.ram {
+                    Some(ref ram) => Ok(ram[address] as INT),
+                    None          => {
+                        // stop the hook, which is run again with a copy of memory
+                        machine.missed_read = true;
+                        Err("memory was not copied".into())
+                    },
+                }
+            })
+            .register_fn("write", |machine: &mut Machine, address: INT, value: INT| -> Result<(), Box<EvalAltResult>> {
+                let address = checked(address, MEMORY_SIZE as INT - 1, "address")?;
+                let value = checked(value, 0xFF, "value")? as u8;
+                if let Some(ref mut ram) = machine.ram {
+                    ram[address] = value;
+                }
+                machine.writes.push((address, value));
+                Ok(())
+            })
+            .register_fn("key", |machine: &mut Machine, key: INT| -> Result<bool, Box<EvalAltResult>> {
+                Ok(machine.keys >> checked(key, 0xF, "key")? & 1 == 1)
+            })
+            .register_fn("press", |machine: &mut Machine, key: INT| -> Result<(), Box<EvalAltResult>> {
+                machine.keys |= 1 << checked(key, 0xF, "key")?;
+                Ok(())
+            })
+            .register_fn("release", |machine: &mut Machine, key: INT| -> Result<(), Box<EvalAltResult>> {
+                machine.keys &= !(1 << checked(key, 0xF, "key")?);
+                Ok(())
+            })
+            .register_fn("text", |machine: &mut Machine, row: INT, text: &str| -> Result<(), Box<EvalAltResult>> {
+                let row = checked(row, TEXT_LINES as INT - 1, "row")?;
+                if machine.text.len() <= row {
+                    machine.text.resize(row + 1, String::new());
+                }
+                machine.text[row] = text.to_string();
+                // blank lines at the bottom take no room
+                while machine.text.last().is_some_and(String::is_empty) {
+                    machine.text.pop();
+                }
+                Ok(())
+            })
+            .register_fn("clear_text", |machine: &mut Machine| machine.text.clear())
+            .register_fn("quit", |machine: &mut Machine| machine.quit = true);
+    }
+}
+
+/// Check that a number passed by a script is within `0..=max`.
+fn checked(value: INT, max: INT, what: &str) -> Result<usize, Box<EvalAltResult>> {
+    if (0..=max).contains(&value) {
+        Ok(value as usize)
+    } else {
+        Err(format!("{} should be from 0 to {:#X}, not {}", what, max, value).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use graphics::TestDisplay;
+    use input::TestKeyboard;
+    use interconnect::Interconnect;
+
+    /// A machine running a program from 0x200, with a script loaded.
+    fn cpu(program: &[u8], source: &str) -> Cpu<TestKeyboard, TestDisplay> {
+        let mut cpu = Cpu::new(Interconnect::new_test(program.to_vec()));
+        cpu.set_script(Script::compile(source).unwrap());
+        cpu
+    }
+
+    #[test]
+    fn frame_hook_keeps_values_and_draws_text() {
+        let source = r#"
+            fn on_frame() {
+                this.vars.frames = (this.vars.frames ?? 0) + 1;
+                this.write(0x300, this.vars.frames);
+                this.text(1, `frame ${this.frame}`);
+                if this.vars.frames == 3 { this.quit(); }
+            }
+        "#;
+        let mut cpu = cpu(&[0x12, 0x00], source);
+        for _ in 0..3 {
+            cpu.execute_frame();
+        }
+        assert_eq!(cpu.interconnect().memory.read(0x300), 3);
+        let script = cpu.script_mut().unwrap();
+        assert!(script.quit_requested());
+        assert!(!script.watches_instructions());
+        assert_eq!(script.take_text(), Some(vec![String::new(), "frame 3".to_string()]));
+        assert_eq!(script.take_text(), None, "the text did not change since");
+    }
+
+    #[test]
+    fn pc_hook_runs_before_the_instruction() {
+        // LD V0, 0x01; JP 0x200
+        let source = "fn on_pc(pc) { if pc == 0x200 { this.set_v(1, this.v(0) + 1); this.press(0xA); } }";
+        let mut cpu = cpu(&[0x60, 0x01, 0x12, 0x00], source);
+        cpu.execute_cycle();
+        assert_eq!(cpu.state().v[..2], [1, 1], "V1 was set before V0 was loaded");
+        assert!(cpu.interconnect().input.is_key_down(0xA));
+        cpu.execute_cycle();
+        cpu.execute_cycle();
+        assert_eq!(cpu.state().v[1], 2);
+    }
+
+    #[test]
+    fn pc_hook_can_skip_instructions() {
+        // LD V0, 0x01; LD V0, 0x02
+        let mut cpu = cpu(&[0x60, 0x01, 0x60, 0x02], "fn on_pc(pc) { if pc == 0x200 { this.pc = 0x202; } }");
+        cpu.execute_cycle();
+        assert_eq!((cpu.state().v[0], cpu.pc()), (2, 0x204));
+    }
+
+    #[test]
+    fn hooks_read_memory() {
+        // JP 0x200
+        let source = r#"
+            fn on_frame() {
+                this.vars.calls = (this.vars.calls ?? 0) + 1;
+                this.write(0x300, this.read(0x200) + this.vars.calls);
+            }
+        "#;
+        let mut cpu = cpu(&[0x12, 0x00], source);
+        cpu.execute_frame();
+        assert_eq!(cpu.interconnect().memory.read(0x300), 0x13, "the hook changed nothing before reading memory");
+        cpu.execute_frame();
+        assert_eq!(cpu.interconnect().memory.read(0x300), 0x14);
+        assert!(cpu.script_mut().unwrap().reads_memory);
+    }
+
+    #[test]
+    fn write_and_draw_hooks_see_the_instruction() {
+        // LD I, 0x300; LD V0, 123; LD B, V0; DRW V0, V0, 3; DRW V0, V0, 3
+        let source = r#"
+            fn on_write(address, value) { this.vars.writes = (this.vars.writes ?? "") + `${address}=${value} `; }
+            fn on_draw(x, y, rows, collision) { this.text(0, `${x},${y},${rows},${collision} ${this.vars.writes}`); }
+        "#;
+        let mut cpu = cpu(&[0xA3, 0x00, 0x60, 0x7B, 0xF0, 0x33, 0xD0, 0x03, 0xD0, 0x03], source);
+        for _ in 0..5 {
+            cpu.execute_cycle();
+        }
+        let text = cpu.script_mut().unwrap().take_text().unwrap();
+        assert_eq!(text, vec!["123,123,3,true 768=1 769=2 770=3 ".to_string()]);
+    }
+
+    #[test]
+    fn failing_scripts_are_dropped() {
+        let mut failing = cpu(&[0x12, 0x00], "fn on_frame() { this.set_v(16, 0); }");
+        failing.execute_frame();
+        assert!(failing.script_mut().is_none());
+        let mut stuck = cpu(&[0x12, 0x00], "fn on_frame() { loop {} }");
+        stuck.execute_frame();
+        assert!(stuck.script_mut().is_none(), "scripts stuck in a loop are stopped");
+    }
+
+    #[test]
+    fn malformed_scripts_do_not_load() {
+        assert!(Script::compile("fn on_frame( {").is_err());
+        let error = Script::compile("fn on_pc() {}").err();
+        assert_eq!(error.as_deref(), Some("wrong number of parameters for on_pc, expected 1"));
+        assert!(Script::compile("throw \"at load\";").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Speed module. Contains the emulation speed controls: pause, frame advance, fast-forward, turbo and slow motion.
+//!
+//! The controls decide how many emulated frames are run on each 60 Hz tick of the frontend. Speeds below one are
+//! reached by skipping ticks, so timers and input keep their usual per-frame behaviour at every speed.
+
+/// Default speed while the fast-forward key is held.
+pub const DEFAULT_FAST_FORWARD: f32 = 4.0;
+/// Default speed while turbo is on.
+pub const DEFAULT_TURBO: f32 = 2.0;
+/// Default speed while slow motion is on.
+pub const DEFAULT_SLOW_MOTION: f32 = 0.25;
+
+/// State of the speed controls.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpeedControl {
+    fast_forward: f32,
+    turbo: f32,
+    slow_motion: f32,
+    paused: bool,
+    // whether a single frame should run although paused
+    advance: bool,
+    fast_forwarding: bool,
+    turbo_on: bool,
+    slow_motion_on: bool,
+    // fraction of a frame carried over to the next tick
+    budget: f32,
+}
+
+impl SpeedControl {
+    /// Constructor.
+    ///
+    /// # Arguments
+    /// - `fast_forward`: the speed multiplier while fast-forwarding.
+    /// - `turbo`: the speed multiplier while turbo is on.
+    /// - `slow_motion`: the speed multiplier while slow motion is on.
+    pub fn new(fast_forward: f32, turbo: f32, slow_motion: f32) -> SpeedControl {
+        SpeedControl {
+            fast_forward,
+            turbo,
+            slow_motion,
+            paused: false,
+            advance: false,
+            fast_forwarding: false,
+            turbo_on: false,
+            slow_motion_on: false,
+            budget: 0.0,
+        }
+    }
+
+    /// Checks if the emulation is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pause or resume the emulation.
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.advance = false;
+        self.budget = 0.0;
+    }
+
+    /// Run a single frame on the next tick. Does nothing unless paused.
+    pub fn advance_frame(&mut self) {
+        self.advance = self.paused;
+    }
+
+    /// Set whether the fast-forward key is held.
+    pub fn set_fast_forward(&mut self, held: bool) {
+        self.fast_forwarding = held;
+    }
+
+    /// Switch turbo on or off.
+    pub fn toggle_turbo(&mut self) {
+        self.turbo_on = !self.turbo_on;
+    }
+
+    /// Switch slow motion on or off.
+    pub fn toggle_slow_motion(&mut self) {
+        self.slow_motion_on = !self.slow_motion_on;
+    }
+
+    /// Current speed multiplier. Fast-forward takes precedence over turbo, which takes precedence over slow motion.
+    pub fn multiplier(&self) -> f32 {
+        if self.fast_forwarding {
+            self.fast_forward
+        } else if self.turbo_on {
+            self.turbo
+        } else if self.slow_motion_on {
+            self.slow_motion
+        } else {
+            1.0
+        }
+    }
+
+    /// Advance by one tick of the frontend.
+    ///
+    /// # Returns
+    /// Returns the number of emulated frames to run during this tick.
+    pub fn frames_to_run(&mut self) -> u32 {
+        if self.paused {
+            let frames = if self.advance { 1 } else { 0 };
+            self.advance = false;
+            return frames;
+        }
+        self.budget += self.multiplier();
+        let frames = self.budget.floor();
+        self.budget -= frames;
+        frames as u32
+    }
+}
+
+impl Default for SpeedControl {
+    fn default() -> SpeedControl {
+        SpeedControl::new(DEFAULT_FAST_FORWARD, DEFAULT_TURBO, DEFAULT_SLOW_MOTION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(speed: &mut SpeedControl, ticks: usize) -> Vec<u32> {
+        (0..ticks).map(|_| speed.frames_to_run()).collect()
+    }
+
+    #[test]
+    fn normal_speed_runs_a_frame_per_tick() {
+        let mut speed = SpeedControl::default();
+        assert_eq!(run(&mut speed, 3), vec![1, 1, 1]);
+    }
+
+    #[test]
+    fn pause_and_advance() {
+        let mut speed = SpeedControl::default();
+        speed.advance_frame();
+        assert_eq!(speed.frames_to_run(), 1, "advancing does nothing while running");
+
+        speed.toggle_pause();
+        assert_eq!(run(&mut speed, 2), vec![0, 0]);
+        speed.advance_frame();
+        assert_eq!(run(&mut speed, 2), vec![1, 0], "a single frame is advanced");
+
+        speed.toggle_pause();
+        assert_eq!(speed.frames_to_run(), 1);
+    }
+
+    #[test]
+    fn fast_forward_takes_precedence() {
+        let mut speed = SpeedControl::new(4.0, 2.0, 0.5);
+        speed.toggle_turbo();
+        assert_eq!(speed.frames_to_run(), 2);
+        speed.set_fast_forward(true);
+        assert_eq!(speed.frames_to_run(), 4);
+        speed.set_fast_forward(false);
+        assert_eq!(speed.frames_to_run(), 2, "turbo is back once fast-forward is released");
+    }
+
+    #[test]
+    fn slow_motion_skips_ticks() {
+        let mut speed = SpeedControl::new(4.0, 2.0, 0.25);
+        speed.toggle_slow_motion();
+        assert_eq!(run(&mut speed, 8), vec![0, 0, 0, 1, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn fractional_multipliers_average_out() {
+        let mut speed = SpeedControl::new(1.5, 2.0, 0.25);
+        speed.set_fast_forward(true);
+        assert_eq!(run(&mut speed, 4), vec![1, 2, 1, 2]);
+    }
+}
@@ -0,0 +1,208 @@
+//! Trace module. Logs every executed instruction with the machine state, one line per instruction.
+//!
+//! Each line shows the state before the instruction is executed. The `full` format is meant to be read, the
+//! `registers` format only contains fields every emulator has, so traces of other emulators are easily converted to
+//! it and diffed against ours.
+
+use std::io::{self, Write};
+use std::ops::RangeInclusive;
+
+use disassembler::disassemble;
+
+/// Layout of a trace line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// Cycle count, frame, registers and mnemonic, such as
+    /// `cycle=12 frame=1 pc=0206 op=6A02 V0=00 ... VF=00 I=02EA sp=0 dt=00 ; LD VA, 0x02`.
+    #[default]
+    Full,
+    /// Program counter, opcode and registers as plain hex, such as `0206 6A02 00 ... 00 02EA 0 00`.
+    Registers,
+}
+
+impl TraceFormat {
+    /// Look up a format by its name.
+    pub fn from_name(name: &str) -> Option<TraceFormat> {
+        match name {
+            "full"      => Some(TraceFormat::Full),
+            "registers" => Some(TraceFormat::Registers),
+            _           => None,
+        }
+    }
+}
+
+/// Machine state before an instruction is executed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// Number of instructions executed before this one.
+    pub cycle: u64,
+    /// Number of frames completed before this instruction.
+    pub frame: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub sp: u8,
+    pub dt: u8,
+}
+
+/// Instructions that are logged.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    /// Addresses of the logged instructions, all if absent.
+    pub addresses: Option<RangeInclusive<u16>>,
+    /// Frames the logged instructions are executed in, all if absent.
+    pub frames: Option<RangeInclusive<u64>>,
+}
+
+impl TraceFilter {
+    /// Checks if an instruction is logged.
+    pub fn matches(&self, entry: &TraceEntry) -> bool {
+        self.addresses.as_ref().is_none_or(|range| range.contains(&entry.pc))
+            && self.frames.as_ref().is_none_or(|range| range.contains(&entry.frame))
+    }
+
+    /// Checks if no instruction after the given frame can be logged anymore.
+    pub fn is_done(&self, frame: u64) -> bool {
+        self.frames.as_ref().is_some_and(|range| frame > *range.end())
+    }
+}
+
+/// Writes trace lines of the instructions passing a filter.
+pub struct Tracer {
+    writer: Box<dyn Write>,
+    format: TraceFormat,
+    filter: TraceFilter,
+}
+
+impl Tracer {
+    /// Constructor.
+    ///
+    /// # Arguments
+    /// - `writer`: the destination of the trace, preferably buffered.
+    /// - `format`: the layout of the lines.
+    /// - `filter`: the instructions to log.
+    pub fn new(writer: Box<dyn Write>, format: TraceFormat, filter: TraceFilter) -> Tracer {
+        Tracer { writer, format, filter }
+    }
+
+    /// Checks if no instruction after the given frame can be logged anymore.
+    pub fn is_done(&self, frame: u64) -> bool {
+        self.filter.is_done(frame)
+    }
+
+    /// Log an instruction if it passes the filter.
+    pub fn trace(&mut self, entry: &TraceEntry) -> io::Result<()> {
+        if !self.filter.matches(entry) {
+            return Ok(());
+        }
+        writeln!(self.writer, "{}", format_entry(entry, self.format))
+    }
+
+    /// Flush the buffered lines.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Format a trace line.
+pub fn format_entry(entry: &TraceEntry, format: TraceFormat) -> String {
+    match format {
+        TraceFormat::Full      => {
+            let registers: Vec<String> = entry.v.iter().enumerate()
+                .map(|(x, value)| format!("V{:X}={:02X}", x, value))
+                .collect();
+            format!("cycle={} frame={} pc={:04X} op={:04X} {} I={:04X} sp={:X} dt={:02X} ; {}",
+                    entry.cycle, entry.frame, entry.pc, entry.opcode, registers.join(" "), entry.i, entry.sp,
+                    entry.dt, disassemble(entry.opcode))
+        },
+        TraceFormat::Registers => {
+            let registers: Vec<String> = entry.v.iter().map(|value| format!("{:02X}", value)).collect();
+            format!("{:04X} {:04X} {} {:04X} {:X} {:02X}", entry.pc, entry.opcode, registers.join(" "), entry.i,
+                    entry.sp, entry.dt)
+        },
+    }
+}
+
+/// Parse an inclusive range `START-END` of numbers in the given radix. Either bound may be omitted to leave the range
+/// open on that side.
+pub fn parse_range(text: &str, radix: u32) -> Option<(Option<u64>, Option<u64>)> {
+    let mut bounds = text.splitn(2, '-');
+    let parse = |bound: &str| -> Option<Option<u64>> {
+        let bound = bound.trim().trim_start_matches("0x").trim_start_matches("0X");
+        if bound.is_empty() {
+            Some(None)
+        } else {
+            u64::from_str_radix(bound, radix).ok().map(Some)
+        }
+    };
+    let start = parse(bounds.next()?)?;
+    let end = parse(bounds.next()?)?;
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A writer appending to a buffer that stays accessible after the writer was boxed.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn entry(pc: u16, frame: u64) -> TraceEntry {
+        let mut v = [0; 16];
+        v[0xA] = 0x2A;
+        TraceEntry { cycle: 12, frame, pc, opcode: 0x6A02, v, i: 0x2EA, sp: 1, dt: 0x3C }
+    }
+
+    #[test]
+    fn full_format() {
+        assert_eq!(format_entry(&entry(0x206, 1), TraceFormat::Full),
+                   "cycle=12 frame=1 pc=0206 op=6A02 V0=00 V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00 V8=00 V9=00 \
+                    VA=2A VB=00 VC=00 VD=00 VE=00 VF=00 I=02EA sp=1 dt=3C ; LD VA, 0x02");
+    }
+
+    #[test]
+    fn registers_format() {
+        assert_eq!(format_entry(&entry(0x206, 1), TraceFormat::Registers),
+                   "0206 6A02 00 00 00 00 00 00 00 00 00 00 2A 00 00 00 00 00 02EA 1 3C");
+    }
+
+    #[test]
+    fn filters_addresses_and_frames() {
+        let buffer = SharedBuffer::default();
+        let filter = TraceFilter { addresses: Some(0x200..=0x2FF), frames: Some(2..=3) };
+        let mut tracer = Tracer::new(Box::new(buffer.clone()), TraceFormat::Registers, filter);
+        tracer.trace(&entry(0x206, 1)).unwrap();
+        tracer.trace(&entry(0x206, 2)).unwrap();
+        tracer.trace(&entry(0x306, 2)).unwrap();
+        tracer.trace(&entry(0x208, 3)).unwrap();
+        let text = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        let pcs: Vec<&str> = text.lines().map(|line| &line[..4]).collect();
+        assert_eq!(pcs, vec!["0206", "0208"]);
+        assert!(!tracer.is_done(3));
+        assert!(tracer.is_done(4));
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(parse_range("200-2FF", 16), Some((Some(0x200), Some(0x2FF))));
+        assert_eq!(parse_range("0x200-", 16), Some((Some(0x200), None)));
+        assert_eq!(parse_range("-100", 10), Some((None, Some(100))));
+        assert_eq!(parse_range("100", 10), None, "both bounds are separated by a dash");
+        assert_eq!(parse_range("1x-2", 10), None);
+    }
+}
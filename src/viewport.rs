@@ -0,0 +1,107 @@
+//! Viewport module. Computes where the emulated display is placed inside the window.
+
+/// How the emulated display is fitted into the window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Fit {
+    /// Scale by the largest whole factor that fits, keeping pixels square and equally sized.
+    #[default]
+    Integer,
+    /// Scale as large as possible while keeping the aspect ratio.
+    Aspect,
+}
+
+impl Fit {
+    /// Look up a fit mode by its name.
+    pub fn from_name(name: &str) -> Option<Fit> {
+        match name {
+            "integer" => Some(Fit::Integer),
+            "aspect"  => Some(Fit::Aspect),
+            _         => None,
+        }
+    }
+}
+
+/// Area of the window the emulated display is drawn to. The rest of the window is letterboxed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    /// Compute the viewport of a display centred in a window.
+    ///
+    /// # Arguments
+    /// - `window`: the size of the window in pixels.
+    /// - `resolution`: the emulated resolution.
+    /// - `fit`: how the display is scaled to the window.
+    pub fn new(window: (u32, u32), resolution: (u32, u32), fit: Fit) -> Viewport {
+        let (window_w, window_h) = window;
+        let (res_w, res_h) = resolution;
+        let (width, height) = match fit {
+            Fit::Integer => {
+                let scale = (window_w / res_w).min(window_h / res_h).max(1);
+                (res_w * scale, res_h * scale)
+            },
+            Fit::Aspect => {
+                // compare window_w / window_h against res_w / res_h without floating point
+                if window_w as u64 * res_h as u64 <= window_h as u64 * res_w as u64 {
+                    (window_w, (window_w as u64 * res_h as u64 / res_w as u64) as u32)
+                } else {
+                    ((window_h as u64 * res_w as u64 / res_h as u64) as u32, window_h)
+                }
+            },
+        };
+
+        Viewport {
+            x: (window_w as i32 - width as i32) / 2,
+            y: (window_h as i32 - height as i32) / 2,
+            width,
+            height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integer_fit_exact() {
+        let viewport = Viewport::new((640, 320), (64, 32), Fit::Integer);
+        assert_eq!(viewport, Viewport { x: 0, y: 0, width: 640, height: 320 });
+    }
+
+    #[test]
+    fn integer_fit_letterboxes() {
+        let viewport = Viewport::new((700, 400), (64, 32), Fit::Integer);
+        assert_eq!(viewport, Viewport { x: 30, y: 40, width: 640, height: 320 }, "scale 10 is centred");
+    }
+
+    #[test]
+    fn integer_fit_never_below_one() {
+        let viewport = Viewport::new((32, 16), (64, 32), Fit::Integer);
+        assert_eq!((viewport.width, viewport.height), (64, 32));
+    }
+
+    #[test]
+    fn aspect_fit_pillarboxes() {
+        let viewport = Viewport::new((1000, 300), (64, 32), Fit::Aspect);
+        assert_eq!(viewport, Viewport { x: 200, y: 0, width: 600, height: 300 });
+    }
+
+    #[test]
+    fn aspect_fit_letterboxes() {
+        let viewport = Viewport::new((700, 700), (64, 32), Fit::Aspect);
+        assert_eq!(viewport, Viewport { x: 0, y: 175, width: 700, height: 350 });
+    }
+
+    #[test]
+    fn resolution_change_keeps_size() {
+        let low = Viewport::new((1280, 640), (64, 32), Fit::Integer);
+        let high = Viewport::new((1280, 640), (128, 64), Fit::Integer);
+        assert_eq!(low, high, "hi-res output fills the same area");
+    }
+}
@@ -1,51 +1,375 @@
-//! Virtual machine module. Contains a machine that is being emulated.
+//! Virtual machine module. Contains a machine that is being emulated, and its windowless counterpart driven over the
+//! remote control server or by a script.
 
 use std;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Read};
 use std::path::Path;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use cpu::Cpu;
+use sdl2;
+
+use prelude::*;
+use config::Config;
+use cpu::{FRAMES_PER_SECOND, PLATFORM};
+use emulator::{Command, Emulator, Frame, Output, Tick};
 use input::Keyboard;
 use graphics::Display;
-use interconnect::Interconnect;
+use memory::{Memory, MEMORY_SIZE};
+use memory_viewer::{MemoryViewer, Registers};
+use netplay::{NetplayConfig, Peer, Session};
+use rpc::{self, Call};
+use osd::{Stats, StatsMeter};
+use recorder::{Recorder, STDOUT};
+
+/// Duration of a frame.
+pub const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / FRAMES_PER_SECOND as u64);
 
 
-/// A virtual machine emulating the CHIP-8.
+/// A virtual machine emulating the CHIP-8. The machine runs on the emulation thread, the virtual machine is its
+/// frontend: it forwards the keys, presents the frames, records them, and shows the memory viewer.
 pub struct VirtualMachine {
-    cpu: Cpu<Keyboard, Display>
+    display: Display,
+    keyboard: Keyboard,
+    config: Config,
+    recorder: Option<Recorder>,
+    // whether a recording was streamed to stdout, which holds a single video
+    recorded_to_stdout: bool,
+    stats: StatsMeter,
+    video: sdl2::VideoSubsystem,
+    // the viewer with a copy of the memory kept up to date with the writes reported by the emulation thread
+    memory_viewer: Option<(MemoryViewer, Memory)>,
+    // the state of the emulation as of the last tick
+    paused: bool,
+    registers: Registers,
+    fast_forward_held: bool,
+    commands: Sender<Command>,
+    outputs: Receiver<Output>,
+    emulation: Option<JoinHandle<()>>,
 }
 
 impl VirtualMachine {
     /// Constructor.
-    pub fn new(rom: &str) -> VirtualMachine {
-        let memory = VirtualMachine::get_bytes(rom);
-        let interconnect: Interconnect<Keyboard, Display> = Interconnect::new(memory);
-        let cpu = Cpu::new(interconnect);
-        VirtualMachine { cpu }
+    pub fn new(rom: &str, config: &Config) -> VirtualMachine {
+        let (bytes, memory) = VirtualMachine::get_bytes(rom);
+        let session = config.netplay.as_ref().map(|netplay| VirtualMachine::connect(netplay, &bytes, config));
+        let calls = VirtualMachine::serve(config);
+        let context = sdl2::init().unwrap();
+        let (commands, outputs, emulation) = Emulator::spawn(rom, memory, config, session, calls);
+        let mut vm = VirtualMachine {
+            display: Display::new(&context, config),
+            keyboard: Keyboard::new(&context),
+            config: config.clone(),
+            recorder: None,
+            recorded_to_stdout: false,
+            stats: StatsMeter::new(Instant::now()),
+            video: context.video().unwrap(),
+            memory_viewer: None,
+            paused: false,
+            registers: Registers { pc: 0, i: 0 },
+            fast_forward_held: false,
+            commands,
+            outputs,
+            emulation: Some(emulation),
+        };
+        if config.record {
+            vm.toggle_recording();
+        }
+        vm
     }
 
-    /// Run the VM.
+    /// Run the emulation without a window until a remote control client or the script stops it, printing the
+    /// messages and the script text it shows, to stderr if the trace is written to stdout.
+    pub fn run_headless(rom: &str, config: &Config) {
+        let (_, memory) = VirtualMachine::get_bytes(rom);
+        let calls = VirtualMachine::serve(config);
+        // the emulation stops when the frontend hangs up, so the commands are kept open although none are sent
+        let (_commands, outputs, emulation) = Emulator::spawn(rom, memory, config, None, calls);
+        // the trace written to stdout takes it whole
+        let print = |text: &str| if config.traces_to_stdout() { eprintln!("{}", text) } else { println!("{}", text) };
+        for output in outputs {
+            match output {
+                Output::Message(text)     => print(&text),
+                Output::ScriptText(lines) => for line in lines.iter().filter(|line| !line.is_empty()) {
+                    print(line);
+                },
+                _                         => (),
+            }
+        }
+        if emulation.join().is_err() {
+            eprintln!("The emulation stopped unexpectedly");
+        }
+    }
+
+    /// Run the frontend until a close is requested, presenting the display once per tick of the emulation.
     pub fn run(&mut self) {
-        self.cpu.run();
+        loop {
+            self.keyboard.handle_inputs();
+            self.forward_keys();
+            self.handle_hotkeys();
+            if self.keyboard.close_requested() || !self.handle_outputs() {
+                break
+            }
+            self.display.present();
+            self.update_memory_viewer();
+        }
+        if self.recorder.is_some() {
+            self.toggle_recording();
+        }
+        self.stop_emulation();
     }
 
-    /// Get binary from storage
-    fn get_bytes<P: AsRef<Path>>(path: P) -> Vec<u8> {
-        let mut buffer: Vec<u8> = Vec::new();
+    /// Stop the emulation thread and wait for it to write the profile and the trace.
+    fn stop_emulation(&mut self) {
+        let _ = self.commands.send(Command::Quit);
+        if let Some(emulation) = self.emulation.take() {
+            if emulation.join().is_err() {
+                eprintln!("The emulation stopped unexpectedly");
+            }
+        }
+    }
+
+    /// Send the keypad keys pressed and released since the last call, and the fast-forward key if it changed.
+    fn forward_keys(&mut self) {
+        while let Some((key, pressed)) = self.keyboard.next_key_event() {
+            let _ = self.commands.send(Command::Key(key, pressed));
+        }
+        let held = self.keyboard.fast_forward_held();
+        if held != self.fast_forward_held {
+            self.fast_forward_held = held;
+            let _ = self.commands.send(Command::FastForward(held));
+        }
+    }
+
+    /// Handle the outputs of the emulation thread until the end of its next tick, waiting for one frame at most, and
+    /// catch up with the outputs queued up meanwhile.
+    ///
+    /// # Returns
+    /// Returns `false` if the emulation stopped.
+    fn handle_outputs(&mut self) -> bool {
+        let deadline = Instant::now() + FRAME_DURATION;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.outputs.recv_timeout(timeout) {
+                Ok(output)                          => if self.handle_output(output) {
+                    break;
+                },
+                Err(RecvTimeoutError::Timeout)      => break,
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+        }
+        while let Ok(output) = self.outputs.try_recv() {
+            self.handle_output(output);
+        }
+        true
+    }
+
+    /// Handle an output of the emulation thread.
+    ///
+    /// # Returns
+    /// Returns `true` if the output ends a tick.
+    fn handle_output(&mut self, output: Output) -> bool {
+        match output {
+            Output::Frame(frame)      => self.handle_frame(frame),
+            Output::Tick(tick)        => {
+                self.handle_tick(tick);
+                return true;
+            },
+            Output::Memory(memory)    => if let Some((_, ref mut copy)) = self.memory_viewer {
+                *copy = *memory;
+            },
+            Output::Message(text)     => self.show_message(&text),
+            Output::ScriptText(lines) => self.display.set_script_text(&lines),
+        }
+        false
+    }
+
+    /// Show a completed frame, recording it.
+    fn handle_frame(&mut self, frame: Frame) {
+        self.display.framebuffer_mut().clone_from(&frame.framebuffer);
+        self.record_frame(frame.buzzer);
+    }
+
+    /// Take note of the end of a tick of the emulation thread.
+    fn handle_tick(&mut self, tick: Tick) {
+        self.paused = tick.paused;
+        self.registers = tick.registers;
+        if let Some((_, ref mut memory)) = self.memory_viewer {
+            for (addr, byte) in tick.writes {
+                memory.write(addr, byte);
+            }
+        }
+        self.update_stats(tick.frames, tick.cycles_per_frame);
+    }
+
+    /// Handle the emulator hotkeys pressed since the last frame.
+    fn handle_hotkeys(&mut self) {
+        while let Some(hotkey) = self.keyboard.next_hotkey() {
+            match hotkey {
+                Hotkey::CycleTheme         => self.display.cycle_theme(),
+                Hotkey::ToggleFullscreen   => self.display.toggle_fullscreen(),
+                Hotkey::Screenshot         => self.display.screenshot(),
+                Hotkey::ToggleRecording    => self.toggle_recording(),
+                Hotkey::ToggleStats        => self.display.toggle_stats(),
+                Hotkey::ToggleMemoryViewer => self.toggle_memory_viewer(),
+                Hotkey::TogglePause | Hotkey::AdvanceFrame | Hotkey::ToggleTurbo | Hotkey::ToggleSlowMotion |
+                Hotkey::Reset              => {
+                    let _ = self.commands.send(Command::Hotkey(hotkey));
+                },
+            }
+        }
+        if self.keyboard.take_redraw_request() {
+            self.display.refresh();
+        }
+    }
+
+    /// Count the presented frame and the instructions executed during it, updating the statistics once per second.
+    fn update_stats(&mut self, frames: u32, cycles_per_frame: u32) {
+        let instructions = frames as u64 * cycles_per_frame as u64;
+        if let Some((fps, ips)) = self.stats.tick(Instant::now(), instructions) {
+            self.display.set_stats(Stats { fps, ips, platform: PLATFORM });
+        }
+    }
+
+    /// Show a transient message over the output.
+    fn show_message(&mut self, text: &str) {
+        self.display.show_message(text);
+    }
+
+    /// Open the memory viewer, or close the open one.
+    fn toggle_memory_viewer(&mut self) {
+        if self.memory_viewer.take().is_some() {
+            self.keyboard.set_debug_window(None);
+            let _ = self.commands.send(Command::WatchMemory(false));
+            return;
+        }
+        match MemoryViewer::open(&self.video) {
+            Ok(viewer) => {
+                self.keyboard.set_debug_window(Some(viewer.window_id()));
+                // blank until the emulation thread sends the memory
+                self.memory_viewer = Some((viewer, Memory::new(Vec::new()).expect("an empty program fits in memory")));
+                let _ = self.commands.send(Command::WatchMemory(true));
+            },
+            Err(why)   => eprintln!("Cannot open memory viewer: {}", why),
+        }
+    }
+
+    /// Handle the keys pressed in the memory viewer, sending the edits to the emulation thread, and present it.
+    fn update_memory_viewer(&mut self) {
+        if let Some((ref mut viewer, ref mut memory)) = self.memory_viewer {
+            let editable = self.paused;
+            while let Some(keycode) = self.keyboard.next_debug_key() {
+                let before = memory.clone();
+                viewer.handle_key(keycode, memory, self.registers, editable);
+                for addr in (0..MEMORY_SIZE).filter(|&addr| memory.read(addr) != before.read(addr)) {
+                    let _ = self.commands.send(Command::WriteMemory(addr, memory.read(addr)));
+                }
+            }
+            viewer.present(memory, self.registers, editable);
+        }
+    }
+
+    /// Start a recording, or stop the running one.
+    fn toggle_recording(&mut self) {
+        match self.recorder.take() {
+            Some(recorder) => match recorder.finish() {
+                Ok(location) => {
+                    eprintln!("Saved recording to {}", location);
+                    self.show_message("Recording saved");
+                },
+                Err(why)     => {
+                    eprintln!("Cannot save recording: {}", why);
+                    self.show_message("Recording failed");
+                },
+            },
+            None           => {
+                let config = &self.config;
+                let to_stdout = config.record_dir == Path::new(STDOUT);
+                if to_stdout && self.recorded_to_stdout {
+                    eprintln!("Cannot start recording: stdout already holds a video, record to a directory instead");
+                    self.show_message("Recording failed");
+                    return;
+                }
+                match Recorder::start(config.record_format, &config.record_dir, &config.rom_name) {
+                    Ok(recorder) => {
+                        self.recorder = Some(recorder);
+                        self.recorded_to_stdout |= to_stdout;
+                        self.show_message("Recording");
+                    },
+                    Err(why)     => eprintln!("Cannot start recording: {}", why),
+                }
+            },
+        }
+    }
+
+    /// Record the frame that was just completed, stopping the recording if it fails.
+    fn record_frame(&mut self, buzzer: bool) {
+        if let Some(ref mut recorder) = self.recorder {
+            let image = self.display.to_image(1);
+            if let Err(why) = recorder.record_frame(&image, buzzer) {
+                eprintln!("Cannot record frame: {}", why);
+                self.recorder = None;
+            }
+        }
+    }
+
+    /// Start the two-player game, exiting if the other player cannot be reached or runs a different game.
+    fn connect(netplay: &NetplayConfig, rom: &[u8], config: &Config) -> Session {
+        match netplay.peer {
+            Peer::Host(port)        => eprintln!("Waiting for the other player on port {}", port),
+            Peer::Join(ref address) => eprintln!("Joining the game at {}", address),
+        }
+        match Session::connect(netplay, rom, config.quirks, config.cycles_per_frame) {
+            Ok(session) => {
+                eprintln!("Playing as the {}", if session.is_host() { "host" } else { "guest" });
+                session
+            },
+            Err(why)    => {
+                eprintln!("Cannot start the two-player game: {}", why);
+                std::process::exit(1);
+            },
+        }
+    }
+
+    /// Start the remote control server if enabled, exiting if it cannot listen.
+    fn serve(config: &Config) -> Option<Receiver<Call>> {
+        config.rpc.as_ref().map(|address| match rpc::serve(address) {
+            Ok((calls, local)) => {
+                eprintln!("Remote control listening on {}", local);
+                calls
+            },
+            Err(why)           => {
+                eprintln!("Cannot listen on {}: {}", address, why);
+                std::process::exit(1);
+            },
+        })
+    }
+
+    /// Get binary from storage and load it into memory, exiting if it cannot be read or does not fit.
+    ///
+    /// # Returns
+    /// Returns the binary and the memory it is loaded into.
+    fn get_bytes<P: AsRef<Path>>(path: P) -> (Vec<u8>, Memory) {
         let filename = format!("{}", path.as_ref().display());
 
         // Open and read the file if it exists.
-        match File::open(path) {
-            Ok(ref mut file)    => {
-                file.read_to_end(&mut buffer).unwrap();
-            },
+        let loaded = VirtualMachine::read_rom(path).map_err(|why| why.to_string())
+            .and_then(|buffer| Ok((buffer.clone(), Memory::new(buffer)?)));
+        match loaded {
+            Ok(loaded)          => loaded,
             Err(why)            => {
-                println!("Cannot open '{}': {}", filename, why);
+                eprintln!("Cannot open '{}': {}", filename, why);
                 std::process::exit(1);
             },
-        };
+        }
+    }
 
-        buffer
+    /// Read a binary from storage.
+    fn read_rom<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+        let mut buffer: Vec<u8> = Vec::new();
+        File::open(path)?.read_to_end(&mut buffer)?;
+        Ok(buffer)
     }
 }
@@ -0,0 +1,384 @@
+//! Conformance test suite. Runs test ROMs headlessly under every quirk profile. The ROMs are assembled by the runner
+//! itself, the community CHIP-8 test suite is not bundled. Each ROM performs a series of checks and draws a tick or a
+//! cross per check in a grid on the display, like the community ROMs do; the runner reads the outcome back off the
+//! display by matching the glyph bitmaps.
+//!
+//! The quirk checks expect the behaviour of the machine each profile stands for, listed in `MACHINES` independently of
+//! the `Quirks` presets, so that a wrong preset fails them.
+//!
+//! The resulting pass/fail matrix is compared against `tests/conformance/matrix.txt`, so failures of known bugs are
+//! recorded there instead of failing the build, while any change in conformance does fail it. The matrix is printed
+//! with
+//!
+//! ```sh
+//! cargo test --test conformance -- --nocapture
+//! ```
+//!
+//! and re-blessed after an intended change with `CHIP8_BLESS=1 cargo test --test conformance`.
+
+extern crate chip_8;
+
+use std::env;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use chip_8::cpu::Cpu;
+use chip_8::graphics::Graphics;
+use chip_8::interconnect::Interconnect;
+use chip_8::quirks::{Quirks, PROFILES};
+
+/// Address programs are loaded at.
+const START: u16 = 0x200;
+/// Tick drawn by passing checks.
+const PASS: [u8; 5] = [0x01, 0x02, 0x84, 0x48, 0x30];
+/// Cross drawn by failing checks.
+const FAIL: [u8; 5] = [0x88, 0x50, 0x20, 0x50, 0x88];
+/// Address of the tick, the ROM starts with a jump over its data.
+const PASS_ADDR: u16 = START + 2;
+/// Address of the cross.
+const FAIL_ADDR: u16 = PASS_ADDR + 5;
+/// Address of four bytes of scratch memory.
+const SCRATCH: u16 = FAIL_ADDR + 5;
+/// Address of the first instruction after the data.
+const CODE: u16 = SCRATCH + 4;
+/// Size of a cell of the result grid.
+const CELL_WIDTH: usize = 8;
+const CELL_HEIGHT: usize = 6;
+/// Cells per row of the result grid.
+const COLUMNS: usize = 8;
+/// Maximum number of checks per ROM, leaving the last display row free for the drawing checks.
+const MAX_CHECKS: usize = COLUMNS * 5;
+/// Frames a ROM may run for before it is considered stuck.
+const MAX_FRAMES: u32 = 600;
+/// Key held down while the keypad ROM runs.
+const HELD_KEY: u8 = 0x5;
+/// Frames the held key is released, pressed again and released again in, long after the checks before `FX0A` ran, as
+/// `FX0A` only takes a key pressed while it waits, and with a quirk only once released.
+const KEY_EVENTS: [(u32, bool); 3] = [
+    (MAX_FRAMES / 2, false),
+    (MAX_FRAMES / 2 + 10, true),
+    (MAX_FRAMES / 2 + 20, false),
+];
+/// Environment variable requesting the matrix to be rewritten instead of compared.
+const BLESS: &str = "CHIP8_BLESS";
+
+/// Behaviour of a machine on the instructions interpreters disagree on.
+struct Machine {
+    vf_reset: bool,
+    shift_vy: bool,
+    load_store_increment: bool,
+    jump_vx: bool,
+    clip: bool,
+}
+
+/// The machine each profile stands for.
+const MACHINES: [(&str, Machine); 3] = [
+    // modern interpreters, as documented for this emulator: shifting in place and wrapping sprites around
+    ("default", Machine { vf_reset: false, shift_vy: false, load_store_increment: false, jump_vx: false, clip: false }),
+    // the original COSMAC VIP interpreter
+    ("cosmac",  Machine { vf_reset: true, shift_vy: true, load_store_increment: true, jump_vx: false, clip: true }),
+    // SUPER-CHIP 1.1 on the HP 48
+    ("schip",   Machine { vf_reset: false, shift_vy: false, load_store_increment: false, jump_vx: true, clip: true }),
+];
+
+/// A test ROM under construction. VC holds the value a check compares, VD and VE the position of the next cell.
+struct Rom {
+    bytes: Vec<u8>,
+    checks: Vec<&'static str>,
+}
+
+impl Rom {
+    fn new() -> Rom {
+        let mut rom = Rom { bytes: Vec::new(), checks: Vec::new() };
+        rom.op(0x1000 | CODE);
+        rom.bytes.extend_from_slice(&PASS);
+        rom.bytes.extend_from_slice(&FAIL);
+        rom.bytes.extend_from_slice(&[0; 4]);
+        rom.op(0x00E0).op(0x6D00).op(0x6E00);
+        rom
+    }
+
+    /// Address of the next instruction.
+    fn addr(&self) -> u16 {
+        START + self.bytes.len() as u16
+    }
+
+    fn op(&mut self, opcode: u16) -> &mut Rom {
+        self.bytes.push((opcode >> 8) as u8);
+        self.bytes.push(opcode as u8);
+        self
+    }
+
+    fn ops(&mut self, opcodes: &[u16]) -> &mut Rom {
+        for &opcode in opcodes {
+            self.op(opcode);
+        }
+        self
+    }
+
+    /// Draw a tick if register `reg` holds `expected`, a cross otherwise, and move on to the next cell.
+    fn check(&mut self, name: &'static str, reg: u16, expected: u8) -> &mut Rom {
+        assert!(self.checks.len() < MAX_CHECKS, "too many checks");
+        self.checks.push(name);
+        // LD VC, Vr; LD I, cross; SNE VC, expected; LD I, tick; DRW VD, VE, 5; ADD VD, 8
+        self.ops(&[0x8C00 | reg << 4, 0xA000 | FAIL_ADDR, 0x4C00 | expected as u16, 0xA000 | PASS_ADDR, 0xDDE5,
+                   0x7D00 | CELL_WIDTH as u16]);
+        if self.checks.len().is_multiple_of(COLUMNS) {
+            self.ops(&[0x6D00, 0x7E00 | CELL_HEIGHT as u16]);
+        }
+        self
+    }
+
+    /// Set VC to 1 unless the previous instruction skips, so a check of VC against 0 tests that it does.
+    fn skipped(&mut self) -> &mut Rom {
+        self.op(0x6C01)
+    }
+
+    /// End the ROM with an endless loop, returning it along with the address of the loop.
+    fn finish(&self) -> (Vec<u8>, u16) {
+        let halt = self.addr();
+        let mut bytes = self.bytes.clone();
+        bytes.extend_from_slice(&[0x10 | (halt >> 8) as u8, halt as u8]);
+        (bytes, halt)
+    }
+}
+
+/// Instructions and their results.
+fn opcodes(_: &Machine) -> Rom {
+    let mut rom = Rom::new();
+    rom.ops(&[0x602A]).check("6XKK LD", 0, 0x2A);
+    rom.ops(&[0x60FF, 0x7002]).check("7XKK ADD wraps", 0, 0x01);
+    rom.ops(&[0x6133, 0x8010]).check("8XY0 LD", 0, 0x33);
+    rom.ops(&[0x600C, 0x610A, 0x8011]).check("8XY1 OR", 0, 0x0E);
+    rom.ops(&[0x600C, 0x610A, 0x8012]).check("8XY2 AND", 0, 0x08);
+    rom.ops(&[0x600C, 0x610A, 0x8013]).check("8XY3 XOR", 0, 0x06);
+    rom.ops(&[0x60F0, 0x6120, 0x8014]).check("8XY4 ADD", 0, 0x10);
+    rom.ops(&[0x600A, 0x6105, 0x8015]).check("8XY5 SUB", 0, 0x05);
+    rom.ops(&[0x6006, 0x6106, 0x8016]).check("8XY6 SHR", 0, 0x03);
+    rom.ops(&[0x6005, 0x610A, 0x8017]).check("8XY7 SUBN", 0, 0x05);
+    rom.ops(&[0x6021, 0x6121, 0x801E]).check("8XYE SHL", 0, 0x42);
+    rom.ops(&[0x6C00, 0x6005, 0x3005]).skipped().check("3XKK SE skips", 0xC, 0);
+    rom.ops(&[0x6C00, 0x6005, 0x3006]).skipped().check("3XKK SE falls through", 0xC, 1);
+    rom.ops(&[0x6C00, 0x6005, 0x4006]).skipped().check("4XKK SNE skips", 0xC, 0);
+    rom.ops(&[0x6C00, 0x6005, 0x6105, 0x5010]).skipped().check("5XY0 SE skips", 0xC, 0);
+    rom.ops(&[0x6C00, 0x6005, 0x6106, 0x9010]).skipped().check("9XY0 SNE skips", 0xC, 0);
+    rom.op(0x6C00);
+    let target = rom.addr() + 4;
+    rom.op(0x1000 | target).skipped().check("1NNN JP", 0xC, 0);
+    rom.op(0x6C00);
+    let subroutine = rom.addr() + 6;
+    let after = subroutine + 4;
+    rom.ops(&[0x2000 | subroutine, 0x7C01, 0x1000 | after, 0x7C01, 0x00EE]).check("2NNN 00EE CALL RET", 0xC, 2);
+    rom.ops(&[0xA000 | SCRATCH, 0x60AB, 0xF055, 0x6000, 0xA000 | SCRATCH, 0xF065])
+        .check("FX55 FX65 LD [I]", 0, 0xAB);
+    rom.ops(&[0x6001, 0x6102, 0x6203, 0xA000 | SCRATCH, 0xF255, 0x6000, 0x6100, 0x6200, 0xA000 | SCRATCH, 0xF265])
+        .check("FX55 FX65 up to VX", 2, 0x03);
+    rom.ops(&[0xA000 | SCRATCH, 0x60AB, 0xF055, 0xA000 | (SCRATCH - 5), 0x6005, 0xF01E, 0xF065])
+        .check("FX1E ADD I", 0, 0xAB);
+    rom.ops(&[0x600A, 0xF029, 0xF065]).check("FX29 LD F", 0, 0xF0);
+    rom.ops(&[0x60FE, 0xA000 | SCRATCH, 0xF033, 0xA000 | SCRATCH, 0xF265]).check("FX33 BCD hundreds", 0, 2)
+        .check("FX33 BCD tens", 1, 5)
+        .check("FX33 BCD ones", 2, 4);
+    rom.ops(&[0x6020, 0xF015]);
+    let wait = rom.addr();
+    rom.ops(&[0xF107, 0x3100, 0x1000 | wait]).check("FX15 FX07 timer", 1, 0);
+    rom.ops(&[0xC0FF, 0xC00F, 0x61F0, 0x8012]).check("CXKK RND mask", 0, 0);
+    // the drawing checks use the last row of the display, which the result grid leaves free
+    rom.ops(&[0x6020, 0x611F, 0xA000 | PASS_ADDR, 0xD011]).check("DXYN draws", 0xF, 0);
+    rom.op(0xD011).check("DXYN collides", 0xF, 1);
+    rom
+}
+
+/// VF after arithmetic instructions.
+fn flags(_: &Machine) -> Rom {
+    let mut rom = Rom::new();
+    rom.ops(&[0x60F0, 0x6120, 0x8014]).check("8XY4 carry", 0xF, 1);
+    rom.ops(&[0x6010, 0x6120, 0x8014]).check("8XY4 no carry", 0xF, 0);
+    rom.ops(&[0x6FF0, 0x6120, 0x8F14]).check("8XY4 carry into VF", 0xF, 1);
+    rom.ops(&[0x600A, 0x6105, 0x8015]).check("8XY5 no borrow", 0xF, 1);
+    rom.ops(&[0x6005, 0x610A, 0x8015]).check("8XY5 borrow", 0xF, 0);
+    rom.ops(&[0x6005, 0x610A, 0x8015]).check("8XY5 borrow result", 0, 0xFB);
+    rom.ops(&[0x6005, 0x6105, 0x8016]).check("8XY6 shifted out 1", 0xF, 1);
+    rom.ops(&[0x6004, 0x6104, 0x8016]).check("8XY6 shifted out 0", 0xF, 0);
+    rom.ops(&[0x6005, 0x610A, 0x8017]).check("8XY7 no borrow", 0xF, 1);
+    rom.ops(&[0x600A, 0x6105, 0x8017]).check("8XY7 borrow", 0xF, 0);
+    rom.ops(&[0x600A, 0x6105, 0x8017]).check("8XY7 borrow result", 0, 0xFB);
+    rom.ops(&[0x6081, 0x6181, 0x801E]).check("8XYE shifted out 1", 0xF, 1);
+    rom.ops(&[0x6041, 0x6141, 0x801E]).check("8XYE shifted out 0", 0xF, 0);
+    rom
+}
+
+/// Key instructions while `HELD_KEY` is held down.
+fn keypad(_: &Machine) -> Rom {
+    let held = 0x6000 | HELD_KEY as u16;
+    let mut rom = Rom::new();
+    rom.ops(&[0x6C00, held, 0xE09E]).skipped().check("EX9E SKP held", 0xC, 0);
+    rom.ops(&[0x6C00, held + 1, 0xE09E]).skipped().check("EX9E SKP released", 0xC, 1);
+    rom.ops(&[0x6C00, held, 0xE0A1]).skipped().check("EXA1 SKNP held", 0xC, 1);
+    rom.ops(&[0x6C00, held + 1, 0xE0A1]).skipped().check("EXA1 SKNP released", 0xC, 0);
+    rom.ops(&[0x6000, 0xF00A]).check("FX0A LD K", 0, HELD_KEY);
+    rom
+}
+
+/// The behaviour of the machine the quirk profile stands for.
+fn quirks(machine: &Machine) -> Rom {
+    let mut rom = Rom::new();
+    rom.ops(&[0x6F01, 0x600C, 0x610A, 0x8011]).check("VF reset", 0xF, if machine.vf_reset { 0 } else { 1 });
+    rom.ops(&[0x6001, 0x6104, 0x8016]).check("shift VY", 0, if machine.shift_vy { 2 } else { 0 });
+    rom.ops(&[0xA000 | SCRATCH, 0x60AB, 0xF055, 0x60CD, 0xF055, 0xA000 | SCRATCH, 0xF065])
+        .check("load store increment", 0, if machine.load_store_increment { 0xAB } else { 0xCD });
+    // BNNN lands on the skipped instruction when jumping relative to V0 = 0, after it relative to V2 = 2
+    rom.ops(&[0x6000, 0x6202, 0x6C00]);
+    let target = rom.addr() + 2;
+    assert_eq!(target >> 8, 2, "the jump uses V2 with the jump VX quirk");
+    rom.op(0xB000 | target).skipped().check("jump VX", 0xC, if machine.jump_vx { 0 } else { 1 });
+    // a sprite at the right edge reaches the left edge only if it wraps around
+    rom.ops(&[0x603E, 0x611F, 0xA000 | FAIL_ADDR, 0xD011, 0x6000, 0xA000 | (FAIL_ADDR + 2), 0xD011])
+        .check("clip", 0xF, if machine.clip { 0 } else { 1 });
+    rom
+}
+
+/// Outcome of a check.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+    Pass,
+    Fail,
+    /// Neither glyph was found, the ROM did not get to the check.
+    Missing,
+}
+
+impl Outcome {
+    fn name(self) -> &'static str {
+        match self {
+            Outcome::Pass    => "pass",
+            Outcome::Fail    => "FAIL",
+            Outcome::Missing => "none",
+        }
+    }
+}
+
+/// Outcomes of the checks of a ROM, or the message of the panic that stopped it.
+type Outcomes = Result<Vec<Outcome>, String>;
+
+/// A suite name and the builder of its ROM for the machine a quirk profile stands for.
+type Suite = (&'static str, fn(&Machine) -> Rom);
+
+/// Read the glyph in a cell of the result grid.
+fn read_cell<G: Graphics>(display: &G, cell: usize) -> Outcome {
+    let (left, top) = ((cell % COLUMNS) * CELL_WIDTH, (cell / COLUMNS) * CELL_HEIGHT);
+    let rows: Vec<u8> = (0..PASS.len())
+        .map(|y| (0..8).fold(0, |row, x| row << 1 | display.get_pixel(left + x, top + y) as u8))
+        .collect();
+    if rows[..] == PASS[..] {
+        Outcome::Pass
+    } else if rows[..] == FAIL[..] {
+        Outcome::Fail
+    } else {
+        Outcome::Missing
+    }
+}
+
+/// Run a ROM until it halts and read the outcome of its checks. A panic is reported as its message.
+fn run(rom: &Rom, quirks: Quirks) -> Outcomes {
+    let (bytes, halt) = rom.finish();
+    let mut cpu = Cpu::new(Interconnect::new_test(bytes));
+    cpu.set_quirks(quirks);
+    cpu.interconnect_mut().input.press_key(HELD_KEY);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        for frame in 0..MAX_FRAMES {
+            match KEY_EVENTS.iter().find(|&&(at, _)| at == frame) {
+                Some(&(_, true))  => cpu.interconnect_mut().input.press_key(HELD_KEY),
+                Some(&(_, false)) => cpu.interconnect_mut().input.release_key(HELD_KEY),
+                None              => (),
+            }
+            cpu.execute_frame();
+            if cpu.pc() == halt {
+                break;
+            }
+        }
+    }));
+    if let Err(cause) = result {
+        return Err(cause.downcast_ref::<String>().cloned()
+            .or_else(|| cause.downcast_ref::<&str>().map(|message| message.to_string()))
+            .unwrap_or_default());
+    }
+    Ok((0..rom.checks.len()).map(|cell| read_cell(&cpu.interconnect().graphics, cell)).collect())
+}
+
+/// Run every suite under every profile and lay out the outcomes as a matrix with one row per check.
+fn matrix() -> String {
+    let suites: [Suite; 4] =
+        [("opcodes", opcodes), ("flags", flags), ("keypad", keypad), ("quirks", quirks)];
+    let mut text = format!("{:<8} {:<24}", "suite", "check");
+    for profile in PROFILES.iter() {
+        text += &format!(" {:<8}", profile);
+    }
+    text = text.trim_end().to_string();
+    text.push('\n');
+    let mut failures = vec![0; PROFILES.len()];
+    let mut total = 0;
+    for &(suite, build) in suites.iter() {
+        let columns: Vec<(Vec<&str>, Outcomes)> = PROFILES.iter()
+            .map(|profile| {
+                let quirks = Quirks::from_name(profile).unwrap();
+                let machine = MACHINES.iter().find(|&&(name, _)| name == *profile).map(|(_, machine)| machine)
+                    .expect("every profile should stand for a machine");
+                let rom = build(machine);
+                let outcomes = run(&rom, quirks);
+                (rom.checks, outcomes)
+            })
+            .collect();
+        let checks = &columns[0].0;
+        total += checks.len();
+        for (check, name) in checks.iter().enumerate() {
+            text += &format!("{:<8} {:<24}", suite, name);
+            for (profile, (_, outcomes)) in columns.iter().enumerate() {
+                let outcome = match outcomes {
+                    Ok(outcomes) => outcomes[check].name(),
+                    Err(_)           => "PANIC",
+                };
+                if outcome != "pass" {
+                    failures[profile] += 1;
+                }
+                text += &format!(" {:<8}", outcome);
+            }
+            text = text.trim_end().to_string();
+            text.push('\n');
+        }
+        for (profile, (_, outcomes)) in columns.iter().enumerate() {
+            if let Err(message) = outcomes {
+                text += &format!("{:<8} panicked under {}: {}\n", suite, PROFILES[profile], message);
+            }
+        }
+    }
+    text += &format!("{:<8} {:<24}", "", "passed");
+    for failed in failures {
+        text += &format!(" {:<8}", format!("{}/{}", total - failed, total));
+    }
+    text.trim_end().to_string() + "\n"
+}
+
+/// File the expected matrix is committed in.
+fn matrix_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("conformance").join("matrix.txt")
+}
+
+#[test]
+fn conformance_matrix() {
+    // silence the messages of ROMs panicking, the panics are part of the matrix
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let actual = matrix();
+    panic::set_hook(hook);
+    println!("{}", actual);
+
+    if env::var_os(BLESS).is_some() {
+        fs::write(matrix_path(), &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(matrix_path()).unwrap_or_default();
+    assert!(expected == actual, "the conformance matrix changed, re-bless it with `{}=1 cargo test --test \
+            conformance` if the change is intended:\n\nexpected:\n{}\nactual:\n{}", BLESS, expected, actual);
+}
+
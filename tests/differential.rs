@@ -0,0 +1,72 @@
+//! Differential tests of the CPU against the reference interpreter in `tests/reference`. Runs a fixed set of random
+//! programs from random states; the fuzz target in `fuzz` does the same with inputs from libFuzzer.
+//!
+//! The CPU may only disagree on VF with the known bugs listed in `KNOWN_BUGS`, and never panic. The minimized
+//! reproducers are printed with
+//!
+//! ```sh
+//! cargo test --test differential -- --nocapture
+//! ```
+
+extern crate chip_8;
+extern crate rand;
+
+mod reference;
+
+use std::collections::BTreeMap;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use reference::differential::{run, Input, Mismatch, HEADER_LEN, KNOWN_BUGS};
+
+/// Number of random inputs.
+const INPUTS: usize = 1000;
+/// Seed of the random inputs.
+const SEED: u64 = 0xC8;
+#[test]
+fn cpu_matches_reference() {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    // the first mismatch of each instruction, separately for the known bugs and for anything else
+    let mut mismatches: BTreeMap<(bool, &str), Mismatch> = BTreeMap::new();
+    for _ in 0..INPUTS {
+        let len = HEADER_LEN + 2 * rng.gen_range(1..64);
+        let data: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+        if let Err(mismatch) = run(&Input::from_bytes(&data).unwrap()) {
+            // minimizing takes a while, once per instruction is enough
+            let key = (mismatch.known_bug().is_some(), mismatch.pattern());
+            mismatches.entry(key).or_insert_with(|| mismatch.minimize());
+        }
+    }
+    for (&(known, _), mismatch) in &mismatches {
+        if let (true, Some(bug)) = (known, mismatch.known_bug()) {
+            println!("// known bug: {} {}", bug.pattern, bug.description);
+        }
+        println!("{}\n", mismatch);
+    }
+    let unexpected: Vec<&str> = mismatches.keys().filter(|&&(known, _)| !known).map(|&(_, pattern)| pattern).collect();
+    assert!(unexpected.is_empty(), "the CPU disagrees with the reference on {:?} beyond the known bugs, see \
+            `cargo test --test differential -- --nocapture` for reproducers", unexpected);
+}
+
+#[test]
+fn known_bugs_are_listed_in_the_readme() {
+    let readme = include_str!("../README.md");
+    for bug in KNOWN_BUGS.iter() {
+        assert!(readme.contains(&format!("`{}` {}", bug.pattern, bug.description)), "{} is not listed", bug.pattern);
+    }
+}
+
+#[test]
+fn mismatches_minimize_to_a_single_instruction() {
+    // LD V1, 0x05; LD V2, 0x0A; LD V3, 0x77; SUB V1, V2, the borrow being inverted
+    let mut data = vec![0; HEADER_LEN];
+    data.extend_from_slice(&[0x61, 0x05, 0x62, 0x0A, 0x63, 0x77, 0x81, 0x25]);
+    let mismatch = run(&Input::from_bytes(&data).unwrap()).expect_err("8XY5 disagrees with the reference");
+    assert_eq!(mismatch.known_bug().map(|bug| bug.pattern), Some("8XY5"));
+    let reproducer = mismatch.minimize().to_string();
+    assert!(!reproducer.contains("cpu.v[0x3]"), "unneeded state is dropped:\n{}", reproducer);
+    assert_eq!(reproducer.matches("cpu.process_opcode(").count(), 1, "{}", reproducer);
+    assert!(reproducer.contains("cpu.process_opcode(0x8125);"), "{}", reproducer);
+    assert!(reproducer.contains("assert_eq!(cpu.v[0xF], "), "{}", reproducer);
+}
@@ -0,0 +1,145 @@
+//! Differential tests of the execution engines. Every engine runs the same programs in lockstep with the interpreter,
+//! which is the reference, and the machines are compared after every frame: the registers, all of memory and the
+//! display.
+
+extern crate chip_8;
+extern crate rand;
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use chip_8::cpu::{Cpu, Engine, State};
+use chip_8::graphics::{Graphics, TestDisplay, HEIGHT, WIDTH};
+use chip_8::input::TestKeyboard;
+use chip_8::interconnect::Interconnect;
+use chip_8::memory::MEMORY_SIZE;
+
+/// Seed of the random number generators.
+const SEED: u64 = 0xC8;
+/// Frames every bundled ROM runs for.
+const ROM_FRAMES: u32 = 300;
+/// Number of random programs.
+const PROGRAMS: usize = 500;
+/// Frames every random program runs for.
+const PROGRAM_FRAMES: u32 = 20;
+
+/// Engines compared against the interpreter.
+fn engines() -> Vec<Engine> {
+    #[allow(unused_mut)]
+    let mut engines = vec![Engine::Cached];
+    #[cfg(feature = "jit")]
+    engines.push(Engine::Jit);
+    engines
+}
+
+/// Everything observable of a machine.
+#[derive(Debug, PartialEq, Eq)]
+struct Snapshot {
+    state: State,
+    memory: Vec<u8>,
+    display: Vec<bool>,
+}
+
+fn snapshot(cpu: &Cpu<TestKeyboard, TestDisplay>) -> Snapshot {
+    let interconnect = cpu.interconnect();
+    Snapshot {
+        state: cpu.state(),
+        memory: (0..MEMORY_SIZE).map(|addr| interconnect.memory.read(addr)).collect(),
+        display: (0..WIDTH * HEIGHT).map(|idx| interconnect.graphics.get_pixel(idx % WIDTH, idx / WIDTH)).collect(),
+    }
+}
+
+/// A machine running a ROM from its first instruction.
+fn machine(rom: &[u8], engine: Engine, key: Option<u8>) -> Cpu<TestKeyboard, TestDisplay> {
+    let mut cpu = Cpu::new(Interconnect::new_test(rom.to_vec()));
+    cpu.set_engine(engine);
+    cpu.seed_rng(SEED);
+    if let Some(key) = key {
+        cpu.interconnect_mut().input.press_key(key);
+    }
+    cpu
+}
+
+/// Run a ROM on the interpreter and on an engine, describing the first difference. A panic is fine as long as both
+/// panic in the same frame.
+fn lockstep(rom: &[u8], engine: Engine, key: Option<u8>, frames: u32) -> Result<(), String> {
+    let mut reference = machine(rom, Engine::Interpreter, key);
+    let mut cpu = machine(rom, engine, key);
+    for frame in 0..frames {
+        let expected = panic::catch_unwind(AssertUnwindSafe(|| reference.execute_frame())).is_ok();
+        let actual = panic::catch_unwind(AssertUnwindSafe(|| cpu.execute_frame())).is_ok();
+        if expected != actual {
+            return Err(format!("{:?} panics in frame {}: {}, the interpreter: {}", engine, frame, !actual, !expected));
+        }
+        if !expected {
+            return Ok(());
+        }
+        let (expected, actual) = (snapshot(&reference), snapshot(&cpu));
+        if expected.state != actual.state {
+            return Err(format!("{:?} differs in frame {}:\n{:?}\ninstead of\n{:?}", engine, frame, actual.state,
+                               expected.state));
+        }
+        if let Some(addr) = (0..MEMORY_SIZE).find(|&addr| expected.memory[addr] != actual.memory[addr]) {
+            return Err(format!("{:?} differs in frame {} at address 0x{:03X}", engine, frame, addr));
+        }
+        if expected.display != actual.display {
+            return Err(format!("{:?} differs in frame {} on the display", engine, frame));
+        }
+    }
+    Ok(())
+}
+
+/// A random program, biased towards writing into itself and jumping around in itself.
+fn random_program(rng: &mut StdRng) -> Vec<u8> {
+    let len = rng.gen_range(8..64);
+    let mut rom = Vec::new();
+    for _ in 0..len {
+        let x = rng.gen_range(0..16) << 8;
+        let opcode: u16 = match rng.gen_range(0..8) {
+            // LD I, an address within the program
+            0 => 0xA200 | rng.gen_range(0..2 * len) as u16,
+            // LD [I], Vx
+            1 => 0xF055 | x,
+            // LD B, Vx
+            2 => 0xF033 | x,
+            // JP to an instruction of the program
+            3 => 0x1200 | (2 * rng.gen_range(0..len) as u16),
+            _ => rng.gen(),
+        };
+        rom.push((opcode >> 8) as u8);
+        rom.push(opcode as u8);
+    }
+    rom
+}
+
+#[test]
+fn engines_run_bundled_roms_like_the_interpreter() {
+    let roms = Path::new(env!("CARGO_MANIFEST_DIR")).join("static").join("roms");
+    for entry in fs::read_dir(roms).unwrap() {
+        let path = entry.unwrap().path();
+        let rom = fs::read(&path).unwrap();
+        for engine in engines() {
+            if let Err(difference) = lockstep(&rom, engine, None, ROM_FRAMES) {
+                panic!("{}: {}", path.display(), difference);
+            }
+        }
+    }
+}
+
+#[test]
+fn engines_run_self_modifying_programs_like_the_interpreter() {
+    let mut rng = StdRng::seed_from_u64(SEED);
+    for _ in 0..PROGRAMS {
+        let rom = random_program(&mut rng);
+        let key = Some(rng.gen_range(0..16));
+        for engine in engines() {
+            if let Err(difference) = lockstep(&rom, engine, key, PROGRAM_FRAMES) {
+                panic!("{}\nprogram: {:02X?}", difference, rom);
+            }
+        }
+    }
+}
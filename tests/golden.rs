@@ -0,0 +1,154 @@
+//! Golden image regression tests. Boots every bundled ROM headlessly for a fixed number of frames with a fixed random
+//! seed and optional scripted input, and compares the final display against the snapshot committed in
+//! `tests/golden`.
+//!
+//! Snapshots are re-blessed after an intended change in behaviour with
+//!
+//! ```sh
+//! CHIP8_BLESS=1 cargo test --test golden
+//! ```
+//!
+//! Input scripts are optional files `tests/golden/<ROM>.input` with one event per line, `<frame> <key> down|up`, the
+//! event being applied before the frame is executed. Lines starting with `#` are comments.
+
+extern crate chip_8;
+
+use std::env;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use chip_8::cpu::Cpu;
+use chip_8::graphics::{Graphics, TestDisplay, HEIGHT, WIDTH};
+use chip_8::interconnect::Interconnect;
+
+/// Number of frames every ROM runs for, ten seconds of emulated time.
+const FRAMES: u32 = 600;
+/// Seed of the random number generator.
+const SEED: u64 = 0xC8;
+/// Environment variable requesting the snapshots to be rewritten instead of compared.
+const BLESS: &str = "CHIP8_BLESS";
+
+/// A scripted key event.
+struct KeyEvent {
+    frame: u32,
+    key: u8,
+    down: bool,
+}
+
+/// Parse an input script.
+fn parse_script(script: &str) -> Vec<KeyEvent> {
+    script.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            assert_eq!(fields.len(), 3, "expected '<frame> <key> down|up' but got '{}'", line);
+            KeyEvent {
+                frame: fields[0].parse().expect("frame should be a decimal number"),
+                key: u8::from_str_radix(fields[1], 16).expect("key should be a hex digit"),
+                down: match fields[2] {
+                    "down" => true,
+                    "up"   => false,
+                    state  => panic!("expected down or up but got '{}'", state),
+                },
+            }
+        })
+        .collect()
+}
+
+/// FNV-1a hash of the display, one byte per pixel.
+fn hash(display: &TestDisplay) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            hash ^= display.get_pixel(x, y) as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+/// Run a ROM and describe the outcome as a snapshot: the hash and a drawing of the final display, or the panic that
+/// stopped the emulation.
+fn snapshot(rom: Vec<u8>, script: &[KeyEvent]) -> String {
+    let mut cpu = Cpu::new(Interconnect::new_test(rom));
+    cpu.seed_rng(SEED);
+
+    let mut frame = 0;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        while frame < FRAMES {
+            for event in script.iter().filter(|event| event.frame == frame) {
+                let input = &mut cpu.interconnect_mut().input;
+                if event.down { input.press_key(event.key) } else { input.release_key(event.key) }
+            }
+            cpu.execute_frame();
+            frame += 1;
+        }
+    }));
+    if let Err(cause) = result {
+        let message = cause.downcast_ref::<String>().cloned()
+            .or_else(|| cause.downcast_ref::<&str>().map(|message| message.to_string()))
+            .unwrap_or_default();
+        return format!("frames {}\npanic in frame {}: {}\n", FRAMES, frame, message);
+    }
+
+    let display = &cpu.interconnect().graphics;
+    let mut snapshot = format!("frames {}\nhash {:016x}\n", FRAMES, hash(display));
+    for y in 0..HEIGHT {
+        let row: String = (0..WIDTH).map(|x| if display.get_pixel(x, y) { '#' } else { '.' }).collect();
+        snapshot += &row;
+        snapshot.push('\n');
+    }
+    snapshot
+}
+
+/// Directory of the bundled ROMs.
+fn rom_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("static").join("roms")
+}
+
+/// Directory of the snapshots and input scripts.
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+#[test]
+fn golden_images() {
+    let bless = env::var_os(BLESS).is_some();
+    let mut roms: Vec<PathBuf> = fs::read_dir(rom_dir()).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    roms.sort();
+    assert!(!roms.is_empty(), "no ROMs found");
+
+    // silence the messages of ROMs panicking, the panics are part of their snapshots
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let mut mismatches = Vec::new();
+    for path in &roms {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let script = fs::read_to_string(golden_dir().join(format!("{}.input", name)))
+            .map(|script| parse_script(&script))
+            .unwrap_or_default();
+        let actual = snapshot(fs::read(path).unwrap(), &script);
+        let golden = golden_dir().join(format!("{}.golden", name));
+        if bless {
+            fs::write(&golden, &actual).unwrap();
+        } else if fs::read_to_string(&golden).ok().as_ref() != Some(&actual) {
+            mismatches.push(format!("{}:\n{}", name, actual));
+        }
+    }
+    panic::set_hook(hook);
+
+    assert!(mismatches.is_empty(), "{} snapshots differ, re-bless them with `{}=1 cargo test --test golden` if the \
+            change is intended:\n\n{}", mismatches.len(), BLESS, mismatches.join("\n"));
+}
+
+#[test]
+fn scripts_parse() {
+    let events = parse_script("# comment\n\n12 a down\n20 A up\n");
+    assert_eq!(events.len(), 2);
+    assert_eq!((events[0].frame, events[0].key, events[0].down), (12, 0xA, true));
+    assert_eq!((events[1].frame, events[1].key, events[1].down), (20, 0xA, false));
+}
@@ -0,0 +1,72 @@
+//! Games played through the reinforcement learning environment, with the bundled game definitions.
+
+extern crate chip_8;
+
+use std::fs;
+use std::path::Path;
+
+use chip_8::framebuffer::Framebuffer;
+use chip_8::gym::{Environment, Game};
+
+/// Steps after which an episode is considered stuck.
+const MAX_STEPS: usize = 10_000;
+
+/// Environment of a bundled ROM, with its bundled definition.
+fn environment(name: &str) -> Environment {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("static");
+    let rom = fs::read(root.join("roms").join(name)).unwrap();
+    let game = Game::load(&root.join("games").join(format!("{}.game", name))).unwrap();
+    Environment::new(rom, game).unwrap()
+}
+
+/// Play an episode with actions picked by a pseudo-random generator.
+///
+/// # Returns
+/// Returns the rewards of the steps, and the last display.
+fn play(env: &mut Environment, seed: u64) -> (Vec<f32>, Framebuffer) {
+    let actions = env.actions();
+    let mut rewards = Vec::new();
+    assert!(env.reset(seed).rows().iter().all(|&row| row == 0), "the display should be cleared");
+    // xorshift, to pick the same actions for the same seed
+    let mut x = seed | 1;
+    loop {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        let (observation, reward, done) = env.step(actions[(x % actions.len() as u64) as usize]);
+        rewards.push(reward);
+        if done {
+            return (rewards, observation);
+        }
+        assert!(rewards.len() < MAX_STEPS, "the episode should end");
+    }
+}
+
+#[test]
+fn pong_rewards_the_points() {
+    let mut env = environment("PONG");
+    assert_eq!(env.actions().len(), 4);
+    let (rewards, _) = play(&mut env, 1);
+    // the right paddle stays still, so the left player wins every point
+    assert_eq!(rewards.iter().sum::<f32>(), 9.0);
+    assert_eq!(rewards.iter().filter(|&&reward| reward != 0.0).count(), 9, "points are rewarded one at a time");
+    assert_eq!(env.step(0).1, 0.0, "the episode is over");
+}
+
+#[test]
+fn brix_ends_with_the_last_ball() {
+    let mut env = environment("BRIX");
+    let (rewards, screen) = play(&mut env, 2);
+    assert_eq!(env.cpu().pc(), 0x2DE);
+    assert_eq!(env.cpu().state().v[0xE], 0, "no lives should be left");
+    assert!(screen.rows().iter().any(|&row| row != 0), "the bricks should be displayed");
+    assert!(rewards.len() > 100);
+}
+
+#[test]
+fn episodes_are_reproducible() {
+    let mut env = environment("PONG");
+    let first = play(&mut env, 3);
+    assert_eq!(play(&mut env, 3), first);
+    assert_ne!(play(&mut env, 4), first, "another seed should play another episode");
+}
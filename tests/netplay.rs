@@ -0,0 +1,90 @@
+//! Two-player game between two processes on loopback. The test hosts the game and spawns its own binary to run the
+//! ignored `guest` test, which joins it. Both players run PONG in lockstep, each moving its paddle with a key it owns,
+//! and the guest reports the hash of its machine at the end for the host to compare with its own.
+
+extern crate chip_8;
+
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use chip_8::cpu::Cpu;
+use chip_8::graphics::TestDisplay;
+use chip_8::input::TestKeyboard;
+use chip_8::interconnect::Interconnect;
+use chip_8::netplay::{self, Hello, Session, DEFAULT_KEYS};
+use chip_8::quirks::Quirks;
+
+/// Environment variable holding the address of the host, telling the `guest` test to join it.
+const HOST: &str = "CHIP8_NETPLAY_HOST";
+/// Frames both players run for.
+const FRAMES: u64 = 600;
+/// Frames the keys are sent ahead.
+const INPUT_DELAY: u8 = 3;
+/// Time to wait for the other player before considering the game stuck.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn rom() -> Vec<u8> {
+    fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("static").join("roms").join("PONG")).unwrap()
+}
+
+fn hello(keys: u16) -> Hello {
+    Hello::new(&rom(), Quirks::default(), 10, INPUT_DELAY, keys)
+}
+
+/// Play the game, holding down a key for half a second every second.
+///
+/// # Returns
+/// Returns the hash of the machine at the end, and the session, to be dropped once the other player is done as well.
+fn play(mut session: Session, key: u8) -> (u64, Session) {
+    let mut cpu: Cpu<TestKeyboard, TestDisplay> = Cpu::new(Interconnect::new_test(rom()));
+    cpu.seed_rng(session.seed());
+    while session.frame() < FRAMES {
+        let keys = if (session.frame() / 30).is_multiple_of(2) { 1 << key } else { 0 };
+        assert!(session.step(&mut cpu, keys, TIMEOUT).unwrap(), "the keys of the other player should arrive");
+    }
+    assert_eq!(session.resyncs(), 0, "the players should never desync");
+    (netplay::hash(&cpu.snapshot()), session)
+}
+
+#[test]
+fn players_in_two_processes_stay_in_lockstep() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut guest = Command::new(env::current_exe().unwrap())
+        .args(["guest", "--exact", "--ignored", "--nocapture", "--test-threads=1"])
+        .env(HOST, listener.local_addr().unwrap().to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let (hash, session) = play(Session::host(&listener, hello(DEFAULT_KEYS[0])).unwrap(), 0x1);
+    // closing the input of the guest lets it hang up
+    drop(guest.stdin.take());
+    let output = guest.wait_with_output().unwrap();
+    drop(session);
+
+    let report = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "the guest failed:\n{}", report);
+    assert!(report.contains(&format!("hash {:016x}", hash)), "the guest ended with another machine than the host's \
+            {:016x}:\n{}", hash, report);
+}
+
+/// The other player of `players_in_two_processes_stay_in_lockstep`, doing nothing when run on its own.
+#[test]
+#[ignore]
+fn guest() {
+    let host = match env::var(HOST) {
+        Ok(host) => host,
+        Err(_)   => return,
+    };
+    let (hash, session) = play(Session::join(host.as_str(), hello(DEFAULT_KEYS[1])).unwrap(), 0xC);
+    println!("hash {:016x}", hash);
+    // stay connected until the host is done as well
+    io::stdin().read_to_end(&mut Vec::new()).unwrap();
+    drop(session);
+}
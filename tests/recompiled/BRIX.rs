@@ -0,0 +1,604 @@
+// CHIP-8 ROM BRIX recompiled to Rust with the default quirks: 2 routines, 134 instructions.
+//
+// Generated by `chip-8 recompile`.
+
+extern crate chip_8;
+
+use std::ops::Range;
+
+use chip_8::prelude::*;
+use chip_8::recompiled::{self, Exit, Machine, Routine};
+
+/// Quirk profile the routines were recompiled for.
+pub const QUIRKS: &str = "default";
+/// Instructions executed per frame.
+pub const CYCLES_PER_FRAME: u32 = 10;
+/// The ROM.
+pub const ROM: &[u8] = &[
+    0x6E, 0x05, 0x65, 0x00, 0x6B, 0x06, 0x6A, 0x00, 0xA3, 0x0C, 0xDA, 0xB1, 0x7A, 0x04, 0x3A, 0x40,
+    0x12, 0x08, 0x7B, 0x02, 0x3B, 0x12, 0x12, 0x06, 0x6C, 0x20, 0x6D, 0x1F, 0xA3, 0x10, 0xDC, 0xD1,
+    0x22, 0xF6, 0x60, 0x00, 0x61, 0x00, 0xA3, 0x12, 0xD0, 0x11, 0x70, 0x08, 0xA3, 0x0E, 0xD0, 0x11,
+    0x60, 0x40, 0xF0, 0x15, 0xF0, 0x07, 0x30, 0x00, 0x12, 0x34, 0xC6, 0x0F, 0x67, 0x1E, 0x68, 0x01,
+    0x69, 0xFF, 0xA3, 0x0E, 0xD6, 0x71, 0xA3, 0x10, 0xDC, 0xD1, 0x60, 0x04, 0xE0, 0xA1, 0x7C, 0xFE,
+    0x60, 0x06, 0xE0, 0xA1, 0x7C, 0x02, 0x60, 0x3F, 0x8C, 0x02, 0xDC, 0xD1, 0xA3, 0x0E, 0xD6, 0x71,
+    0x86, 0x84, 0x87, 0x94, 0x60, 0x3F, 0x86, 0x02, 0x61, 0x1F, 0x87, 0x12, 0x47, 0x1F, 0x12, 0xAC,
+    0x46, 0x00, 0x68, 0x01, 0x46, 0x3F, 0x68, 0xFF, 0x47, 0x00, 0x69, 0x01, 0xD6, 0x71, 0x3F, 0x01,
+    0x12, 0xAA, 0x47, 0x1F, 0x12, 0xAA, 0x60, 0x05, 0x80, 0x75, 0x3F, 0x00, 0x12, 0xAA, 0x60, 0x01,
+    0xF0, 0x18, 0x80, 0x60, 0x61, 0xFC, 0x80, 0x12, 0xA3, 0x0C, 0xD0, 0x71, 0x60, 0xFE, 0x89, 0x03,
+    0x22, 0xF6, 0x75, 0x01, 0x22, 0xF6, 0x45, 0x60, 0x12, 0xDE, 0x12, 0x46, 0x69, 0xFF, 0x80, 0x60,
+    0x80, 0xC5, 0x3F, 0x01, 0x12, 0xCA, 0x61, 0x02, 0x80, 0x15, 0x3F, 0x01, 0x12, 0xE0, 0x80, 0x15,
+    0x3F, 0x01, 0x12, 0xEE, 0x80, 0x15, 0x3F, 0x01, 0x12, 0xE8, 0x60, 0x20, 0xF0, 0x18, 0xA3, 0x0E,
+    0x7E, 0xFF, 0x80, 0xE0, 0x80, 0x04, 0x61, 0x00, 0xD0, 0x11, 0x3E, 0x00, 0x12, 0x30, 0x12, 0xDE,
+    0x78, 0xFF, 0x48, 0xFE, 0x68, 0xFF, 0x12, 0xEE, 0x78, 0x01, 0x48, 0x02, 0x68, 0x01, 0x60, 0x04,
+    0xF0, 0x18, 0x69, 0xFF, 0x12, 0x70, 0xA3, 0x14, 0xF5, 0x33, 0xF2, 0x65, 0xF1, 0x29, 0x63, 0x37,
+    0x64, 0x00, 0xD3, 0x45, 0x73, 0x05, 0xF2, 0x29, 0xD3, 0x45, 0x00, 0xEE, 0xE0, 0x00, 0x80, 0x00,
+    0xFC, 0x00, 0xAA, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+/// Addresses of the recovered code.
+#[allow(clippy::single_range_in_vec_init)]
+pub const CODE: &[Range<u16>] = &[
+    0x200..0x30C,
+];
+
+fn main() {
+    recompiled::run(ROM, CODE, QUIRKS, CYCLES_PER_FRAME, dispatch);
+}
+
+/// Look up the routine with a basic block starting at an address.
+pub fn dispatch<T: Input, U: Graphics>(pc: u16) -> Option<Routine<T, U>> {
+    let routine: Routine<T, U> = match pc {
+        0x200 | 0x206 | 0x208 | 0x210 | 0x212 | 0x216 | 0x218 | 0x222 | 0x230 | 0x234 | 0x238 | 0x23A | 0x246 | 0x24E |
+            0x250 | 0x254 | 0x256 | 0x26E | 0x270 | 0x272 | 0x274 | 0x276 | 0x278 | 0x27A | 0x27C | 0x280 | 0x282 |
+            0x284 | 0x286 | 0x28C | 0x28E | 0x2A2 | 0x2A6 | 0x2A8 | 0x2AA | 0x2AC | 0x2B4 | 0x2B6 | 0x2BC | 0x2BE |
+            0x2C2 | 0x2C4 | 0x2C8 | 0x2CA | 0x2DC | 0x2DE | 0x2E0 | 0x2E4 | 0x2E6 | 0x2E8 | 0x2EC | 0x2EE => sub_200,
+        0x2F6 => sub_2f6,
+        _ => return None,
+    };
+    Some(routine)
+}
+
+/// Routine at 0x200.
+pub fn sub_200<T: Input, U: Graphics>(m: &mut Machine<T, U>) -> Result<(), Exit> {
+    let mut pc = m.state.pc;
+    loop {
+        match pc {
+            0x200 => {
+                // 0x200: LD VE, 0x05
+                m.state.v[0xE] = 0x05;
+                m.tick(0x202)?;
+                // 0x202: LD V5, 0x00
+                m.state.v[0x5] = 0x00;
+                m.tick(0x204)?;
+                // 0x204: LD VB, 0x06
+                m.state.v[0xB] = 0x06;
+                m.tick(0x206)?;
+                pc = 0x206;
+            },
+            0x206 => {
+                // 0x206: LD VA, 0x00
+                m.state.v[0xA] = 0x00;
+                m.tick(0x208)?;
+                pc = 0x208;
+            },
+            0x208 => {
+                // 0x208: LD I, 0x30C
+                m.state.i = 0x30C;
+                m.tick(0x20A)?;
+                // 0x20A: DRW VA, VB, 1
+                m.host(0x20A, 0xDAB1)?;
+                // 0x20C: ADD VA, 0x04
+                m.state.v[0xA] = m.state.v[0xA].wrapping_add(0x04);
+                m.tick(0x20E)?;
+                // 0x20E: SE VA, 0x40
+                pc = if m.state.v[0xA] == 0x40 { 0x212 } else { 0x210 };
+                m.tick(pc)?;
+            },
+            0x210 => {
+                // 0x210: JP 0x208
+                m.tick(0x208)?;
+                pc = 0x208;
+            },
+            0x212 => {
+                // 0x212: ADD VB, 0x02
+                m.state.v[0xB] = m.state.v[0xB].wrapping_add(0x02);
+                m.tick(0x214)?;
+                // 0x214: SE VB, 0x12
+                pc = if m.state.v[0xB] == 0x12 { 0x218 } else { 0x216 };
+                m.tick(pc)?;
+            },
+            0x216 => {
+                // 0x216: JP 0x206
+                m.tick(0x206)?;
+                pc = 0x206;
+            },
+            0x218 => {
+                // 0x218: LD VC, 0x20
+                m.state.v[0xC] = 0x20;
+                m.tick(0x21A)?;
+                // 0x21A: LD VD, 0x1F
+                m.state.v[0xD] = 0x1F;
+                m.tick(0x21C)?;
+                // 0x21C: LD I, 0x310
+                m.state.i = 0x310;
+                m.tick(0x21E)?;
+                // 0x21E: DRW VC, VD, 1
+                m.host(0x21E, 0xDCD1)?;
+                // 0x220: CALL 0x2F6
+                m.call(0x222, 0x2F6)?;
+                sub_2f6(m)?;
+                pc = m.state.pc;
+            },
+            0x222 => {
+                // 0x222: LD V0, 0x00
+                m.state.v[0x0] = 0x00;
+                m.tick(0x224)?;
+                // 0x224: LD V1, 0x00
+                m.state.v[0x1] = 0x00;
+                m.tick(0x226)?;
+                // 0x226: LD I, 0x312
+                m.state.i = 0x312;
+                m.tick(0x228)?;
+                // 0x228: DRW V0, V1, 1
+                m.host(0x228, 0xD011)?;
+                // 0x22A: ADD V0, 0x08
+                m.state.v[0x0] = m.state.v[0x0].wrapping_add(0x08);
+                m.tick(0x22C)?;
+                // 0x22C: LD I, 0x30E
+                m.state.i = 0x30E;
+                m.tick(0x22E)?;
+                // 0x22E: DRW V0, V1, 1
+                m.host(0x22E, 0xD011)?;
+                pc = 0x230;
+            },
+            0x230 => {
+                // 0x230: LD V0, 0x40
+                m.state.v[0x0] = 0x40;
+                m.tick(0x232)?;
+                // 0x232: LD DT, V0
+                m.state.dt = m.state.v[0x0];
+                m.tick(0x234)?;
+                pc = 0x234;
+            },
+            0x234 => {
+                // 0x234: LD V0, DT
+                m.state.v[0x0] = m.state.dt;
+                m.tick(0x236)?;
+                // 0x236: SE V0, 0x00
+                pc = if m.state.v[0x0] == 0x00 { 0x23A } else { 0x238 };
+                m.tick(pc)?;
+            },
+            0x238 => {
+                // 0x238: JP 0x234
+                m.tick(0x234)?;
+                pc = 0x234;
+            },
+            0x23A => {
+                // 0x23A: RND V6, 0x0F
+                m.host(0x23A, 0xC60F)?;
+                // 0x23C: LD V7, 0x1E
+                m.state.v[0x7] = 0x1E;
+                m.tick(0x23E)?;
+                // 0x23E: LD V8, 0x01
+                m.state.v[0x8] = 0x01;
+                m.tick(0x240)?;
+                // 0x240: LD V9, 0xFF
+                m.state.v[0x9] = 0xFF;
+                m.tick(0x242)?;
+                // 0x242: LD I, 0x30E
+                m.state.i = 0x30E;
+                m.tick(0x244)?;
+                // 0x244: DRW V6, V7, 1
+                m.host(0x244, 0xD671)?;
+                pc = 0x246;
+            },
+            0x246 => {
+                // 0x246: LD I, 0x310
+                m.state.i = 0x310;
+                m.tick(0x248)?;
+                // 0x248: DRW VC, VD, 1
+                m.host(0x248, 0xDCD1)?;
+                // 0x24A: LD V0, 0x04
+                m.state.v[0x0] = 0x04;
+                m.tick(0x24C)?;
+                // 0x24C: SKNP V0
+                m.host(0x24C, 0xE0A1)?;
+                pc = m.state.pc;
+            },
+            0x24E => {
+                // 0x24E: ADD VC, 0xFE
+                m.state.v[0xC] = m.state.v[0xC].wrapping_add(0xFE);
+                m.tick(0x250)?;
+                pc = 0x250;
+            },
+            0x250 => {
+                // 0x250: LD V0, 0x06
+                m.state.v[0x0] = 0x06;
+                m.tick(0x252)?;
+                // 0x252: SKNP V0
+                m.host(0x252, 0xE0A1)?;
+                pc = m.state.pc;
+            },
+            0x254 => {
+                // 0x254: ADD VC, 0x02
+                m.state.v[0xC] = m.state.v[0xC].wrapping_add(0x02);
+                m.tick(0x256)?;
+                pc = 0x256;
+            },
+            0x256 => {
+                // 0x256: LD V0, 0x3F
+                m.state.v[0x0] = 0x3F;
+                m.tick(0x258)?;
+                // 0x258: AND VC, V0
+                m.state.v[0xC] &= m.state.v[0x0];
+                m.tick(0x25A)?;
+                // 0x25A: DRW VC, VD, 1
+                m.host(0x25A, 0xDCD1)?;
+                // 0x25C: LD I, 0x30E
+                m.state.i = 0x30E;
+                m.tick(0x25E)?;
+                // 0x25E: DRW V6, V7, 1
+                m.host(0x25E, 0xD671)?;
+                // 0x260: ADD V6, V8
+                let sum = u16::from(m.state.v[0x6]) + u16::from(m.state.v[0x8]);
+                m.state.v[0xF] = u8::from(sum > 0xFF);
+                m.state.v[0x6] = sum as u8;
+                m.tick(0x262)?;
+                // 0x262: ADD V7, V9
+                let sum = u16::from(m.state.v[0x7]) + u16::from(m.state.v[0x9]);
+                m.state.v[0xF] = u8::from(sum > 0xFF);
+                m.state.v[0x7] = sum as u8;
+                m.tick(0x264)?;
+                // 0x264: LD V0, 0x3F
+                m.state.v[0x0] = 0x3F;
+                m.tick(0x266)?;
+                // 0x266: AND V6, V0
+                m.state.v[0x6] &= m.state.v[0x0];
+                m.tick(0x268)?;
+                // 0x268: LD V1, 0x1F
+                m.state.v[0x1] = 0x1F;
+                m.tick(0x26A)?;
+                // 0x26A: AND V7, V1
+                m.state.v[0x7] &= m.state.v[0x1];
+                m.tick(0x26C)?;
+                // 0x26C: SNE V7, 0x1F
+                pc = if m.state.v[0x7] != 0x1F { 0x270 } else { 0x26E };
+                m.tick(pc)?;
+            },
+            0x26E => {
+                // 0x26E: JP 0x2AC
+                m.tick(0x2AC)?;
+                pc = 0x2AC;
+            },
+            0x270 => {
+                // 0x270: SNE V6, 0x00
+                pc = if m.state.v[0x6] != 0x00 { 0x274 } else { 0x272 };
+                m.tick(pc)?;
+            },
+            0x272 => {
+                // 0x272: LD V8, 0x01
+                m.state.v[0x8] = 0x01;
+                m.tick(0x274)?;
+                pc = 0x274;
+            },
+            0x274 => {
+                // 0x274: SNE V6, 0x3F
+                pc = if m.state.v[0x6] != 0x3F { 0x278 } else { 0x276 };
+                m.tick(pc)?;
+            },
+            0x276 => {
+                // 0x276: LD V8, 0xFF
+                m.state.v[0x8] = 0xFF;
+                m.tick(0x278)?;
+                pc = 0x278;
+            },
+            0x278 => {
+                // 0x278: SNE V7, 0x00
+                pc = if m.state.v[0x7] != 0x00 { 0x27C } else { 0x27A };
+                m.tick(pc)?;
+            },
+            0x27A => {
+                // 0x27A: LD V9, 0x01
+                m.state.v[0x9] = 0x01;
+                m.tick(0x27C)?;
+                pc = 0x27C;
+            },
+            0x27C => {
+                // 0x27C: DRW V6, V7, 1
+                m.host(0x27C, 0xD671)?;
+                // 0x27E: SE VF, 0x01
+                pc = if m.state.v[0xF] == 0x01 { 0x282 } else { 0x280 };
+                m.tick(pc)?;
+            },
+            0x280 => {
+                // 0x280: JP 0x2AA
+                m.tick(0x2AA)?;
+                pc = 0x2AA;
+            },
+            0x282 => {
+                // 0x282: SNE V7, 0x1F
+                pc = if m.state.v[0x7] != 0x1F { 0x286 } else { 0x284 };
+                m.tick(pc)?;
+            },
+            0x284 => {
+                // 0x284: JP 0x2AA
+                m.tick(0x2AA)?;
+                pc = 0x2AA;
+            },
+            0x286 => {
+                // 0x286: LD V0, 0x05
+                m.state.v[0x0] = 0x05;
+                m.tick(0x288)?;
+                // 0x288: SUB V0, V7
+                let difference = (m.state.v[0x0] as i8).wrapping_sub(m.state.v[0x7] as i8);
+                m.state.v[0x0] = difference as u8;
+                m.state.v[0xF] = u8::from(difference < 0);
+                m.tick(0x28A)?;
+                // 0x28A: SE VF, 0x00
+                pc = if m.state.v[0xF] == 0x00 { 0x28E } else { 0x28C };
+                m.tick(pc)?;
+            },
+            0x28C => {
+                // 0x28C: JP 0x2AA
+                m.tick(0x2AA)?;
+                pc = 0x2AA;
+            },
+            0x28E => {
+                // 0x28E: LD V0, 0x01
+                m.state.v[0x0] = 0x01;
+                m.tick(0x290)?;
+                // 0x290: LD ST, V0
+                m.state.st = m.state.v[0x0];
+                m.tick(0x292)?;
+                // 0x292: LD V0, V6
+                m.state.v[0x0] = m.state.v[0x6];
+                m.tick(0x294)?;
+                // 0x294: LD V1, 0xFC
+                m.state.v[0x1] = 0xFC;
+                m.tick(0x296)?;
+                // 0x296: AND V0, V1
+                m.state.v[0x0] &= m.state.v[0x1];
+                m.tick(0x298)?;
+                // 0x298: LD I, 0x30C
+                m.state.i = 0x30C;
+                m.tick(0x29A)?;
+                // 0x29A: DRW V0, V7, 1
+                m.host(0x29A, 0xD071)?;
+                // 0x29C: LD V0, 0xFE
+                m.state.v[0x0] = 0xFE;
+                m.tick(0x29E)?;
+                // 0x29E: XOR V9, V0
+                m.state.v[0x9] ^= m.state.v[0x0];
+                m.tick(0x2A0)?;
+                // 0x2A0: CALL 0x2F6
+                m.call(0x2A2, 0x2F6)?;
+                sub_2f6(m)?;
+                pc = m.state.pc;
+            },
+            0x2A2 => {
+                // 0x2A2: ADD V5, 0x01
+                m.state.v[0x5] = m.state.v[0x5].wrapping_add(0x01);
+                m.tick(0x2A4)?;
+                // 0x2A4: CALL 0x2F6
+                m.call(0x2A6, 0x2F6)?;
+                sub_2f6(m)?;
+                pc = m.state.pc;
+            },
+            0x2A6 => {
+                // 0x2A6: SNE V5, 0x60
+                pc = if m.state.v[0x5] != 0x60 { 0x2AA } else { 0x2A8 };
+                m.tick(pc)?;
+            },
+            0x2A8 => {
+                // 0x2A8: JP 0x2DE
+                m.tick(0x2DE)?;
+                pc = 0x2DE;
+            },
+            0x2AA => {
+                // 0x2AA: JP 0x246
+                m.tick(0x246)?;
+                pc = 0x246;
+            },
+            0x2AC => {
+                // 0x2AC: LD V9, 0xFF
+                m.state.v[0x9] = 0xFF;
+                m.tick(0x2AE)?;
+                // 0x2AE: LD V0, V6
+                m.state.v[0x0] = m.state.v[0x6];
+                m.tick(0x2B0)?;
+                // 0x2B0: SUB V0, VC
+                let difference = (m.state.v[0x0] as i8).wrapping_sub(m.state.v[0xC] as i8);
+                m.state.v[0x0] = difference as u8;
+                m.state.v[0xF] = u8::from(difference < 0);
+                m.tick(0x2B2)?;
+                // 0x2B2: SE VF, 0x01
+                pc = if m.state.v[0xF] == 0x01 { 0x2B6 } else { 0x2B4 };
+                m.tick(pc)?;
+            },
+            0x2B4 => {
+                // 0x2B4: JP 0x2CA
+                m.tick(0x2CA)?;
+                pc = 0x2CA;
+            },
+            0x2B6 => {
+                // 0x2B6: LD V1, 0x02
+                m.state.v[0x1] = 0x02;
+                m.tick(0x2B8)?;
+                // 0x2B8: SUB V0, V1
+                let difference = (m.state.v[0x0] as i8).wrapping_sub(m.state.v[0x1] as i8);
+                m.state.v[0x0] = difference as u8;
+                m.state.v[0xF] = u8::from(difference < 0);
+                m.tick(0x2BA)?;
+                // 0x2BA: SE VF, 0x01
+                pc = if m.state.v[0xF] == 0x01 { 0x2BE } else { 0x2BC };
+                m.tick(pc)?;
+            },
+            0x2BC => {
+                // 0x2BC: JP 0x2E0
+                m.tick(0x2E0)?;
+                pc = 0x2E0;
+            },
+            0x2BE => {
+                // 0x2BE: SUB V0, V1
+                let difference = (m.state.v[0x0] as i8).wrapping_sub(m.state.v[0x1] as i8);
+                m.state.v[0x0] = difference as u8;
+                m.state.v[0xF] = u8::from(difference < 0);
+                m.tick(0x2C0)?;
+                // 0x2C0: SE VF, 0x01
+                pc = if m.state.v[0xF] == 0x01 { 0x2C4 } else { 0x2C2 };
+                m.tick(pc)?;
+            },
+            0x2C2 => {
+                // 0x2C2: JP 0x2EE
+                m.tick(0x2EE)?;
+                pc = 0x2EE;
+            },
+            0x2C4 => {
+                // 0x2C4: SUB V0, V1
+                let difference = (m.state.v[0x0] as i8).wrapping_sub(m.state.v[0x1] as i8);
+                m.state.v[0x0] = difference as u8;
+                m.state.v[0xF] = u8::from(difference < 0);
+                m.tick(0x2C6)?;
+                // 0x2C6: SE VF, 0x01
+                pc = if m.state.v[0xF] == 0x01 { 0x2CA } else { 0x2C8 };
+                m.tick(pc)?;
+            },
+            0x2C8 => {
+                // 0x2C8: JP 0x2E8
+                m.tick(0x2E8)?;
+                pc = 0x2E8;
+            },
+            0x2CA => {
+                // 0x2CA: LD V0, 0x20
+                m.state.v[0x0] = 0x20;
+                m.tick(0x2CC)?;
+                // 0x2CC: LD ST, V0
+                m.state.st = m.state.v[0x0];
+                m.tick(0x2CE)?;
+                // 0x2CE: LD I, 0x30E
+                m.state.i = 0x30E;
+                m.tick(0x2D0)?;
+                // 0x2D0: ADD VE, 0xFF
+                m.state.v[0xE] = m.state.v[0xE].wrapping_add(0xFF);
+                m.tick(0x2D2)?;
+                // 0x2D2: LD V0, VE
+                m.state.v[0x0] = m.state.v[0xE];
+                m.tick(0x2D4)?;
+                // 0x2D4: ADD V0, V0
+                let sum = u16::from(m.state.v[0x0]) + u16::from(m.state.v[0x0]);
+                m.state.v[0xF] = u8::from(sum > 0xFF);
+                m.state.v[0x0] = sum as u8;
+                m.tick(0x2D6)?;
+                // 0x2D6: LD V1, 0x00
+                m.state.v[0x1] = 0x00;
+                m.tick(0x2D8)?;
+                // 0x2D8: DRW V0, V1, 1
+                m.host(0x2D8, 0xD011)?;
+                // 0x2DA: SE VE, 0x00
+                pc = if m.state.v[0xE] == 0x00 { 0x2DE } else { 0x2DC };
+                m.tick(pc)?;
+            },
+            0x2DC => {
+                // 0x2DC: JP 0x230
+                m.tick(0x230)?;
+                pc = 0x230;
+            },
+            0x2DE => {
+                // 0x2DE: JP 0x2DE
+                m.tick(0x2DE)?;
+                pc = 0x2DE;
+            },
+            0x2E0 => {
+                // 0x2E0: ADD V8, 0xFF
+                m.state.v[0x8] = m.state.v[0x8].wrapping_add(0xFF);
+                m.tick(0x2E2)?;
+                // 0x2E2: SNE V8, 0xFE
+                pc = if m.state.v[0x8] != 0xFE { 0x2E6 } else { 0x2E4 };
+                m.tick(pc)?;
+            },
+            0x2E4 => {
+                // 0x2E4: LD V8, 0xFF
+                m.state.v[0x8] = 0xFF;
+                m.tick(0x2E6)?;
+                pc = 0x2E6;
+            },
+            0x2E6 => {
+                // 0x2E6: JP 0x2EE
+                m.tick(0x2EE)?;
+                pc = 0x2EE;
+            },
+            0x2E8 => {
+                // 0x2E8: ADD V8, 0x01
+                m.state.v[0x8] = m.state.v[0x8].wrapping_add(0x01);
+                m.tick(0x2EA)?;
+                // 0x2EA: SNE V8, 0x02
+                pc = if m.state.v[0x8] != 0x02 { 0x2EE } else { 0x2EC };
+                m.tick(pc)?;
+            },
+            0x2EC => {
+                // 0x2EC: LD V8, 0x01
+                m.state.v[0x8] = 0x01;
+                m.tick(0x2EE)?;
+                pc = 0x2EE;
+            },
+            0x2EE => {
+                // 0x2EE: LD V0, 0x04
+                m.state.v[0x0] = 0x04;
+                m.tick(0x2F0)?;
+                // 0x2F0: LD ST, V0
+                m.state.st = m.state.v[0x0];
+                m.tick(0x2F2)?;
+                // 0x2F2: LD V9, 0xFF
+                m.state.v[0x9] = 0xFF;
+                m.tick(0x2F4)?;
+                // 0x2F4: JP 0x270
+                m.tick(0x270)?;
+                pc = 0x270;
+            },
+            _ => {
+                m.state.pc = pc;
+                return Err(Exit::Leave);
+            },
+        }
+    }
+}
+
+/// Routine at 0x2F6.
+pub fn sub_2f6<T: Input, U: Graphics>(m: &mut Machine<T, U>) -> Result<(), Exit> {
+    let pc = m.state.pc;
+    match pc {
+        0x2F6 => {
+            // 0x2F6: LD I, 0x314
+            m.state.i = 0x314;
+            m.tick(0x2F8)?;
+            // 0x2F8: LD B, V5
+            m.host(0x2F8, 0xF533)?;
+            // 0x2FA: LD V2, [I]
+            m.host(0x2FA, 0xF265)?;
+            // 0x2FC: LD F, V1
+            m.state.i = u16::from(m.state.v[0x1]) * 5;
+            m.tick(0x2FE)?;
+            // 0x2FE: LD V3, 0x37
+            m.state.v[0x3] = 0x37;
+            m.tick(0x300)?;
+            // 0x300: LD V4, 0x00
+            m.state.v[0x4] = 0x00;
+            m.tick(0x302)?;
+            // 0x302: DRW V3, V4, 5
+            m.host(0x302, 0xD345)?;
+            // 0x304: ADD V3, 0x05
+            m.state.v[0x3] = m.state.v[0x3].wrapping_add(0x05);
+            m.tick(0x306)?;
+            // 0x306: LD F, V2
+            m.state.i = u16::from(m.state.v[0x2]) * 5;
+            m.tick(0x308)?;
+            // 0x308: DRW V3, V4, 5
+            m.host(0x308, 0xD345)?;
+            // 0x30A: RET
+            m.ret()?;
+            Ok(())
+        },
+        _ => {
+            Err(Exit::Leave)
+        },
+    }
+}
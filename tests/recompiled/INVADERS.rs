@@ -0,0 +1,1153 @@
+// CHIP-8 ROM INVADERS recompiled to Rust with the default quirks: 6 routines, 202 instructions.
+//
+// Generated by `chip-8 recompile`.
+
+extern crate chip_8;
+
+use std::ops::Range;
+
+use chip_8::prelude::*;
+use chip_8::recompiled::{self, Exit, Machine, Routine};
+
+/// Quirk profile the routines were recompiled for.
+pub const QUIRKS: &str = "default";
+/// Instructions executed per frame.
+pub const CYCLES_PER_FRAME: u32 = 10;
+/// The ROM.
+pub const ROM: &[u8] = &[
+    0x12, 0x25, 0x53, 0x50, 0x41, 0x43, 0x45, 0x20, 0x49, 0x4E, 0x56, 0x41, 0x44, 0x45, 0x52, 0x53,
+    0x20, 0x76, 0x30, 0x2E, 0x39, 0x20, 0x42, 0x79, 0x20, 0x44, 0x61, 0x76, 0x69, 0x64, 0x20, 0x57,
+    0x49, 0x4E, 0x54, 0x45, 0x52, 0x60, 0x00, 0x61, 0x00, 0x62, 0x08, 0xA3, 0xD3, 0xD0, 0x18, 0x71,
+    0x08, 0xF2, 0x1E, 0x31, 0x20, 0x12, 0x2D, 0x70, 0x08, 0x61, 0x00, 0x30, 0x40, 0x12, 0x2D, 0x69,
+    0x05, 0x6C, 0x15, 0x6E, 0x00, 0x23, 0x87, 0x60, 0x0A, 0xF0, 0x15, 0xF0, 0x07, 0x30, 0x00, 0x12,
+    0x4B, 0x23, 0x87, 0x7E, 0x01, 0x12, 0x45, 0x66, 0x00, 0x68, 0x1C, 0x69, 0x00, 0x6A, 0x04, 0x6B,
+    0x0A, 0x6C, 0x04, 0x6D, 0x3C, 0x6E, 0x0F, 0x00, 0xE0, 0x23, 0x6B, 0x23, 0x47, 0xFD, 0x15, 0x60,
+    0x04, 0xE0, 0x9E, 0x12, 0x7D, 0x23, 0x6B, 0x38, 0x00, 0x78, 0xFF, 0x23, 0x6B, 0x60, 0x06, 0xE0,
+    0x9E, 0x12, 0x8B, 0x23, 0x6B, 0x38, 0x39, 0x78, 0x01, 0x23, 0x6B, 0x36, 0x00, 0x12, 0x9F, 0x60,
+    0x05, 0xE0, 0x9E, 0x12, 0xE9, 0x66, 0x01, 0x65, 0x1B, 0x84, 0x80, 0xA3, 0xCF, 0xD4, 0x51, 0xA3,
+    0xCF, 0xD4, 0x51, 0x75, 0xFF, 0x35, 0xFF, 0x12, 0xAD, 0x66, 0x00, 0x12, 0xE9, 0xD4, 0x51, 0x3F,
+    0x01, 0x12, 0xE9, 0xD4, 0x51, 0x66, 0x00, 0x83, 0x40, 0x73, 0x03, 0x83, 0xB5, 0x62, 0xF8, 0x83,
+    0x22, 0x62, 0x08, 0x33, 0x00, 0x12, 0xC9, 0x23, 0x73, 0x82, 0x06, 0x43, 0x08, 0x12, 0xD3, 0x33,
+    0x10, 0x12, 0xD5, 0x23, 0x73, 0x82, 0x06, 0x33, 0x18, 0x12, 0xDD, 0x23, 0x73, 0x82, 0x06, 0x43,
+    0x20, 0x12, 0xE7, 0x33, 0x28, 0x12, 0xE9, 0x23, 0x73, 0x3E, 0x00, 0x13, 0x07, 0x79, 0x06, 0x49,
+    0x18, 0x69, 0x00, 0x6A, 0x04, 0x6B, 0x0A, 0x6C, 0x04, 0x7D, 0xF4, 0x6E, 0x0F, 0x00, 0xE0, 0x23,
+    0x47, 0x23, 0x6B, 0xFD, 0x15, 0x12, 0x6F, 0xF7, 0x07, 0x37, 0x00, 0x12, 0x6F, 0xFD, 0x15, 0x23,
+    0x47, 0x8B, 0xA4, 0x3B, 0x12, 0x13, 0x1B, 0x7C, 0x02, 0x6A, 0xFC, 0x3B, 0x02, 0x13, 0x23, 0x7C,
+    0x02, 0x6A, 0x04, 0x23, 0x47, 0x3C, 0x18, 0x12, 0x6F, 0x00, 0xE0, 0xA4, 0xD3, 0x60, 0x14, 0x61,
+    0x08, 0x62, 0x0F, 0xD0, 0x1F, 0x70, 0x08, 0xF2, 0x1E, 0x30, 0x2C, 0x13, 0x33, 0xF0, 0x0A, 0x00,
+    0xE0, 0xA6, 0xF4, 0xFE, 0x65, 0x12, 0x25, 0xA3, 0xB7, 0xF9, 0x1E, 0x61, 0x08, 0x23, 0x5F, 0x81,
+    0x06, 0x23, 0x5F, 0x81, 0x06, 0x23, 0x5F, 0x81, 0x06, 0x23, 0x5F, 0x7B, 0xD0, 0x00, 0xEE, 0x80,
+    0xE0, 0x80, 0x12, 0x30, 0x00, 0xDB, 0xC6, 0x7B, 0x0C, 0x00, 0xEE, 0xA3, 0xCF, 0x60, 0x1C, 0xD8,
+    0x04, 0x00, 0xEE, 0x23, 0x47, 0x8E, 0x23, 0x23, 0x47, 0x60, 0x05, 0xF0, 0x18, 0xF0, 0x15, 0xF0,
+    0x07, 0x30, 0x00, 0x13, 0x7F, 0x00, 0xEE, 0x6A, 0x00, 0x8D, 0xE0, 0x6B, 0x04, 0xE9, 0xA1, 0x12,
+    0x57, 0xA6, 0x02, 0xFD, 0x1E, 0xF0, 0x65, 0x30, 0xFF, 0x13, 0xA5, 0x6A, 0x00, 0x6B, 0x04, 0x6D,
+    0x01, 0x6E, 0x01, 0x13, 0x8D, 0xA5, 0x00, 0xF0, 0x1E, 0xDB, 0xC6, 0x7B, 0x08, 0x7D, 0x01, 0x7A,
+    0x01, 0x3A, 0x07, 0x13, 0x8D, 0x00, 0xEE, 0x3C, 0x7E, 0xFF, 0xFF, 0x99, 0x99, 0x7E, 0xFF, 0xFF,
+    0x24, 0x24, 0xE7, 0x7E, 0xFF, 0x3C, 0x3C, 0x7E, 0xDB, 0x81, 0x42, 0x3C, 0x7E, 0xFF, 0xDB, 0x10,
+    0x38, 0x7C, 0xFE, 0x00, 0x00, 0x7F, 0x00, 0x3F, 0x00, 0x7F, 0x00, 0x00, 0x00, 0x01, 0x01, 0x01,
+    0x03, 0x03, 0x03, 0x03, 0x00, 0x00, 0x3F, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x3F,
+    0x08, 0x08, 0xFF, 0x00, 0x00, 0xFE, 0x00, 0xFC, 0x00, 0xFE, 0x00, 0x00, 0x00, 0x7E, 0x42, 0x42,
+    0x62, 0x62, 0x62, 0x62, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF,
+    0x00, 0x00, 0xFF, 0x00, 0x7D, 0x00, 0x41, 0x7D, 0x05, 0x7D, 0x7D, 0x00, 0x00, 0xC2, 0xC2, 0xC6,
+    0x44, 0x6C, 0x28, 0x38, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF,
+    0x00, 0x00, 0xFF, 0x00, 0xF7, 0x10, 0x14, 0xF7, 0xF7, 0x04, 0x04, 0x00, 0x00, 0x7C, 0x44, 0xFE,
+    0xC2, 0xC2, 0xC2, 0xC2, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF,
+    0x00, 0x00, 0xFF, 0x00, 0xEF, 0x20, 0x28, 0xE8, 0xE8, 0x2F, 0x2F, 0x00, 0x00, 0xF9, 0x85, 0xC5,
+    0xC5, 0xC5, 0xC5, 0xF9, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF,
+    0x00, 0x00, 0xFF, 0x00, 0xBE, 0x00, 0x20, 0x30, 0x20, 0xBE, 0xBE, 0x00, 0x00, 0xF7, 0x04, 0xE7,
+    0x85, 0x85, 0x84, 0xF4, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF,
+    0x00, 0x00, 0xFF, 0x00, 0x00, 0x7F, 0x00, 0x3F, 0x00, 0x7F, 0x00, 0x00, 0x00, 0xEF, 0x28, 0xEF,
+    0x00, 0xE0, 0x60, 0x6F, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF,
+    0x00, 0x00, 0xFF, 0x00, 0x00, 0xFE, 0x00, 0xFC, 0x00, 0xFE, 0x00, 0x00, 0x00, 0xC0, 0x00, 0xC0,
+    0xC0, 0xC0, 0xC0, 0xC0, 0x00, 0x00, 0xFC, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0xFC,
+    0x10, 0x10, 0xFF, 0xF9, 0x81, 0xB9, 0x8B, 0x9A, 0x9A, 0xFA, 0x00, 0xFA, 0x8A, 0x9A, 0x9A, 0x9B,
+    0x99, 0xF8, 0xE6, 0x25, 0x25, 0xF4, 0x34, 0x34, 0x34, 0x00, 0x17, 0x14, 0x34, 0x37, 0x36, 0x26,
+    0xC7, 0xDF, 0x50, 0x50, 0x5C, 0xD8, 0xD8, 0xDF, 0x00, 0xDF, 0x11, 0x1F, 0x12, 0x1B, 0x19, 0xD9,
+    0x7C, 0x44, 0xFE, 0x86, 0x86, 0x86, 0xFC, 0x84, 0xFE, 0x82, 0x82, 0xFE, 0xFE, 0x80, 0xC0, 0xC0,
+    0xC0, 0xFE, 0xFC, 0x82, 0xC2, 0xC2, 0xC2, 0xFC, 0xFE, 0x80, 0xF8, 0xC0, 0xC0, 0xFE, 0xFE, 0x80,
+    0xF0, 0xC0, 0xC0, 0xC0, 0xFE, 0x80, 0xBE, 0x86, 0x86, 0xFE, 0x86, 0x86, 0xFE, 0x86, 0x86, 0x86,
+    0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x18, 0x18, 0x18, 0x48, 0x48, 0x78, 0x9C, 0x90, 0xB0, 0xC0,
+    0xB0, 0x9C, 0x80, 0x80, 0xC0, 0xC0, 0xC0, 0xFE, 0xEE, 0x92, 0x92, 0x86, 0x86, 0x86, 0xFE, 0x82,
+    0x86, 0x86, 0x86, 0x86, 0x7C, 0x82, 0x86, 0x86, 0x86, 0x7C, 0xFE, 0x82, 0xFE, 0xC0, 0xC0, 0xC0,
+    0x7C, 0x82, 0xC2, 0xCA, 0xC4, 0x7A, 0xFE, 0x86, 0xFE, 0x90, 0x9C, 0x84, 0xFE, 0xC0, 0xFE, 0x02,
+    0x02, 0xFE, 0xFE, 0x10, 0x30, 0x30, 0x30, 0x30, 0x82, 0x82, 0xC2, 0xC2, 0xC2, 0xFE, 0x82, 0x82,
+    0x82, 0xEE, 0x38, 0x10, 0x86, 0x86, 0x96, 0x92, 0x92, 0xEE, 0x82, 0x44, 0x38, 0x38, 0x44, 0x82,
+    0x82, 0x82, 0xFE, 0x30, 0x30, 0x30, 0xFE, 0x02, 0x1E, 0xF0, 0x80, 0xFE, 0x00, 0x00, 0x00, 0x00,
+    0x06, 0x06, 0x00, 0x00, 0x00, 0x60, 0x60, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18,
+    0x18, 0x18, 0x00, 0x18, 0x7C, 0xC6, 0x0C, 0x18, 0x00, 0x18, 0x00, 0x00, 0xFE, 0xFE, 0x00, 0x00,
+    0xFE, 0x82, 0x86, 0x86, 0x86, 0xFE, 0x08, 0x08, 0x08, 0x18, 0x18, 0x18, 0xFE, 0x02, 0xFE, 0xC0,
+    0xC0, 0xFE, 0xFE, 0x02, 0x1E, 0x06, 0x06, 0xFE, 0x84, 0xC4, 0xC4, 0xFE, 0x04, 0x04, 0xFE, 0x80,
+    0xFE, 0x06, 0x06, 0xFE, 0xC0, 0xC0, 0xC0, 0xFE, 0x82, 0xFE, 0xFE, 0x02, 0x02, 0x06, 0x06, 0x06,
+    0x7C, 0x44, 0xFE, 0x86, 0x86, 0xFE, 0xFE, 0x82, 0xFE, 0x06, 0x06, 0x06, 0x44, 0xFE, 0x44, 0x44,
+    0xFE, 0x44, 0xA8, 0xA8, 0xA8, 0xA8, 0xA8, 0xA8, 0xA8, 0x6C, 0x5A, 0x00, 0x0C, 0x18, 0xA8, 0x30,
+    0x4E, 0x7E, 0x00, 0x12, 0x18, 0x66, 0x6C, 0xA8, 0x5A, 0x66, 0x54, 0x24, 0x66, 0x00, 0x48, 0x48,
+    0x18, 0x12, 0xA8, 0x06, 0x90, 0xA8, 0x12, 0x00, 0x7E, 0x30, 0x12, 0xA8, 0x84, 0x30, 0x4E, 0x72,
+    0x18, 0x66, 0xA8, 0xA8, 0xA8, 0xA8, 0xA8, 0xA8, 0x90, 0x54, 0x78, 0xA8, 0x48, 0x78, 0x6C, 0x72,
+    0xA8, 0x12, 0x18, 0x6C, 0x72, 0x66, 0x54, 0x90, 0xA8, 0x72, 0x2A, 0x18, 0xA8, 0x30, 0x4E, 0x7E,
+    0x00, 0x12, 0x18, 0x66, 0x6C, 0xA8, 0x72, 0x54, 0xA8, 0x5A, 0x66, 0x18, 0x7E, 0x18, 0x4E, 0x72,
+    0xA8, 0x72, 0x2A, 0x18, 0x30, 0x66, 0xA8, 0x30, 0x4E, 0x7E, 0x00, 0x6C, 0x30, 0x54, 0x4E, 0x9C,
+    0xA8, 0xA8, 0xA8, 0xA8, 0xA8, 0xA8, 0xA8, 0x48, 0x54, 0x7E, 0x18, 0xA8, 0x90, 0x54, 0x78, 0x66,
+    0xA8, 0x6C, 0x2A, 0x30, 0x5A, 0xA8, 0x84, 0x30, 0x72, 0x2A, 0xA8, 0xD8, 0xA8, 0x00, 0x4E, 0x12,
+    0xA8, 0xE4, 0xA2, 0xA8, 0x00, 0x4E, 0x12, 0xA8, 0x6C, 0x2A, 0x54, 0x54, 0x72, 0xA8, 0x84, 0x30,
+    0x72, 0x2A, 0xA8, 0xDE, 0x9C, 0xA8, 0x72, 0x2A, 0x18, 0xA8, 0x0C, 0x54, 0x48, 0x5A, 0x78, 0x72,
+    0x18, 0x66, 0xA8, 0x72, 0x18, 0x42, 0x42, 0x6C, 0xA8, 0x72, 0x2A, 0x00, 0x72, 0xA8, 0x72, 0x2A,
+    0x18, 0xA8, 0x30, 0x4E, 0x7E, 0x00, 0x12, 0x18, 0x66, 0x6C, 0xA8, 0x30, 0x4E, 0x0C, 0x66, 0x18,
+    0x00, 0x6C, 0x18, 0xA8, 0x72, 0x2A, 0x18, 0x30, 0x66, 0xA8, 0x1E, 0x54, 0x66, 0x0C, 0x18, 0x9C,
+    0xA8, 0x24, 0x54, 0x54, 0x12, 0xA8, 0x42, 0x78, 0x0C, 0x3C, 0xA8, 0xAE, 0xA8, 0xA8, 0xA8, 0xA8,
+    0xA8, 0xA8, 0xA8, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00,
+];
+/// Addresses of the recovered code.
+#[allow(clippy::single_range_in_vec_init)]
+pub const CODE: &[Range<u16>] = &[
+    0x200..0x202, 0x225..0x3B7,
+];
+
+fn main() {
+    recompiled::run(ROM, CODE, QUIRKS, CYCLES_PER_FRAME, dispatch);
+}
+
+/// Look up the routine with a basic block starting at an address.
+pub fn dispatch<T: Input, U: Graphics>(pc: u16) -> Option<Routine<T, U>> {
+    let routine: Routine<T, U> = match pc {
+        0x200 | 0x225 | 0x22D | 0x235 | 0x237 | 0x23D | 0x23F | 0x245 | 0x247 | 0x24B | 0x24F | 0x251 |
+            0x253 => sub_200,
+        0x347 | 0x34F | 0x353 | 0x357 | 0x35B => sub_347,
+        0x35F | 0x365 | 0x367 => sub_35f,
+        0x36B => sub_36b,
+        0x373 | 0x375 | 0x379 | 0x37F | 0x383 | 0x385 => sub_373,
+        0x257 | 0x26B | 0x26D | 0x26F | 0x273 | 0x275 | 0x277 | 0x279 | 0x27B | 0x27D | 0x281 | 0x283 | 0x285 | 0x287 |
+            0x289 | 0x28B | 0x28D | 0x28F | 0x293 | 0x295 | 0x29F | 0x2A7 | 0x2A9 | 0x2AD | 0x2B1 | 0x2B3 | 0x2C5 |
+            0x2C7 | 0x2C9 | 0x2CD | 0x2CF | 0x2D1 | 0x2D3 | 0x2D5 | 0x2D9 | 0x2DB | 0x2DD | 0x2E1 | 0x2E3 | 0x2E5 |
+            0x2E7 | 0x2E9 | 0x2EB | 0x2ED | 0x2F1 | 0x2F3 | 0x301 | 0x303 | 0x307 | 0x30B | 0x30D | 0x311 | 0x315 |
+            0x317 | 0x31B | 0x31D | 0x31F | 0x323 | 0x325 | 0x327 | 0x329 | 0x333 | 0x33B | 0x33D | 0x33F | 0x387 |
+            0x38D | 0x38F | 0x391 | 0x399 | 0x39B | 0x3A5 | 0x3B3 | 0x3B5 => sub_387,
+        _ => return None,
+    };
+    Some(routine)
+}
+
+/// Routine at 0x200.
+pub fn sub_200<T: Input, U: Graphics>(m: &mut Machine<T, U>) -> Result<(), Exit> {
+    let mut pc = m.state.pc;
+    loop {
+        match pc {
+            0x200 => {
+                // 0x200: JP 0x225
+                m.tick(0x225)?;
+                pc = 0x225;
+            },
+            0x225 => {
+                // 0x225: LD V0, 0x00
+                m.state.v[0x0] = 0x00;
+                m.tick(0x227)?;
+                // 0x227: LD V1, 0x00
+                m.state.v[0x1] = 0x00;
+                m.tick(0x229)?;
+                // 0x229: LD V2, 0x08
+                m.state.v[0x2] = 0x08;
+                m.tick(0x22B)?;
+                // 0x22B: LD I, 0x3D3
+                m.state.i = 0x3D3;
+                m.tick(0x22D)?;
+                pc = 0x22D;
+            },
+            0x22D => {
+                // 0x22D: DRW V0, V1, 8
+                m.host(0x22D, 0xD018)?;
+                // 0x22F: ADD V1, 0x08
+                m.state.v[0x1] = m.state.v[0x1].wrapping_add(0x08);
+                m.tick(0x231)?;
+                // 0x231: ADD I, V2
+                m.state.i = m.state.i.wrapping_add(u16::from(m.state.v[0x2]));
+                m.tick(0x233)?;
+                // 0x233: SE V1, 0x20
+                pc = if m.state.v[0x1] == 0x20 { 0x237 } else { 0x235 };
+                m.tick(pc)?;
+            },
+            0x235 => {
+                // 0x235: JP 0x22D
+                m.tick(0x22D)?;
+                pc = 0x22D;
+            },
+            0x237 => {
+                // 0x237: ADD V0, 0x08
+                m.state.v[0x0] = m.state.v[0x0].wrapping_add(0x08);
+                m.tick(0x239)?;
+                // 0x239: LD V1, 0x00
+                m.state.v[0x1] = 0x00;
+                m.tick(0x23B)?;
+                // 0x23B: SE V0, 0x40
+                pc = if m.state.v[0x0] == 0x40 { 0x23F } else { 0x23D };
+                m.tick(pc)?;
+            },
+            0x23D => {
+                // 0x23D: JP 0x22D
+                m.tick(0x22D)?;
+                pc = 0x22D;
+            },
+            0x23F => {
+                // 0x23F: LD V9, 0x05
+                m.state.v[0x9] = 0x05;
+                m.tick(0x241)?;
+                // 0x241: LD VC, 0x15
+                m.state.v[0xC] = 0x15;
+                m.tick(0x243)?;
+                // 0x243: LD VE, 0x00
+                m.state.v[0xE] = 0x00;
+                m.tick(0x245)?;
+                pc = 0x245;
+            },
+            0x245 => {
+                // 0x245: CALL 0x387
+                m.call(0x247, 0x387)?;
+                sub_387(m)?;
+                pc = m.state.pc;
+            },
+            0x247 => {
+                // 0x247: LD V0, 0x0A
+                m.state.v[0x0] = 0x0A;
+                m.tick(0x249)?;
+                // 0x249: LD DT, V0
+                m.state.dt = m.state.v[0x0];
+                m.tick(0x24B)?;
+                pc = 0x24B;
+            },
+            0x24B => {
+                // 0x24B: LD V0, DT
+                m.state.v[0x0] = m.state.dt;
+                m.tick(0x24D)?;
+                // 0x24D: SE V0, 0x00
+                pc = if m.state.v[0x0] == 0x00 { 0x251 } else { 0x24F };
+                m.tick(pc)?;
+            },
+            0x24F => {
+                // 0x24F: JP 0x24B
+                m.tick(0x24B)?;
+                pc = 0x24B;
+            },
+            0x251 => {
+                // 0x251: CALL 0x387
+                m.call(0x253, 0x387)?;
+                sub_387(m)?;
+                pc = m.state.pc;
+            },
+            0x253 => {
+                // 0x253: ADD VE, 0x01
+                m.state.v[0xE] = m.state.v[0xE].wrapping_add(0x01);
+                m.tick(0x255)?;
+                // 0x255: JP 0x245
+                m.tick(0x245)?;
+                pc = 0x245;
+            },
+            _ => {
+                m.state.pc = pc;
+                return Err(Exit::Leave);
+            },
+        }
+    }
+}
+
+/// Routine at 0x347.
+pub fn sub_347<T: Input, U: Graphics>(m: &mut Machine<T, U>) -> Result<(), Exit> {
+    let mut pc = m.state.pc;
+    loop {
+        match pc {
+            0x347 => {
+                // 0x347: LD I, 0x3B7
+                m.state.i = 0x3B7;
+                m.tick(0x349)?;
+                // 0x349: ADD I, V9
+                m.state.i = m.state.i.wrapping_add(u16::from(m.state.v[0x9]));
+                m.tick(0x34B)?;
+                // 0x34B: LD V1, 0x08
+                m.state.v[0x1] = 0x08;
+                m.tick(0x34D)?;
+                // 0x34D: CALL 0x35F
+                m.call(0x34F, 0x35F)?;
+                sub_35f(m)?;
+                pc = m.state.pc;
+            },
+            0x34F => {
+                // 0x34F: SHR V1
+                let value = m.state.v[0x1];
+                m.state.v[0xF] = value & 0x1;
+                m.state.v[0x1] = value >> 1;
+                m.tick(0x351)?;
+                // 0x351: CALL 0x35F
+                m.call(0x353, 0x35F)?;
+                sub_35f(m)?;
+                pc = m.state.pc;
+            },
+            0x353 => {
+                // 0x353: SHR V1
+                let value = m.state.v[0x1];
+                m.state.v[0xF] = value & 0x1;
+                m.state.v[0x1] = value >> 1;
+                m.tick(0x355)?;
+                // 0x355: CALL 0x35F
+                m.call(0x357, 0x35F)?;
+                sub_35f(m)?;
+                pc = m.state.pc;
+            },
+            0x357 => {
+                // 0x357: SHR V1
+                let value = m.state.v[0x1];
+                m.state.v[0xF] = value & 0x1;
+                m.state.v[0x1] = value >> 1;
+                m.tick(0x359)?;
+                // 0x359: CALL 0x35F
+                m.call(0x35B, 0x35F)?;
+                sub_35f(m)?;
+                pc = m.state.pc;
+            },
+            0x35B => {
+                // 0x35B: ADD VB, 0xD0
+                m.state.v[0xB] = m.state.v[0xB].wrapping_add(0xD0);
+                m.tick(0x35D)?;
+                // 0x35D: RET
+                m.ret()?;
+                return Ok(());
+            },
+            _ => {
+                m.state.pc = pc;
+                return Err(Exit::Leave);
+            },
+        }
+    }
+}
+
+/// Routine at 0x35F.
+pub fn sub_35f<T: Input, U: Graphics>(m: &mut Machine<T, U>) -> Result<(), Exit> {
+    let mut pc = m.state.pc;
+    loop {
+        match pc {
+            0x35F => {
+                // 0x35F: LD V0, VE
+                m.state.v[0x0] = m.state.v[0xE];
+                m.tick(0x361)?;
+                // 0x361: AND V0, V1
+                m.state.v[0x0] &= m.state.v[0x1];
+                m.tick(0x363)?;
+                // 0x363: SE V0, 0x00
+                pc = if m.state.v[0x0] == 0x00 { 0x367 } else { 0x365 };
+                m.tick(pc)?;
+            },
+            0x365 => {
+                // 0x365: DRW VB, VC, 6
+                m.host(0x365, 0xDBC6)?;
+                pc = 0x367;
+            },
+            0x367 => {
+                // 0x367: ADD VB, 0x0C
+                m.state.v[0xB] = m.state.v[0xB].wrapping_add(0x0C);
+                m.tick(0x369)?;
+                // 0x369: RET
+                m.ret()?;
+                return Ok(());
+            },
+            _ => {
+                m.state.pc = pc;
+                return Err(Exit::Leave);
+            },
+        }
+    }
+}
+
+/// Routine at 0x36B.
+pub fn sub_36b<T: Input, U: Graphics>(m: &mut Machine<T, U>) -> Result<(), Exit> {
+    let pc = m.state.pc;
+    match pc {
+        0x36B => {
+            // 0x36B: LD I, 0x3CF
+            m.state.i = 0x3CF;
+            m.tick(0x36D)?;
+            // 0x36D: LD V0, 0x1C
+            m.state.v[0x0] = 0x1C;
+            m.tick(0x36F)?;
+            // 0x36F: DRW V8, V0, 4
+            m.host(0x36F, 0xD804)?;
+            // 0x371: RET
+            m.ret()?;
+            Ok(())
+        },
+        _ => {
+            Err(Exit::Leave)
+        },
+    }
+}
+
+/// Routine at 0x373.
+pub fn sub_373<T: Input, U: Graphics>(m: &mut Machine<T, U>) -> Result<(), Exit> {
+    let mut pc = m.state.pc;
+    loop {
+        match pc {
+            0x373 => {
+                // 0x373: CALL 0x347
+                m.call(0x375, 0x347)?;
+                sub_347(m)?;
+                pc = m.state.pc;
+            },
+            0x375 => {
+                // 0x375: XOR VE, V2
+                m.state.v[0xE] ^= m.state.v[0x2];
+                m.tick(0x377)?;
+                // 0x377: CALL 0x347
+                m.call(0x379, 0x347)?;
+                sub_347(m)?;
+                pc = m.state.pc;
+            },
+            0x379 => {
+                // 0x379: LD V0, 0x05
+                m.state.v[0x0] = 0x05;
+                m.tick(0x37B)?;
+                // 0x37B: LD ST, V0
+                m.state.st = m.state.v[0x0];
+                m.tick(0x37D)?;
+                // 0x37D: LD DT, V0
+                m.state.dt = m.state.v[0x0];
+                m.tick(0x37F)?;
+                pc = 0x37F;
+            },
+            0x37F => {
+                // 0x37F: LD V0, DT
+                m.state.v[0x0] = m.state.dt;
+                m.tick(0x381)?;
+                // 0x381: SE V0, 0x00
+                pc = if m.state.v[0x0] == 0x00 { 0x385 } else { 0x383 };
+                m.tick(pc)?;
+            },
+            0x383 => {
+                // 0x383: JP 0x37F
+                m.tick(0x37F)?;
+                pc = 0x37F;
+            },
+            0x385 => {
+                // 0x385: RET
+                m.ret()?;
+                return Ok(());
+            },
+            _ => {
+                m.state.pc = pc;
+                return Err(Exit::Leave);
+            },
+        }
+    }
+}
+
+/// Routine at 0x387.
+pub fn sub_387<T: Input, U: Graphics>(m: &mut Machine<T, U>) -> Result<(), Exit> {
+    let mut pc = m.state.pc;
+    loop {
+        match pc {
+            0x225 => {
+                // 0x225: LD V0, 0x00
+                m.state.v[0x0] = 0x00;
+                m.tick(0x227)?;
+                // 0x227: LD V1, 0x00
+                m.state.v[0x1] = 0x00;
+                m.tick(0x229)?;
+                // 0x229: LD V2, 0x08
+                m.state.v[0x2] = 0x08;
+                m.tick(0x22B)?;
+                // 0x22B: LD I, 0x3D3
+                m.state.i = 0x3D3;
+                m.tick(0x22D)?;
+                pc = 0x22D;
+            },
+            0x22D => {
+                // 0x22D: DRW V0, V1, 8
+                m.host(0x22D, 0xD018)?;
+                // 0x22F: ADD V1, 0x08
+                m.state.v[0x1] = m.state.v[0x1].wrapping_add(0x08);
+                m.tick(0x231)?;
+                // 0x231: ADD I, V2
+                m.state.i = m.state.i.wrapping_add(u16::from(m.state.v[0x2]));
+                m.tick(0x233)?;
+                // 0x233: SE V1, 0x20
+                pc = if m.state.v[0x1] == 0x20 { 0x237 } else { 0x235 };
+                m.tick(pc)?;
+            },
+            0x235 => {
+                // 0x235: JP 0x22D
+                m.tick(0x22D)?;
+                pc = 0x22D;
+            },
+            0x237 => {
+                // 0x237: ADD V0, 0x08
+                m.state.v[0x0] = m.state.v[0x0].wrapping_add(0x08);
+                m.tick(0x239)?;
+                // 0x239: LD V1, 0x00
+                m.state.v[0x1] = 0x00;
+                m.tick(0x23B)?;
+                // 0x23B: SE V0, 0x40
+                pc = if m.state.v[0x0] == 0x40 { 0x23F } else { 0x23D };
+                m.tick(pc)?;
+            },
+            0x23D => {
+                // 0x23D: JP 0x22D
+                m.tick(0x22D)?;
+                pc = 0x22D;
+            },
+            0x23F => {
+                // 0x23F: LD V9, 0x05
+                m.state.v[0x9] = 0x05;
+                m.tick(0x241)?;
+                // 0x241: LD VC, 0x15
+                m.state.v[0xC] = 0x15;
+                m.tick(0x243)?;
+                // 0x243: LD VE, 0x00
+                m.state.v[0xE] = 0x00;
+                m.tick(0x245)?;
+                pc = 0x245;
+            },
+            0x245 => {
+                // 0x245: CALL 0x387
+                m.call(0x247, 0x387)?;
+                sub_387(m)?;
+                pc = m.state.pc;
+            },
+            0x247 => {
+                // 0x247: LD V0, 0x0A
+                m.state.v[0x0] = 0x0A;
+                m.tick(0x249)?;
+                // 0x249: LD DT, V0
+                m.state.dt = m.state.v[0x0];
+                m.tick(0x24B)?;
+                pc = 0x24B;
+            },
+            0x24B => {
+                // 0x24B: LD V0, DT
+                m.state.v[0x0] = m.state.dt;
+                m.tick(0x24D)?;
+                // 0x24D: SE V0, 0x00
+                pc = if m.state.v[0x0] == 0x00 { 0x251 } else { 0x24F };
+                m.tick(pc)?;
+            },
+            0x24F => {
+                // 0x24F: JP 0x24B
+                m.tick(0x24B)?;
+                pc = 0x24B;
+            },
+            0x251 => {
+                // 0x251: CALL 0x387
+                m.call(0x253, 0x387)?;
+                sub_387(m)?;
+                pc = m.state.pc;
+            },
+            0x253 => {
+                // 0x253: ADD VE, 0x01
+                m.state.v[0xE] = m.state.v[0xE].wrapping_add(0x01);
+                m.tick(0x255)?;
+                // 0x255: JP 0x245
+                m.tick(0x245)?;
+                pc = 0x245;
+            },
+            0x257 => {
+                // 0x257: LD V6, 0x00
+                m.state.v[0x6] = 0x00;
+                m.tick(0x259)?;
+                // 0x259: LD V8, 0x1C
+                m.state.v[0x8] = 0x1C;
+                m.tick(0x25B)?;
+                // 0x25B: LD V9, 0x00
+                m.state.v[0x9] = 0x00;
+                m.tick(0x25D)?;
+                // 0x25D: LD VA, 0x04
+                m.state.v[0xA] = 0x04;
+                m.tick(0x25F)?;
+                // 0x25F: LD VB, 0x0A
+                m.state.v[0xB] = 0x0A;
+                m.tick(0x261)?;
+                // 0x261: LD VC, 0x04
+                m.state.v[0xC] = 0x04;
+                m.tick(0x263)?;
+                // 0x263: LD VD, 0x3C
+                m.state.v[0xD] = 0x3C;
+                m.tick(0x265)?;
+                // 0x265: LD VE, 0x0F
+                m.state.v[0xE] = 0x0F;
+                m.tick(0x267)?;
+                // 0x267: CLS
+                m.host(0x267, 0x00E0)?;
+                // 0x269: CALL 0x36B
+                m.call(0x26B, 0x36B)?;
+                sub_36b(m)?;
+                pc = m.state.pc;
+            },
+            0x26B => {
+                // 0x26B: CALL 0x347
+                m.call(0x26D, 0x347)?;
+                sub_347(m)?;
+                pc = m.state.pc;
+            },
+            0x26D => {
+                // 0x26D: LD DT, VD
+                m.state.dt = m.state.v[0xD];
+                m.tick(0x26F)?;
+                pc = 0x26F;
+            },
+            0x26F => {
+                // 0x26F: LD V0, 0x04
+                m.state.v[0x0] = 0x04;
+                m.tick(0x271)?;
+                // 0x271: SKP V0
+                m.host(0x271, 0xE09E)?;
+                pc = m.state.pc;
+            },
+            0x273 => {
+                // 0x273: JP 0x27D
+                m.tick(0x27D)?;
+                pc = 0x27D;
+            },
+            0x275 => {
+                // 0x275: CALL 0x36B
+                m.call(0x277, 0x36B)?;
+                sub_36b(m)?;
+                pc = m.state.pc;
+            },
+            0x277 => {
+                // 0x277: SE V8, 0x00
+                pc = if m.state.v[0x8] == 0x00 { 0x27B } else { 0x279 };
+                m.tick(pc)?;
+            },
+            0x279 => {
+                // 0x279: ADD V8, 0xFF
+                m.state.v[0x8] = m.state.v[0x8].wrapping_add(0xFF);
+                m.tick(0x27B)?;
+                pc = 0x27B;
+            },
+            0x27B => {
+                // 0x27B: CALL 0x36B
+                m.call(0x27D, 0x36B)?;
+                sub_36b(m)?;
+                pc = m.state.pc;
+            },
+            0x27D => {
+                // 0x27D: LD V0, 0x06
+                m.state.v[0x0] = 0x06;
+                m.tick(0x27F)?;
+                // 0x27F: SKP V0
+                m.host(0x27F, 0xE09E)?;
+                pc = m.state.pc;
+            },
+            0x281 => {
+                // 0x281: JP 0x28B
+                m.tick(0x28B)?;
+                pc = 0x28B;
+            },
+            0x283 => {
+                // 0x283: CALL 0x36B
+                m.call(0x285, 0x36B)?;
+                sub_36b(m)?;
+                pc = m.state.pc;
+            },
+            0x285 => {
+                // 0x285: SE V8, 0x39
+                pc = if m.state.v[0x8] == 0x39 { 0x289 } else { 0x287 };
+                m.tick(pc)?;
+            },
+            0x287 => {
+                // 0x287: ADD V8, 0x01
+                m.state.v[0x8] = m.state.v[0x8].wrapping_add(0x01);
+                m.tick(0x289)?;
+                pc = 0x289;
+            },
+            0x289 => {
+                // 0x289: CALL 0x36B
+                m.call(0x28B, 0x36B)?;
+                sub_36b(m)?;
+                pc = m.state.pc;
+            },
+            0x28B => {
+                // 0x28B: SE V6, 0x00
+                pc = if m.state.v[0x6] == 0x00 { 0x28F } else { 0x28D };
+                m.tick(pc)?;
+            },
+            0x28D => {
+                // 0x28D: JP 0x29F
+                m.tick(0x29F)?;
+                pc = 0x29F;
+            },
+            0x28F => {
+                // 0x28F: LD V0, 0x05
+                m.state.v[0x0] = 0x05;
+                m.tick(0x291)?;
+                // 0x291: SKP V0
+                m.host(0x291, 0xE09E)?;
+                pc = m.state.pc;
+            },
+            0x293 => {
+                // 0x293: JP 0x2E9
+                m.tick(0x2E9)?;
+                pc = 0x2E9;
+            },
+            0x295 => {
+                // 0x295: LD V6, 0x01
+                m.state.v[0x6] = 0x01;
+                m.tick(0x297)?;
+                // 0x297: LD V5, 0x1B
+                m.state.v[0x5] = 0x1B;
+                m.tick(0x299)?;
+                // 0x299: LD V4, V8
+                m.state.v[0x4] = m.state.v[0x8];
+                m.tick(0x29B)?;
+                // 0x29B: LD I, 0x3CF
+                m.state.i = 0x3CF;
+                m.tick(0x29D)?;
+                // 0x29D: DRW V4, V5, 1
+                m.host(0x29D, 0xD451)?;
+                pc = 0x29F;
+            },
+            0x29F => {
+                // 0x29F: LD I, 0x3CF
+                m.state.i = 0x3CF;
+                m.tick(0x2A1)?;
+                // 0x2A1: DRW V4, V5, 1
+                m.host(0x2A1, 0xD451)?;
+                // 0x2A3: ADD V5, 0xFF
+                m.state.v[0x5] = m.state.v[0x5].wrapping_add(0xFF);
+                m.tick(0x2A5)?;
+                // 0x2A5: SE V5, 0xFF
+                pc = if m.state.v[0x5] == 0xFF { 0x2A9 } else { 0x2A7 };
+                m.tick(pc)?;
+            },
+            0x2A7 => {
+                // 0x2A7: JP 0x2AD
+                m.tick(0x2AD)?;
+                pc = 0x2AD;
+            },
+            0x2A9 => {
+                // 0x2A9: LD V6, 0x00
+                m.state.v[0x6] = 0x00;
+                m.tick(0x2AB)?;
+                // 0x2AB: JP 0x2E9
+                m.tick(0x2E9)?;
+                pc = 0x2E9;
+            },
+            0x2AD => {
+                // 0x2AD: DRW V4, V5, 1
+                m.host(0x2AD, 0xD451)?;
+                // 0x2AF: SE VF, 0x01
+                pc = if m.state.v[0xF] == 0x01 { 0x2B3 } else { 0x2B1 };
+                m.tick(pc)?;
+            },
+            0x2B1 => {
+                // 0x2B1: JP 0x2E9
+                m.tick(0x2E9)?;
+                pc = 0x2E9;
+            },
+            0x2B3 => {
+                // 0x2B3: DRW V4, V5, 1
+                m.host(0x2B3, 0xD451)?;
+                // 0x2B5: LD V6, 0x00
+                m.state.v[0x6] = 0x00;
+                m.tick(0x2B7)?;
+                // 0x2B7: LD V3, V4
+                m.state.v[0x3] = m.state.v[0x4];
+                m.tick(0x2B9)?;
+                // 0x2B9: ADD V3, 0x03
+                m.state.v[0x3] = m.state.v[0x3].wrapping_add(0x03);
+                m.tick(0x2BB)?;
+                // 0x2BB: SUB V3, VB
+                let difference = (m.state.v[0x3] as i8).wrapping_sub(m.state.v[0xB] as i8);
+                m.state.v[0x3] = difference as u8;
+                m.state.v[0xF] = u8::from(difference < 0);
+                m.tick(0x2BD)?;
+                // 0x2BD: LD V2, 0xF8
+                m.state.v[0x2] = 0xF8;
+                m.tick(0x2BF)?;
+                // 0x2BF: AND V3, V2
+                m.state.v[0x3] &= m.state.v[0x2];
+                m.tick(0x2C1)?;
+                // 0x2C1: LD V2, 0x08
+                m.state.v[0x2] = 0x08;
+                m.tick(0x2C3)?;
+                // 0x2C3: SE V3, 0x00
+                pc = if m.state.v[0x3] == 0x00 { 0x2C7 } else { 0x2C5 };
+                m.tick(pc)?;
+            },
+            0x2C5 => {
+                // 0x2C5: JP 0x2C9
+                m.tick(0x2C9)?;
+                pc = 0x2C9;
+            },
+            0x2C7 => {
+                // 0x2C7: CALL 0x373
+                m.call(0x2C9, 0x373)?;
+                sub_373(m)?;
+                pc = m.state.pc;
+            },
+            0x2C9 => {
+                // 0x2C9: SHR V2
+                let value = m.state.v[0x2];
+                m.state.v[0xF] = value & 0x1;
+                m.state.v[0x2] = value >> 1;
+                m.tick(0x2CB)?;
+                // 0x2CB: SNE V3, 0x08
+                pc = if m.state.v[0x3] != 0x08 { 0x2CF } else { 0x2CD };
+                m.tick(pc)?;
+            },
+            0x2CD => {
+                // 0x2CD: JP 0x2D3
+                m.tick(0x2D3)?;
+                pc = 0x2D3;
+            },
+            0x2CF => {
+                // 0x2CF: SE V3, 0x10
+                pc = if m.state.v[0x3] == 0x10 { 0x2D3 } else { 0x2D1 };
+                m.tick(pc)?;
+            },
+            0x2D1 => {
+                // 0x2D1: JP 0x2D5
+                m.tick(0x2D5)?;
+                pc = 0x2D5;
+            },
+            0x2D3 => {
+                // 0x2D3: CALL 0x373
+                m.call(0x2D5, 0x373)?;
+                sub_373(m)?;
+                pc = m.state.pc;
+            },
+            0x2D5 => {
+                // 0x2D5: SHR V2
+                let value = m.state.v[0x2];
+                m.state.v[0xF] = value & 0x1;
+                m.state.v[0x2] = value >> 1;
+                m.tick(0x2D7)?;
+                // 0x2D7: SE V3, 0x18
+                pc = if m.state.v[0x3] == 0x18 { 0x2DB } else { 0x2D9 };
+                m.tick(pc)?;
+            },
+            0x2D9 => {
+                // 0x2D9: JP 0x2DD
+                m.tick(0x2DD)?;
+                pc = 0x2DD;
+            },
+            0x2DB => {
+                // 0x2DB: CALL 0x373
+                m.call(0x2DD, 0x373)?;
+                sub_373(m)?;
+                pc = m.state.pc;
+            },
+            0x2DD => {
+                // 0x2DD: SHR V2
+                let value = m.state.v[0x2];
+                m.state.v[0xF] = value & 0x1;
+                m.state.v[0x2] = value >> 1;
+                m.tick(0x2DF)?;
+                // 0x2DF: SNE V3, 0x20
+                pc = if m.state.v[0x3] != 0x20 { 0x2E3 } else { 0x2E1 };
+                m.tick(pc)?;
+            },
+            0x2E1 => {
+                // 0x2E1: JP 0x2E7
+                m.tick(0x2E7)?;
+                pc = 0x2E7;
+            },
+            0x2E3 => {
+                // 0x2E3: SE V3, 0x28
+                pc = if m.state.v[0x3] == 0x28 { 0x2E7 } else { 0x2E5 };
+                m.tick(pc)?;
+            },
+            0x2E5 => {
+                // 0x2E5: JP 0x2E9
+                m.tick(0x2E9)?;
+                pc = 0x2E9;
+            },
+            0x2E7 => {
+                // 0x2E7: CALL 0x373
+                m.call(0x2E9, 0x373)?;
+                sub_373(m)?;
+                pc = m.state.pc;
+            },
+            0x2E9 => {
+                // 0x2E9: SE VE, 0x00
+                pc = if m.state.v[0xE] == 0x00 { 0x2ED } else { 0x2EB };
+                m.tick(pc)?;
+            },
+            0x2EB => {
+                // 0x2EB: JP 0x307
+                m.tick(0x307)?;
+                pc = 0x307;
+            },
+            0x2ED => {
+                // 0x2ED: ADD V9, 0x06
+                m.state.v[0x9] = m.state.v[0x9].wrapping_add(0x06);
+                m.tick(0x2EF)?;
+                // 0x2EF: SNE V9, 0x18
+                pc = if m.state.v[0x9] != 0x18 { 0x2F3 } else { 0x2F1 };
+                m.tick(pc)?;
+            },
+            0x2F1 => {
+                // 0x2F1: LD V9, 0x00
+                m.state.v[0x9] = 0x00;
+                m.tick(0x2F3)?;
+                pc = 0x2F3;
+            },
+            0x2F3 => {
+                // 0x2F3: LD VA, 0x04
+                m.state.v[0xA] = 0x04;
+                m.tick(0x2F5)?;
+                // 0x2F5: LD VB, 0x0A
+                m.state.v[0xB] = 0x0A;
+                m.tick(0x2F7)?;
+                // 0x2F7: LD VC, 0x04
+                m.state.v[0xC] = 0x04;
+                m.tick(0x2F9)?;
+                // 0x2F9: ADD VD, 0xF4
+                m.state.v[0xD] = m.state.v[0xD].wrapping_add(0xF4);
+                m.tick(0x2FB)?;
+                // 0x2FB: LD VE, 0x0F
+                m.state.v[0xE] = 0x0F;
+                m.tick(0x2FD)?;
+                // 0x2FD: CLS
+                m.host(0x2FD, 0x00E0)?;
+                // 0x2FF: CALL 0x347
+                m.call(0x301, 0x347)?;
+                sub_347(m)?;
+                pc = m.state.pc;
+            },
+            0x301 => {
+                // 0x301: CALL 0x36B
+                m.call(0x303, 0x36B)?;
+                sub_36b(m)?;
+                pc = m.state.pc;
+            },
+            0x303 => {
+                // 0x303: LD DT, VD
+                m.state.dt = m.state.v[0xD];
+                m.tick(0x305)?;
+                // 0x305: JP 0x26F
+                m.tick(0x26F)?;
+                pc = 0x26F;
+            },
+            0x307 => {
+                // 0x307: LD V7, DT
+                m.state.v[0x7] = m.state.dt;
+                m.tick(0x309)?;
+                // 0x309: SE V7, 0x00
+                pc = if m.state.v[0x7] == 0x00 { 0x30D } else { 0x30B };
+                m.tick(pc)?;
+            },
+            0x30B => {
+                // 0x30B: JP 0x26F
+                m.tick(0x26F)?;
+                pc = 0x26F;
+            },
+            0x30D => {
+                // 0x30D: LD DT, VD
+                m.state.dt = m.state.v[0xD];
+                m.tick(0x30F)?;
+                // 0x30F: CALL 0x347
+                m.call(0x311, 0x347)?;
+                sub_347(m)?;
+                pc = m.state.pc;
+            },
+            0x311 => {
+                // 0x311: ADD VB, VA
+                let sum = u16::from(m.state.v[0xB]) + u16::from(m.state.v[0xA]);
+                m.state.v[0xF] = u8::from(sum > 0xFF);
+                m.state.v[0xB] = sum as u8;
+                m.tick(0x313)?;
+                // 0x313: SE VB, 0x12
+                pc = if m.state.v[0xB] == 0x12 { 0x317 } else { 0x315 };
+                m.tick(pc)?;
+            },
+            0x315 => {
+                // 0x315: JP 0x31B
+                m.tick(0x31B)?;
+                pc = 0x31B;
+            },
+            0x317 => {
+                // 0x317: ADD VC, 0x02
+                m.state.v[0xC] = m.state.v[0xC].wrapping_add(0x02);
+                m.tick(0x319)?;
+                // 0x319: LD VA, 0xFC
+                m.state.v[0xA] = 0xFC;
+                m.tick(0x31B)?;
+                pc = 0x31B;
+            },
+            0x31B => {
+                // 0x31B: SE VB, 0x02
+                pc = if m.state.v[0xB] == 0x02 { 0x31F } else { 0x31D };
+                m.tick(pc)?;
+            },
+            0x31D => {
+                // 0x31D: JP 0x323
+                m.tick(0x323)?;
+                pc = 0x323;
+            },
+            0x31F => {
+                // 0x31F: ADD VC, 0x02
+                m.state.v[0xC] = m.state.v[0xC].wrapping_add(0x02);
+                m.tick(0x321)?;
+                // 0x321: LD VA, 0x04
+                m.state.v[0xA] = 0x04;
+                m.tick(0x323)?;
+                pc = 0x323;
+            },
+            0x323 => {
+                // 0x323: CALL 0x347
+                m.call(0x325, 0x347)?;
+                sub_347(m)?;
+                pc = m.state.pc;
+            },
+            0x325 => {
+                // 0x325: SE VC, 0x18
+                pc = if m.state.v[0xC] == 0x18 { 0x329 } else { 0x327 };
+                m.tick(pc)?;
+            },
+            0x327 => {
+                // 0x327: JP 0x26F
+                m.tick(0x26F)?;
+                pc = 0x26F;
+            },
+            0x329 => {
+                // 0x329: CLS
+                m.host(0x329, 0x00E0)?;
+                // 0x32B: LD I, 0x4D3
+                m.state.i = 0x4D3;
+                m.tick(0x32D)?;
+                // 0x32D: LD V0, 0x14
+                m.state.v[0x0] = 0x14;
+                m.tick(0x32F)?;
+                // 0x32F: LD V1, 0x08
+                m.state.v[0x1] = 0x08;
+                m.tick(0x331)?;
+                // 0x331: LD V2, 0x0F
+                m.state.v[0x2] = 0x0F;
+                m.tick(0x333)?;
+                pc = 0x333;
+            },
+            0x333 => {
+                // 0x333: DRW V0, V1, 15
+                m.host(0x333, 0xD01F)?;
+                // 0x335: ADD V0, 0x08
+                m.state.v[0x0] = m.state.v[0x0].wrapping_add(0x08);
+                m.tick(0x337)?;
+                // 0x337: ADD I, V2
+                m.state.i = m.state.i.wrapping_add(u16::from(m.state.v[0x2]));
+                m.tick(0x339)?;
+                // 0x339: SE V0, 0x2C
+                pc = if m.state.v[0x0] == 0x2C { 0x33D } else { 0x33B };
+                m.tick(pc)?;
+            },
+            0x33B => {
+                // 0x33B: JP 0x333
+                m.tick(0x333)?;
+                pc = 0x333;
+            },
+            0x33D => {
+                // 0x33D: LD V0, K
+                m.host(0x33D, 0xF00A)?;
+                pc = m.state.pc;
+            },
+            0x33F => {
+                // 0x33F: CLS
+                m.host(0x33F, 0x00E0)?;
+                // 0x341: LD I, 0x6F4
+                m.state.i = 0x6F4;
+                m.tick(0x343)?;
+                // 0x343: LD VE, [I]
+                m.host(0x343, 0xFE65)?;
+                // 0x345: JP 0x225
+                m.tick(0x225)?;
+                pc = 0x225;
+            },
+            0x387 => {
+                // 0x387: LD VA, 0x00
+                m.state.v[0xA] = 0x00;
+                m.tick(0x389)?;
+                // 0x389: LD VD, VE
+                m.state.v[0xD] = m.state.v[0xE];
+                m.tick(0x38B)?;
+                // 0x38B: LD VB, 0x04
+                m.state.v[0xB] = 0x04;
+                m.tick(0x38D)?;
+                pc = 0x38D;
+            },
+            0x38D => {
+                // 0x38D: SKNP V9
+                m.host(0x38D, 0xE9A1)?;
+                pc = m.state.pc;
+            },
+            0x38F => {
+                // 0x38F: JP 0x257
+                m.tick(0x257)?;
+                pc = 0x257;
+            },
+            0x391 => {
+                // 0x391: LD I, 0x602
+                m.state.i = 0x602;
+                m.tick(0x393)?;
+                // 0x393: ADD I, VD
+                m.state.i = m.state.i.wrapping_add(u16::from(m.state.v[0xD]));
+                m.tick(0x395)?;
+                // 0x395: LD V0, [I]
+                m.host(0x395, 0xF065)?;
+                // 0x397: SE V0, 0xFF
+                pc = if m.state.v[0x0] == 0xFF { 0x39B } else { 0x399 };
+                m.tick(pc)?;
+            },
+            0x399 => {
+                // 0x399: JP 0x3A5
+                m.tick(0x3A5)?;
+                pc = 0x3A5;
+            },
+            0x39B => {
+                // 0x39B: LD VA, 0x00
+                m.state.v[0xA] = 0x00;
+                m.tick(0x39D)?;
+                // 0x39D: LD VB, 0x04
+                m.state.v[0xB] = 0x04;
+                m.tick(0x39F)?;
+                // 0x39F: LD VD, 0x01
+                m.state.v[0xD] = 0x01;
+                m.tick(0x3A1)?;
+                // 0x3A1: LD VE, 0x01
+                m.state.v[0xE] = 0x01;
+                m.tick(0x3A3)?;
+                // 0x3A3: JP 0x38D
+                m.tick(0x38D)?;
+                pc = 0x38D;
+            },
+            0x3A5 => {
+                // 0x3A5: LD I, 0x500
+                m.state.i = 0x500;
+                m.tick(0x3A7)?;
+                // 0x3A7: ADD I, V0
+                m.state.i = m.state.i.wrapping_add(u16::from(m.state.v[0x0]));
+                m.tick(0x3A9)?;
+                // 0x3A9: DRW VB, VC, 6
+                m.host(0x3A9, 0xDBC6)?;
+                // 0x3AB: ADD VB, 0x08
+                m.state.v[0xB] = m.state.v[0xB].wrapping_add(0x08);
+                m.tick(0x3AD)?;
+                // 0x3AD: ADD VD, 0x01
+                m.state.v[0xD] = m.state.v[0xD].wrapping_add(0x01);
+                m.tick(0x3AF)?;
+                // 0x3AF: ADD VA, 0x01
+                m.state.v[0xA] = m.state.v[0xA].wrapping_add(0x01);
+                m.tick(0x3B1)?;
+                // 0x3B1: SE VA, 0x07
+                pc = if m.state.v[0xA] == 0x07 { 0x3B5 } else { 0x3B3 };
+                m.tick(pc)?;
+            },
+            0x3B3 => {
+                // 0x3B3: JP 0x38D
+                m.tick(0x38D)?;
+                pc = 0x38D;
+            },
+            0x3B5 => {
+                // 0x3B5: RET
+                m.ret()?;
+                return Ok(());
+            },
+            _ => {
+                m.state.pc = pc;
+                return Err(Exit::Leave);
+            },
+        }
+    }
+}
@@ -0,0 +1,144 @@
+// CHIP-8 ROM JUMP_TABLE recompiled to Rust with the default quirks: 1 routines, 22 instructions.
+//
+// Generated by `chip-8 recompile`.
+
+extern crate chip_8;
+
+use std::ops::Range;
+
+use chip_8::prelude::*;
+use chip_8::recompiled::{self, Exit, Machine, Routine};
+
+/// Quirk profile the routines were recompiled for.
+pub const QUIRKS: &str = "default";
+/// Instructions executed per frame.
+pub const CYCLES_PER_FRAME: u32 = 10;
+/// The ROM.
+pub const ROM: &[u8] = &[
+    0x60, 0x00, 0x61, 0x00, 0xB2, 0x06, 0x12, 0x0E, 0x12, 0x14, 0x12, 0x1A, 0x12, 0x14, 0x71, 0x01,
+    0x70, 0x02, 0x12, 0x20, 0x71, 0x03, 0x70, 0x02, 0x12, 0x20, 0x81, 0x14, 0x70, 0x02, 0x12, 0x20,
+    0x40, 0x08, 0x60, 0x00, 0xF1, 0x29, 0x00, 0xE0, 0xD0, 0x05, 0x12, 0x04,
+];
+/// Addresses of the recovered code.
+#[allow(clippy::single_range_in_vec_init)]
+pub const CODE: &[Range<u16>] = &[
+    0x200..0x22C,
+];
+
+fn main() {
+    recompiled::run(ROM, CODE, QUIRKS, CYCLES_PER_FRAME, dispatch);
+}
+
+/// Look up the routine with a basic block starting at an address.
+pub fn dispatch<T: Input, U: Graphics>(pc: u16) -> Option<Routine<T, U>> {
+    let routine: Routine<T, U> = match pc {
+        0x200 | 0x204 | 0x206 | 0x208 | 0x20A | 0x20C | 0x20E | 0x214 | 0x21A | 0x220 | 0x222 | 0x224 => sub_200,
+        _ => return None,
+    };
+    Some(routine)
+}
+
+/// Routine at 0x200.
+pub fn sub_200<T: Input, U: Graphics>(m: &mut Machine<T, U>) -> Result<(), Exit> {
+    let mut pc = m.state.pc;
+    loop {
+        match pc {
+            0x200 => {
+                // 0x200: LD V0, 0x00
+                m.state.v[0x0] = 0x00;
+                m.tick(0x202)?;
+                // 0x202: LD V1, 0x00
+                m.state.v[0x1] = 0x00;
+                m.tick(0x204)?;
+                pc = 0x204;
+            },
+            0x204 => {
+                // 0x204: JP V0, 0x206
+                pc = 0x206 + u16::from(m.state.v[0x0]);
+                m.tick(pc)?;
+            },
+            0x206 => {
+                // 0x206: JP 0x20E
+                m.tick(0x20E)?;
+                pc = 0x20E;
+            },
+            0x208 => {
+                // 0x208: JP 0x214
+                m.tick(0x214)?;
+                pc = 0x214;
+            },
+            0x20A => {
+                // 0x20A: JP 0x21A
+                m.tick(0x21A)?;
+                pc = 0x21A;
+            },
+            0x20C => {
+                // 0x20C: JP 0x214
+                m.tick(0x214)?;
+                pc = 0x214;
+            },
+            0x20E => {
+                // 0x20E: ADD V1, 0x01
+                m.state.v[0x1] = m.state.v[0x1].wrapping_add(0x01);
+                m.tick(0x210)?;
+                // 0x210: ADD V0, 0x02
+                m.state.v[0x0] = m.state.v[0x0].wrapping_add(0x02);
+                m.tick(0x212)?;
+                // 0x212: JP 0x220
+                m.tick(0x220)?;
+                pc = 0x220;
+            },
+            0x214 => {
+                // 0x214: ADD V1, 0x03
+                m.state.v[0x1] = m.state.v[0x1].wrapping_add(0x03);
+                m.tick(0x216)?;
+                // 0x216: ADD V0, 0x02
+                m.state.v[0x0] = m.state.v[0x0].wrapping_add(0x02);
+                m.tick(0x218)?;
+                // 0x218: JP 0x220
+                m.tick(0x220)?;
+                pc = 0x220;
+            },
+            0x21A => {
+                // 0x21A: ADD V1, V1
+                let sum = u16::from(m.state.v[0x1]) + u16::from(m.state.v[0x1]);
+                m.state.v[0xF] = u8::from(sum > 0xFF);
+                m.state.v[0x1] = sum as u8;
+                m.tick(0x21C)?;
+                // 0x21C: ADD V0, 0x02
+                m.state.v[0x0] = m.state.v[0x0].wrapping_add(0x02);
+                m.tick(0x21E)?;
+                // 0x21E: JP 0x220
+                m.tick(0x220)?;
+                pc = 0x220;
+            },
+            0x220 => {
+                // 0x220: SNE V0, 0x08
+                pc = if m.state.v[0x0] != 0x08 { 0x224 } else { 0x222 };
+                m.tick(pc)?;
+            },
+            0x222 => {
+                // 0x222: LD V0, 0x00
+                m.state.v[0x0] = 0x00;
+                m.tick(0x224)?;
+                pc = 0x224;
+            },
+            0x224 => {
+                // 0x224: LD F, V1
+                m.state.i = u16::from(m.state.v[0x1]) * 5;
+                m.tick(0x226)?;
+                // 0x226: CLS
+                m.host(0x226, 0x00E0)?;
+                // 0x228: DRW V0, V0, 5
+                m.host(0x228, 0xD005)?;
+                // 0x22A: JP 0x204
+                m.tick(0x204)?;
+                pc = 0x204;
+            },
+            _ => {
+                m.state.pc = pc;
+                return Err(Exit::Leave);
+            },
+        }
+    }
+}
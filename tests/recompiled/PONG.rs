@@ -0,0 +1,548 @@
+// CHIP-8 ROM PONG recompiled to Rust with the default quirks: 2 routines, 117 instructions.
+//
+// Generated by `chip-8 recompile`.
+
+extern crate chip_8;
+
+use std::ops::Range;
+
+use chip_8::prelude::*;
+use chip_8::recompiled::{self, Exit, Machine, Routine};
+
+/// Quirk profile the routines were recompiled for.
+pub const QUIRKS: &str = "default";
+/// Instructions executed per frame.
+pub const CYCLES_PER_FRAME: u32 = 10;
+/// The ROM.
+pub const ROM: &[u8] = &[
+    0x6A, 0x02, 0x6B, 0x0C, 0x6C, 0x3F, 0x6D, 0x0C, 0xA2, 0xEA, 0xDA, 0xB6, 0xDC, 0xD6, 0x6E, 0x00,
+    0x22, 0xD4, 0x66, 0x03, 0x68, 0x02, 0x60, 0x60, 0xF0, 0x15, 0xF0, 0x07, 0x30, 0x00, 0x12, 0x1A,
+    0xC7, 0x17, 0x77, 0x08, 0x69, 0xFF, 0xA2, 0xF0, 0xD6, 0x71, 0xA2, 0xEA, 0xDA, 0xB6, 0xDC, 0xD6,
+    0x60, 0x01, 0xE0, 0xA1, 0x7B, 0xFE, 0x60, 0x04, 0xE0, 0xA1, 0x7B, 0x02, 0x60, 0x1F, 0x8B, 0x02,
+    0xDA, 0xB6, 0x60, 0x0C, 0xE0, 0xA1, 0x7D, 0xFE, 0x60, 0x0D, 0xE0, 0xA1, 0x7D, 0x02, 0x60, 0x1F,
+    0x8D, 0x02, 0xDC, 0xD6, 0xA2, 0xF0, 0xD6, 0x71, 0x86, 0x84, 0x87, 0x94, 0x60, 0x3F, 0x86, 0x02,
+    0x61, 0x1F, 0x87, 0x12, 0x46, 0x02, 0x12, 0x78, 0x46, 0x3F, 0x12, 0x82, 0x47, 0x1F, 0x69, 0xFF,
+    0x47, 0x00, 0x69, 0x01, 0xD6, 0x71, 0x12, 0x2A, 0x68, 0x02, 0x63, 0x01, 0x80, 0x70, 0x80, 0xB5,
+    0x12, 0x8A, 0x68, 0xFE, 0x63, 0x0A, 0x80, 0x70, 0x80, 0xD5, 0x3F, 0x01, 0x12, 0xA2, 0x61, 0x02,
+    0x80, 0x15, 0x3F, 0x01, 0x12, 0xBA, 0x80, 0x15, 0x3F, 0x01, 0x12, 0xC8, 0x80, 0x15, 0x3F, 0x01,
+    0x12, 0xC2, 0x60, 0x20, 0xF0, 0x18, 0x22, 0xD4, 0x8E, 0x34, 0x22, 0xD4, 0x66, 0x3E, 0x33, 0x01,
+    0x66, 0x03, 0x68, 0xFE, 0x33, 0x01, 0x68, 0x02, 0x12, 0x16, 0x79, 0xFF, 0x49, 0xFE, 0x69, 0xFF,
+    0x12, 0xC8, 0x79, 0x01, 0x49, 0x02, 0x69, 0x01, 0x60, 0x04, 0xF0, 0x18, 0x76, 0x01, 0x46, 0x40,
+    0x76, 0xFE, 0x12, 0x6C, 0xA2, 0xF2, 0xFE, 0x33, 0xF2, 0x65, 0xF1, 0x29, 0x64, 0x14, 0x65, 0x00,
+    0xD4, 0x55, 0x74, 0x15, 0xF2, 0x29, 0xD4, 0x55, 0x00, 0xEE, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80,
+    0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+/// Addresses of the recovered code.
+#[allow(clippy::single_range_in_vec_init)]
+pub const CODE: &[Range<u16>] = &[
+    0x200..0x2EA,
+];
+
+fn main() {
+    recompiled::run(ROM, CODE, QUIRKS, CYCLES_PER_FRAME, dispatch);
+}
+
+/// Look up the routine with a basic block starting at an address.
+pub fn dispatch<T: Input, U: Graphics>(pc: u16) -> Option<Routine<T, U>> {
+    let routine: Routine<T, U> = match pc {
+        0x200 | 0x212 | 0x216 | 0x21A | 0x21E | 0x220 | 0x22A | 0x234 | 0x236 | 0x23A | 0x23C | 0x246 | 0x248 | 0x24C |
+            0x24E | 0x266 | 0x268 | 0x26A | 0x26C | 0x26E | 0x270 | 0x272 | 0x274 | 0x278 | 0x282 | 0x28A | 0x28C |
+            0x28E | 0x294 | 0x296 | 0x29A | 0x29C | 0x2A0 | 0x2A2 | 0x2A8 | 0x2AC | 0x2B0 | 0x2B2 | 0x2B6 | 0x2B8 |
+            0x2BA | 0x2BE | 0x2C0 | 0x2C2 | 0x2C6 | 0x2C8 | 0x2D0 | 0x2D2 => sub_200,
+        0x2D4 => sub_2d4,
+        _ => return None,
+    };
+    Some(routine)
+}
+
+/// Routine at 0x200.
+pub fn sub_200<T: Input, U: Graphics>(m: &mut Machine<T, U>) -> Result<(), Exit> {
+    let mut pc = m.state.pc;
+    loop {
+        match pc {
+            0x200 => {
+                // 0x200: LD VA, 0x02
+                m.state.v[0xA] = 0x02;
+                m.tick(0x202)?;
+                // 0x202: LD VB, 0x0C
+                m.state.v[0xB] = 0x0C;
+                m.tick(0x204)?;
+                // 0x204: LD VC, 0x3F
+                m.state.v[0xC] = 0x3F;
+                m.tick(0x206)?;
+                // 0x206: LD VD, 0x0C
+                m.state.v[0xD] = 0x0C;
+                m.tick(0x208)?;
+                // 0x208: LD I, 0x2EA
+                m.state.i = 0x2EA;
+                m.tick(0x20A)?;
+                // 0x20A: DRW VA, VB, 6
+                m.host(0x20A, 0xDAB6)?;
+                // 0x20C: DRW VC, VD, 6
+                m.host(0x20C, 0xDCD6)?;
+                // 0x20E: LD VE, 0x00
+                m.state.v[0xE] = 0x00;
+                m.tick(0x210)?;
+                // 0x210: CALL 0x2D4
+                m.call(0x212, 0x2D4)?;
+                sub_2d4(m)?;
+                pc = m.state.pc;
+            },
+            0x212 => {
+                // 0x212: LD V6, 0x03
+                m.state.v[0x6] = 0x03;
+                m.tick(0x214)?;
+                // 0x214: LD V8, 0x02
+                m.state.v[0x8] = 0x02;
+                m.tick(0x216)?;
+                pc = 0x216;
+            },
+            0x216 => {
+                // 0x216: LD V0, 0x60
+                m.state.v[0x0] = 0x60;
+                m.tick(0x218)?;
+                // 0x218: LD DT, V0
+                m.state.dt = m.state.v[0x0];
+                m.tick(0x21A)?;
+                pc = 0x21A;
+            },
+            0x21A => {
+                // 0x21A: LD V0, DT
+                m.state.v[0x0] = m.state.dt;
+                m.tick(0x21C)?;
+                // 0x21C: SE V0, 0x00
+                pc = if m.state.v[0x0] == 0x00 { 0x220 } else { 0x21E };
+                m.tick(pc)?;
+            },
+            0x21E => {
+                // 0x21E: JP 0x21A
+                m.tick(0x21A)?;
+                pc = 0x21A;
+            },
+            0x220 => {
+                // 0x220: RND V7, 0x17
+                m.host(0x220, 0xC717)?;
+                // 0x222: ADD V7, 0x08
+                m.state.v[0x7] = m.state.v[0x7].wrapping_add(0x08);
+                m.tick(0x224)?;
+                // 0x224: LD V9, 0xFF
+                m.state.v[0x9] = 0xFF;
+                m.tick(0x226)?;
+                // 0x226: LD I, 0x2F0
+                m.state.i = 0x2F0;
+                m.tick(0x228)?;
+                // 0x228: DRW V6, V7, 1
+                m.host(0x228, 0xD671)?;
+                pc = 0x22A;
+            },
+            0x22A => {
+                // 0x22A: LD I, 0x2EA
+                m.state.i = 0x2EA;
+                m.tick(0x22C)?;
+                // 0x22C: DRW VA, VB, 6
+                m.host(0x22C, 0xDAB6)?;
+                // 0x22E: DRW VC, VD, 6
+                m.host(0x22E, 0xDCD6)?;
+                // 0x230: LD V0, 0x01
+                m.state.v[0x0] = 0x01;
+                m.tick(0x232)?;
+                // 0x232: SKNP V0
+                m.host(0x232, 0xE0A1)?;
+                pc = m.state.pc;
+            },
+            0x234 => {
+                // 0x234: ADD VB, 0xFE
+                m.state.v[0xB] = m.state.v[0xB].wrapping_add(0xFE);
+                m.tick(0x236)?;
+                pc = 0x236;
+            },
+            0x236 => {
+                // 0x236: LD V0, 0x04
+                m.state.v[0x0] = 0x04;
+                m.tick(0x238)?;
+                // 0x238: SKNP V0
+                m.host(0x238, 0xE0A1)?;
+                pc = m.state.pc;
+            },
+            0x23A => {
+                // 0x23A: ADD VB, 0x02
+                m.state.v[0xB] = m.state.v[0xB].wrapping_add(0x02);
+                m.tick(0x23C)?;
+                pc = 0x23C;
+            },
+            0x23C => {
+                // 0x23C: LD V0, 0x1F
+                m.state.v[0x0] = 0x1F;
+                m.tick(0x23E)?;
+                // 0x23E: AND VB, V0
+                m.state.v[0xB] &= m.state.v[0x0];
+                m.tick(0x240)?;
+                // 0x240: DRW VA, VB, 6
+                m.host(0x240, 0xDAB6)?;
+                // 0x242: LD V0, 0x0C
+                m.state.v[0x0] = 0x0C;
+                m.tick(0x244)?;
+                // 0x244: SKNP V0
+                m.host(0x244, 0xE0A1)?;
+                pc = m.state.pc;
+            },
+            0x246 => {
+                // 0x246: ADD VD, 0xFE
+                m.state.v[0xD] = m.state.v[0xD].wrapping_add(0xFE);
+                m.tick(0x248)?;
+                pc = 0x248;
+            },
+            0x248 => {
+                // 0x248: LD V0, 0x0D
+                m.state.v[0x0] = 0x0D;
+                m.tick(0x24A)?;
+                // 0x24A: SKNP V0
+                m.host(0x24A, 0xE0A1)?;
+                pc = m.state.pc;
+            },
+            0x24C => {
+                // 0x24C: ADD VD, 0x02
+                m.state.v[0xD] = m.state.v[0xD].wrapping_add(0x02);
+                m.tick(0x24E)?;
+                pc = 0x24E;
+            },
+            0x24E => {
+                // 0x24E: LD V0, 0x1F
+                m.state.v[0x0] = 0x1F;
+                m.tick(0x250)?;
+                // 0x250: AND VD, V0
+                m.state.v[0xD] &= m.state.v[0x0];
+                m.tick(0x252)?;
+                // 0x252: DRW VC, VD, 6
+                m.host(0x252, 0xDCD6)?;
+                // 0x254: LD I, 0x2F0
+                m.state.i = 0x2F0;
+                m.tick(0x256)?;
+                // 0x256: DRW V6, V7, 1
+                m.host(0x256, 0xD671)?;
+                // 0x258: ADD V6, V8
+                let sum = u16::from(m.state.v[0x6]) + u16::from(m.state.v[0x8]);
+                m.state.v[0xF] = u8::from(sum > 0xFF);
+                m.state.v[0x6] = sum as u8;
+                m.tick(0x25A)?;
+                // 0x25A: ADD V7, V9
+                let sum = u16::from(m.state.v[0x7]) + u16::from(m.state.v[0x9]);
+                m.state.v[0xF] = u8::from(sum > 0xFF);
+                m.state.v[0x7] = sum as u8;
+                m.tick(0x25C)?;
+                // 0x25C: LD V0, 0x3F
+                m.state.v[0x0] = 0x3F;
+                m.tick(0x25E)?;
+                // 0x25E: AND V6, V0
+                m.state.v[0x6] &= m.state.v[0x0];
+                m.tick(0x260)?;
+                // 0x260: LD V1, 0x1F
+                m.state.v[0x1] = 0x1F;
+                m.tick(0x262)?;
+                // 0x262: AND V7, V1
+                m.state.v[0x7] &= m.state.v[0x1];
+                m.tick(0x264)?;
+                // 0x264: SNE V6, 0x02
+                pc = if m.state.v[0x6] != 0x02 { 0x268 } else { 0x266 };
+                m.tick(pc)?;
+            },
+            0x266 => {
+                // 0x266: JP 0x278
+                m.tick(0x278)?;
+                pc = 0x278;
+            },
+            0x268 => {
+                // 0x268: SNE V6, 0x3F
+                pc = if m.state.v[0x6] != 0x3F { 0x26C } else { 0x26A };
+                m.tick(pc)?;
+            },
+            0x26A => {
+                // 0x26A: JP 0x282
+                m.tick(0x282)?;
+                pc = 0x282;
+            },
+            0x26C => {
+                // 0x26C: SNE V7, 0x1F
+                pc = if m.state.v[0x7] != 0x1F { 0x270 } else { 0x26E };
+                m.tick(pc)?;
+            },
+            0x26E => {
+                // 0x26E: LD V9, 0xFF
+                m.state.v[0x9] = 0xFF;
+                m.tick(0x270)?;
+                pc = 0x270;
+            },
+            0x270 => {
+                // 0x270: SNE V7, 0x00
+                pc = if m.state.v[0x7] != 0x00 { 0x274 } else { 0x272 };
+                m.tick(pc)?;
+            },
+            0x272 => {
+                // 0x272: LD V9, 0x01
+                m.state.v[0x9] = 0x01;
+                m.tick(0x274)?;
+                pc = 0x274;
+            },
+            0x274 => {
+                // 0x274: DRW V6, V7, 1
+                m.host(0x274, 0xD671)?;
+                // 0x276: JP 0x22A
+                m.tick(0x22A)?;
+                pc = 0x22A;
+            },
+            0x278 => {
+                // 0x278: LD V8, 0x02
+                m.state.v[0x8] = 0x02;
+                m.tick(0x27A)?;
+                // 0x27A: LD V3, 0x01
+                m.state.v[0x3] = 0x01;
+                m.tick(0x27C)?;
+                // 0x27C: LD V0, V7
+                m.state.v[0x0] = m.state.v[0x7];
+                m.tick(0x27E)?;
+                // 0x27E: SUB V0, VB
+                let difference = (m.state.v[0x0] as i8).wrapping_sub(m.state.v[0xB] as i8);
+                m.state.v[0x0] = difference as u8;
+                m.state.v[0xF] = u8::from(difference < 0);
+                m.tick(0x280)?;
+                // 0x280: JP 0x28A
+                m.tick(0x28A)?;
+                pc = 0x28A;
+            },
+            0x282 => {
+                // 0x282: LD V8, 0xFE
+                m.state.v[0x8] = 0xFE;
+                m.tick(0x284)?;
+                // 0x284: LD V3, 0x0A
+                m.state.v[0x3] = 0x0A;
+                m.tick(0x286)?;
+                // 0x286: LD V0, V7
+                m.state.v[0x0] = m.state.v[0x7];
+                m.tick(0x288)?;
+                // 0x288: SUB V0, VD
+                let difference = (m.state.v[0x0] as i8).wrapping_sub(m.state.v[0xD] as i8);
+                m.state.v[0x0] = difference as u8;
+                m.state.v[0xF] = u8::from(difference < 0);
+                m.tick(0x28A)?;
+                pc = 0x28A;
+            },
+            0x28A => {
+                // 0x28A: SE VF, 0x01
+                pc = if m.state.v[0xF] == 0x01 { 0x28E } else { 0x28C };
+                m.tick(pc)?;
+            },
+            0x28C => {
+                // 0x28C: JP 0x2A2
+                m.tick(0x2A2)?;
+                pc = 0x2A2;
+            },
+            0x28E => {
+                // 0x28E: LD V1, 0x02
+                m.state.v[0x1] = 0x02;
+                m.tick(0x290)?;
+                // 0x290: SUB V0, V1
+                let difference = (m.state.v[0x0] as i8).wrapping_sub(m.state.v[0x1] as i8);
+                m.state.v[0x0] = difference as u8;
+                m.state.v[0xF] = u8::from(difference < 0);
+                m.tick(0x292)?;
+                // 0x292: SE VF, 0x01
+                pc = if m.state.v[0xF] == 0x01 { 0x296 } else { 0x294 };
+                m.tick(pc)?;
+            },
+            0x294 => {
+                // 0x294: JP 0x2BA
+                m.tick(0x2BA)?;
+                pc = 0x2BA;
+            },
+            0x296 => {
+                // 0x296: SUB V0, V1
+                let difference = (m.state.v[0x0] as i8).wrapping_sub(m.state.v[0x1] as i8);
+                m.state.v[0x0] = difference as u8;
+                m.state.v[0xF] = u8::from(difference < 0);
+                m.tick(0x298)?;
+                // 0x298: SE VF, 0x01
+                pc = if m.state.v[0xF] == 0x01 { 0x29C } else { 0x29A };
+                m.tick(pc)?;
+            },
+            0x29A => {
+                // 0x29A: JP 0x2C8
+                m.tick(0x2C8)?;
+                pc = 0x2C8;
+            },
+            0x29C => {
+                // 0x29C: SUB V0, V1
+                let difference = (m.state.v[0x0] as i8).wrapping_sub(m.state.v[0x1] as i8);
+                m.state.v[0x0] = difference as u8;
+                m.state.v[0xF] = u8::from(difference < 0);
+                m.tick(0x29E)?;
+                // 0x29E: SE VF, 0x01
+                pc = if m.state.v[0xF] == 0x01 { 0x2A2 } else { 0x2A0 };
+                m.tick(pc)?;
+            },
+            0x2A0 => {
+                // 0x2A0: JP 0x2C2
+                m.tick(0x2C2)?;
+                pc = 0x2C2;
+            },
+            0x2A2 => {
+                // 0x2A2: LD V0, 0x20
+                m.state.v[0x0] = 0x20;
+                m.tick(0x2A4)?;
+                // 0x2A4: LD ST, V0
+                m.state.st = m.state.v[0x0];
+                m.tick(0x2A6)?;
+                // 0x2A6: CALL 0x2D4
+                m.call(0x2A8, 0x2D4)?;
+                sub_2d4(m)?;
+                pc = m.state.pc;
+            },
+            0x2A8 => {
+                // 0x2A8: ADD VE, V3
+                let sum = u16::from(m.state.v[0xE]) + u16::from(m.state.v[0x3]);
+                m.state.v[0xF] = u8::from(sum > 0xFF);
+                m.state.v[0xE] = sum as u8;
+                m.tick(0x2AA)?;
+                // 0x2AA: CALL 0x2D4
+                m.call(0x2AC, 0x2D4)?;
+                sub_2d4(m)?;
+                pc = m.state.pc;
+            },
+            0x2AC => {
+                // 0x2AC: LD V6, 0x3E
+                m.state.v[0x6] = 0x3E;
+                m.tick(0x2AE)?;
+                // 0x2AE: SE V3, 0x01
+                pc = if m.state.v[0x3] == 0x01 { 0x2B2 } else { 0x2B0 };
+                m.tick(pc)?;
+            },
+            0x2B0 => {
+                // 0x2B0: LD V6, 0x03
+                m.state.v[0x6] = 0x03;
+                m.tick(0x2B2)?;
+                pc = 0x2B2;
+            },
+            0x2B2 => {
+                // 0x2B2: LD V8, 0xFE
+                m.state.v[0x8] = 0xFE;
+                m.tick(0x2B4)?;
+                // 0x2B4: SE V3, 0x01
+                pc = if m.state.v[0x3] == 0x01 { 0x2B8 } else { 0x2B6 };
+                m.tick(pc)?;
+            },
+            0x2B6 => {
+                // 0x2B6: LD V8, 0x02
+                m.state.v[0x8] = 0x02;
+                m.tick(0x2B8)?;
+                pc = 0x2B8;
+            },
+            0x2B8 => {
+                // 0x2B8: JP 0x216
+                m.tick(0x216)?;
+                pc = 0x216;
+            },
+            0x2BA => {
+                // 0x2BA: ADD V9, 0xFF
+                m.state.v[0x9] = m.state.v[0x9].wrapping_add(0xFF);
+                m.tick(0x2BC)?;
+                // 0x2BC: SNE V9, 0xFE
+                pc = if m.state.v[0x9] != 0xFE { 0x2C0 } else { 0x2BE };
+                m.tick(pc)?;
+            },
+            0x2BE => {
+                // 0x2BE: LD V9, 0xFF
+                m.state.v[0x9] = 0xFF;
+                m.tick(0x2C0)?;
+                pc = 0x2C0;
+            },
+            0x2C0 => {
+                // 0x2C0: JP 0x2C8
+                m.tick(0x2C8)?;
+                pc = 0x2C8;
+            },
+            0x2C2 => {
+                // 0x2C2: ADD V9, 0x01
+                m.state.v[0x9] = m.state.v[0x9].wrapping_add(0x01);
+                m.tick(0x2C4)?;
+                // 0x2C4: SNE V9, 0x02
+                pc = if m.state.v[0x9] != 0x02 { 0x2C8 } else { 0x2C6 };
+                m.tick(pc)?;
+            },
+            0x2C6 => {
+                // 0x2C6: LD V9, 0x01
+                m.state.v[0x9] = 0x01;
+                m.tick(0x2C8)?;
+                pc = 0x2C8;
+            },
+            0x2C8 => {
+                // 0x2C8: LD V0, 0x04
+                m.state.v[0x0] = 0x04;
+                m.tick(0x2CA)?;
+                // 0x2CA: LD ST, V0
+                m.state.st = m.state.v[0x0];
+                m.tick(0x2CC)?;
+                // 0x2CC: ADD V6, 0x01
+                m.state.v[0x6] = m.state.v[0x6].wrapping_add(0x01);
+                m.tick(0x2CE)?;
+                // 0x2CE: SNE V6, 0x40
+                pc = if m.state.v[0x6] != 0x40 { 0x2D2 } else { 0x2D0 };
+                m.tick(pc)?;
+            },
+            0x2D0 => {
+                // 0x2D0: ADD V6, 0xFE
+                m.state.v[0x6] = m.state.v[0x6].wrapping_add(0xFE);
+                m.tick(0x2D2)?;
+                pc = 0x2D2;
+            },
+            0x2D2 => {
+                // 0x2D2: JP 0x26C
+                m.tick(0x26C)?;
+                pc = 0x26C;
+            },
+            _ => {
+                m.state.pc = pc;
+                return Err(Exit::Leave);
+            },
+        }
+    }
+}
+
+/// Routine at 0x2D4.
+pub fn sub_2d4<T: Input, U: Graphics>(m: &mut Machine<T, U>) -> Result<(), Exit> {
+    let pc = m.state.pc;
+    match pc {
+        0x2D4 => {
+            // 0x2D4: LD I, 0x2F2
+            m.state.i = 0x2F2;
+            m.tick(0x2D6)?;
+            // 0x2D6: LD B, VE
+            m.host(0x2D6, 0xFE33)?;
+            // 0x2D8: LD V2, [I]
+            m.host(0x2D8, 0xF265)?;
+            // 0x2DA: LD F, V1
+            m.state.i = u16::from(m.state.v[0x1]) * 5;
+            m.tick(0x2DC)?;
+            // 0x2DC: LD V4, 0x14
+            m.state.v[0x4] = 0x14;
+            m.tick(0x2DE)?;
+            // 0x2DE: LD V5, 0x00
+            m.state.v[0x5] = 0x00;
+            m.tick(0x2E0)?;
+            // 0x2E0: DRW V4, V5, 5
+            m.host(0x2E0, 0xD455)?;
+            // 0x2E2: ADD V4, 0x15
+            m.state.v[0x4] = m.state.v[0x4].wrapping_add(0x15);
+            m.tick(0x2E4)?;
+            // 0x2E4: LD F, V2
+            m.state.i = u16::from(m.state.v[0x2]) * 5;
+            m.tick(0x2E6)?;
+            // 0x2E6: DRW V4, V5, 5
+            m.host(0x2E6, 0xD455)?;
+            // 0x2E8: RET
+            m.ret()?;
+            Ok(())
+        },
+        _ => {
+            Err(Exit::Leave)
+        },
+    }
+}
@@ -0,0 +1,188 @@
+//! Tests of the recompiler. The programs recompiled from a few bundled ROMs and from a program using a jump table are
+//! checked in, compiled into this test, and run in lockstep with the interpreter, comparing the machines after every
+//! frame. Set `CHIP8_BLESS=1` to regenerate the programs after changing the recompiler.
+
+extern crate chip_8;
+
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use chip_8::cpu::Cpu;
+use chip_8::graphics::{Graphics, TestDisplay, HEIGHT, WIDTH};
+use chip_8::input::TestKeyboard;
+use chip_8::interconnect::Interconnect;
+use chip_8::memory::MEMORY_SIZE;
+use chip_8::quirks::Quirks;
+use chip_8::recompiled::{Dispatch, Machine};
+use chip_8::recompiler::Program;
+
+#[allow(dead_code)]
+mod brix {
+    include!("recompiled/BRIX.rs");
+}
+#[allow(dead_code)]
+mod invaders {
+    include!("recompiled/INVADERS.rs");
+}
+#[allow(dead_code)]
+mod jump_table {
+    include!("recompiled/JUMP_TABLE.rs");
+}
+#[allow(dead_code)]
+mod pong {
+    include!("recompiled/PONG.rs");
+}
+
+/// Seed of the random number generators.
+const SEED: u64 = 0xC8;
+/// Frames every program runs for.
+const FRAMES: u64 = 300;
+/// A program drawing digits, picking how to compute the next one through a jump table.
+const JUMP_TABLE: &[u8] = &[
+    0x60, 0x00, // 0x200: LD V0, 0x00
+    0x61, 0x00, // 0x202: LD V1, 0x00
+    0xB2, 0x06, // 0x204: JP V0, 0x206
+    0x12, 0x0E, // 0x206: JP 0x20E
+    0x12, 0x14, // 0x208: JP 0x214
+    0x12, 0x1A, // 0x20A: JP 0x21A
+    0x12, 0x14, // 0x20C: JP 0x214
+    0x71, 0x01, // 0x20E: ADD V1, 0x01
+    0x70, 0x02, // 0x210: ADD V0, 0x02
+    0x12, 0x20, // 0x212: JP 0x220
+    0x71, 0x03, // 0x214: ADD V1, 0x03
+    0x70, 0x02, // 0x216: ADD V0, 0x02
+    0x12, 0x20, // 0x218: JP 0x220
+    0x81, 0x14, // 0x21A: ADD V1, V1
+    0x70, 0x02, // 0x21C: ADD V0, 0x02
+    0x12, 0x20, // 0x21E: JP 0x220
+    0x40, 0x08, // 0x220: SNE V0, 0x08
+    0x60, 0x00, // 0x222: LD V0, 0x00
+    0xF1, 0x29, // 0x224: LD F, V1
+    0x00, 0xE0, // 0x226: CLS
+    0xD0, 0x05, // 0x228: DRW V0, V0, 5
+    0x12, 0x04, // 0x22A: JP 0x204
+];
+
+/// A recompiled program.
+struct Recompiled {
+    name: &'static str,
+    rom: Vec<u8>,
+    quirks: &'static str,
+    cycles_per_frame: u32,
+    code: &'static [Range<u16>],
+    dispatch: Dispatch<TestKeyboard, TestDisplay>,
+}
+
+fn programs() -> Vec<Recompiled> {
+    vec![
+        Recompiled {
+            name: "BRIX",
+            rom: rom("BRIX"),
+            quirks: brix::QUIRKS,
+            cycles_per_frame: brix::CYCLES_PER_FRAME,
+            code: brix::CODE,
+            dispatch: brix::dispatch,
+        },
+        Recompiled {
+            name: "INVADERS",
+            rom: rom("INVADERS"),
+            quirks: invaders::QUIRKS,
+            cycles_per_frame: invaders::CYCLES_PER_FRAME,
+            code: invaders::CODE,
+            dispatch: invaders::dispatch,
+        },
+        Recompiled {
+            name: "JUMP_TABLE",
+            rom: JUMP_TABLE.to_vec(),
+            quirks: jump_table::QUIRKS,
+            cycles_per_frame: jump_table::CYCLES_PER_FRAME,
+            code: jump_table::CODE,
+            dispatch: jump_table::dispatch,
+        },
+        Recompiled {
+            name: "PONG",
+            rom: rom("PONG"),
+            quirks: pong::QUIRKS,
+            cycles_per_frame: pong::CYCLES_PER_FRAME,
+            code: pong::CODE,
+            dispatch: pong::dispatch,
+        },
+    ]
+}
+
+fn rom(name: &str) -> Vec<u8> {
+    fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("static").join("roms").join(name)).unwrap()
+}
+
+fn generated(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("recompiled").join(format!("{}.rs", name))
+}
+
+/// A CPU running a ROM from its first instruction.
+fn cpu(rom: &[u8], program: &Recompiled) -> Cpu<TestKeyboard, TestDisplay> {
+    let mut cpu = Cpu::new(Interconnect::new_test(rom.to_vec()));
+    cpu.set_quirks(Quirks::from_name(program.quirks).unwrap());
+    cpu.set_cycles_per_frame(program.cycles_per_frame);
+    cpu.seed_rng(SEED);
+    cpu
+}
+
+/// Describe the first difference between the interpreter and a recompiled program.
+fn difference(reference: &Cpu<TestKeyboard, TestDisplay>, machine: &Machine<TestKeyboard, TestDisplay>)
+    -> Option<String> {
+    if reference.state() != machine.state {
+        return Some(format!("{:?}\ninstead of\n{:?}", machine.state, reference.state()));
+    }
+    let (expected, actual) = (reference.interconnect(), machine.interconnect());
+    if let Some(addr) = (0..MEMORY_SIZE).find(|&addr| expected.memory.read(addr) != actual.memory.read(addr)) {
+        return Some(format!("at address 0x{:03X}", addr));
+    }
+    let pixels = (0..WIDTH * HEIGHT).map(|idx| (idx % WIDTH, idx / WIDTH));
+    if pixels.clone().any(|(x, y)| expected.graphics.get_pixel(x, y) != actual.graphics.get_pixel(x, y)) {
+        return Some("on the display".to_string());
+    }
+    None
+}
+
+#[test]
+fn recompiled_programs_are_up_to_date() {
+    let bless = env::var("CHIP8_BLESS").is_ok();
+    for program in programs() {
+        let recovered = Program::recover(program.name, &program.rom).unwrap();
+        let source = recovered.to_rust(program.quirks, program.cycles_per_frame);
+        let path = generated(program.name);
+        if bless {
+            fs::write(&path, &source).unwrap();
+        } else {
+            let expected = fs::read_to_string(&path).unwrap();
+            assert!(expected == source, "{} is outdated, regenerate it with CHIP8_BLESS=1", path.display());
+        }
+    }
+}
+
+#[test]
+fn recompiled_programs_run_like_the_interpreter() {
+    for program in programs() {
+        let mut reference = cpu(&program.rom, &program);
+        let mut machine = Machine::new(cpu(&program.rom, &program), program.code);
+        let failure = Rc::new(RefCell::new(None));
+        let report = Rc::clone(&failure);
+        machine.set_frame_handler(Box::new(move |machine| {
+            reference.execute_frame();
+            if let Some(difference) = difference(&reference, machine) {
+                *report.borrow_mut() = Some(format!("differs in frame {} {}", machine.frames(), difference));
+                return false;
+            }
+            machine.frames() < FRAMES
+        }));
+        machine.run(program.dispatch);
+        if let Some(failure) = failure.borrow_mut().take() {
+            panic!("{} {}", program.name, failure);
+        }
+        assert_eq!(machine.frames(), FRAMES);
+    }
+}
@@ -0,0 +1,389 @@
+//! Differential testing of `Cpu` against the reference interpreter. Both run the same program from the same state in
+//! lockstep until the reference finds an undefined instruction, and every step is compared. The CPU executes the
+//! undefined instruction too, which it must not panic on. A disagreement, or a panic of the CPU, is minimized to a
+//! single instruction with as little state as still shows it, and printed as a unit test for `cpu.rs`.
+
+use std::cell::Cell;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Once;
+
+use chip_8::cpu::{Cpu, State};
+use chip_8::disassembler::{disassemble, pattern};
+use chip_8::graphics::{Graphics, TestDisplay, HEIGHT, WIDTH};
+use chip_8::input::TestKeyboard;
+use chip_8::interconnect::Interconnect;
+use chip_8::memory::{FONTSET, MEMORY_SIZE};
+
+use super::{Machine, START};
+
+/// Number of bytes of an input before the program.
+pub const HEADER_LEN: usize = 63;
+/// Maximum number of instructions executed per input.
+pub const MAX_STEPS: usize = 64;
+
+/// Initial state and program of a run.
+#[derive(Clone, Debug)]
+pub struct Input {
+    pub state: State,
+    pub keys: u16,
+    pub seed: u64,
+    pub rom: Vec<u8>,
+}
+
+impl Input {
+    /// Decode the raw bytes of the fuzzer: the registers, I, the stack pointer, the stack, the timers, the held keys
+    /// and the seed of `CXKK`, followed by the program. Values are brought into range rather than rejected so that
+    /// every input gets executed.
+    pub fn from_bytes(data: &[u8]) -> Option<Input> {
+        if data.len() < HEADER_LEN {
+            return None;
+        }
+        let word = |at: usize| (data[at] as u16) << 8 | data[at + 1] as u16;
+        let mut state = State { pc: START, ..State::default() };
+        state.v.copy_from_slice(&data[0..16]);
+        state.i = word(16) & 0xFFF;
+        state.sp = data[18] % (state.stack.len() as u8 + 1);
+        for (entry, at) in state.stack.iter_mut().zip((19..51).step_by(2)) {
+            *entry = word(at) & 0xFFF;
+        }
+        state.dt = data[51];
+        state.st = data[52];
+        let keys = word(53);
+        let mut seed = [0; 8];
+        seed.copy_from_slice(&data[55..63]);
+        let rom_len = (data.len() - HEADER_LEN).min(MEMORY_SIZE - START as usize);
+        Some(Input {
+            state,
+            keys,
+            seed: u64::from_le_bytes(seed),
+            rom: data[HEADER_LEN..HEADER_LEN + rom_len].to_vec(),
+        })
+    }
+}
+
+/// A bug of the CPU showing as a disagreement with the reference, listed in the TODOs of the README.
+pub struct KnownBug {
+    /// Pattern of the instruction, such as `8XY5`.
+    pub pattern: &'static str,
+    /// Whether the bug only shows when VF is the destination of the instruction.
+    pub vf_destination: bool,
+    pub description: &'static str,
+}
+
+/// Bugs of the CPU, each of them differing from the reference in VF alone. A disagreement in any other field, on any
+/// other instruction, or a panic is not one of them.
+pub const KNOWN_BUGS: [KnownBug; 5] = [
+    KnownBug {
+        pattern: "8XY4",
+        vf_destination: true,
+        description: "writes the sum after the carry, so the sum wins over the carry in VF if X is F",
+    },
+    KnownBug { pattern: "8XY5", vf_destination: false, description: "sets VF to the borrow instead of NOT borrow" },
+    KnownBug {
+        pattern: "8XY6",
+        vf_destination: true,
+        description: "writes the result after the bit shifted out, so the result wins in VF if X is F",
+    },
+    KnownBug { pattern: "8XY7", vf_destination: false, description: "sets VF to the borrow instead of NOT borrow" },
+    KnownBug {
+        pattern: "8XYE",
+        vf_destination: false,
+        description: "sets VF to the bit shifted out in place, `0x80`, instead of 1",
+    },
+];
+
+/// A field of the machine that differs between the CPU and the reference.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    /// Expression reading the field in the unit tests of `cpu.rs`, such as `cpu.v[0xF]`.
+    pub field: String,
+    /// Value of the reference.
+    pub expected: String,
+    /// Value of the CPU.
+    pub actual: String,
+}
+
+/// A step the CPU and the reference disagree on.
+#[derive(Clone)]
+pub struct Mismatch {
+    /// Machine before the step, the instruction being at its program counter.
+    pub before: Machine,
+    pub opcode: u16,
+    pub seed: u64,
+    /// Fields that differ after the step, empty if the CPU panicked.
+    pub differences: Vec<Difference>,
+    /// Message of the panic of the CPU.
+    pub panic: Option<String>,
+}
+
+/// Build a CPU in the state of a machine.
+fn cpu_from(machine: &Machine, seed: u64) -> Cpu<TestKeyboard, TestDisplay> {
+    let mut cpu = Cpu::new(Interconnect::new_test(Vec::new()));
+    cpu.set_state(machine.state);
+    cpu.seed_rng(seed);
+    let interconnect = cpu.interconnect_mut();
+    for (addr, &byte) in machine.memory.iter().enumerate() {
+        interconnect.memory.write(addr, byte);
+    }
+    interconnect.memory.take_writes();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            interconnect.graphics.set_pixel(x, y, machine.pixel(x, y));
+        }
+    }
+    for key in 0..16 {
+        if machine.keys & 1 << key != 0 {
+            interconnect.input.press_key(key);
+        }
+    }
+    cpu
+}
+
+thread_local!(static SILENT: Cell<bool> = const { Cell::new(false) });
+static SILENCE_HOOK: Once = Once::new();
+
+/// Execute one instruction on the CPU, catching its panic.
+fn step_cpu(cpu: &mut Cpu<TestKeyboard, TestDisplay>) -> Result<(), String> {
+    // silence the panic message, the panic is reported as a mismatch. The hook in place, which aborts under libFuzzer,
+    // is wrapped once rather than swapped per step so that threads running in parallel do not lose it.
+    SILENCE_HOOK.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| if !SILENT.with(Cell::get) { hook(info) }));
+    });
+    SILENT.with(|silent| silent.set(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| cpu.execute_cycle()));
+    SILENT.with(|silent| silent.set(false));
+    result.map_err(|cause| {
+        cause.downcast_ref::<String>().cloned()
+            .or_else(|| cause.downcast_ref::<&str>().map(|message| message.to_string()))
+            .unwrap_or_default()
+    })
+}
+
+/// Compare the CPU to the reference after a step. Memory is compared where either of them wrote.
+fn compare(cpu: &mut Cpu<TestKeyboard, TestDisplay>, machine: &Machine) -> Vec<Difference> {
+    let mut differences = Vec::new();
+    {
+        let mut field = |field: String, expected: String, actual: String| {
+            if expected != actual {
+                differences.push(Difference { field, expected, actual });
+            }
+        };
+        let (actual, expected) = (cpu.state(), machine.state);
+        field("cpu.pc".to_string(), format!("0x{:04X}", expected.pc), format!("0x{:04X}", actual.pc));
+        field("cpu.i".to_string(), format!("0x{:04X}", expected.i), format!("0x{:04X}", actual.i));
+        field("cpu.sp".to_string(), format!("{}", expected.sp), format!("{}", actual.sp));
+        field("cpu.dt".to_string(), format!("0x{:02X}", expected.dt), format!("0x{:02X}", actual.dt));
+        field("cpu.st".to_string(), format!("0x{:02X}", expected.st), format!("0x{:02X}", actual.st));
+        for reg in 0..16 {
+            field(format!("cpu.v[0x{:X}]", reg), format!("0x{:02X}", expected.v[reg]),
+                  format!("0x{:02X}", actual.v[reg]));
+        }
+        for entry in 0..16 {
+            field(format!("cpu.stack[{}]", entry), format!("0x{:04X}", expected.stack[entry]),
+                  format!("0x{:04X}", actual.stack[entry]));
+        }
+        let memory = &mut cpu.interconnect_mut().memory;
+        let mut written = memory.take_writes();
+        written.extend(machine.writes.iter().cloned());
+        written.sort();
+        written.dedup();
+        for addr in written {
+            field(format!("cpu.interconnect.memory.read(0x{:03X})", addr), format!("0x{:02X}", machine.memory[addr]),
+                  format!("0x{:02X}", memory.read(addr)));
+        }
+        let graphics = &cpu.interconnect().graphics;
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let (expected, actual) = (machine.pixel(x, y), graphics.get_pixel(x, y));
+                if expected != actual {
+                    field(format!("cpu.interconnect.graphics.get_pixel({}, {})", x, y), expected.to_string(),
+                          actual.to_string());
+                }
+            }
+        }
+    }
+    differences
+}
+
+/// Run an input on the CPU and the reference in lockstep.
+pub fn run(input: &Input) -> Result<(), Box<Mismatch>> {
+    let mut machine = Machine::new(&input.rom, input.state, input.keys, input.seed);
+    let mut cpu = cpu_from(&machine, input.seed);
+    for _ in 0..MAX_STEPS {
+        let before = machine.clone();
+        // the instruction the CPU fetches, zero past the end of memory
+        let opcode = machine.opcode().unwrap_or(0);
+        let defined = machine.step().is_ok();
+        let mismatch = |differences, panic| Box::new(Mismatch { before, opcode, seed: input.seed, differences, panic });
+        // the CPU may do anything with an undefined instruction but panic, and the runs stop comparing after it
+        if let Err(message) = step_cpu(&mut cpu) {
+            return Err(mismatch(Vec::new(), Some(message)));
+        }
+        if !defined {
+            break;
+        }
+        let differences = compare(&mut cpu, &machine);
+        if !differences.is_empty() {
+            return Err(mismatch(differences, None));
+        }
+    }
+    Ok(())
+}
+
+impl Mismatch {
+    /// Pattern of the instruction, such as `8XY5`.
+    pub fn pattern(&self) -> &'static str {
+        pattern(self.opcode)
+    }
+
+    /// The known bug the mismatch shows, if it shows one and nothing else.
+    pub fn known_bug(&self) -> Option<&'static KnownBug> {
+        let vf_destination = self.opcode >> 8 & 0xF == 0xF;
+        KNOWN_BUGS.iter().find(|bug| {
+            bug.pattern == self.pattern()
+                && (vf_destination || !bug.vf_destination)
+                && self.panic.is_none()
+                && self.differences.iter().all(|difference| difference.field == "cpu.v[0xF]")
+        })
+    }
+
+    /// Execute the instruction alone from a machine, returning the mismatch it shows if any.
+    fn replay(&self, before: &Machine) -> Option<Mismatch> {
+        let mut machine = before.clone();
+        machine.seed_rng(self.seed);
+        machine.execute(self.opcode).ok()?;
+        let mut cpu = cpu_from(before, self.seed);
+        let (differences, panic) = match step_cpu(&mut cpu) {
+            Ok(())       => (compare(&mut cpu, &machine), None),
+            Err(message) => (Vec::new(), Some(message)),
+        };
+        if differences.is_empty() && panic.is_none() {
+            return None;
+        }
+        Some(Mismatch { before: before.clone(), opcode: self.opcode, seed: self.seed, differences, panic })
+    }
+
+    /// Shrink the state before the instruction as long as the mismatch persists, one field at a time.
+    pub fn minimize(&self) -> Mismatch {
+        let mut best = match self.replay(&self.before) {
+            Some(mismatch) => mismatch,
+            // the mismatch depends on the random numbers drawn before, keep it as it is
+            None           => return self.clone(),
+        };
+        let attempt = |best: &mut Mismatch, change: &dyn Fn(&mut Machine)| {
+            let mut machine = best.before.clone();
+            change(&mut machine);
+            if let Some(mismatch) = self.replay(&machine) {
+                *best = mismatch;
+            }
+        };
+
+        let (pc, opcode) = (START as usize, self.opcode);
+        let move_to_start = move |machine: &mut Machine| {
+            machine.state.pc = pc as u16;
+            machine.memory[pc] = (opcode >> 8) as u8;
+            machine.memory[pc + 1] = opcode as u8;
+        };
+        attempt(&mut best, &move_to_start);
+        // memory other than the instruction, first all of it, then in ever smaller blocks
+        let fresh = Machine::new(&[], State::default(), 0, 0).memory;
+        let restore = |start: usize, len: usize| {
+            let fresh = fresh.clone();
+            move |machine: &mut Machine| {
+                let pc = machine.state.pc as usize;
+                for addr in (start..start + len).filter(|&addr| addr != pc && addr != pc + 1) {
+                    machine.memory[addr] = fresh[addr];
+                }
+            }
+        };
+        for &len in [MEMORY_SIZE, 256, 16, 1].iter() {
+            for start in (0..MEMORY_SIZE).step_by(len) {
+                if best.before.memory[start..start + len] != fresh[start..start + len] {
+                    attempt(&mut best, &restore(start, len));
+                }
+            }
+        }
+        attempt(&mut best, &|machine| machine.display = vec![false; WIDTH * HEIGHT]);
+        for index in 0..WIDTH * HEIGHT {
+            if best.before.display[index] {
+                attempt(&mut best, &|machine| machine.display[index] = false);
+            }
+        }
+        attempt(&mut best, &|machine| machine.keys = 0);
+        for key in 0..16 {
+            attempt(&mut best, &|machine| machine.keys &= !(1 << key));
+        }
+        for reg in 0..16 {
+            attempt(&mut best, &|machine| machine.state.v[reg] = 0);
+        }
+        attempt(&mut best, &|machine| machine.state.i = 0);
+        attempt(&mut best, &|machine| machine.state.dt = 0);
+        attempt(&mut best, &|machine| machine.state.st = 0);
+        attempt(&mut best, &|machine| { machine.state.sp = 0; machine.state.stack = [0; 16]; });
+        for entry in 0..16 {
+            attempt(&mut best, &|machine| machine.state.stack[entry] = 0);
+        }
+        best
+    }
+}
+
+/// The mismatch as a unit test for `cpu.rs`.
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = &self.before.state;
+        writeln!(f, "#[test]")?;
+        writeln!(f, "fn differential_{}() {{", self.pattern().to_lowercase())?;
+        writeln!(f, "    let mut cpu = get_cpu();")?;
+        writeln!(f, "    cpu.pc = 0x{:04X};", state.pc)?;
+        for (reg, &value) in state.v.iter().enumerate().filter(|&(_, &value)| value != 0) {
+            writeln!(f, "    cpu.v[0x{:X}] = 0x{:02X};", reg, value)?;
+        }
+        if state.i != 0 {
+            writeln!(f, "    cpu.i = 0x{:04X};", state.i)?;
+        }
+        if state.sp != 0 {
+            writeln!(f, "    cpu.sp = {};", state.sp)?;
+        }
+        for (entry, &addr) in state.stack.iter().enumerate().filter(|&(_, &addr)| addr != 0) {
+            writeln!(f, "    cpu.stack[{}] = 0x{:04X};", entry, addr)?;
+        }
+        if state.dt != 0 {
+            writeln!(f, "    cpu.dt = 0x{:02X};", state.dt)?;
+        }
+        if state.st != 0 {
+            writeln!(f, "    cpu.st = 0x{:02X};", state.st)?;
+        }
+        let pc = state.pc as usize;
+        for (addr, &byte) in self.before.memory.iter().enumerate() {
+            let fresh = if addr < FONTSET.len() { FONTSET[addr] } else { 0 };
+            if byte != fresh && addr != pc && addr != pc + 1 {
+                writeln!(f, "    cpu.interconnect.memory.write(0x{:03X}, 0x{:02X});", addr, byte)?;
+            }
+        }
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                if self.before.pixel(x, y) {
+                    writeln!(f, "    cpu.interconnect.graphics.set_pixel({}, {}, true);", x, y)?;
+                }
+            }
+        }
+        for key in (0..16).filter(|key| self.before.keys & 1 << key != 0) {
+            writeln!(f, "    cpu.interconnect.input.press_key(0x{:X});", key)?;
+        }
+        if self.pattern() == "CXKK" {
+            writeln!(f, "    cpu.seed_rng({});", self.seed)?;
+        }
+        writeln!(f)?;
+        if let Some(ref message) = self.panic {
+            writeln!(f, "    // panics: {}", message)?;
+        }
+        writeln!(f, "    cpu.process_opcode(0x{:04X}); // {}", self.opcode, disassemble(self.opcode))?;
+        for difference in &self.differences {
+            writeln!(f, "    assert_eq!({}, {}, \"the reference has {}, not {}\");", difference.field,
+                     difference.expected, difference.expected, difference.actual)?;
+        }
+        write!(f, "}}")
+    }
+}
@@ -0,0 +1,282 @@
+//! Reference interpreter for differential testing. A deliberately plain CHIP-8 interpreter following Cowgod's technical
+//! reference one instruction at a time, with the default quirks. It favours being obviously correct over being fast,
+//! and refuses to execute anything the reference leaves undefined instead of guessing.
+//!
+//! VF after `8XY5` and `8XY7` is the inverted borrow, as on the COSMAC VIP, rather than Cowgod's strict comparison.
+//! Whenever an instruction sets VF as a flag, the flag is written after the result, so it wins if X is F.
+
+#![allow(dead_code)]
+
+pub mod differential;
+
+use std::collections::BTreeSet;
+
+use chip_8::cpu::State;
+use chip_8::graphics::{HEIGHT, WIDTH};
+use chip_8::memory::{FONTSET, MEMORY_SIZE};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Address programs are loaded at.
+pub const START: u16 = 0x200;
+
+/// Why the reference refuses to execute an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Undefined {
+    /// The opcode is not a CHIP-8 instruction.
+    UnknownOpcode(u16),
+    /// A call with all 16 stack entries in use.
+    StackOverflow,
+    /// A return with an empty stack.
+    StackUnderflow,
+    /// An access beyond the end of memory.
+    OutOfMemory,
+    /// A key or font digit above F.
+    NotADigit(u8),
+}
+
+/// A CHIP-8 machine.
+#[derive(Clone)]
+pub struct Machine {
+    pub state: State,
+    pub memory: Vec<u8>,
+    pub display: Vec<bool>,
+    /// Held keys, bit N being key N.
+    pub keys: u16,
+    /// Addresses written by the last instruction.
+    pub writes: BTreeSet<usize>,
+    rng: StdRng,
+}
+
+impl Machine {
+    /// A machine with the fontset and the ROM loaded, a clear display and the given registers.
+    pub fn new(rom: &[u8], state: State, keys: u16, seed: u64) -> Machine {
+        let mut memory = vec![0; MEMORY_SIZE];
+        memory[..FONTSET.len()].copy_from_slice(&FONTSET);
+        memory[START as usize..START as usize + rom.len()].copy_from_slice(rom);
+        Machine {
+            state,
+            memory,
+            display: vec![false; WIDTH * HEIGHT],
+            keys,
+            writes: BTreeSet::new(),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Restart the random number generator of `CXKK`.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.display[x + y * WIDTH]
+    }
+
+    /// The instruction at the program counter, if it lies within memory.
+    pub fn opcode(&self) -> Result<u16, Undefined> {
+        let pc = self.state.pc as usize;
+        if pc + 1 >= MEMORY_SIZE {
+            return Err(Undefined::OutOfMemory);
+        }
+        Ok((self.memory[pc] as u16) << 8 | self.memory[pc + 1] as u16)
+    }
+
+    /// Execute the instruction at the program counter.
+    pub fn step(&mut self) -> Result<(), Undefined> {
+        let opcode = self.opcode()?;
+        self.execute(opcode)
+    }
+
+    /// Execute an instruction as if it was found at the program counter. The machine is left untouched if the
+    /// instruction is undefined.
+    pub fn execute(&mut self, opcode: u16) -> Result<(), Undefined> {
+        let mut next = self.clone();
+        next.writes.clear();
+        next.apply(opcode)?;
+        *self = next;
+        Ok(())
+    }
+
+    fn write(&mut self, addr: usize, byte: u8) -> Result<(), Undefined> {
+        if addr >= MEMORY_SIZE {
+            return Err(Undefined::OutOfMemory);
+        }
+        self.memory[addr] = byte;
+        self.writes.insert(addr);
+        Ok(())
+    }
+
+    fn read(&self, addr: usize) -> Result<u8, Undefined> {
+        self.memory.get(addr).cloned().ok_or(Undefined::OutOfMemory)
+    }
+
+    fn apply(&mut self, opcode: u16) -> Result<(), Undefined> {
+        let x = (opcode >> 8 & 0xF) as usize;
+        let y = (opcode >> 4 & 0xF) as usize;
+        let n = opcode & 0xF;
+        let kk = opcode as u8;
+        let nnn = opcode & 0xFFF;
+        let vx = self.state.v[x];
+        let vy = self.state.v[y];
+        let unknown = Err(Undefined::UnknownOpcode(opcode));
+        // the instruction after this one
+        let mut pc = self.state.pc + 2;
+
+        match opcode >> 12 {
+            0x0 => match opcode {
+                // 00E0 - CLS: clear the display
+                0x00E0 => self.display = vec![false; WIDTH * HEIGHT],
+                // 00EE - RET: return from a subroutine
+                0x00EE => {
+                    if self.state.sp == 0 {
+                        return Err(Undefined::StackUnderflow);
+                    }
+                    self.state.sp -= 1;
+                    pc = self.state.stack[self.state.sp as usize];
+                },
+                // 0NNN - SYS addr: jump to a machine code routine, ignored by interpreters
+                _      => (),
+            },
+            // 1NNN - JP addr
+            0x1 => pc = nnn,
+            // 2NNN - CALL addr
+            0x2 => {
+                if self.state.sp as usize >= self.state.stack.len() {
+                    return Err(Undefined::StackOverflow);
+                }
+                self.state.stack[self.state.sp as usize] = pc;
+                self.state.sp += 1;
+                pc = nnn;
+            },
+            // 3XKK - SE Vx, byte
+            0x3 => if vx == kk { pc += 2 },
+            // 4XKK - SNE Vx, byte
+            0x4 => if vx != kk { pc += 2 },
+            // 5XY0 - SE Vx, Vy
+            0x5 if n == 0 => if vx == vy { pc += 2 },
+            // 6XKK - LD Vx, byte
+            0x6 => self.state.v[x] = kk,
+            // 7XKK - ADD Vx, byte, without carry
+            0x7 => self.state.v[x] = vx.wrapping_add(kk),
+            0x8 => match n {
+                // 8XY0 - LD Vx, Vy
+                0x0 => self.state.v[x] = vy,
+                // 8XY1 - OR Vx, Vy
+                0x1 => self.state.v[x] = vx | vy,
+                // 8XY2 - AND Vx, Vy
+                0x2 => self.state.v[x] = vx & vy,
+                // 8XY3 - XOR Vx, Vy
+                0x3 => self.state.v[x] = vx ^ vy,
+                // 8XY4 - ADD Vx, Vy: VF is the carry
+                0x4 => {
+                    let sum = vx as u16 + vy as u16;
+                    self.state.v[x] = sum as u8;
+                    self.state.v[0xF] = (sum > 0xFF) as u8;
+                },
+                // 8XY5 - SUB Vx, Vy: VF is NOT borrow
+                0x5 => {
+                    self.state.v[x] = vx.wrapping_sub(vy);
+                    self.state.v[0xF] = (vx >= vy) as u8;
+                },
+                // 8XY6 - SHR Vx: VF is the bit shifted out
+                0x6 => {
+                    self.state.v[x] = vx >> 1;
+                    self.state.v[0xF] = vx & 0x01;
+                },
+                // 8XY7 - SUBN Vx, Vy: VF is NOT borrow
+                0x7 => {
+                    self.state.v[x] = vy.wrapping_sub(vx);
+                    self.state.v[0xF] = (vy >= vx) as u8;
+                },
+                // 8XYE - SHL Vx: VF is the bit shifted out
+                0xE => {
+                    self.state.v[x] = vx << 1;
+                    self.state.v[0xF] = vx >> 7;
+                },
+                _   => return unknown,
+            },
+            // 9XY0 - SNE Vx, Vy
+            0x9 if n == 0 => if vx != vy { pc += 2 },
+            // ANNN - LD I, addr
+            0xA => self.state.i = nnn,
+            // BNNN - JP V0, addr
+            0xB => pc = nnn + self.state.v[0] as u16,
+            // CXKK - RND Vx, byte
+            0xC => self.state.v[x] = self.rng.gen::<u8>() & kk,
+            // DXYN - DRW Vx, Vy, nibble: XOR an N bytes sprite at I onto the display, wrapping around its edges, VF
+            // is set if a lit pixel is turned off
+            0xD => {
+                let mut collision = false;
+                for row in 0..n as usize {
+                    let bits = self.read(self.state.i as usize + row)?;
+                    for col in 0..8 {
+                        if bits & (0x80 >> col) != 0 {
+                            let index = (vx as usize + col) % WIDTH + (vy as usize + row) % HEIGHT * WIDTH;
+                            collision |= self.display[index];
+                            self.display[index] = !self.display[index];
+                        }
+                    }
+                }
+                self.state.v[0xF] = collision as u8;
+            },
+            0xE => {
+                if vx > 0xF {
+                    return Err(Undefined::NotADigit(vx));
+                }
+                let held = self.keys & 1 << vx != 0;
+                match kk {
+                    // EX9E - SKP Vx
+                    0x9E => if held { pc += 2 },
+                    // EXA1 - SKNP Vx
+                    0xA1 => if !held { pc += 2 },
+                    _    => return unknown,
+                }
+            },
+            0xF => match kk {
+                // FX07 - LD Vx, DT
+                0x07 => self.state.v[x] = self.state.dt,
+                // FX0A - LD Vx, K: wait for a key pressed after the wait started, which the keys held in a single step
+                // were not, so the instruction runs again
+                0x0A => pc = self.state.pc,
+                // FX15 - LD DT, Vx
+                0x15 => self.state.dt = vx,
+                // FX18 - LD ST, Vx
+                0x18 => self.state.st = vx,
+                // FX1E - ADD I, Vx
+                0x1E => self.state.i += vx as u16,
+                // FX29 - LD F, Vx: I points to the font sprite of digit Vx
+                0x29 => {
+                    if vx > 0xF {
+                        return Err(Undefined::NotADigit(vx));
+                    }
+                    self.state.i = vx as u16 * 5;
+                },
+                // FX33 - LD B, Vx: store the decimal digits of Vx at I, I + 1 and I + 2
+                0x33 => {
+                    let i = self.state.i as usize;
+                    self.write(i, vx / 100)?;
+                    self.write(i + 1, vx / 10 % 10)?;
+                    self.write(i + 2, vx % 10)?;
+                },
+                // FX55 - LD [I], Vx: store V0 to VX at I, leaving I unchanged
+                0x55 => {
+                    for reg in 0..=x {
+                        let value = self.state.v[reg];
+                        self.write(self.state.i as usize + reg, value)?;
+                    }
+                },
+                // FX65 - LD Vx, [I]: load V0 to VX from I, leaving I unchanged
+                0x65 => {
+                    for reg in 0..=x {
+                        self.state.v[reg] = self.read(self.state.i as usize + reg)?;
+                    }
+                },
+                _    => return unknown,
+            },
+            _   => return unknown,
+        }
+        self.state.pc = pc;
+        Ok(())
+    }
+}
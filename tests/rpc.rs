@@ -0,0 +1,172 @@
+//! Remote control of a running emulation. The tests start the server and the emulation thread as the headless mode
+//! does, and drive PONG as a client would, over TCP and over a Unix socket.
+
+extern crate chip_8;
+#[macro_use]
+extern crate serde_json;
+
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use chip_8::config::Config;
+use chip_8::emulator::{Command, Emulator, Output};
+use chip_8::memory::Memory;
+use chip_8::rpc::{self, Address};
+
+/// Time to wait for an answer before considering the emulation stuck.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn rom(name: &str) -> String {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("static").join("roms").join(name).display().to_string()
+}
+
+/// A file in the temporary directory, unique to the test process.
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("chip8-rpc-{}-{}", process::id(), name))
+}
+
+/// Serve remote control on an address and run PONG.
+///
+/// # Returns
+/// Returns the address listened on, the commands and the outputs, to be kept open while the emulation runs, and the
+/// emulation thread.
+fn start(address: &Address) -> (Address, Sender<Command>, Receiver<Output>, JoinHandle<()>) {
+    let (calls, local) = rpc::serve(address).unwrap();
+    let config = Config { rom_name: "PONG".to_string(), ..Config::default() };
+    let rom = rom("PONG");
+    let memory = Memory::new(fs::read(&rom).unwrap()).unwrap();
+    let (commands, outputs, thread) = Emulator::spawn(&rom, memory, &config, None, Some(calls));
+    (local, commands, outputs, thread)
+}
+
+/// A remote control client.
+struct Client<S: Write> {
+    reader: BufReader<S>,
+    writer: S,
+    next_id: u64,
+    // notifications received while waiting for responses
+    notifications: Vec<Value>,
+}
+
+impl<S: std::io::Read + Write> Client<S> {
+    fn new(reader: S, writer: S) -> Client<S> {
+        Client { reader: BufReader::new(reader), writer, next_id: 1, notifications: Vec::new() }
+    }
+
+    /// Send a line and wait for the response to it.
+    fn send(&mut self, line: &str) -> Value {
+        writeln!(self.writer, "{}", line).unwrap();
+        loop {
+            let mut response = String::new();
+            assert!(self.reader.read_line(&mut response).unwrap() > 0, "the server should answer");
+            let response: Value = serde_json::from_str(&response).unwrap();
+            if response.get("id").is_some() {
+                return response;
+            }
+            self.notifications.push(response);
+        }
+    }
+
+    /// Call a method, returning its result or its error.
+    fn call(&mut self, method: &str, params: Value) -> Result<Value, Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut response = self.send(&json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+            .to_string());
+        assert_eq!(response["id"], json!(id));
+        match response.get_mut("error") {
+            Some(error) => Err(error.take()),
+            None        => Ok(response["result"].take()),
+        }
+    }
+}
+
+#[test]
+fn drives_the_emulation_over_tcp() {
+    let (address, _commands, _outputs, thread) = start(&Address::Tcp("127.0.0.1:0".to_string()));
+    let stream = TcpStream::connect(address.to_string()).unwrap();
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    let mut client = Client::new(stream.try_clone().unwrap(), stream);
+
+    // stepping pauses, and runs exactly the frames asked for
+    client.call("subscribe", json!({"events": ["frame", "message"]})).unwrap();
+    let before = client.call("status", json!({})).unwrap()["frame"].as_u64().unwrap();
+    client.call("step", json!({"frames": 3})).unwrap();
+    let status = client.call("status", json!({})).unwrap();
+    assert_eq!((&status["paused"], &status["rom"]), (&json!(true), &json!("PONG")));
+    let after = status["frame"].as_u64().unwrap();
+    let stepped: Vec<u64> = client.notifications.iter()
+        .filter(|notification| notification["method"] == "frame")
+        .map(|notification| notification["params"]["frame"].as_u64().unwrap())
+        .filter(|&frame| frame > after - 3)
+        .collect();
+    assert_eq!(stepped, vec![after - 2, after - 1, after]);
+    assert!(after >= before + 3);
+    let paused = json!({"jsonrpc": "2.0", "method": "message", "params": {"text": "Paused"}});
+    assert!(client.notifications.contains(&paused));
+
+    // the machine stays still while paused
+    let registers = client.call("get_registers", json!({})).unwrap();
+    let stepped = client.call("step", json!({"instructions": 1})).unwrap();
+    assert_ne!(registers["pc"], stepped["pc"]);
+    assert_eq!(client.call("get_registers", json!({})).unwrap(), stepped);
+
+    let registers = client.call("set_registers", json!({"v": {"3": 42}, "i": 0x300})).unwrap();
+    assert_eq!((registers["v"][3].as_u64(), registers["i"].as_u64()), (Some(42), Some(0x300)));
+    client.call("write_memory", json!({"address": 0x300, "bytes": [0xDE, 0xAD]})).unwrap();
+    let memory = client.call("read_memory", json!({"address": 0x300, "length": 2})).unwrap();
+    assert_eq!(memory["bytes"], json!([0xDE, 0xAD]));
+
+    client.call("press_key", json!({"key": "1"})).unwrap();
+    client.call("release_key", json!({"key": 1})).unwrap();
+    assert_eq!(client.call("press_key", json!({"key": 16})).unwrap_err()["code"], json!(-32602));
+    assert_eq!(client.call("rewind", json!({})).unwrap_err()["code"], json!(-32601));
+    assert_eq!(client.send("{")["error"]["code"], json!(-32700));
+
+    let path = temp_path("screenshot.png");
+    let saved = client.call("screenshot", json!({"path": path, "scale": 2})).unwrap();
+    assert_eq!(saved["paths"], json!([path.display().to_string()]));
+    assert_eq!(&fs::read(&path).unwrap()[..8], b"\x89PNG\r\n\x1a\n");
+    fs::remove_file(&path).unwrap();
+
+    let loaded = client.call("load_rom", json!({"path": rom("BRIX")})).unwrap();
+    assert_eq!(loaded["rom"], json!("BRIX"));
+    assert_eq!(client.call("status", json!({})).unwrap()["rom"], json!("BRIX"));
+    assert_eq!(client.call("load_rom", json!({"path": rom("NOPE")})).unwrap_err()["code"], json!(-32000));
+    let path = temp_path("huge.ch8");
+    fs::write(&path, vec![0; 4096]).unwrap();
+    assert_eq!(client.call("load_rom", json!({"path": path})).unwrap_err()["code"], json!(-32602));
+    assert_eq!(client.call("status", json!({})).unwrap()["rom"], json!("BRIX"), "a ROM too large is not loaded");
+    fs::remove_file(&path).unwrap();
+
+    client.call("quit", json!({})).unwrap();
+    thread.join().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn serves_a_unix_socket() {
+    use std::os::unix::net::UnixStream;
+
+    let path = temp_path("socket");
+    let (address, _commands, _outputs, thread) = start(&Address::Unix(path.clone()));
+    assert_eq!(address, Address::Unix(path.clone()));
+    let stream = UnixStream::connect(&path).unwrap();
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    let mut client = Client::new(stream.try_clone().unwrap(), stream);
+
+    assert_eq!(client.call("pause", json!({})).unwrap()["paused"], json!(true));
+    assert_eq!(client.call("resume", json!({})).unwrap()["paused"], json!(false));
+    client.call("quit", json!({})).unwrap();
+    thread.join().unwrap();
+    fs::remove_file(&path).unwrap();
+}
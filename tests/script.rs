@@ -0,0 +1,82 @@
+//! Scripts driving the emulator: the bundled BRIX trainer, and a script stopping a headless emulation.
+
+extern crate chip_8;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::sync::mpsc::RecvTimeoutError;
+use std::time::Duration;
+
+use chip_8::config::Config;
+use chip_8::cpu::Cpu;
+use chip_8::emulator::{Emulator, Output};
+use chip_8::graphics::TestDisplay;
+use chip_8::input::TestKeyboard;
+use chip_8::interconnect::Interconnect;
+use chip_8::memory::Memory;
+use chip_8::script::Script;
+
+/// Seed of the random number generator, making the games reproducible.
+const SEED: u64 = 0x5EED;
+/// Frames BRIX is played for, a minute of emulated time.
+const FRAMES: u64 = 3600;
+/// Time to wait for the emulation before considering it stuck.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn root() -> &'static Path {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Play BRIX for a minute without touching the keys, with a script or without.
+///
+/// # Returns
+/// Returns the lives left, in VE, and the text the script draws.
+fn play_brix(script: Option<Script>) -> (u8, Option<Vec<String>>) {
+    let rom = fs::read(root().join("static").join("roms").join("BRIX")).unwrap();
+    let mut cpu: Cpu<TestKeyboard, TestDisplay> = Cpu::new(Interconnect::new_test(rom));
+    cpu.seed_rng(SEED);
+    if let Some(script) = script {
+        cpu.set_script(script);
+    }
+    for _ in 0..FRAMES {
+        cpu.execute_frame();
+    }
+    let lives = cpu.state().v[0xE];
+    (lives, cpu.script_mut().and_then(Script::take_text))
+}
+
+#[test]
+fn trainer_keeps_the_lives() {
+    assert_eq!(play_brix(None).0, 0, "every ball is missed without moving the paddle");
+    let trainer = Script::load(&root().join("static").join("scripts").join("brix_lives.rhai")).unwrap();
+    let (lives, text) = play_brix(Some(trainer));
+    assert_eq!(lives, 5);
+    let text = text.expect("the trainer should count the balls missed");
+    assert!(text[0].starts_with("MISSED "), "unexpected text {:?}", text);
+}
+
+#[test]
+fn script_stops_the_headless_emulation() {
+    let path = env::temp_dir().join(format!("chip8-script-{}.rhai", process::id()));
+    fs::write(&path, "fn on_frame() { this.text(0, `FRAME ${this.frame}`); if this.frame == 3 { this.quit(); } }")
+        .unwrap();
+    let rom = root().join("static").join("roms").join("MAZE").display().to_string();
+    let config = Config { script: Some(path.clone()), ..Config::default() };
+    let memory = Memory::new(fs::read(&rom).unwrap()).unwrap();
+    let (_commands, outputs, thread) = Emulator::spawn(&rom, memory, &config, None, None);
+
+    let mut lines = Vec::new();
+    loop {
+        match outputs.recv_timeout(TIMEOUT) {
+            Ok(Output::ScriptText(text))        => lines.extend(text),
+            Ok(_)                               => (),
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout)      => panic!("the script should stop the emulation"),
+        }
+    }
+    thread.join().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(lines, vec!["FRAME 1", "FRAME 2", "FRAME 3"]);
+}